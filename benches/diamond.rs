@@ -0,0 +1,113 @@
+//! Per-edit cost of `DiamondCRDT` on documents with long histories.
+//!
+//! Every benchmark edits a document holding 100k operations by two agents. The
+//! `local_insert_rebuilding_branch` benchmark makes a local insert the way
+//! `DiamondCRDT` used to, adding the operation to the oplog and then rebuilding the
+//! branch at the tip from it; `local_insert` is the same edit advancing the branch
+//! incrementally, and the others measure the remaining kinds of edit. An update behind
+//! the tip still checks out the text at its parents once, so it costs about one
+//! rebuild.
+//!
+//! Run with `cargo bench --bench diamond`.
+
+use braid_axum_http::merge::{DiamondCRDT, MergeType};
+use braid_axum_http::{Patch, Update, Version};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use diamond_types::list::{Branch, OpLog};
+use std::time::{Duration, Instant};
+
+/// Operations in each benchmarked document.
+const HISTORY: usize = 100_000;
+
+/// A document built from `HISTORY` single-character edits at pseudo-random positions.
+fn long_document() -> DiamondCRDT {
+    let mut doc = DiamondCRDT::new("bench");
+    let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut len = 0;
+    for i in 0..HISTORY {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        let agent = if i % 2 == 0 { "alice" } else { "bob" };
+        if len > 0 && i % 5 == 4 {
+            let pos = seed as usize % len;
+            doc.add_delete_remote(agent, pos..pos + 1).unwrap();
+            len -= 1;
+        } else {
+            doc.add_insert_remote(agent, seed as usize % (len + 1), "x").unwrap();
+            len += 1;
+        }
+    }
+    doc
+}
+
+fn edits(c: &mut Criterion) {
+    let mut group = c.benchmark_group("diamond_100k_ops");
+    let base = long_document();
+
+    // The same operations, edited without the document's incremental branch
+    group.bench_function("local_insert_rebuilding_branch", |b| {
+        let mut oplog = OpLog::load_from(&base.encode()).unwrap();
+        let agent = oplog.get_or_create_agent_id("bench");
+        b.iter(|| {
+            oplog.add_insert(agent, 0, "a");
+            black_box(Branch::new_at_tip(&oplog))
+        })
+    });
+
+    // Each benchmark starts from its own copy, since the edits grow the document
+    group.bench_function("local_insert", |b| {
+        let mut doc = base.clone();
+        b.iter(|| doc.add_insert(0, "a"))
+    });
+
+    group.bench_function("remote_insert", |b| {
+        let mut doc = base.clone();
+        b.iter(|| doc.add_insert_remote("carol", 0, "b").unwrap())
+    });
+
+    group.bench_function("apply_update", |b| {
+        let mut doc = base.clone();
+        b.iter(|| {
+            let update = Update::patched(Version::new("_"), vec![Patch::text("[0:1]", "c")])
+                .with_parents(doc.version());
+            doc.apply(&update, "dave").unwrap()
+        })
+    });
+
+    // Only the stale update is timed, not the concurrent edit that makes it stale
+    group.bench_function("apply_update_behind_tip", |b| {
+        let mut doc = base.clone();
+        b.iter_custom(|iters| {
+            let mut elapsed = Duration::ZERO;
+            for _ in 0..iters {
+                let parents = doc.version();
+                doc.add_insert_remote("erin", 0, "d").unwrap();
+                let update = Update::patched(Version::new("_"), vec![Patch::text("[0:0]", "e")])
+                    .with_parents(parents);
+                let start = Instant::now();
+                black_box(doc.apply(&update, "frank").unwrap());
+                elapsed += start.elapsed();
+            }
+            elapsed
+        })
+    });
+
+    group.bench_function("apply_patches_batch_of_32", |b| {
+        let mut doc = base.clone();
+        let patches: Vec<Patch> = (0..32).map(|i| Patch::text(format!("[{i}:{i}]"), "f")).collect();
+        b.iter(|| {
+            let parents = doc.version();
+            doc.apply_patches_at("grace", &parents, &patches).unwrap()
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(20);
+    targets = edits
+}
+criterion_main!(benches);
@@ -7,19 +7,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("Braid-HTTP Comprehensive Example");
     println!("================================\n");
 
-    let client = BraidClient::new();
+    let _client = BraidClient::new();
 
     println!("1. VERSIONING FOR RESOURCES");
     println!("--------------------------");
     println!("   Basic version tracking:");
-    let req = BraidRequest::new()
+    let _req = BraidRequest::new()
         .with_version(Version::from("v1"));
     println!("   GET /resource with Version: v1\n");
 
     println!("2. GETTING HISTORICAL VERSIONS");
     println!("-----------------------------");
     println!("   Request range of versions:");
-    let req = BraidRequest::new()
+    let _req = BraidRequest::new()
         .with_version(Version::from("v3"))
         .with_parents(vec![Version::from("v1a"), Version::from("v1b")]);
     println!("   GET /resource with Version: v3, Parents: v1a, v1b");
@@ -29,12 +29,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("---------------------------------");
     
     println!("   a) Snapshot update:");
-    let update = Update::snapshot(Version::from("v2"), r#"[{"text": "Hello"}]"#);
+    let _update = Update::snapshot(Version::from("v2"), r#"[{"text": "Hello"}]"#);
     println!("      Full JSON snapshot\n");
 
     println!("   b) Patch update:");
     let patch = Patch::json(".messages[1:1]", r#"[{"text": "Added"}]"#);
-    let update = Update::patched(Version::from("v3"), vec![patch]);
+    let _update = Update::patched(Version::from("v3"), vec![patch]);
     println!("      Incremental JSON patch\n");
 
     println!("   c) Range patch:");
@@ -46,7 +46,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     println!("4. MERGE-TYPES FOR CONFLICT RESOLUTION");
     println!("------------------------------------");
-    let req = BraidRequest::new()
+    let _req = BraidRequest::new()
         .with_version(Version::from("v2"))
         .with_parents(vec![Version::from("v1a"), Version::from("v1b")])
         .with_merge_type("sync9");
@@ -55,20 +55,20 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     println!("5. SUBSCRIPTIONS");
     println!("---------------");
-    let req = BraidRequest::new().subscribe();
+    let _req = BraidRequest::new().subscribe();
     println!("   GET /resource with Subscribe: true");
     println!("   Response: HTTP 209 Subscription");
     println!("   Server streams updates as they occur\n");
 
     println!("6. SUBSCRIPTION WITH CATCH-UP SIGNALING");
     println!("-------------------------------------");
-    let req = BraidRequest::new().subscribe();
+    let _req = BraidRequest::new().subscribe();
     println!("   Response includes Current-Version header");
     println!("   Client knows when it has caught up to server state\n");
 
     println!("7. RESUMING SUBSCRIPTIONS");
     println!("------------------------");
-    let req = BraidRequest::new()
+    let _req = BraidRequest::new()
         .subscribe()
         .with_parent(Version::from("v2"));
     println!("   GET /resource with Subscribe: true, Parents: v2");
@@ -81,7 +81,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Patch::json(".timestamp", r#"1234567890"#),
     ];
     let mut update = Update::patched(Version::from("v5"), patches);
-    update.patches.as_mut().unwrap().iter_mut().enumerate().for_each(|(i, p)| {
+    update.patches.as_mut().unwrap().iter_mut().for_each(|p| {
         p.content_length = Some(p.content.len());
     });
     println!("   Patches: 2");
@@ -89,7 +89,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     println!("9. CONTENT-TYPE AND MERGE-TYPE HANDLING");
     println!("------------------------------------");
-    let update = Update::snapshot(Version::from("v6"), r#"{"data": "value"}"#)
+    let _update = Update::snapshot(Version::from("v6"), r#"{"data": "value"}"#)
         .with_content_type("application/json")
         .with_merge_type("sync9");
     println!("   Content-Type: application/json");
@@ -98,7 +98,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("10. DAG VERSION TRACKING");
     println!("----------------------");
     println!("    Multiple parents represent concurrent edits:");
-    let req = BraidRequest::new()
+    let _req = BraidRequest::new()
         .with_version(Version::from("v_merged"))
         .with_parents(vec![
             Version::from("v_client_edit"),
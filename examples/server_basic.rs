@@ -5,11 +5,12 @@
 //! Run with: cargo run --example server_basic
 
 use axum::{
-    extract::State,
+    extract::{Extension, State},
+    middleware,
     response::IntoResponse,
     routing::{get, Router},
 };
-use braid_axum_http::{BraidState, Update, Version};
+use braid_axum_http::{BraidLayer, BraidState, Update, Version};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
@@ -28,8 +29,10 @@ async fn main() {
         current_version: Arc::new(RwLock::new("v1".to_string())),
     };
 
+    let braid = BraidLayer::new();
     let app = Router::new()
         .route("/data", get(handle_get_data))
+        .layer(middleware::from_fn(braid.middleware()))
         .with_state(state);
 
     let listener = tokio::net::TcpListener::bind("127.0.0.1:3000")
@@ -41,7 +44,7 @@ async fn main() {
 
 async fn handle_get_data(
     State(state): State<AppState>,
-    braid_state: BraidState,
+    Extension(braid_state): Extension<Arc<BraidState>>,
 ) -> impl IntoResponse {
     println!("\nReceived request:");
     println!("  Subscribe: {}", braid_state.subscribe);
@@ -50,17 +53,19 @@ async fn handle_get_data(
 
     let version = braid_state
         .version
+        .clone()
         .unwrap_or_else(|| vec![Version::from("v0")]);
 
+    let current = state.current_version.read().await.clone();
     let update = Update::snapshot(
-        Version::from("v1"),
+        Version::from(current),
         r#"{"message": "Hello from Braid server", "timestamp": "2024-01-01T00:00:00Z"}"#,
     )
     .with_parents(version);
 
     println!("Sending update:");
     println!("  Version: {:?}", update.version);
-    println!("  Body: {:?}", String::from_utf8_lossy(&update.body.as_ref().unwrap()));
+    println!("  Body: {:?}", String::from_utf8_lossy(update.body.as_ref().unwrap()));
 
     update
 }
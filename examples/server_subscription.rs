@@ -5,15 +5,15 @@
 //! Run with: cargo run --example server_subscription
 
 use axum::{
-    extract::State,
+    extract::{Extension, State},
+    middleware,
     response::IntoResponse,
     routing::get,
     Router,
 };
-use braid_axum_http::{BraidState, Update, Version, Patch};
+use braid_axum_http::{BraidLayer, BraidState, Update, Version};
 use std::sync::Arc;
 use tokio::sync::RwLock;
-use tokio::time::{interval, Duration};
 
 #[derive(Clone)]
 pub struct AppState {
@@ -35,8 +35,10 @@ async fn main() {
         data: Arc::new(RwLock::new(r#"{"count": 0}"#.to_string())),
     };
 
+    let braid = BraidLayer::new();
     let app = Router::new()
         .route("/updates", get(handle_subscription))
+        .layer(middleware::from_fn(braid.middleware()))
         .with_state(state);
 
     let listener = tokio::net::TcpListener::bind("127.0.0.1:3001")
@@ -48,20 +50,21 @@ async fn main() {
 
 async fn handle_subscription(
     State(state): State<AppState>,
-    braid_state: BraidState,
+    Extension(braid_state): Extension<Arc<BraidState>>,
 ) -> impl IntoResponse {
     println!("\nSubscription request received:");
     println!("  Subscribe: {}", braid_state.subscribe);
     println!("  Requested version: {:?}", braid_state.version);
 
     if !braid_state.subscribe {
-        let data = state.data.read().await;
-        let update = Update::snapshot(Version::from("v1"), data.as_str());
+        let data = state.data.read().await.clone();
+        let update = Update::snapshot(Version::from("v1"), data);
         return update.into_response();
     }
 
     let parent_version = braid_state
         .version
+        .clone()
         .unwrap_or_else(|| vec![Version::from("v0")]);
 
     let mut update = Update::snapshot(Version::from("v1"), r#"{"count": 0}"#)
@@ -37,6 +37,7 @@
 //!     connection_timeout_secs: 60,
 //!     enable_logging: true,
 //!     max_subscriptions: 50,
+//!     ..Default::default()
 //! };
 //! ```
 //!
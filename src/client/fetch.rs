@@ -95,8 +95,9 @@ impl BraidClient {
 
     /// Create a new Braid client with custom configuration
     pub fn with_config(config: ClientConfig) -> Self {
+        // The request timeout is set per request, as subscriptions stay open for good
         let mut builder = reqwest::Client::builder()
-            .timeout(std::time::Duration::from_millis(config.request_timeout_ms))
+            .connect_timeout(std::time::Duration::from_millis(config.request_timeout_ms))
             .pool_idle_timeout(std::time::Duration::from_secs(90))
             .pool_max_idle_per_host(config.max_total_connections as usize);
 
@@ -143,9 +144,64 @@ impl BraidClient {
         self.fetch_with_retries(url, request, 0).await
     }
 
+    /// Make a Braid protocol request, writing a successful response's body to `writer`
+    /// as it arrives.
+    ///
+    /// Meant for large binary snapshots, which are never held in memory as a whole:
+    /// the returned response has the status and headers, and an empty body. The body
+    /// of an unsuccessful response is returned as usual instead. The request isn't
+    /// retried, and only the connect timeout applies, as a download may take a while.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The URL to request
+    /// * `request` - The Braid request configuration
+    /// * `writer` - Where the body is written
+    ///
+    /// # Examples
+    /// ```ignore
+    /// let mut file = tokio::fs::File::create("video.mp4").await?;
+    /// let response = client.fetch_into("http://example.com/video.mp4", BraidRequest::new(), &mut file).await?;
+    /// println!("Version: {:?}", response.get_version());
+    /// ```
+    pub async fn fetch_into<W>(&self, url: &str, request: BraidRequest, writer: &mut W) -> Result<BraidResponse>
+    where
+        W: tokio::io::AsyncWrite + Unpin,
+    {
+        use futures::StreamExt;
+        use tokio::io::AsyncWriteExt;
+
+        let response = self.request_builder(url, &request)?.send().await
+            .map_err(|e| BraidError::Http(e.to_string()))?;
+
+        let status = response.status().as_u16();
+        let headers = response_headers(&response);
+        let mut braid_response = BraidResponse {
+            status,
+            headers,
+            body: bytes::Bytes::new(),
+            is_subscription: status == 209,
+        };
+        if !braid_response.is_success() {
+            braid_response.body = response.bytes().await
+                .map_err(|e| BraidError::Http(e.to_string()))?;
+            return Ok(braid_response);
+        }
+
+        let mut body = response.bytes_stream();
+        while let Some(chunk) = body.next().await {
+            let chunk = chunk.map_err(|e| BraidError::Http(e.to_string()))?;
+            writer.write_all(&chunk).await?;
+        }
+        writer.flush().await?;
+        Ok(braid_response)
+    }
+
     /// Subscribe to streaming updates
     ///
-    /// Returns a subscription that yields updates as they arrive.
+    /// Returns a subscription that yields updates as they arrive. The request's
+    /// `Parents`, `Peer`, `Merge-Type` and extra headers are sent along, so a
+    /// subscription can resume from the last version the caller has seen.
     ///
     /// # Arguments
     ///
@@ -170,17 +226,7 @@ impl BraidClient {
     ) -> Result<crate::client::Subscription> {
         request.subscribe = true;
 
-        // Manually build request for streaming
-        // Duplicate some logic from fetch_internal to get the RequestBuilder
-        let mut req_builder = self.client.get(url);
-        req_builder = req_builder.header(
-             crate::protocol::constants::headers::SUBSCRIBE.as_str(),
-             "true"
-        );
-        // ... (simplified for brevity, should use a shared helper in real impl) ...
-        // For now, assuming standard subscribe request
-
-        let response = req_builder.send().await
+        let response = self.request_builder(url, &request)?.send().await
              .map_err(|e| BraidError::Http(e.to_string()))?;
 
         if response.status().as_u16() != 209 {
@@ -194,7 +240,15 @@ impl BraidClient {
             use futures::StreamExt;
             let mut parser = MessageParser::new();
 
-            while let Some(chunk_res) = stream.next().await {
+            loop {
+                 // Stop reading, closing the connection, once the subscription is dropped
+                 let chunk_res = tokio::select! {
+                     next = stream.next() => match next {
+                         Some(chunk_res) => chunk_res,
+                         None => return,
+                     },
+                     () = tx.closed() => return,
+                 };
                  match chunk_res {
                      Ok(chunk) => {
                          match parser.feed(&chunk) {
@@ -202,7 +256,7 @@ impl BraidClient {
                                  for msg in messages {
                                      // Convert Message to Update
                                      let update = crate::client::utils::message_to_update(msg);
-                                     if let Err(_) = tx.send(Ok(update)).await {
+                                     if tx.send(Ok(update)).await.is_err() {
                                          return; // Receiver dropped
                                      }
                                  }
@@ -254,6 +308,34 @@ impl BraidClient {
 
     /// Internal fetch implementation
     async fn fetch_internal(&self, url: &str, request: &BraidRequest) -> Result<BraidResponse> {
+        let response = self
+            .request_builder(url, request)?
+            .timeout(std::time::Duration::from_millis(self.config.request_timeout_ms))
+            .send()
+            .await
+            .map_err(|e| BraidError::Http(e.to_string()))?;
+
+        let status = response.status().as_u16();
+
+        let headers = response_headers(&response);
+
+        // Read body
+        let body = response.bytes().await
+            .map_err(|e| BraidError::Http(e.to_string()))?;
+
+        Ok(BraidResponse {
+            status,
+            headers,
+            body,
+            is_subscription: status == 209,
+        })
+    }
+
+    /// Build the HTTP request for a Braid request, with its headers and body.
+    ///
+    /// A single patch is sent as the body with a `Content-Range` header; several are
+    /// sent as a multi-patch body with a `Patches` header.
+    fn request_builder(&self, url: &str, request: &BraidRequest) -> Result<reqwest::RequestBuilder> {
         let method = match request.method.to_uppercase().as_str() {
             "POST" => reqwest::Method::POST,
             "PUT" => reqwest::Method::PUT,
@@ -270,13 +352,13 @@ impl BraidClient {
         }
 
         // Add Braid headers
-        if let Some(versions) = &request.version {
+        if let Some(versions) = request.version.as_ref().filter(|v| !v.is_empty()) {
             req_builder = req_builder.header(
                 crate::protocol::constants::headers::VERSION.as_str(),
                 crate::protocol::format_version_header(versions)
             );
         }
-        if let Some(parents) = &request.parents {
+        if let Some(parents) = request.parents.as_ref().filter(|p| !p.is_empty()) {
             req_builder = req_builder.header(
                 crate::protocol::constants::headers::PARENTS.as_str(),
                 crate::protocol::format_version_header(parents)
@@ -300,35 +382,38 @@ impl BraidClient {
                 merge_type
             );
         }
-
-        // Add body
-        if !request.body.is_empty() {
-            req_builder = req_builder.body(request.body.clone());
+        if let Some(content_type) = &request.content_type {
+             req_builder = req_builder.header(
+                crate::protocol::constants::headers::CONTENT_TYPE.as_str(),
+                content_type
+            );
         }
 
-        let response = req_builder.send().await
-            .map_err(|e| BraidError::Http(e.to_string()))?;
-
-        let status = response.status().as_u16();
-
-        // Convert headers
-        let mut headers = std::collections::BTreeMap::new();
-        for (k, v) in response.headers() {
-            if let Ok(val) = v.to_str() {
-                headers.insert(k.as_str().to_string(), val.to_string());
+        // Add patches or body
+        match request.patches.as_deref() {
+            Some([patch]) => {
+                req_builder = req_builder
+                    .header(
+                        crate::protocol::constants::headers::CONTENT_RANGE.as_str(),
+                        patch.content_range_header(),
+                    )
+                    .body(patch.content.clone());
+            }
+            Some(patches) if !patches.is_empty() => {
+                req_builder = req_builder
+                    .header(
+                        crate::protocol::constants::headers::PATCHES.as_str(),
+                        patches.len().to_string(),
+                    )
+                    .body(crate::protocol::format_patches(patches)?);
             }
+            _ if !request.body.is_empty() => {
+                req_builder = req_builder.body(request.body.clone());
+            }
+            _ => {}
         }
 
-        // Read body
-        let body = response.bytes().await
-            .map_err(|e| BraidError::Http(e.to_string()))?;
-
-        Ok(BraidResponse {
-            status,
-            headers,
-            body,
-            is_subscription: status == 209,
-        })
+        Ok(req_builder)
     }
 
     /// Get the client configuration
@@ -337,6 +422,17 @@ impl BraidClient {
     }
 }
 
+/// Convert the headers of a response, skipping values that aren't visible ASCII.
+fn response_headers(response: &reqwest::Response) -> std::collections::BTreeMap<String, String> {
+    let mut headers = std::collections::BTreeMap::new();
+    for (k, v) in response.headers() {
+        if let Ok(val) = v.to_str() {
+            headers.insert(k.as_str().to_string(), val.to_string());
+        }
+    }
+    headers
+}
+
 impl Default for BraidClient {
     fn default() -> Self {
         Self::new()
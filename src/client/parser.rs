@@ -13,9 +13,10 @@
 //!
 //! # Multi-Patch Support
 //!
-//! For multiple patches in a single response (Section 3.3), each patch declares
-//! a Content-Length header in its own headers section. The parser tracks this
-//! to correctly identify patch boundaries in the stream.
+//! For multiple patches in a single response (Section 3.3), the message declares
+//! their number in a `Patches` header instead of a Content-Length, and each patch
+//! declares a Content-Length and Content-Range in its own headers section. The
+//! parser reads that many patches into [`Message::patches`].
 //!
 //! # Examples
 //!
@@ -37,6 +38,7 @@
 //! See Section 3.3 of draft-toomim-httpbis-braid-http for multi-patch format.
 
 use crate::error::{BraidError, Result};
+use crate::protocol;
 use crate::types::Patch;
 use bytes::{Bytes, BytesMut};
 use std::collections::BTreeMap;
@@ -94,6 +96,8 @@ pub struct MessageParser {
     read_body_length: usize,
     /// Accumulated patches (for multi-patch messages)
     patches: Vec<Patch>,
+    /// Number of patches declared by the Patches header
+    expected_patches: usize,
     /// Current patch being built
     current_patch: Option<Patch>,
 }
@@ -109,6 +113,7 @@ impl MessageParser {
             expected_body_length: 0,
             read_body_length: 0,
             patches: Vec::new(),
+            expected_patches: 0,
             current_patch: None,
         }
     }
@@ -121,6 +126,9 @@ impl MessageParser {
         loop {
             match self.state {
                 ParseState::WaitingForHeaders => {
+                    // Blank lines between messages (e.g. heartbeats) carry nothing
+                    let blank = self.buffer.iter().take_while(|&&b| b == b'\r' || b == b'\n').count();
+                    let _ = self.buffer.split_to(blank);
                     if let Some(pos) = self.find_header_end() {
                         self.parse_headers(pos)?;
                         self.state = ParseState::WaitingForBody;
@@ -150,7 +158,7 @@ impl MessageParser {
     fn find_header_end(&self) -> Option<usize> {
         self.buffer
             .windows(4)
-            .rposition(|w| w == b"\r\n\r\n")
+            .position(|w| w == b"\r\n\r\n")
             .map(|p| p + 4)
     }
 
@@ -173,11 +181,20 @@ impl MessageParser {
             })?;
         }
 
+        if let Some(count) = self.headers.get("patches") {
+            self.expected_patches = count.parse().map_err(|_| {
+                BraidError::HeaderParse(format!("Invalid patches: {}", count))
+            })?;
+        }
+
         Ok(())
     }
 
     /// Try to parse body from buffer
     fn try_parse_body(&mut self) -> Result<bool> {
+        if self.expected_patches > 0 {
+            return self.try_parse_patches();
+        }
         if self.expected_body_length == 0 {
             return Ok(true);
         }
@@ -189,12 +206,27 @@ impl MessageParser {
             self.read_body_length += body_chunk.len();
             Ok(true)
         } else {
-            self.body_buffer.extend_from_slice(&self.buffer.split_to(self.buffer.len()));
-            self.read_body_length += self.body_buffer.len();
+            let body_chunk = self.buffer.split_to(self.buffer.len());
+            self.body_buffer.extend_from_slice(&body_chunk);
+            self.read_body_length += body_chunk.len();
             Ok(false)
         }
     }
 
+    /// Try to parse the patches of a multi-patch message from buffer
+    fn try_parse_patches(&mut self) -> Result<bool> {
+        while self.patches.len() < self.expected_patches {
+            match protocol::parse_patch(&self.buffer)? {
+                Some((patch, len)) => {
+                    let _ = self.buffer.split_to(len);
+                    self.patches.push(patch);
+                }
+                None => return Ok(false),
+            }
+        }
+        Ok(true)
+    }
+
     /// Finalize and return a complete message
     fn finalize_message(&mut self) -> Result<Option<Message>> {
         let body = self.body_buffer.split().freeze();
@@ -213,6 +245,7 @@ impl MessageParser {
         self.expected_body_length = 0;
         self.read_body_length = 0;
         self.patches.clear();
+        self.expected_patches = 0;
         self.current_patch = None;
     }
 
@@ -267,4 +300,24 @@ mod tests {
         assert!(!messages.is_empty());
         assert_eq!(messages[0].body, Bytes::from_static(b"Hello"));
     }
+
+    #[test]
+    fn test_multi_patch_messages_in_pieces() {
+        let update = crate::types::Update::patched(
+            crate::types::Version::new("alice-4"),
+            vec![Patch::text("[0:0]", "hello"), Patch::text("[1:3]", "")],
+        );
+        let mut stream = protocol::format_update(&update).unwrap().to_vec();
+        stream.extend_from_slice(b"\r\ncontent-length: 2\r\n\r\nok");
+
+        let mut parser = MessageParser::new();
+        let mut messages = Vec::new();
+        for piece in stream.chunks(7) {
+            messages.extend(parser.feed(piece).unwrap());
+        }
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].headers["version"], "\"alice-4\"");
+        assert_eq!(messages[0].patches, update.patches.unwrap());
+        assert_eq!(messages[1].body, Bytes::from_static(b"ok"));
+    }
 }
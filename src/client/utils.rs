@@ -12,7 +12,7 @@
 
 use crate::error::{Result, BraidError};
 use crate::protocol;
-use crate::types::{Update, Version};
+use crate::types::{Patch, Update, Version};
 use bytes::{Bytes, BytesMut};
 use std::time::Duration;
 use crate::client::parser::Message;
@@ -62,10 +62,10 @@ pub fn format_content_range(unit: &str, range: &str) -> String {
 /// ```
 pub fn parse_heartbeat(value: &str) -> Result<Duration> {
     let trimmed = value.trim();
-    let (num_str, _unit) = if trimmed.ends_with('s') {
-        (&trimmed[..trimmed.len() - 1], "s")
-    } else if trimmed.ends_with("ms") {
-        (&trimmed[..trimmed.len() - 2], "ms")
+    let (num_str, _unit) = if let Some(secs) = trimmed.strip_suffix('s') {
+        (secs, "s")
+    } else if let Some(ms) = trimmed.strip_suffix("ms") {
+        (ms, "ms")
     } else {
         (trimmed, "s")
     };
@@ -113,19 +113,27 @@ pub fn merge_bodies(body1: &Bytes, body2: &Bytes) -> Bytes {
 /// Convert a parsed protocol message to an Update object.
 ///
 /// Extracts versioning, headers, and content from the raw message
-/// and constructs a typed `Update` struct.
+/// and constructs a typed `Update` struct. A body sent with a Content-Range
+/// header is a single patch, and becomes the update's only patch. Bodies and patches
+/// are kept as bytes, so binary content arrives unchanged.
 pub fn message_to_update(msg: Message) -> Update {
+    // We need a version, fallback to unknown/generated if missing
+    let version = extract_version(&msg.headers).unwrap_or_else(|| vec![Version::new("unknown")]);
+    let range = msg
+        .headers
+        .get("content-range")
+        .and_then(|range| protocol::parse_content_range(range).ok());
+
     let mut builder = if !msg.patches.is_empty() {
         // Construct patched update
-        // We need a version, fallback to unknown/generated if missing
-        let version = extract_version(&msg.headers).unwrap_or_else(|| Version::new("unknown"));
-        Update::patched(version, msg.patches)
+        Update::patched(version[0].clone(), msg.patches)
+    } else if let Some((unit, range)) = range {
+        Update::patched(version[0].clone(), vec![Patch::new(unit, range, msg.body)])
     } else {
-        // Construct snapshot update
-        let version = extract_version(&msg.headers).unwrap_or_else(|| Version::new("unknown"));
-        let body = String::from_utf8_lossy(&msg.body).to_string();
-        Update::snapshot(version, body)
+        // Construct snapshot update, keeping binary bodies as they are
+        Update::snapshot(version[0].clone(), msg.body)
     };
+    builder.version = version;
 
     // Add parents
     if let Some(parents) = extract_parents(&msg.headers) {
@@ -138,6 +146,9 @@ pub fn message_to_update(msg: Message) -> Update {
     if let Some(merge_type) = msg.headers.get("merge-type") {
         builder = builder.with_merge_type(merge_type.clone());
     }
+    if let Some(content_type) = msg.headers.get("content-type") {
+        builder = builder.with_content_type(content_type.clone());
+    }
     
     // Status code is not in Message (it's in the Response wrapper), 
     // but we can assume 200/209 for normal updates. 
@@ -147,10 +158,10 @@ pub fn message_to_update(msg: Message) -> Update {
     builder
 }
 
-fn extract_version(headers: &std::collections::BTreeMap<String, String>) -> Option<Version> {
+fn extract_version(headers: &std::collections::BTreeMap<String, String>) -> Option<Vec<Version>> {
     headers.get("version")
            .and_then(|v| protocol::parse_version_header(v).ok())
-           .and_then(|mut v| v.pop())
+           .filter(|v| !v.is_empty())
 }
 
 fn extract_parents(headers: &std::collections::BTreeMap<String, String>) -> Option<Vec<Version>> {
@@ -194,4 +205,23 @@ mod tests {
         let delay1 = exponential_backoff(1, 100);
         assert!(delay1 > delay0);
     }
+
+    #[test]
+    fn test_message_to_update_keeps_versions_and_range() {
+        let mut parser = crate::client::MessageParser::new();
+        let data = b"version: \"a-1\", \"b-2\"\r\nparents: \"a-0\"\r\ncontent-range: text [0:0]\r\ncontent-length: 2\r\n\r\nhi";
+        let update = message_to_update(parser.feed(data).unwrap().remove(0));
+        assert_eq!(update.version, vec![Version::new("a-1"), Version::new("b-2")]);
+        assert_eq!(update.parents, vec![Version::new("a-0")]);
+        assert_eq!(update.patches, Some(vec![Patch::text("[0:0]", "hi")]));
+    }
+
+    #[test]
+    fn test_message_to_update_keeps_binary_body() {
+        let mut parser = crate::client::MessageParser::new();
+        let mut data = b"version: \"a-1\"\r\ncontent-length: 4\r\n\r\n".to_vec();
+        data.extend_from_slice(&[0x89, 0xff, 0x00, 0xfe]);
+        let update = message_to_update(parser.feed(&data).unwrap().remove(0));
+        assert_eq!(update.body.as_deref(), Some(&[0x89, 0xff, 0x00, 0xfe][..]));
+    }
 }
@@ -12,6 +12,7 @@
 //! | Subscription | `SubscriptionClosed`, `InvalidSubscriptionStatus` | Depends |
 //! | Conflict | `MergeConflict`, `HistoryDropped` | No |
 //! | Configuration | `Config` | No |
+//! | Storage | `Storage` | No |
 //!
 //! # Error Recovery
 //!
@@ -106,6 +107,12 @@ pub enum BraidError {
     #[error("Body parse error: {0}")]
     BodyParse(String),
 
+    /// A patch doesn't fit the document it was made against.
+    ///
+    /// Its range lies outside the text at the update's parents.
+    #[error("Invalid patch: {0}")]
+    InvalidPatch(String),
+
     /// Invalid version format (not a valid version identifier).
     ///
     /// Version IDs must be valid JSON strings or integers (RFC 8941).
@@ -161,6 +168,13 @@ pub enum BraidError {
     #[error("Configuration error: {0}")]
     Config(String),
 
+    /// A storage backend failed to read or write.
+    ///
+    /// Raised by [`ResourceStore`](crate::server::ResourceStore) implementations
+    /// for database errors that aren't plain I/O errors.
+    #[error("Storage error: {0}")]
+    Storage(String),
+
     /// Internal error in the library.
     ///
     /// Indicates a bug or unexpected state in the implementation.
@@ -300,6 +314,12 @@ mod tests {
         assert!(err.to_string().contains("invalid header"));
     }
 
+    #[test]
+    fn test_invalid_patch_error() {
+        let err = BraidError::InvalidPatch("[4:9]".into());
+        assert!(err.to_string().contains("[4:9]"));
+    }
+
     #[test]
     fn test_subscription_closed() {
         let err = BraidError::SubscriptionClosed;
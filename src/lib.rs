@@ -104,7 +104,7 @@
 //! crdt.add_insert(5, " world");
 //!
 //! // Merge remote edits automatically
-//! crdt.add_insert_remote("other-client", 0, ">> ");
+//! crdt.add_insert_remote("other-client", 0, ">> ").unwrap();
 //!
 //! // Send to peers via Braid-HTTP with Diamond merge type
 //! let update = Update::snapshot(
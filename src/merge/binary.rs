@@ -0,0 +1,143 @@
+//! Helpers for `bytes` range patches on binary resources.
+//!
+//! Byte patches use the `bytes` unit with a `start:end` range of byte offsets; the end
+//! is exclusive, so `0:0` inserts at the start and `4:6` replaces two bytes. The forms
+//! `start-end` and `[start:end]` are accepted as the same range. Patches in one update
+//! are applied in order, each against the result of the previous one.
+//!
+//! # Examples
+//!
+//! ```
+//! use braid_axum_http::merge::{apply_byte_patches, diff_bytes};
+//! use braid_axum_http::Patch;
+//!
+//! let header = [0x89, b'P', b'N', b'G', 0x00, 0x00];
+//! let patched = apply_byte_patches(&header, &[Patch::bytes("4:6", &[0x0d, 0x0a][..])]).unwrap();
+//! assert_eq!(&patched[..], &[0x89, b'P', b'N', b'G', 0x0d, 0x0a]);
+//!
+//! let patch = diff_bytes(&header, &patched).unwrap();
+//! assert_eq!(patch.range, "4:6");
+//! ```
+
+use crate::error::{BraidError, Result};
+use crate::types::Patch;
+use bytes::Bytes;
+
+/// Apply `bytes` patches in order to binary content.
+///
+/// Fails without partial effects if a patch isn't a `bytes` patch or its range falls
+/// outside the content.
+///
+/// # Arguments
+///
+/// * `content` - Content the first patch applies to
+/// * `patches` - Patches, each applying to the result of the previous one
+///
+/// # Errors
+///
+/// Returns [`BraidError::HeaderParse`] for a patch of another unit, a malformed range,
+/// or a range past the end of the content.
+pub fn apply_byte_patches(content: &[u8], patches: &[Patch]) -> Result<Bytes> {
+    let mut data = content.to_vec();
+    for patch in patches {
+        if !patch.is_bytes() {
+            return Err(BraidError::HeaderParse(format!(
+                "Expected bytes patch, got unit '{}'",
+                patch.unit
+            )));
+        }
+        let (start, end) = parse_byte_range(&patch.range)?;
+        if end > data.len() {
+            return Err(BraidError::HeaderParse(format!(
+                "Range {}:{} exceeds content length {}",
+                start,
+                end,
+                data.len()
+            )));
+        }
+        data.splice(start..end, patch.content.iter().copied());
+    }
+    Ok(data.into())
+}
+
+/// Describe the change from `old` to `new` as a single `bytes` patch.
+///
+/// The patch replaces the bytes between the common prefix and suffix of the two.
+/// Returns `None` if they're equal.
+#[must_use]
+pub fn diff_bytes(old: &[u8], new: &[u8]) -> Option<Patch> {
+    if old == new {
+        return None;
+    }
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    Some(Patch::bytes(
+        format!("{}:{}", prefix, old.len() - suffix),
+        Bytes::copy_from_slice(&new[prefix..new.len() - suffix]),
+    ))
+}
+
+/// Parse a `bytes` range of the form `start:end` or `start-end` (brackets optional).
+pub(crate) fn parse_byte_range(range: &str) -> Result<(usize, usize)> {
+    let invalid = || BraidError::HeaderParse(format!("Invalid bytes range: {}", range));
+    let trimmed = range.trim();
+    let inner = trimmed
+        .strip_prefix('[')
+        .and_then(|r| r.strip_suffix(']'))
+        .unwrap_or(trimmed);
+    let (start, end) = inner
+        .split_once(':')
+        .or_else(|| inner.split_once('-'))
+        .ok_or_else(invalid)?;
+    let start: usize = start.trim().parse().map_err(|_| invalid())?;
+    let end: usize = end.trim().parse().map_err(|_| invalid())?;
+    if end < start {
+        return Err(invalid());
+    }
+    Ok((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_byte_range() {
+        assert_eq!(parse_byte_range("100:200").unwrap(), (100, 200));
+        assert_eq!(parse_byte_range("100-200").unwrap(), (100, 200));
+        assert_eq!(parse_byte_range("[3:3]").unwrap(), (3, 3));
+        assert!(parse_byte_range("9:1").is_err());
+        assert!(parse_byte_range(".title").is_err());
+    }
+
+    #[test]
+    fn test_apply_byte_patches_in_order() {
+        let content = [0u8, 1, 2, 3, 255];
+        let patches = [
+            Patch::bytes("0:1", &[9u8, 9][..]),
+            Patch::bytes("6:6", &[0xfe][..]),
+            Patch::bytes("2-4", Bytes::new()),
+        ];
+        let patched = apply_byte_patches(&content, &patches).unwrap();
+        assert_eq!(&patched[..], &[9, 9, 3, 255, 0xfe]);
+
+        assert!(apply_byte_patches(&content, &[Patch::bytes("4:9", Bytes::new())]).is_err());
+        assert!(apply_byte_patches(&content, &[Patch::text("[0:0]", "x")]).is_err());
+    }
+
+    #[test]
+    fn test_diff_bytes_round_trips() {
+        let old = [0u8, 1, 2, 3, 4, 0xff];
+        let new = [0u8, 1, 0x80, 0x81, 0x82, 4, 0xff];
+        let patch = diff_bytes(&old, &new).unwrap();
+        assert_eq!(patch.range, "2:4");
+        assert_eq!(&apply_byte_patches(&old, &[patch]).unwrap()[..], &new);
+        assert!(diff_bytes(&old, &old).is_none());
+    }
+}
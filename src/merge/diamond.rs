@@ -44,19 +44,130 @@
 //! doc.add_insert(0, "hello");
 //!
 //! // Concurrent edit from another client
-//! doc.add_insert_remote("editor-2", 5, " world");
+//! doc.add_insert_remote("editor-2", 5, " world").unwrap();
 //!
 //! // Automatically merged without conflict
 //! assert_eq!(doc.content(), "hello world");
 //! ```
 //!
+//! # Braid Versions
+//!
+//! Every diamond-types operation is identified by an `(agent, seq)` pair that is the
+//! same on every peer. Braid `Version`s are derived from the oplog frontier by naming
+//! the last operation of each tip as `"{agent}-{seq}"`:
+//!
+//! | Document history | `Version` |
+//! |------------------|-----------|
+//! | Empty | *(none)* |
+//! | `alice` inserts `"hello"` | `"alice-4"` |
+//! | `bob` then inserts `"!"` concurrently | `"alice-4", "bob-0"` |
+//!
+//! Concurrent tips produce one entry each, sorted so that peers holding the same
+//! operations report identical versions. [`DiamondCRDT::to_local_version`] maps Braid
+//! versions back to diamond-types local versions.
+//!
+//! ```
+//! use braid_axum_http::merge::DiamondCRDT;
+//! use braid_axum_http::Version;
+//!
+//! let mut doc = DiamondCRDT::new("alice");
+//! doc.add_insert(0, "hello");
+//! assert_eq!(doc.version(), vec![Version::new("alice-4")]);
+//! ```
+//!
+//! # Binary Oplog Encoding
+//!
+//! The JSON from [`DiamondCRDT::export_operations`] only describes the current state.
+//! To persist a document or sync it with a peer, encode the oplog itself using
+//! diamond-types' compact binary format:
+//!
+//! | Method | Contents |
+//! |--------|----------|
+//! | [`DiamondCRDT::encode`] | The full history |
+//! | [`DiamondCRDT::encode_since`] | Only operations not contained in a Braid version |
+//! | [`DiamondCRDT::merge_encoded`] | Merges either of the above into a document |
+//!
+//! ```
+//! use braid_axum_http::merge::DiamondCRDT;
+//!
+//! let mut server = DiamondCRDT::new("server");
+//! server.add_insert(0, "hello");
+//! let mut replica = DiamondCRDT::from_encoded("replica", &server.encode()).unwrap();
+//!
+//! let seen = server.version();
+//! server.add_insert(5, " world");
+//! replica.merge_encoded(&server.encode_since(&seen).unwrap()).unwrap();
+//! assert_eq!(replica.content(), "hello world");
+//! ```
+//!
+//! # Undo and Redo
+//!
+//! Every edit is recorded as a change by the agent that made it: one call to
+//! [`DiamondCRDT::add_insert`], [`DiamondCRDT::apply_patches_at`], and so on.
+//! [`DiamondCRDT::undo`] reverts an agent's most recent change by adding inverse
+//! operations at the current version, keeping edits by other agents, and
+//! [`DiamondCRDT::redo`] reverts the undo. The operations are ordinary edits by that
+//! agent, returned as an `Update` to broadcast.
+//!
+//! ```
+//! use braid_axum_http::merge::DiamondCRDT;
+//!
+//! let mut doc = DiamondCRDT::new("alice");
+//! doc.add_insert(0, "hello");
+//! doc.add_insert_remote("bob", 5, "!").unwrap();
+//! doc.add_insert(5, " world");
+//!
+//! doc.undo("alice");
+//! assert_eq!(doc.content(), "hello!");
+//! doc.redo("alice");
+//! assert_eq!(doc.content(), "hello world!");
+//! ```
+//!
+//! # Cursors
+//!
+//! Editors hold cursors and selections as positions in the text they last saw.
+//! [`DiamondCRDT::transform_position`] and [`DiamondCRDT::transform_range`] map them
+//! from that version to any other by following the characters they sit next to, so
+//! they stay on the same text while remote edits arrive.
+//!
+//! # Attribution
+//!
+//! Every character remembers the agent and operation that inserted it.
+//! [`DiamondCRDT::blame`] splits the text into [`AuthorSpan`]s, each naming the agent
+//! that inserted it and the Braid version that did, for review tools and
+//! per-author highlighting.
+//!
+//! # Performance
+//!
+//! The branch always sits at the tip of the oplog. Edits made at the tip, which is
+//! where local typing and up-to-date peers make them, are applied to the branch
+//! directly, and merged oplogs advance it by merging only the new operations. The
+//! update returned by [`MergeType::apply`] is built from the new operations alone, so
+//! the cost of an edit at the tip doesn't grow with the document's history. Edits at
+//! older parents are merged the same way, but first check out the text at those
+//! parents to convert their positions.
+//! Several patches passed to [`DiamondCRDT::apply_patches_at`] are applied as one
+//! batch. Run `cargo bench --bench diamond` to measure this on long histories.
+//!
 //! # Specification References
 //!
 //! - **draft-toomim-httpbis-braid-http**: Section 2.2 (Merge-Types)
 //! - **diamond-types**: <https://docs.rs/diamond-types/>
 
 use std::collections::HashMap;
+use bytes::Bytes;
 use serde_json::{json, Value};
+use super::text::{format_text_range, parse_text_patch_in, splice_chars, PositionUnit, Stickiness};
+use super::merge_type::preview_text_patches;
+use super::MergeType;
+use crate::error::{BraidError, Result};
+use crate::protocol::{self, merge_types};
+use diamond_types::list::encoding::{EncodeOptions, ENCODE_FULL, ENCODE_PATCH};
+use diamond_types::list::operation::{OpKind, Operation};
+use diamond_types::list::remote_ids::RemoteId;
+use diamond_types::AgentId;
+use diamond_types::list::encoding::encode_tools::ParseError;
+use crate::types::{Patch, Update, Version};
 
 /// High-performance text CRDT wrapper for collaborative editing.
 ///
@@ -91,6 +202,42 @@ pub struct DiamondCRDT {
     /// Tracks remote agent IDs and their latest sequence numbers
     /// (useful for detecting and filtering duplicate operations)
     remote_versions: HashMap<String, u32>,
+
+    /// How positions passed in and out of this document are counted
+    position_unit: PositionUnit,
+
+    /// Undo and redo stacks for each agent that has edited this document
+    undo_history: HashMap<String, UndoHistory>,
+
+    /// Deleted character (by insert time) → the character an undo or redo inserted
+    /// in its place
+    restored: HashMap<usize, usize>,
+
+    /// Replays of the oplog for undo, blame and position mapping
+    replays: ReplayCache,
+}
+
+/// A run of text inserted by one agent, from [`DiamondCRDT::blame`].
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct AuthorSpan {
+    /// Start of the span in the text
+    pub start: usize,
+
+    /// End of the span (exclusive)
+    pub end: usize,
+
+    /// Agent that inserted the span
+    pub agent: String,
+
+    /// Earliest version containing the whole span: that of its last inserted character
+    pub version: Version,
+}
+
+/// An agent's changes, as ranges of local times, most recent last.
+#[derive(Clone, Debug, Default)]
+struct UndoHistory {
+    undo: Vec<std::ops::Range<usize>>,
+    redo: Vec<std::ops::Range<usize>>,
 }
 
 impl DiamondCRDT {
@@ -127,9 +274,47 @@ impl DiamondCRDT {
             oplog,
             branch,
             remote_versions: HashMap::new(),
+            position_unit: PositionUnit::Char,
+            undo_history: HashMap::new(),
+            restored: HashMap::new(),
+            replays: ReplayCache::default(),
         }
     }
 
+    /// Set the unit positions are measured in (builder style).
+    ///
+    /// Every position passed to or returned from this document, and every plain `text`
+    /// patch, uses this unit. The default is [`PositionUnit::Char`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use braid_axum_http::merge::{DiamondCRDT, PositionUnit};
+    ///
+    /// let mut doc = DiamondCRDT::new("browser").with_position_unit(PositionUnit::Utf16);
+    /// doc.add_insert(0, "😀!");
+    /// doc.add_insert(2, "?"); // after the emoji, in UTF-16 code units
+    /// assert_eq!(doc.content(), "😀?!");
+    /// ```
+    #[must_use]
+    pub fn with_position_unit(mut self, unit: PositionUnit) -> Self {
+        self.position_unit = unit;
+        self
+    }
+
+    /// Set the unit positions are measured in.
+    #[inline]
+    pub fn set_position_unit(&mut self, unit: PositionUnit) {
+        self.position_unit = unit;
+    }
+
+    /// Get the unit positions are measured in.
+    #[inline]
+    #[must_use]
+    pub fn position_unit(&self) -> PositionUnit {
+        self.position_unit
+    }
+
     // ========== Local Editing Methods ==========
 
     /// Insert text at a position in the document.
@@ -139,12 +324,13 @@ impl DiamondCRDT {
     ///
     /// # Arguments
     ///
-    /// * `pos` - Position to insert at (0-based, in Unicode characters)
+    /// * `pos` - Position to insert at (0-based, in the document's [position unit](Self::position_unit))
     /// * `text` - Text content to insert (must be valid UTF-8)
     ///
     /// # Panics
     ///
-    /// Panics if `pos` exceeds the document length, matching `str::insert()` behavior.
+    /// Panics if `pos` exceeds the document length or falls inside a character, matching
+    /// `str::insert()` behavior.
     ///
     /// # Examples
     ///
@@ -157,9 +343,10 @@ impl DiamondCRDT {
     /// assert_eq!(doc.content(), "hello world");
     /// ```
     pub fn add_insert(&mut self, pos: usize, text: &str) {
-        let agent = self.oplog.get_or_create_agent_id(&self.agent_id);
-        self.oplog.add_insert(agent, pos, text);
-        self.branch = diamond_types::list::Branch::new_at_tip(&self.oplog);
+        let agent_id = self.agent_id.clone();
+        if let Err(e) = self.insert_at_tip(&agent_id, pos, text) {
+            panic!("{}", e);
+        }
     }
 
     /// Delete a range of characters from the document.
@@ -169,11 +356,12 @@ impl DiamondCRDT {
     ///
     /// # Arguments
     ///
-    /// * `range` - Character range to delete (exclusive end, in Unicode characters)
+    /// * `range` - Range to delete (exclusive end, in the document's position unit)
     ///
     /// # Panics
     ///
-    /// Panics if the range exceeds document bounds or is invalid (start > end).
+    /// Panics if the range exceeds document bounds, splits a character, or is invalid
+    /// (start > end).
     ///
     /// # Examples
     ///
@@ -186,9 +374,10 @@ impl DiamondCRDT {
     /// assert_eq!(doc.content(), "helloworld");
     /// ```
     pub fn add_delete(&mut self, range: std::ops::Range<usize>) {
-        let agent = self.oplog.get_or_create_agent_id(&self.agent_id);
-        self.oplog.add_delete_without_content(agent, range);
-        self.branch = diamond_types::list::Branch::new_at_tip(&self.oplog);
+        let agent_id = self.agent_id.clone();
+        if let Err(e) = self.delete_at_tip(&agent_id, range) {
+            panic!("{}", e);
+        }
     }
 
     // ========== Remote Editing Methods ==========
@@ -201,7 +390,7 @@ impl DiamondCRDT {
     /// # Arguments
     ///
     /// * `agent_id` - Unique ID of the remote peer (must differ from local agent ID)
-    /// * `pos` - Position to insert at (in Unicode characters)
+    /// * `pos` - Position to insert at (in the document's position unit)
     /// * `text` - Text content to insert
     ///
     /// # Note
@@ -209,6 +398,11 @@ impl DiamondCRDT {
     /// If the `agent_id` matches the local agent ID, this is treated as a separate operation
     /// and will result in duplicate content. Always use unique agent IDs.
     ///
+    /// # Errors
+    ///
+    /// Returns [`BraidError::InvalidPatch`] if `pos` exceeds the document length or
+    /// falls inside a character.
+    ///
     /// # Examples
     ///
     /// ```
@@ -218,13 +412,12 @@ impl DiamondCRDT {
     /// doc.add_insert(0, "hello");
     ///
     /// // Remote peer inserts at the end
-    /// doc.add_insert_remote("session-2", 5, " world");
+    /// doc.add_insert_remote("session-2", 5, " world").unwrap();
     /// assert_eq!(doc.content(), "hello world");
+    /// assert!(doc.add_insert_remote("session-2", 20, "!").is_err());
     /// ```
-    pub fn add_insert_remote(&mut self, agent_id: &str, pos: usize, text: &str) {
-        let agent = self.oplog.get_or_create_agent_id(agent_id);
-        self.oplog.add_insert(agent, pos, text);
-        self.branch = diamond_types::list::Branch::new_at_tip(&self.oplog);
+    pub fn add_insert_remote(&mut self, agent_id: &str, pos: usize, text: &str) -> Result<()> {
+        self.insert_at_tip(agent_id, pos, text)
     }
 
     /// Apply a deletion from a remote peer.
@@ -235,7 +428,12 @@ impl DiamondCRDT {
     /// # Arguments
     ///
     /// * `agent_id` - Unique ID of the remote peer
-    /// * `range` - Character range to delete (exclusive end)
+    /// * `range` - Range to delete (exclusive end, in the document's position unit)
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BraidError::InvalidPatch`] if the range exceeds the document, splits a
+    /// character, or is invalid (start > end).
     ///
     /// # Examples
     ///
@@ -245,13 +443,233 @@ impl DiamondCRDT {
     /// let mut doc = DiamondCRDT::new("session-1");
     /// doc.add_insert(0, "hello world");
     ///
-    /// doc.add_delete_remote("session-2", 5..6);  // Delete the space
+    /// doc.add_delete_remote("session-2", 5..6).unwrap();  // Delete the space
     /// assert_eq!(doc.content(), "helloworld");
     /// ```
-    pub fn add_delete_remote(&mut self, agent_id: &str, range: std::ops::Range<usize>) {
+    pub fn add_delete_remote(&mut self, agent_id: &str, range: std::ops::Range<usize>) -> Result<()> {
+        self.delete_at_tip(agent_id, range)
+    }
+
+    /// Apply an insertion from a remote peer, made against the version `parents`.
+    ///
+    /// Unlike [`add_insert_remote`](Self::add_insert_remote), `pos` is interpreted in
+    /// the document as it was at `parents`, and diamond-types transforms it past any
+    /// concurrent edits this document already has.
+    ///
+    /// # Arguments
+    ///
+    /// * `agent_id` - Unique ID of the remote peer
+    /// * `parents` - Braid version the peer edited (empty for the empty document)
+    /// * `pos` - Position to insert at, in the document at `parents`
+    /// * `text` - Text content to insert
+    ///
+    /// # Returns
+    ///
+    /// The Braid version of the new insertion.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BraidError::InvalidVersion`] if `parents` is unknown, or
+    /// [`BraidError::HeaderParse`] if `pos` is past the end of the document at `parents`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use braid_axum_http::merge::DiamondCRDT;
+    ///
+    /// let mut doc = DiamondCRDT::new("server");
+    /// doc.add_insert(0, "world");
+    /// let base = doc.version();
+    /// doc.add_insert(0, "hello ");
+    ///
+    /// // A peer that only saw "world" appends "!"
+    /// doc.add_insert_at("peer", &base, 5, "!").unwrap();
+    /// assert_eq!(doc.content(), "hello world!");
+    /// ```
+    pub fn add_insert_at(
+        &mut self,
+        agent_id: &str,
+        parents: &[Version],
+        pos: usize,
+        text: &str,
+    ) -> Result<Vec<Version>> {
+        self.apply_patches_at(
+            agent_id,
+            parents,
+            &[Patch::text(format_text_range(pos, pos), text.to_string())],
+        )
+    }
+
+    /// Apply a deletion from a remote peer, made against the version `parents`.
+    ///
+    /// `range` is interpreted in the document as it was at `parents`; see
+    /// [`add_insert_at`](Self::add_insert_at).
+    ///
+    /// # Returns
+    ///
+    /// The Braid version of the new deletion.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BraidError::InvalidVersion`] if `parents` is unknown, or
+    /// [`BraidError::HeaderParse`] if `range` exceeds the document at `parents`.
+    pub fn add_delete_at(
+        &mut self,
+        agent_id: &str,
+        parents: &[Version],
+        range: std::ops::Range<usize>,
+    ) -> Result<Vec<Version>> {
+        self.apply_patches_at(
+            agent_id,
+            parents,
+            &[Patch::text(format_text_range(range.start, range.end), "")],
+        )
+    }
+
+    /// Apply `text` range patches from a remote peer, made against the version `parents`.
+    ///
+    /// Each patch is interpreted against the result of the previous one, starting from
+    /// the document at `parents`. All patches are validated before any is applied.
+    /// Plain `text` patches are measured in the document's position unit; `text-chars`,
+    /// `text-utf16`, and `text-utf8` patches name their unit explicitly.
+    ///
+    /// # Returns
+    ///
+    /// The Braid version of the last operation added, or `parents` if the patches were
+    /// all empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BraidError::InvalidVersion`] if `parents` is unknown, or
+    /// [`BraidError::HeaderParse`] if a patch is malformed or out of range.
+    pub fn apply_patches_at(
+        &mut self,
+        agent_id: &str,
+        parents: &[Version],
+        patches: &[Patch],
+    ) -> Result<Vec<Version>> {
+        let frontier = self.to_local_version(parents)?;
+        let at_tip = frontier.as_slice() == self.branch.local_version_ref();
+
+        // Patches in another unit are converted against the text they apply to. Edits
+        // behind the tip also take the content of deletes from it; at the tip the
+        // branch supplies that content, so character patches need no copy of the text
+        let mut text = if !at_tip {
+            Some(self.oplog.checkout(&frontier).content().to_string())
+        } else if patches.iter().any(|patch| {
+            !matches!(PositionUnit::resolve(&patch.unit, self.position_unit), Ok(PositionUnit::Char))
+        }) {
+            Some(self.branch.content().to_string())
+        } else {
+            None
+        };
+        let mut len = self.branch.len();
+        let mut ops = Vec::with_capacity(patches.len());
+        for patch in patches {
+            let (unit, start, end, content) = parse_text_patch_in(patch, self.position_unit)?;
+            let (start, end) = match &mut text {
+                Some(text) => {
+                    let (start, end) = unit.range_to_chars(text, start, end)?;
+                    let deleted: String = text.chars().skip(start).take(end - start).collect();
+                    splice_chars(text, start, end, content);
+                    ops.push((start, end, Some(deleted), content));
+                    continue;
+                }
+                None => (start, end),
+            };
+            if end > len {
+                return Err(BraidError::HeaderParse(format!(
+                    "Range [{}:{}] exceeds document length {}",
+                    start, end, len
+                )));
+            }
+            len = len - (end - start) + content.chars().count();
+            ops.push((start, end, None, content));
+        }
+
         let agent = self.oplog.get_or_create_agent_id(agent_id);
-        self.oplog.add_delete_without_content(agent, range);
-        self.branch = diamond_types::list::Branch::new_at_tip(&self.oplog);
+        let start_time = self.oplog.len();
+        if at_tip {
+            for (start, end, _, text) in ops {
+                if end > start {
+                    self.branch.delete(&mut self.oplog, agent, start..end);
+                }
+                if !text.is_empty() {
+                    self.branch.insert(&mut self.oplog, agent, start, text);
+                }
+            }
+        } else {
+            let ops: Vec<Operation> = ops
+                .into_iter()
+                .flat_map(|(start, end, deleted, text)| {
+                    let delete = (end > start).then(|| {
+                        let deleted = deleted.unwrap_or_default();
+                        Operation::new_delete_with_content_range(start..end, deleted.into())
+                    });
+                    let insert = (!text.is_empty()).then(|| Operation::new_insert(start, text));
+                    delete.into_iter().chain(insert)
+                })
+                .collect();
+            if !ops.is_empty() {
+                self.oplog.add_operations_at(agent, &frontier, &ops);
+                self.merge_to_tip();
+            }
+        }
+        self.record_change(agent_id, start_time);
+        if self.oplog.len() == start_time {
+            return Ok(parents.to_vec());
+        }
+        Ok(self.to_braid_version(&[self.oplog.len() - 1]))
+    }
+
+    // ========== Undo Methods ==========
+
+    /// Revert `agent_id`'s most recent change that hasn't been undone.
+    ///
+    /// A change is the edit made by one call such as [`add_insert`](Self::add_insert),
+    /// [`add_delete_remote`](Self::add_delete_remote), or
+    /// [`apply_patches_at`](Self::apply_patches_at). The inverse operations are added
+    /// by `agent_id` at the current version: text the change inserted is deleted and
+    /// text it deleted is inserted again, while edits by other agents are kept. Text
+    /// already reverted by someone else is left alone, and changes with nothing left
+    /// to revert are skipped. The change can be restored with [`redo`](Self::redo).
+    ///
+    /// Undo history is kept in memory only: documents created with
+    /// [`from_encoded`](Self::from_encoded) start with none.
+    ///
+    /// # Returns
+    ///
+    /// The update describing the inverse operations, to be broadcast like any other
+    /// edit, or `None` if `agent_id` has nothing to undo.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use braid_axum_http::merge::DiamondCRDT;
+    ///
+    /// let mut doc = DiamondCRDT::new("alice");
+    /// doc.add_insert(0, "hello world");
+    /// doc.add_delete(0..6);
+    ///
+    /// let update = doc.undo("alice").unwrap();
+    /// assert_eq!(doc.content(), "hello world");
+    /// assert_eq!(update.version, doc.version());
+    /// assert!(doc.undo("bob").is_none());
+    /// ```
+    pub fn undo(&mut self, agent_id: &str) -> Option<Update> {
+        self.revert(agent_id, false)
+    }
+
+    /// Restore `agent_id`'s most recently undone change.
+    ///
+    /// Any new change by `agent_id` clears its redo history.
+    ///
+    /// # Returns
+    ///
+    /// The update describing the restoring operations, or `None` if `agent_id` has
+    /// nothing to redo.
+    pub fn redo(&mut self, agent_id: &str) -> Option<Update> {
+        self.revert(agent_id, true)
     }
 
     // ========== Query Methods ==========
@@ -354,176 +772,1586 @@ impl DiamondCRDT {
         self.oplog.is_empty()
     }
 
-    // ========== Serialization & Export Methods ==========
+    // ========== Braid Version Methods ==========
 
-    /// Export document state and metadata as JSON.
+    /// Get the current version as Braid `Version`s, one per concurrent tip.
     ///
-    /// Creates a JSON representation suitable for transmission via Braid-HTTP.
-    /// This includes current content, operation count, and a version identifier.
-    ///
-    /// # Returns
-    ///
-    /// A JSON object containing:
-    /// - `agent_id` (string): The session's agent ID
-    /// - `operations_count` (number): Total operations in the log
-    /// - `content` (string): Current merged document content
-    /// - `version` (string): Version identifier for this state
+    /// Returns an empty list for an empty document.
     ///
     /// # Examples
     ///
     /// ```
     /// use braid_axum_http::merge::DiamondCRDT;
+    /// use braid_axum_http::Version;
     ///
-    /// let mut doc = DiamondCRDT::new("session-1");
-    /// doc.add_insert(0, "hello");
-    /// let exported = doc.export_operations();
-    /// assert_eq!(exported["agent_id"], "session-1");
+    /// let mut doc = DiamondCRDT::new("alice");
+    /// assert!(doc.version().is_empty());
+    /// doc.add_insert(0, "hi");
+    /// assert_eq!(doc.version(), vec![Version::new("alice-1")]);
     /// ```
     #[must_use]
-    pub fn export_operations(&self) -> Value {
-        let version = format!(
-            "oplog-{}-{}",
-            self.agent_id,
-            self.oplog.len()
-        );
-
-        json!({
-            "agent_id": self.agent_id,
-            "operations_count": self.oplog.len(),
-            "content": self.content(),
-            "version": version,
-        })
+    pub fn version(&self) -> Vec<Version> {
+        self.to_braid_version(self.oplog.local_version_ref())
     }
 
-    /// Generate a version identifier for Braid-HTTP headers.
-    ///
-    /// Returns a unique string representing the current document state. Version
-    /// identifiers should change whenever the document is modified. They're used
-    /// in Braid protocol responses for version tracking and conflict detection.
+    /// Convert a diamond-types local version into Braid `Version`s.
     ///
-    /// # Format
+    /// Each local time is named `"{agent}-{seq}"`, and the result is sorted.
+    #[must_use]
+    pub fn to_braid_version(&self, local_version: &[usize]) -> Vec<Version> {
+        let mut versions: Vec<Version> = self
+            .oplog
+            .local_to_remote_version(local_version)
+            .iter()
+            .map(|id| Version::new(format!("{}-{}", id.agent, id.seq)))
+            .collect();
+        versions.sort();
+        versions
+    }
+
+    /// Convert Braid `Version`s into a diamond-types local version.
+    ///
+    /// Versions that are ancestors of other listed versions are dropped, so the
+    /// result is a proper frontier.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BraidError::InvalidVersion`] if a version is not of the form
+    /// `"{agent}-{seq}"` or names an operation this document doesn't have.
+    pub fn to_local_version(&self, versions: &[Version]) -> Result<Vec<usize>> {
+        let ids = versions
+            .iter()
+            .map(|version| {
+                parse_remote_id(version).ok_or_else(|| {
+                    BraidError::InvalidVersion(format!("Not a diamond version: {}", version))
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut frontier = Vec::new();
+        for id in &ids {
+            let time = self.oplog.try_remote_to_local_time(id).map_err(|_| {
+                BraidError::InvalidVersion(format!("Unknown version: {}-{}", id.agent, id.seq))
+            })?;
+            frontier = self.oplog.version_union(&frontier, &[time]).to_vec();
+        }
+        Ok(frontier)
+    }
+
+    /// Check whether this document contains the operation named by `version`.
+    #[must_use]
+    pub fn contains_version(&self, version: &Version) -> bool {
+        parse_remote_id(version)
+            .is_some_and(|id| self.oplog.try_remote_to_local_time(&id).is_ok())
+    }
+
+    /// Get the agent that made the operation named by a diamond version.
     ///
-    /// Returns strings of the form: `diamond-{agent_id}-{operation_count}`
+    /// Peers replicating a document must apply each update as this agent, so that
+    /// it gets the same version on every peer.
     ///
     /// # Examples
     ///
     /// ```
     /// use braid_axum_http::merge::DiamondCRDT;
+    /// use braid_axum_http::Version;
     ///
-    /// let mut doc = DiamondCRDT::new("session-1");
-    /// let v1 = doc.get_version();
-    /// doc.add_insert(0, "x");
-    /// let v2 = doc.get_version();
-    /// assert_ne!(v1, v2);
+    /// assert_eq!(DiamondCRDT::version_agent(&Version::new("agent-1-42")), Some("agent-1"));
+    /// assert_eq!(DiamondCRDT::version_agent(&Version::new("v1")), None);
     /// ```
     #[must_use]
-    pub fn get_version(&self) -> String {
-        format!("diamond-{}-{}", self.agent_id, self.oplog.len())
+    pub fn version_agent(version: &Version) -> Option<&str> {
+        let (agent, seq) = version.as_str()?.rsplit_once('-')?;
+        seq.parse::<usize>().is_ok().then_some(agent)
     }
 
-    /// Create a checkpoint snapshot of the current state.
+    /// Get the document content as it was at a Braid version.
     ///
-    /// Returns a complete snapshot of the document suitable for Braid responses.
-    /// This includes content, version identifier, agent ID, and operation count.
+    /// The full history is kept, so any version this document knows can be checked
+    /// out. An empty `version` yields the empty document before any edit.
     ///
-    /// # Returns
+    /// # Errors
     ///
-    /// A JSON object containing:
-    /// - `content` (string): Current document text
-    /// - `version` (string): Version identifier
-    /// - `agent_id` (string): Session agent ID
-    /// - `oplog_len` (number): Operation count
+    /// Returns [`BraidError::InvalidVersion`] if `version` names an unknown operation.
     ///
     /// # Examples
     ///
     /// ```
     /// use braid_axum_http::merge::DiamondCRDT;
     ///
-    /// let mut doc = DiamondCRDT::new("session-1");
+    /// let mut doc = DiamondCRDT::new("alice");
     /// doc.add_insert(0, "hello");
-    /// let checkpoint = doc.checkpoint();
-    /// assert_eq!(checkpoint["content"], "hello");
+    /// let v1 = doc.version();
+    /// doc.add_insert(5, " world");
+    ///
+    /// assert_eq!(doc.content_at(&v1).unwrap(), "hello");
+    /// assert_eq!(doc.content_at(&[]).unwrap(), "");
     /// ```
-    #[must_use]
-    pub fn checkpoint(&self) -> Value {
-        json!({
-            "content": self.content(),
-            "version": self.get_version(),
-            "agent_id": self.agent_id,
-            "oplog_len": self.oplog.len(),
-        })
+    pub fn content_at(&self, version: &[Version]) -> Result<String> {
+        let local = self.to_local_version(version)?;
+        Ok(self.oplog.checkout(&local).content().to_string())
     }
 
-    /// Estimate merge quality based on operation diversity.
+    // ========== Cursor Methods ==========
+
+    /// Map a position in the text at one version to the text at another.
     ///
-    /// Returns a heuristic score (0-100) indicating merge convergence quality.
-    /// Higher scores indicate better document stability and fewer conflicting edits.
+    /// The position follows the character it sticks to (see [`Stickiness`]), so it
+    /// stays on the same text through any edits between the versions, including
+    /// concurrent ones. If that character doesn't exist at `to`, because it was
+    /// deleted or `to` doesn't include its insertion, the position moves to where it
+    /// would be. Positions are in the document's [position unit](Self::position_unit).
     ///
-    /// # Heuristic
+    /// This replays the history up to both versions, continuing from replays kept by
+    /// earlier calls, so mapping from each newer version only costs the edits since.
+    /// Clients with the patches between the versions can use
+    /// [`transform_position`](crate::merge::transform_position) instead.
     ///
-    /// Currently based on the diversity of remote agents that have edited the document:
-    /// - 100: Only local edits (no remote agents)
-    /// - < 100: Remote agents present (diversity factor applied)
+    /// # Arguments
     ///
-    /// # Returns
+    /// * `pos` - Position in the text at `from`
+    /// * `from` - Version the position was taken at
+    /// * `to` - Version to map it to, such as the current [`version`](Self::version)
+    /// * `stickiness` - Which way the position moves for text inserted at it
     ///
-    /// An integer from 0 to 100, where 100 is best quality.
+    /// # Errors
+    ///
+    /// Returns [`BraidError::InvalidVersion`] if either version names an unknown
+    /// operation, and [`BraidError::HeaderParse`] if `pos` is outside the text at `from`
+    /// or falls inside a character.
     ///
     /// # Examples
     ///
     /// ```
-    /// use braid_axum_http::merge::DiamondCRDT;
+    /// use braid_axum_http::merge::{DiamondCRDT, Stickiness};
     ///
-    /// let doc = DiamondCRDT::new("session-1");
-    /// assert_eq!(doc.merge_quality(), 100);  // No remote edits yet
+    /// let mut doc = DiamondCRDT::new("alice");
+    /// doc.add_insert(0, "hello");
+    /// let seen = doc.version();
+    ///
+    /// // Bob types at the start and at Alice's cursor, after "hello"
+    /// doc.add_insert_remote("bob", 0, ">> ").unwrap();
+    /// doc.add_insert_remote("bob", 8, "!").unwrap();
+    ///
+    /// let now = doc.version();
+    /// assert_eq!(doc.transform_position(5, &seen, &now, Stickiness::Left).unwrap(), 8);
+    /// assert_eq!(doc.transform_position(5, &seen, &now, Stickiness::Right).unwrap(), 9);
     /// ```
-    #[inline]
-    #[must_use]
-    pub fn merge_quality(&self) -> u32 {
-        if self.remote_versions.is_empty() {
-            100
-        } else {
-            let remote_agents = self.remote_versions.len() as f64;
-            let diversity_factor = (remote_agents / (remote_agents + 1.0)) * 100.0;
-            (diversity_factor.max(0.0).min(100.0)) as u32
-        }
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
+    pub fn transform_position(
+        &self,
+        pos: usize,
+        from: &[Version],
+        to: &[Version],
+        stickiness: Stickiness,
+    ) -> Result<usize> {
+        let before = self.replay_at(&self.to_local_version(from)?);
+        let after = self.replay_at(&self.to_local_version(to)?);
 
-    #[test]
-    fn test_basic_insert() {
-        let mut crdt = DiamondCRDT::new("alice");
-        crdt.add_insert(0, "hello");
-        assert_eq!(crdt.content(), "hello");
-    }
+        let text = before.content();
+        let chars = self.position_unit.to_char_offset(&text, pos).ok_or_else(|| {
+            BraidError::HeaderParse(format!(
+                "Position {} is outside the text at {} or inside a character",
+                pos,
+                protocol::format_version_header(from)
+            ))
+        })?;
+        let visible: Vec<usize> = (0..before.items.len())
+            .filter(|&i| before.items[i].visible)
+            .collect();
 
-    #[test]
-    fn test_multiple_inserts() {
-        let mut crdt = DiamondCRDT::new("alice");
-        crdt.add_insert(0, "hello");
-        crdt.add_insert(5, " world");
-        assert_eq!(crdt.content(), "hello world");
+        // Find the first character, from the followed one outwards, that exists at `to`
+        let found = match stickiness {
+            Stickiness::Left => chars.checked_sub(1).and_then(|followed| {
+                before.items[..=visible[followed]]
+                    .iter()
+                    .rev()
+                    .find_map(|item| after.index.get(&item.time).copied())
+            }),
+            Stickiness::Right => visible.get(chars).and_then(|&followed| {
+                before.items[followed..]
+                    .iter()
+                    .find_map(|item| after.index.get(&item.time).copied())
+            }),
+        };
+        let mapped = match (stickiness, found) {
+            (Stickiness::Left, Some(i)) => after.visible_before(i + 1),
+            (Stickiness::Left, None) => 0,
+            (Stickiness::Right, Some(i)) => after.visible_before(i),
+            (Stickiness::Right, None) => after.visible_before(after.items.len()),
+        };
+        Ok(self.position_unit.to_unit_offset(&after.content(), mapped))
     }
 
-    #[test]
-    fn test_delete() {
-        let mut crdt = DiamondCRDT::new("alice");
-        crdt.add_insert(0, "hello world");
-        crdt.add_delete(5..6);
-        assert_eq!(crdt.content(), "helloworld");
+    /// Map a range, such as a selection, in the text at one version to the text at
+    /// another.
+    ///
+    /// Both ends move with the same stickiness; see
+    /// [`transform_position`](Self::transform_position).
+    ///
+    /// # Errors
+    ///
+    /// As [`transform_position`](Self::transform_position).
+    pub fn transform_range(
+        &self,
+        range: std::ops::Range<usize>,
+        from: &[Version],
+        to: &[Version],
+        stickiness: Stickiness,
+    ) -> Result<std::ops::Range<usize>> {
+        let start = self.transform_position(range.start, from, to, stickiness)?;
+        let end = self.transform_position(range.end, from, to, stickiness)?;
+        Ok(start..end)
     }
 
-    #[test]
-    fn test_concurrent_edits() {
-        let mut crdt = DiamondCRDT::new("alice");
-        crdt.add_insert(0, "hello");
-        crdt.add_insert_remote("bob", 5, " world");
-        assert_eq!(crdt.content(), "hello world");
-    }
+    // ========== Attribution Methods ==========
+
+    /// Attribute the current content to the agents that inserted it.
+    ///
+    /// See [`blame_at`](Self::blame_at).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use braid_axum_http::merge::DiamondCRDT;
+    ///
+    /// let mut doc = DiamondCRDT::new("alice");
+    /// doc.add_insert(0, "hello");
+    /// doc.add_insert_remote("bob", 5, " world").unwrap();
+    ///
+    /// let spans = doc.blame();
+    /// assert_eq!((spans[0].start, spans[0].end, spans[0].agent.as_str()), (0, 5, "alice"));
+    /// assert_eq!((spans[1].start, spans[1].end, spans[1].agent.as_str()), (5, 11, "bob"));
+    /// assert_eq!(spans[1].version, doc.version()[0]);
+    /// ```
+    #[must_use]
+    pub fn blame(&self) -> Vec<AuthorSpan> {
+        self.blame_local(self.oplog.local_version_ref())
+    }
+
+    /// Attribute the text at a version to the agents that inserted it.
+    ///
+    /// The text is split into spans of characters inserted together by one agent, in
+    /// document order, covering the whole text. Positions are in the document's
+    /// [position unit](Self::position_unit).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BraidError::InvalidVersion`] if `version` names an unknown operation.
+    pub fn blame_at(&self, version: &[Version]) -> Result<Vec<AuthorSpan>> {
+        Ok(self.blame_local(&self.to_local_version(version)?))
+    }
+
+    fn blame_local(&self, version: &[usize]) -> Vec<AuthorSpan> {
+        // Span, with the latest insert time in it
+        let mut spans: Vec<(AuthorSpan, usize)> = Vec::new();
+        let mut last_time = None;
+        let mut pos = 0;
+        for item in self.replay_at(version).items.iter().filter(|item| item.visible) {
+            let len = self.position_unit.len(item.ch.encode_utf8(&mut [0; 4]));
+            let agent = self.oplog.local_to_remote_time(item.time).agent;
+            // Runs typed backwards are stored in reverse, so adjacent times go either way
+            let together = last_time.is_some_and(|last: usize| last.abs_diff(item.time) == 1);
+            match spans.last_mut() {
+                Some((span, latest)) if together && span.agent == agent.as_str() => {
+                    span.end = pos + len;
+                    *latest = (*latest).max(item.time);
+                }
+                _ => spans.push((
+                    AuthorSpan {
+                        start: pos,
+                        end: pos + len,
+                        agent: agent.to_string(),
+                        version: Version::new(""),
+                    },
+                    item.time,
+                )),
+            }
+            last_time = Some(item.time);
+            pos += len;
+        }
+
+        spans
+            .into_iter()
+            .map(|(span, latest)| AuthorSpan {
+                version: self.to_braid_version(&[latest]).remove(0),
+                ..span
+            })
+            .collect()
+    }
+
+    // ========== Serialization & Export Methods ==========
+
+    /// Export document state and metadata as JSON.
+    ///
+    /// Creates a JSON representation suitable for transmission via Braid-HTTP.
+    /// This includes current content, operation count, and a version identifier.
+    ///
+    /// # Returns
+    ///
+    /// A JSON object containing:
+    /// - `agent_id` (string): The session's agent ID
+    /// - `operations_count` (number): Total operations in the log
+    /// - `content` (string): Current merged document content
+    /// - `version` (string): Version identifier for this state
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use braid_axum_http::merge::DiamondCRDT;
+    ///
+    /// let mut doc = DiamondCRDT::new("session-1");
+    /// doc.add_insert(0, "hello");
+    /// let exported = doc.export_operations();
+    /// assert_eq!(exported["agent_id"], "session-1");
+    /// ```
+    #[must_use]
+    pub fn export_operations(&self) -> Value {
+        json!({
+            "agent_id": self.agent_id,
+            "operations_count": self.oplog.len(),
+            "content": self.content(),
+            "version": self.get_version(),
+        })
+    }
+
+    // ========== Binary Encoding Methods ==========
+
+    /// Create a document from a binary oplog produced by [`encode`](Self::encode).
+    ///
+    /// # Arguments
+    ///
+    /// * `agent_id` - Session-unique identifier for local edits
+    /// * `data` - Encoded oplog
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BraidError::BodyParse`] if `data` is not a valid encoded oplog, or
+    /// [`BraidError::InvalidVersion`] if it is an incremental export.
+    pub fn from_encoded(agent_id: impl Into<String>, data: &[u8]) -> Result<Self> {
+        let mut doc = Self::new(agent_id);
+        doc.merge_encoded(data)?;
+        Ok(doc)
+    }
+
+    /// Encode the full oplog in diamond-types' binary format.
+    ///
+    /// The result is lossless: [`from_encoded`](Self::from_encoded) reconstructs the
+    /// same content, history, and versions. Deleted text is included.
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        self.oplog.encode(EncodeOptions {
+            store_deleted_content: true,
+            ..ENCODE_FULL
+        })
+    }
+
+    /// Encode only the operations a peer at `since` is missing.
+    ///
+    /// The peer must already have every operation in `since` to merge the result.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BraidError::InvalidVersion`] if `since` names an unknown operation.
+    pub fn encode_since(&self, since: &[Version]) -> Result<Vec<u8>> {
+        let since = self.to_local_version(since)?;
+        let options = EncodeOptions {
+            store_deleted_content: true,
+            ..ENCODE_PATCH
+        };
+        Ok(self.oplog.encode_from(options, &since))
+    }
+
+    /// Merge a binary oplog (full or incremental) into this document.
+    ///
+    /// Operations the document already has are ignored. On error the document is left
+    /// unchanged.
+    ///
+    /// # Returns
+    ///
+    /// The version of the merged data, which may differ from the document's version.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BraidError::InvalidVersion`] if the data builds on operations this
+    /// document doesn't have, or [`BraidError::BodyParse`] if it is malformed.
+    pub fn merge_encoded(&mut self, data: &[u8]) -> Result<Vec<Version>> {
+        let merged = self.oplog.decode_and_add(data).map_err(|e| match e {
+            ParseError::BaseVersionUnknown => BraidError::InvalidVersion(
+                "Encoded oplog builds on operations this document doesn't have".to_string(),
+            ),
+            e => BraidError::BodyParse(format!("Invalid diamond oplog: {:?}", e)),
+        })?;
+        self.merge_to_tip();
+        Ok(self.to_braid_version(&merged))
+    }
+
+    // ========== History Patch Methods ==========
+
+    /// Describe the edits a peer at `since` is missing as `text` patch updates.
+    ///
+    /// Operations are grouped into one update per Braid version: a run of operations
+    /// by one agent, each building on the previous. A run is split where it deletes
+    /// text it typed, so that each update holds one operation per character and
+    /// applying it elsewhere reproduces its version. Every update carries its
+    /// `Version`, its `Parents`, and patches positioned against the document at those
+    /// parents, so applying the updates in order reproduces the current content.
+    ///
+    /// Positions are measured in the document's [position unit](Self::position_unit).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BraidError::InvalidVersion`] if `since` names an unknown operation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use braid_axum_http::merge::DiamondCRDT;
+    /// use braid_axum_http::{Patch, Version};
+    ///
+    /// let mut doc = DiamondCRDT::new("alice");
+    /// doc.add_insert(0, "helo");
+    /// let seen = doc.version();
+    /// doc.add_insert(3, "l");
+    ///
+    /// let updates = doc.updates_since(&seen).unwrap();
+    /// assert_eq!(updates[0].version, vec![Version::new("alice-4")]);
+    /// assert_eq!(updates[0].parents, seen);
+    /// assert_eq!(updates[0].patches, Some(vec![Patch::text("[3:3]", "l")]));
+    /// ```
+    pub fn updates_since(&self, since: &[Version]) -> Result<Vec<Update>> {
+        self.updates_since_in(since, self.position_unit)
+    }
+
+    /// [`updates_since`](Self::updates_since) with positions measured in `unit`.
+    ///
+    /// Patches are labelled `text` if `unit` is the document's own unit, and with the
+    /// explicit unit (e.g. `text-utf16`) otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BraidError::InvalidVersion`] if `since` names an unknown operation.
+    pub fn updates_since_in(&self, since: &[Version], unit: PositionUnit) -> Result<Vec<Update>> {
+        let since = self.to_local_version(since)?;
+        Ok(self.updates_since_local(&since, unit))
+    }
+
+    /// [`updates_since_in`](Self::updates_since_in) for a diamond-types local version.
+    fn updates_since_local(&self, since: &[usize], unit: PositionUnit) -> Vec<Update> {
+        self.group_updates(0, self.oplog.iter(), since, unit)
+    }
+
+    /// The updates for operations added after `before`, the tip at the time.
+    ///
+    /// Only the new operations are visited, so this costs the same however long the
+    /// history before them is.
+    fn updates_after(&self, before: &[usize], unit: PositionUnit) -> Vec<Update> {
+        // Every operation up to the tip's latest is in `before`
+        let start = before.iter().max().map_or(0, |time| time + 1);
+        self.group_updates(start, self.oplog.iter_range_since(before), &[], unit)
+    }
+
+    /// Group the operations `ops`, starting at local time `start`, into one update per
+    /// Braid version, skipping those already in `since`.
+    fn group_updates(
+        &self,
+        start: usize,
+        ops: impl Iterator<Item = Operation>,
+        since: &[usize],
+        unit: PositionUnit,
+    ) -> Vec<Update> {
+        if start >= self.oplog.len() {
+            return Vec::new();
+        }
+        let range = (start..self.oplog.len()).into();
+        // (end time, agent) for each run of operations by one agent
+        let mut agents = self
+            .oplog
+            .iter_mappings_range(range)
+            .scan(start, |end, span| {
+                *end += span.seq_range.end - span.seq_range.start;
+                Some((*end, span.agent))
+            })
+            .peekable();
+        let mut history = self.oplog.iter_history_range(range).peekable();
+
+        let mut groups: Vec<PatchGroup> = Vec::new();
+        let mut time = start;
+        for op in ops {
+            let mut chars = op.content_as_str().unwrap_or_default().chars();
+            for offset in 0..op.end() - op.start() {
+                let t = time;
+                time += 1;
+                let ch = chars.next().unwrap_or_default();
+
+                while agents.next_if(|(end, _)| *end <= t).is_some() {}
+                while history.next_if(|entry| entry.span.end <= t).is_some() {}
+                if !since.is_empty() && self.oplog.version_contains_time(since, t) {
+                    continue;
+                }
+                let Some(&(_, agent)) = agents.peek() else {
+                    break;
+                };
+                let parents = match history.peek() {
+                    Some(entry) if entry.span.start == t => entry.parents.to_vec(),
+                    _ => vec![t - 1],
+                };
+
+                // Runs are stored compactly: a forward insert run types left to right,
+                // a reverse one types at a fixed position; a forward delete run
+                // deletes at a fixed position, a reverse one backspaces
+                let pos = match (op.kind, op.loc.fwd) {
+                    (OpKind::Ins, true) => op.start() + offset,
+                    (OpKind::Ins, false) | (OpKind::Del, true) => op.start(),
+                    (OpKind::Del, false) => op.end() - offset - 1,
+                };
+                let group = match groups.last_mut() {
+                    // Deleting text the group typed would merge both operations away,
+                    // so the update would no longer reproduce its version
+                    Some(group)
+                        if group.agent == agent
+                            && parents == [group.last_time]
+                            && !(op.kind == OpKind::Del && group.typed_at(pos)) =>
+                    {
+                        group
+                    }
+                    _ => {
+                        groups.push(PatchGroup {
+                            agent,
+                            last_time: t,
+                            parents,
+                            patches: Vec::new(),
+                        });
+                        groups.last_mut().unwrap()
+                    }
+                };
+                group.last_time = t;
+                match op.kind {
+                    OpKind::Ins => group.insert(pos, ch),
+                    OpKind::Del => group.delete(pos),
+                }
+            }
+        }
+
+        let label = if unit == self.position_unit {
+            "text"
+        } else {
+            unit.range_unit()
+        };
+        groups
+            .into_iter()
+            .map(|group| {
+                // Character offsets are converted against the text each patch applies to
+                let mut text = (unit != PositionUnit::Char)
+                    .then(|| self.oplog.checkout(&group.parents).content().to_string());
+                let patches = group
+                    .patches
+                    .into_iter()
+                    .map(|patch| {
+                        let (start, end) = match &mut text {
+                            Some(text) => {
+                                let range = (
+                                    unit.to_unit_offset(text, patch.start),
+                                    unit.to_unit_offset(text, patch.end),
+                                );
+                                splice_chars(text, patch.start, patch.end, &patch.text);
+                                range
+                            }
+                            None => (patch.start, patch.end),
+                        };
+                        Patch::new(label, format_text_range(start, end), patch.text)
+                    })
+                    .collect();
+
+                Update {
+                    version: self.to_braid_version(&[group.last_time]),
+                    parents: self.to_braid_version(&group.parents),
+                    patches: Some(patches),
+                    merge_type: Some(merge_types::DIAMOND.to_string()),
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+
+    // ========== Version Identifier Methods ==========
+
+    /// Generate a version identifier for Braid-HTTP headers.
+    ///
+    /// Returns the current [`version`](Self::version) formatted as a `Version` header
+    /// value. Peers holding the same operations return the same string.
+    ///
+    /// # Format
+    ///
+    /// Returns strings of the form: `"{agent}-{seq}"`, with one comma-separated entry
+    /// per concurrent tip (empty for an empty document)
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use braid_axum_http::merge::DiamondCRDT;
+    ///
+    /// let mut doc = DiamondCRDT::new("session-1");
+    /// let v1 = doc.get_version();
+    /// doc.add_insert(0, "x");
+    /// let v2 = doc.get_version();
+    /// assert_ne!(v1, v2);
+    /// ```
+    #[must_use]
+    pub fn get_version(&self) -> String {
+        protocol::format_version_header(&self.version())
+    }
+
+    /// Create a checkpoint snapshot of the current state.
+    ///
+    /// Returns a complete snapshot of the document suitable for Braid responses.
+    /// This includes content, version identifier, agent ID, and operation count.
+    ///
+    /// # Returns
+    ///
+    /// A JSON object containing:
+    /// - `content` (string): Current document text
+    /// - `version` (string): Version identifier
+    /// - `agent_id` (string): Session agent ID
+    /// - `oplog_len` (number): Operation count
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use braid_axum_http::merge::DiamondCRDT;
+    ///
+    /// let mut doc = DiamondCRDT::new("session-1");
+    /// doc.add_insert(0, "hello");
+    /// let checkpoint = doc.checkpoint();
+    /// assert_eq!(checkpoint["content"], "hello");
+    /// ```
+    #[must_use]
+    pub fn checkpoint(&self) -> Value {
+        json!({
+            "content": self.content(),
+            "version": self.get_version(),
+            "agent_id": self.agent_id,
+            "oplog_len": self.oplog.len(),
+        })
+    }
+
+    /// Estimate merge quality based on operation diversity.
+    ///
+    /// Returns a heuristic score (0-100) indicating merge convergence quality.
+    /// Higher scores indicate better document stability and fewer conflicting edits.
+    ///
+    /// # Heuristic
+    ///
+    /// Currently based on the diversity of remote agents that have edited the document:
+    /// - 100: Only local edits (no remote agents)
+    /// - < 100: Remote agents present (diversity factor applied)
+    ///
+    /// # Returns
+    ///
+    /// An integer from 0 to 100, where 100 is best quality.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use braid_axum_http::merge::DiamondCRDT;
+    ///
+    /// let doc = DiamondCRDT::new("session-1");
+    /// assert_eq!(doc.merge_quality(), 100);  // No remote edits yet
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn merge_quality(&self) -> u32 {
+        if self.remote_versions.is_empty() {
+            100
+        } else {
+            let remote_agents = self.remote_versions.len() as f64;
+            let diversity_factor = (remote_agents / (remote_agents + 1.0)) * 100.0;
+            diversity_factor.clamp(0.0, 100.0) as u32
+        }
+    }
+}
+
+// ========== MergeType ==========
+
+impl MergeType for DiamondCRDT {
+    fn name(&self) -> &str {
+        merge_types::DIAMOND
+    }
+
+    /// Adopt the creating agent's ID if the document is still empty.
+    fn initialize(&mut self, _resource_id: &str, agent_id: &str) -> Result<()> {
+        if self.is_empty() {
+            *self = DiamondCRDT::new(agent_id).with_position_unit(self.position_unit);
+        }
+        Ok(())
+    }
+
+    /// Apply an update from `agent_id`.
+    ///
+    /// Accepts three forms:
+    ///
+    /// - `text` range patches (or a body with a `text` content range), applied at the
+    ///   update's `Parents` as remote deletes and inserts; missing parents mean the
+    ///   current version
+    /// - A JSON body `{"inserts": [{pos, text}], "deletes": [{start, end}]}`;
+    ///   malformed or out-of-range operations are skipped
+    /// - Any other body, inserted by `agent_id` at position 0 of the text at `Parents`
+    ///
+    /// Edits are returned as `text` patches tagged with the new operations' Braid
+    /// version and parents (see [`updates_since`](DiamondCRDT::updates_since)), in the
+    /// same position unit as the incoming patches. An
+    /// update whose `Version` is already in the oplog is not re-applied and yields a
+    /// snapshot.
+    fn apply(&mut self, update: &Update, agent_id: &str) -> Result<Update> {
+        let parents = self.version();
+        if update.primary_version().is_some_and(|v| self.contains_version(v)) {
+            return Ok(self.snapshot());
+        }
+        let before = self.oplog.local_version().to_vec();
+
+        let edit_parents = if update.parents.is_empty() {
+            &parents
+        } else {
+            &update.parents
+        };
+        let patches = match (&update.patches, &update.body, &update.content_range) {
+            (Some(patches), _, _) => Some(patches.clone()),
+            (None, Some(body), Some(range)) => Some(vec![Patch::new(
+                range.unit.clone(),
+                range.range.clone(),
+                body.clone(),
+            )]),
+            _ => None,
+        };
+        if let Some(patches) = patches {
+            self.apply_patches_at(agent_id, edit_parents, &patches)?;
+            let unit = patches.first().map_or(self.position_unit, |patch| {
+                PositionUnit::resolve(&patch.unit, self.position_unit).unwrap_or(self.position_unit)
+            });
+            return Ok(self.resolved_update(&before, parents, unit));
+        }
+
+        if let Some(body_bytes) = &update.body {
+            let body_str = std::str::from_utf8(body_bytes).map_err(|e| {
+                BraidError::BodyParse(format!("Invalid UTF-8 in diamond body: {}", e))
+            })?;
+
+            if body_str.starts_with('{') && body_str.ends_with('}') {
+                if let Ok(operations) = serde_json::from_str::<Value>(body_str) {
+                    if operations.is_object() {
+                        self.apply_json_operations(&operations, agent_id);
+                        let response_body = json!({
+                            "content": self.content(),
+                            "merge_quality": self.merge_quality(),
+                            "agents": [agent_id],
+                        })
+                        .to_string();
+                        return Ok(Update {
+                            body: Some(response_body.into()),
+                            ..self.snapshot()
+                        }
+                        .with_parents(parents));
+                    }
+                }
+            }
+
+            self.apply_patches_at(agent_id, edit_parents, &[Patch::text("[0:0]", body_str)])?;
+        }
+
+        Ok(self.resolved_update(&before, parents, self.position_unit))
+    }
+
+    /// Previews `text` patches at the current version, in a document measuring
+    /// positions in characters.
+    fn preview(&self, update: &Update) -> Option<Bytes> {
+        let known = update.primary_version().is_some_and(|v| self.contains_version(v));
+        if known || self.position_unit != PositionUnit::Char {
+            return None;
+        }
+        preview_text_patches(&self.content(), &self.version(), update)
+    }
+
+    fn snapshot(&self) -> Update {
+        Update {
+            version: self.version(),
+            body: Some(self.content().into()),
+            merge_type: Some(merge_types::DIAMOND.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn version(&self) -> Vec<Version> {
+        DiamondCRDT::version(self)
+    }
+
+    fn contains_version(&self, version: &Version) -> bool {
+        DiamondCRDT::contains_version(self, version)
+    }
+
+    /// Returns one patch update per Braid version; see
+    /// [`updates_since`](DiamondCRDT::updates_since).
+    fn patches_since(&self, since: &[Version]) -> Result<Vec<Update>> {
+        self.updates_since(since)
+    }
+
+    /// Checks out any known version; see [`content_at`](DiamondCRDT::content_at).
+    fn snapshot_at(&self, version: &[Version]) -> Result<Update> {
+        Ok(Update {
+            version: version.to_vec(),
+            body: Some(self.content_at(version)?.into()),
+            merge_type: Some(merge_types::DIAMOND.to_string()),
+            ..Default::default()
+        })
+    }
+
+    fn checkpoint(&self) -> Value {
+        DiamondCRDT::checkpoint(self)
+    }
+
+    /// Encodes the oplog; see [`encode`](DiamondCRDT::encode).
+    fn encode(&self) -> Option<Bytes> {
+        Some(DiamondCRDT::encode(self).into())
+    }
+
+    /// Merges an encoded oplog; see [`merge_encoded`](DiamondCRDT::merge_encoded).
+    fn decode(&mut self, data: &[u8]) -> Result<()> {
+        self.merge_encoded(data).map(|_| ())
+    }
+}
+
+/// Parse a `"{agent}-{seq}"` Braid version into a diamond-types remote ID.
+///
+/// The agent name may itself contain `-`; the sequence number follows the last one.
+fn parse_remote_id(version: &Version) -> Option<RemoteId> {
+    let (agent, seq) = version.as_str()?.rsplit_once('-')?;
+    Some(RemoteId {
+        agent: agent.into(),
+        seq: seq.parse().ok()?,
+    })
+}
+
+/// Every character ever inserted, in document order, rebuilt by replaying the
+/// oplog's transformed operations. Used by [`DiamondCRDT::undo`],
+/// [`DiamondCRDT::blame`] and [`DiamondCRDT::transform_position`].
+///
+/// Positions only place new text among visible characters, so where deleted ones end
+/// up beside concurrent inserts depends on the order operations are replayed in.
+#[derive(Clone, Default)]
+struct ItemReplay {
+    items: Vec<Item>,
+    /// Insert time → index in `items`
+    index: HashMap<usize, usize>,
+    /// Delete time → insert time of the character it deleted
+    deleted: HashMap<usize, usize>,
+    /// Local version replayed so far
+    version: Vec<usize>,
+}
+
+/// A character in an [`ItemReplay`].
+#[derive(Clone)]
+struct Item {
+    /// Local time of the insert
+    time: usize,
+    ch: char,
+    /// Whether the character is still in the document
+    visible: bool,
+}
+
+impl ItemReplay {
+    /// Replay the operations in `version` that aren't replayed yet, leaving the
+    /// replay at the union of both versions. Costs O(document length) per run of
+    /// operations.
+    fn advance(&mut self, oplog: &diamond_types::list::OpLog, version: &[usize]) {
+        let items = &mut self.items;
+        for (span, op) in oplog.iter_xf_operations_from(&self.version, version) {
+            // Deletes of text that was already deleted concurrently have no op
+            let Some(op) = op else { continue };
+            let len = op.end() - op.start();
+            // Index of the `pos`th visible character, or the end
+            let at = |items: &[Item], pos: usize| {
+                items
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, item)| item.visible)
+                    .nth(pos)
+                    .map_or(items.len(), |(i, _)| i)
+            };
+            let start = at(items, op.start());
+            match op.kind {
+                OpKind::Ins => {
+                    let mut new: Vec<Item> = op
+                        .content_as_str()
+                        .unwrap_or_default()
+                        .chars()
+                        .zip(span.start..span.end)
+                        .map(|(ch, time)| Item { time, ch, visible: true })
+                        .collect();
+                    // A reverse run typed each character before the previous one
+                    if !op.loc.fwd {
+                        new.reverse();
+                    }
+                    items.splice(start..start, new);
+                }
+                OpKind::Del => {
+                    let visible = items[start..].iter_mut().filter(|item| item.visible);
+                    for (offset, item) in visible.take(len).enumerate() {
+                        item.visible = false;
+                        // A forward run deletes left to right, a reverse one backspaces
+                        let time = if op.loc.fwd {
+                            span.start + offset
+                        } else {
+                            span.end - offset - 1
+                        };
+                        self.deleted.insert(time, item.time);
+                    }
+                }
+            }
+        }
+        self.index = items.iter().enumerate().map(|(i, item)| (item.time, i)).collect();
+        self.version = oplog.version_union(&self.version, version).to_vec();
+    }
+
+    /// The text of the visible characters.
+    fn content(&self) -> String {
+        self.items.iter().filter(|item| item.visible).map(|item| item.ch).collect()
+    }
+
+    /// Number of visible characters before `items[index]`.
+    fn visible_before(&self, index: usize) -> usize {
+        self.items[..index].iter().filter(|item| item.visible).count()
+    }
+}
+
+/// Number of [`ItemReplay`]s a [`ReplayCache`] keeps: one near the tip for undo and
+/// blame, and one for the last older version asked about.
+const REPLAY_CHECKPOINTS: usize = 2;
+
+/// [`ItemReplay`]s kept between calls, most recently used last, so each call only
+/// replays the operations since the nearest one. The oplog only grows, so they
+/// never go stale.
+#[derive(Default)]
+struct ReplayCache(parking_lot::Mutex<Vec<ItemReplay>>);
+
+impl Clone for ReplayCache {
+    fn clone(&self) -> Self {
+        Self(parking_lot::Mutex::new(self.0.lock().clone()))
+    }
+}
+
+impl std::fmt::Debug for ReplayCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ReplayCache").field(&self.0.lock().len()).finish()
+    }
+}
+
+/// Operations forming one Braid version, collected by
+/// [`DiamondCRDT::updates_since`].
+struct PatchGroup {
+    /// Agent that made every operation in the group
+    agent: AgentId,
+    /// Local time of the group's latest operation
+    last_time: usize,
+    /// Local version the group's first operation was made against
+    parents: Vec<usize>,
+    /// Patches so far, each relative to the result of the previous one
+    patches: Vec<PendingPatch>,
+}
+
+/// A `text` patch being built one character at a time: replace `start..end` with `text`.
+struct PendingPatch {
+    start: usize,
+    end: usize,
+    text: String,
+    /// Length of `text` in characters
+    len: usize,
+}
+
+impl PatchGroup {
+    /// Insert `ch` at `pos`, extending the previous patch if it touches its text.
+    fn insert(&mut self, pos: usize, ch: char) {
+        if let Some(patch) = self.patches.last_mut() {
+            if pos == patch.start + patch.len {
+                // Typing forwards appends without scanning the text
+                patch.text.push(ch);
+                patch.len += 1;
+                return;
+            }
+            if (patch.start..patch.start + patch.len).contains(&pos) {
+                let (at, _) = patch.text.char_indices().nth(pos - patch.start).unwrap();
+                patch.text.insert(at, ch);
+                patch.len += 1;
+                return;
+            }
+        }
+        self.patches.push(PendingPatch {
+            start: pos,
+            end: pos,
+            text: ch.to_string(),
+            len: 1,
+        });
+    }
+
+    /// Whether the character at `pos` was typed by the group's latest patch.
+    fn typed_at(&self, pos: usize) -> bool {
+        self.patches
+            .last()
+            .is_some_and(|patch| (patch.start..patch.start + patch.len).contains(&pos))
+    }
+
+    /// Delete the character at `pos`, extending the previous patch if adjacent.
+    ///
+    /// The character must not be one the latest patch typed; see [`typed_at`](Self::typed_at).
+    fn delete(&mut self, pos: usize) {
+        if let Some(patch) = self.patches.last_mut() {
+            let len = patch.len;
+            if pos == patch.start + len {
+                patch.end += 1;
+                return;
+            }
+            if pos + 1 == patch.start {
+                patch.start = pos;
+                return;
+            }
+        }
+        self.patches.push(PendingPatch {
+            start: pos,
+            end: pos + 1,
+            text: String::new(),
+            len: 0,
+        });
+    }
+}
+
+impl DiamondCRDT {
+    /// The update describing the operations added since `before`.
+    ///
+    /// Falls back to a snapshot with `parents` unless the new operations form exactly
+    /// one Braid version.
+    fn resolved_update(&self, before: &[usize], parents: Vec<Version>, unit: PositionUnit) -> Update {
+        let mut updates = self.updates_after(before, unit);
+        match updates.pop() {
+            Some(update) if updates.is_empty() => update,
+            _ => self.snapshot().with_parents(parents),
+        }
+    }
+
+    /// Advance the branch to the tip of the oplog, applying only the operations it lacks.
+    fn merge_to_tip(&mut self) {
+        self.branch.merge(&self.oplog, self.oplog.local_version_ref());
+    }
+
+    /// Insert at the tip as `agent_id`, recording the change for undo.
+    fn insert_at_tip(&mut self, agent_id: &str, pos: usize, text: &str) -> Result<()> {
+        let pos = self.tip_chars(pos..pos)?.start;
+        let start_time = self.oplog.len();
+        let agent = self.oplog.get_or_create_agent_id(agent_id);
+        self.branch.insert(&mut self.oplog, agent, pos, text);
+        self.record_change(agent_id, start_time);
+        Ok(())
+    }
+
+    /// Delete at the tip as `agent_id`, keeping the deleted text so it can be undone.
+    fn delete_at_tip(&mut self, agent_id: &str, range: std::ops::Range<usize>) -> Result<()> {
+        let range = self.tip_chars(range)?;
+        if range.is_empty() {
+            return Ok(());
+        }
+        let start_time = self.oplog.len();
+        let agent = self.oplog.get_or_create_agent_id(agent_id);
+        self.branch.delete(&mut self.oplog, agent, range);
+        self.record_change(agent_id, start_time);
+        Ok(())
+    }
+
+    /// Push the operations `agent_id` added since `start_time` onto its undo stack.
+    fn record_change(&mut self, agent_id: &str, start_time: usize) {
+        let end_time = self.oplog.len();
+        if end_time > start_time {
+            let history = self.undo_history.entry(agent_id.to_string()).or_default();
+            history.undo.push(start_time..end_time);
+            history.redo.clear();
+        }
+    }
+
+    /// Invert the top change on `agent_id`'s undo stack, or its redo stack if `redo`.
+    ///
+    /// Characters are tracked by identity, so text inserted by one undo is deleted by
+    /// undoing the change that first inserted it.
+    fn revert(&mut self, agent_id: &str, redo: bool) -> Option<Update> {
+        let replay = self.replay_at(self.oplog.local_version_ref());
+        let inverse = loop {
+            let history = self.undo_history.get_mut(agent_id)?;
+            let change = if redo { history.redo.pop() } else { history.undo.pop() }?;
+
+            // What each character of the document should become
+            let mut toggled: HashMap<usize, bool> = HashMap::new();
+            for time in change.clone() {
+                let item = match replay.deleted.get(&time) {
+                    Some(&item) => item,
+                    None => time,
+                };
+                let item = self.current_item(item);
+                let Some(&index) = replay.index.get(&item) else {
+                    // A delete of text that was already deleted
+                    continue;
+                };
+                let visible = replay.deleted.contains_key(&time);
+                if replay.items[index].visible != visible {
+                    toggled.insert(index, visible);
+                }
+            }
+            if !toggled.is_empty() {
+                break toggled;
+            }
+        };
+
+        let parents = self.version();
+        let before = self.oplog.local_version().to_vec();
+        let start_time = self.oplog.len();
+        let mut ops = Vec::with_capacity(inverse.len());
+        let mut pos = 0;
+        for (index, item) in replay.items.iter().enumerate() {
+            match inverse.get(&index) {
+                Some(true) => {
+                    self.restored.insert(item.time, start_time + ops.len());
+                    ops.push(Operation::new_insert(pos, &item.ch.to_string()));
+                    pos += 1;
+                }
+                Some(false) => ops.push(Operation::new_delete_with_content_range(
+                    pos..pos + 1,
+                    item.ch.to_string().into(),
+                )),
+                None if item.visible => pos += 1,
+                None => {}
+            }
+        }
+        let agent = self.oplog.get_or_create_agent_id(agent_id);
+        self.branch.apply_local_operations(&mut self.oplog, agent, &ops);
+
+        let reverted = start_time..self.oplog.len();
+        let history = self.undo_history.entry(agent_id.to_string()).or_default();
+        if redo {
+            history.undo.push(reverted);
+        } else {
+            history.redo.push(reverted);
+        }
+        Some(self.resolved_update(&before, parents, self.position_unit))
+    }
+
+    /// The oplog replayed at a local version, continued from the latest cached replay
+    /// the version contains.
+    fn replay_at(&self, version: &[usize]) -> ItemReplay {
+        let mut cache = self.replays.0.lock();
+        let nearest = cache
+            .iter()
+            .rposition(|replay| self.local_version_contains(version, &replay.version));
+        let mut replay = nearest.map(|i| cache.remove(i)).unwrap_or_default();
+        replay.advance(&self.oplog, version);
+        cache.push(replay.clone());
+        if cache.len() > REPLAY_CHECKPOINTS {
+            cache.remove(0);
+        }
+        replay
+    }
+
+    /// Whether every operation in local version `other` is in `version`.
+    fn local_version_contains(&self, version: &[usize], other: &[usize]) -> bool {
+        let mut union = self.oplog.version_union(version, other).to_vec();
+        let mut version = version.to_vec();
+        union.sort_unstable();
+        version.sort_unstable();
+        union == version
+    }
+
+    /// The character standing in for `item` after any undos or redos restored it.
+    fn current_item(&self, mut item: usize) -> usize {
+        while let Some(&restored) = self.restored.get(&item) {
+            item = restored;
+        }
+        item
+    }
+
+    /// Apply `{"inserts": [...], "deletes": [...]}` operations from a remote agent.
+    ///
+    /// Silently skips malformed operations and operations outside the document.
+    /// Positions are in the document's position unit.
+    fn apply_json_operations(&mut self, operations: &Value, agent_id: &str) {
+        if let Some(inserts) = operations.get("inserts").and_then(|v| v.as_array()) {
+            for insert in inserts {
+                if let (Some(pos), Some(text)) = (
+                    insert.get("pos").and_then(|v| v.as_u64()),
+                    insert.get("text").and_then(|v| v.as_str()),
+                ) {
+                    let pos = pos as usize;
+                    let patch = Patch::text(format_text_range(pos, pos), text.to_string());
+                    let _ = self.apply_patches_at(agent_id, &self.version(), &[patch]);
+                }
+            }
+        }
+
+        if let Some(deletes) = operations.get("deletes").and_then(|v| v.as_array()) {
+            for delete in deletes {
+                if let (Some(start), Some(end)) = (
+                    delete.get("start").and_then(|v| v.as_u64()),
+                    delete.get("end").and_then(|v| v.as_u64()),
+                ) {
+                    if start <= end {
+                        let patch = Patch::text(format_text_range(start as usize, end as usize), "");
+                        let _ = self.apply_patches_at(agent_id, &self.version(), &[patch]);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Convert a range at the tip from the document's position unit into characters.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BraidError::InvalidPatch`] if the range is out of bounds or splits a
+    /// character.
+    fn tip_chars(&self, range: std::ops::Range<usize>) -> Result<std::ops::Range<usize>> {
+        let content = (self.position_unit != PositionUnit::Char).then(|| self.content());
+        let len = content
+            .as_deref()
+            .map_or(self.branch.len(), |text| self.position_unit.len(text));
+        if range.start > range.end || range.end > len {
+            return Err(BraidError::InvalidPatch(format!(
+                "Range [{}:{}] exceeds document length {}",
+                range.start, range.end, len
+            )));
+        }
+        let Some(text) = content else {
+            return Ok(range);
+        };
+        match (
+            self.position_unit.to_char_offset(&text, range.start),
+            self.position_unit.to_char_offset(&text, range.end),
+        ) {
+            (Some(start), Some(end)) => Ok(start..end),
+            _ => Err(BraidError::InvalidPatch(format!(
+                "Range [{}:{}] splits a character ({} positions)",
+                range.start,
+                range.end,
+                self.position_unit.range_unit()
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merge::text::parse_text_range;
+
+    #[test]
+    fn test_merge_type_apply_text_patches() {
+        let mut doc = DiamondCRDT::new("server");
+        MergeType::initialize(&mut doc, "doc1", "alice").unwrap();
+        assert_eq!(doc.agent_id(), "alice");
+
+        doc.apply(&Update::snapshot(Version::new("v1"), "hello"), "alice")
+            .unwrap();
+        let update = Update::patched(
+            Version::new("v2"),
+            vec![crate::Patch::text("[0:1]", "J"), crate::Patch::text("[5:5]", "y")],
+        );
+        let resolved = doc.apply(&update, "bob").unwrap();
+
+        assert_eq!(
+            resolved.patches.unwrap(),
+            vec![crate::Patch::text("[0:1]", "J"), crate::Patch::text("[5:5]", "y")]
+        );
+        assert_eq!(doc.content(), "Jelloy");
+        assert_eq!(doc.patches_since(&resolved.version).unwrap().len(), 0);
+        let out_of_range = Update::patched(Version::new("v3"), vec![crate::Patch::text("[9:9]", "!")]);
+        assert!(doc.apply(&out_of_range, "bob").is_err());
+    }
+
+    #[test]
+    fn test_remote_edits_at_parents() {
+        let mut crdt = DiamondCRDT::new("server");
+        crdt.add_insert(0, "abc");
+        let base = crdt.version();
+        crdt.add_insert(0, "123");
+
+        // Positions are relative to "abc", not "123abc"
+        let deleted = crdt.add_delete_at("bob", &base, 0..1).unwrap();
+        assert_eq!(deleted, vec![Version::new("bob-0")]);
+        crdt.add_insert_at("carol", &base, 3, "!").unwrap();
+        assert_eq!(crdt.content(), "123bc!");
+
+        assert!(crdt.add_insert_at("bob", &base, 4, "x").is_err());
+        assert!(crdt.add_insert_at("bob", &[Version::new("nobody-0")], 0, "x").is_err());
+        assert_eq!(crdt.content(), "123bc!");
+    }
+
+    #[test]
+    fn test_merge_type_apply_patches_at_parents() {
+        let mut doc = DiamondCRDT::new("server");
+        let first = doc.apply(&Update::snapshot(Version::new("x"), "hello"), "alice").unwrap();
+        let edit = |range: &str, text: &str| {
+            Update::patched(Version::new("_"), vec![Patch::text(range, text)])
+                .with_parents(first.version.clone())
+        };
+
+        doc.apply(&edit("[0:1]", "J"), "alice").unwrap();
+        // bob hasn't seen alice's edit
+        let resolved = doc.apply(&edit("[5:5]", " world"), "bob").unwrap();
+        assert_eq!(resolved.version, vec![Version::new("bob-5")]);
+        assert_eq!(resolved.parents, first.version);
+        assert_eq!(resolved.patches.unwrap(), vec![Patch::text("[5:5]", " world")]);
+        assert_eq!(doc.content(), "Jello world");
+        assert_eq!(doc.version().len(), 2);
+
+        // Multiple patches in one update apply in sequence
+        let update = Update::patched(Version::new("_"), vec![Patch::text("[0:0]", "<"), Patch::text("[12:12]", ">")]);
+        doc.apply(&update, "carol").unwrap();
+        assert_eq!(doc.content(), "<Jello world>");
+    }
+
+    #[test]
+    fn test_patches_at_tip_match_patches_behind_tip() {
+        let patches = vec![
+            Patch::text("[0:5]", "Howdy"),
+            Patch::text("[11:11]", "!"),
+            Patch::text("[5:6]", ""),
+        ];
+        let mut ahead = DiamondCRDT::new("server");
+        ahead.add_insert(0, "hello world");
+        let base = ahead.version();
+        ahead.add_insert_remote("bob", 11, "?").unwrap();
+        let mut at_tip = DiamondCRDT::new("server");
+        at_tip.add_insert(0, "hello world");
+
+        // Applied straight to the branch, or merged from behind the tip
+        at_tip.apply_patches_at("alice", &base, &patches).unwrap();
+        ahead.apply_patches_at("alice", &base, &patches).unwrap();
+        assert_eq!(at_tip.content(), "Howdyworld!");
+        assert_eq!(ahead.content(), "Howdyworld!?");
+        assert_eq!(at_tip.version(), vec![Version::new("alice-11")]);
+        assert_eq!(at_tip.updates_since(&base).unwrap().len(), 1);
+
+        // Nothing is applied when a later patch is out of range
+        let bad = vec![Patch::text("[0:0]", "x"), Patch::text("[40:40]", "y")];
+        assert!(matches!(
+            at_tip.apply_patches_at("alice", &at_tip.version(), &bad),
+            Err(BraidError::HeaderParse(_))
+        ));
+        assert_eq!(at_tip.content(), "Howdyworld!");
+    }
+
+    #[test]
+    fn test_apply_resolves_only_new_operations() {
+        let mut doc = DiamondCRDT::new("server");
+        for i in 0..200 {
+            doc.add_insert_remote(if i % 2 == 0 { "alice" } else { "bob" }, i, "x").unwrap();
+        }
+        let before = doc.version();
+        let update = Update::patched(Version::new("_"), vec![Patch::text("[200:200]", "yz")])
+            .with_parents(before.clone());
+
+        let resolved = doc.apply(&update, "carol").unwrap();
+        assert_eq!(resolved.version, vec![Version::new("carol-1")]);
+        assert_eq!(resolved.parents, before);
+        assert_eq!(resolved.patches.unwrap(), vec![Patch::text("[200:200]", "yz")]);
+
+        // Typing then backspacing in one change is split where the backspacing starts
+        let mut doc = DiamondCRDT::new("alice");
+        doc.add_insert(0, "abc");
+        let base = doc.version();
+        doc.apply_patches_at("alice", &base, &[Patch::text("[3:3]", "def"), Patch::text("[4:6]", "")])
+            .unwrap();
+        let updates = doc.updates_since(&base).unwrap();
+        assert_eq!(updates.len(), 2);
+        assert_eq!(updates[0].patches, Some(vec![Patch::text("[3:3]", "def")]));
+        assert_eq!(updates[1].parents, vec![Version::new("alice-5")]);
+        assert_eq!(updates[1].patches, Some(vec![Patch::text("[4:6]", "")]));
+
+        // Each reproduces its version elsewhere
+        let mut replica = DiamondCRDT::new("replica");
+        replica.add_insert_remote("alice", 0, "abc").unwrap();
+        for update in &updates {
+            replica.apply(update, "alice").unwrap();
+        }
+        assert_eq!(replica.version(), doc.version());
+        assert_eq!(replica.content(), "abcd");
+    }
+
+    #[test]
+    fn test_updates_since_groups_per_version() {
+        let mut doc = DiamondCRDT::new("alice");
+        doc.add_insert(0, "abc");
+        let base = doc.version();
+        doc.add_insert_at("bob", &base, 3, "!").unwrap();
+        doc.add_delete_at("carol", &base, 0..1).unwrap();
+
+        let updates = doc.updates_since(&base).unwrap();
+        assert_eq!(updates.len(), 2);
+        assert_eq!(updates[0].version, vec![Version::new("bob-0")]);
+        assert_eq!(updates[1].version, vec![Version::new("carol-0")]);
+        for update in &updates {
+            assert_eq!(update.parents, base);
+        }
+        assert_eq!(updates[1].patches, Some(vec![Patch::text("[0:1]", "")]));
+
+        // From the beginning, patches replay the whole history
+        let all = doc.updates_since(&[]).unwrap();
+        assert_eq!(all[0].parents, Vec::<Version>::new());
+        assert_eq!(all[0].patches, Some(vec![Patch::text("[0:0]", "abc")]));
+        assert!(doc.updates_since(&doc.version()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_updates_since_replays_to_current_content() {
+        let mut doc = DiamondCRDT::new("alice");
+        doc.add_insert(0, "hello world");
+        // Backspace, forward delete, typing backwards, and a mid-word insert
+        for pos in (6..11).rev() {
+            doc.add_delete(pos..pos + 1);
+        }
+        doc.add_delete(0..1);
+        doc.add_delete(0..1);
+        for ch in ["c", "b", "a"] {
+            doc.add_insert(0, ch);
+        }
+        doc.add_insert(4, "💥");
+        doc.add_insert_remote("bob", 2, "中文").unwrap();
+
+        let mut text = String::new();
+        for update in doc.updates_since(&[]).unwrap() {
+            text = crate::merge::text::apply_text_patches(&text, update.patches.as_ref().unwrap()).unwrap();
+        }
+        assert_eq!(text, doc.content());
+    }
+
+    #[test]
+    fn test_utf16_positions_with_emoji_and_cjk() {
+        let mut doc = DiamondCRDT::new("browser").with_position_unit(PositionUnit::Utf16);
+        doc.add_insert(0, "😀中文");
+        // "😀" is two UTF-16 code units
+        doc.add_insert(2, "-");
+        doc.add_insert(5, "!");
+        assert_eq!(doc.content(), "😀-中文!");
+        doc.add_delete(0..2);
+        assert_eq!(doc.content(), "-中文!");
+
+        let base = doc.version();
+        doc.add_insert(0, "🎉");
+        // Offset 1 splits the new emoji, but not the text at `base`
+        assert!(doc.add_insert_at("bob", &doc.version(), 1, "x").is_err());
+        assert!(doc.add_insert_at("bob", &base, 5, "x").is_err());
+        let update = Update::patched(Version::new("_"), vec![Patch::text("[1:1]", "x")]).with_parents(base);
+        doc.apply(&update, "bob").unwrap();
+        assert_eq!(doc.content(), "🎉-x中文!");
+    }
+
+    #[test]
+    fn test_remote_edits_reject_split_characters() {
+        let mut doc = DiamondCRDT::new("browser").with_position_unit(PositionUnit::Utf16);
+        doc.add_insert(0, "😀a");
+
+        let err = doc.add_insert_remote("bob", 1, "x").unwrap_err();
+        assert!(matches!(err, BraidError::InvalidPatch(_)));
+        assert!(matches!(doc.add_delete_remote("bob", 1..3), Err(BraidError::InvalidPatch(_))));
+        assert!(matches!(doc.add_delete_remote("bob", 2..9), Err(BraidError::InvalidPatch(_))));
+        assert_eq!(doc.content(), "😀a");
+
+        doc.add_delete_remote("bob", 0..2).unwrap();
+        assert_eq!(doc.content(), "a");
+    }
+
+    #[test]
+    fn test_explicit_patch_units() {
+        let mut doc = DiamondCRDT::new("server");
+        doc.add_insert(0, "日本🎉");
+        let base = doc.version();
+
+        // Insert after "本" using byte, UTF-16 and scalar offsets
+        doc.apply_patches_at("a", &base, &[Patch::new("text-utf8", "[6:6]", "1")]).unwrap();
+        doc.apply_patches_at("b", &base, &[Patch::new("text-utf16", "[2:2]", "2")]).unwrap();
+        doc.apply_patches_at("c", &base, &[Patch::new("text-chars", "[2:2]", "3")]).unwrap();
+        let content = doc.content();
+        assert!(content.starts_with("日本") && content.ends_with("🎉"));
+        assert_eq!(content.chars().count(), 6);
+
+        // Mid-character offsets are rejected without partial effects
+        let bad = [Patch::new("text-utf8", "[0:0]", "x"), Patch::new("text-utf8", "[2:2]", "y")];
+        assert!(doc.apply_patches_at("d", &base, &bad).is_err());
+        assert_eq!(doc.content(), content);
+        assert!(doc.apply_patches_at("d", &base, &[Patch::new("lines", "[0:0]", "x")]).is_err());
+    }
+
+    #[test]
+    fn test_patches_out_in_requested_unit() {
+        let mut doc = DiamondCRDT::new("server");
+        doc.add_insert(0, "🎉中");
+        let base = doc.version();
+        let update = Update::patched(Version::new("_"), vec![Patch::new("text-utf16", "[3:3]", "x")])
+            .with_parents(base.clone());
+
+        // The response uses the unit of the request
+        let resolved = doc.apply(&update, "browser").unwrap();
+        assert_eq!(resolved.patches.unwrap(), vec![Patch::new("text-utf16", "[3:3]", "x")]);
+        assert_eq!(doc.content(), "🎉中x");
+
+        let utf8 = doc.updates_since_in(&base, PositionUnit::Utf8).unwrap();
+        assert_eq!(utf8[0].patches, Some(vec![Patch::new("text-utf8", "[7:7]", "x")]));
+        let chars = doc.updates_since(&base).unwrap();
+        assert_eq!(chars[0].patches, Some(vec![Patch::text("[2:2]", "x")]));
+
+        // A UTF-16 document labels its own unit as plain `text`
+        doc.set_position_unit(PositionUnit::Utf16);
+        let utf16 = doc.updates_since(&[]).unwrap();
+        let mut text = String::new();
+        for update in &utf16 {
+            for patch in update.patches.as_ref().unwrap() {
+                assert_eq!(patch.unit, "text");
+                let (start, end) = parse_text_range(&patch.range).unwrap();
+                let (start, end) = PositionUnit::Utf16.range_to_chars(&text, start, end).unwrap();
+                splice_chars(&mut text, start, end, std::str::from_utf8(&patch.content).unwrap());
+            }
+        }
+        assert_eq!(text, doc.content());
+    }
+
+    #[test]
+    fn test_initialize_keeps_position_unit() {
+        let mut doc = DiamondCRDT::new("server").with_position_unit(PositionUnit::Utf8);
+        MergeType::initialize(&mut doc, "doc1", "alice").unwrap();
+        assert_eq!(doc.agent_id(), "alice");
+        assert_eq!(doc.position_unit(), PositionUnit::Utf8);
+    }
+
+    #[test]
+    fn test_basic_insert() {
+        let mut crdt = DiamondCRDT::new("alice");
+        crdt.add_insert(0, "hello");
+        assert_eq!(crdt.content(), "hello");
+    }
+
+    #[test]
+    fn test_multiple_inserts() {
+        let mut crdt = DiamondCRDT::new("alice");
+        crdt.add_insert(0, "hello");
+        crdt.add_insert(5, " world");
+        assert_eq!(crdt.content(), "hello world");
+    }
+
+    #[test]
+    fn test_delete() {
+        let mut crdt = DiamondCRDT::new("alice");
+        crdt.add_insert(0, "hello world");
+        crdt.add_delete(5..6);
+        assert_eq!(crdt.content(), "helloworld");
+    }
+
+    #[test]
+    fn test_concurrent_edits() {
+        let mut crdt = DiamondCRDT::new("alice");
+        crdt.add_insert(0, "hello");
+        crdt.add_insert_remote("bob", 5, " world").unwrap();
+        assert_eq!(crdt.content(), "hello world");
+    }
 
     #[test]
     fn test_agent_id() {
@@ -555,11 +2383,422 @@ mod tests {
     fn test_get_version() {
         let mut crdt = DiamondCRDT::new("alice");
         let v1 = crdt.get_version();
-        assert!(v1.contains("alice"));
+        assert!(v1.is_empty());
         
         crdt.add_insert(0, "text");
         let v2 = crdt.get_version();
         assert_ne!(v1, v2);
+        assert_eq!(v2, r#""alice-3""#);
+    }
+
+    #[test]
+    fn test_versions_match_across_peers() {
+        let mut a = DiamondCRDT::new("server-a");
+        let mut b = DiamondCRDT::new("server-b");
+        for doc in [&mut a, &mut b] {
+            doc.add_insert_remote("alice", 0, "hi").unwrap();
+        }
+        // Concurrent tips, added in different orders
+        let base = a.to_local_version(&[Version::new("alice-1")]).unwrap();
+        let agent = a.oplog.get_or_create_agent_id("bob");
+        a.oplog.add_insert_at(agent, &base, 0, ">");
+        let agent = a.oplog.get_or_create_agent_id("carol");
+        a.oplog.add_insert_at(agent, &base, 2, "!");
+        let agent = b.oplog.get_or_create_agent_id("carol");
+        b.oplog.add_insert_at(agent, &base, 2, "!");
+        let agent = b.oplog.get_or_create_agent_id("bob");
+        b.oplog.add_insert_at(agent, &base, 0, ">");
+
+        let expected = vec![Version::new("bob-0"), Version::new("carol-0")];
+        assert_eq!(a.version(), expected);
+        assert_eq!(b.version(), expected);
+        assert_eq!(a.to_local_version(&expected).unwrap().len(), 2);
+        assert_eq!(a.to_braid_version(&base), vec![Version::new("alice-1")]);
+    }
+
+    #[test]
+    fn test_version_reverse_mapping() {
+        let mut crdt = DiamondCRDT::new("my-agent");
+        crdt.add_insert(0, "abc");
+        crdt.add_insert(3, "d");
+
+        // The earlier version is dropped as an ancestor of the later one
+        let local = crdt
+            .to_local_version(&[Version::new("my-agent-1"), Version::new("my-agent-3")])
+            .unwrap();
+        assert_eq!(crdt.to_braid_version(&local), vec![Version::new("my-agent-3")]);
+        assert!(crdt.contains_version(&Version::new("my-agent-0")));
+        assert!(!crdt.contains_version(&Version::new("my-agent-4")));
+        assert!(crdt.to_local_version(&[Version::new("bob-0")]).is_err());
+        assert!(crdt.to_local_version(&[Version::new("v1")]).is_err());
+    }
+
+    #[test]
+    fn test_content_at_historical_versions() {
+        let mut doc = DiamondCRDT::new("alice");
+        doc.add_insert(0, "hello");
+        let v1 = doc.version();
+        doc.add_delete(0..1);
+        doc.add_insert_at("bob", &v1, 5, "!").unwrap();
+        assert_eq!(doc.content(), "ello!");
+
+        assert_eq!(doc.content_at(&v1).unwrap(), "hello");
+        assert_eq!(doc.content_at(&[Version::new("alice-5")]).unwrap(), "ello");
+        assert_eq!(doc.content_at(&[Version::new("bob-0")]).unwrap(), "hello!");
+        assert_eq!(doc.content_at(&doc.version()).unwrap(), "ello!");
+        assert!(matches!(
+            doc.content_at(&[Version::new("carol-0")]),
+            Err(BraidError::InvalidVersion(_))
+        ));
+
+        let snapshot = doc.snapshot_at(&v1).unwrap();
+        assert_eq!(snapshot.version, v1);
+        assert_eq!(snapshot.body_str(), Some("hello"));
+    }
+
+    #[test]
+    fn test_merge_type_apply_sets_version_and_parents() {
+        let mut doc = DiamondCRDT::new("server");
+        let first = doc.apply(&Update::snapshot(Version::new("x"), "hi"), "alice").unwrap();
+        assert_eq!(first.version, vec![Version::new("alice-1")]);
+        assert!(first.parents.is_empty());
+        assert_eq!(doc.blame()[0].agent, "alice");
+
+        let patch = Update::patched(Version::new("y"), vec![crate::Patch::text("[2:2]", "!")]);
+        let second = doc.apply(&patch, "bob").unwrap();
+        assert_eq!(second.version, vec![Version::new("bob-0")]);
+        assert_eq!(second.parents, first.version);
+
+        // Re-sending a known version is a no-op
+        let replay = Update::patched(Version::new("bob-0"), vec![crate::Patch::text("[0:0]", "?")]);
+        doc.apply(&replay, "bob").unwrap();
+        assert_eq!(doc.content(), "hi!");
+        assert!(doc.patches_since(&[Version::new("nobody-9")]).is_err());
+
+        // A plain body is inserted at the update's parents
+        let body = Update::snapshot(Version::new("z"), ">").with_parents(first.version.clone());
+        let third = doc.apply(&body, "carol").unwrap();
+        assert_eq!(third.version, vec![Version::new("carol-0")]);
+        assert_eq!(third.parents, first.version);
+        assert_eq!(doc.content(), ">hi!");
+    }
+
+    #[test]
+    fn test_merge_type_apply_rejects_binary_bodies() {
+        let mut doc = DiamondCRDT::new("server");
+        let update = Update::snapshot(Version::new("x"), vec![b'h', 0xff]);
+        assert!(matches!(doc.apply(&update, "alice"), Err(BraidError::BodyParse(_))));
+        assert!(doc.is_empty());
+    }
+
+    #[test]
+    fn test_encode_roundtrip() {
+        let mut crdt = DiamondCRDT::new("alice");
+        crdt.add_insert(0, "hello world");
+        crdt.add_delete_remote("bob", 5..6).unwrap();
+
+        let copy = DiamondCRDT::from_encoded("carol", &crdt.encode()).unwrap();
+        assert_eq!(copy.content(), "helloworld");
+        assert_eq!(copy.version(), crdt.version());
+        assert_eq!(copy.operation_count(), crdt.operation_count());
+        assert_eq!(copy.agent_id(), "carol");
+    }
+
+    #[test]
+    fn test_encode_since_merges_concurrent_history() {
+        let mut a = DiamondCRDT::new("alice");
+        a.add_insert(0, "ab");
+        let mut b = DiamondCRDT::from_encoded("bob", &a.encode()).unwrap();
+        let base = a.version();
+
+        a.add_insert(0, "<");
+        b.add_insert(2, ">");
+        let from_a = a.encode_since(&base).unwrap();
+        let from_b = b.encode_since(&base).unwrap();
+
+        let merged = b.merge_encoded(&from_a).unwrap();
+        assert_eq!(merged, vec![Version::new("alice-2")]);
+        a.merge_encoded(&from_b).unwrap();
+        assert_eq!(a.content(), "<ab>");
+        assert_eq!(a.content(), b.content());
+        assert_eq!(a.version(), b.version());
+
+        // Merging the same data twice changes nothing
+        a.merge_encoded(&from_b).unwrap();
+        assert_eq!(a.content(), "<ab>");
+    }
+
+    #[test]
+    fn test_merge_encoded_errors() {
+        let mut a = DiamondCRDT::new("alice");
+        a.add_insert(0, "ab");
+        let base = a.version();
+        a.add_insert(2, "c");
+        let patch = a.encode_since(&base).unwrap();
+
+        let mut empty = DiamondCRDT::new("bob");
+        assert!(matches!(empty.merge_encoded(&patch), Err(BraidError::InvalidVersion(_))));
+        assert!(matches!(empty.merge_encoded(b"garbage"), Err(BraidError::BodyParse(_))));
+        assert!(empty.is_empty());
+        assert!(a.encode_since(&[Version::new("zed-0")]).is_err());
+    }
+
+    #[test]
+    fn test_undo_keeps_concurrent_edits() {
+        let mut doc = DiamondCRDT::new("alice");
+        doc.add_insert(0, "hello world");
+        let seen = doc.version();
+        doc.add_delete(0..6);
+        // Bob edits concurrently with alice's delete
+        doc.add_insert_at("bob", &seen, 11, "!").unwrap();
+        assert_eq!(doc.content(), "world!");
+
+        let update = doc.undo("alice").unwrap();
+        assert_eq!(doc.content(), "hello world!");
+        assert_eq!(update.patches, Some(vec![Patch::text("[0:0]", "hello ")]));
+        assert_eq!(update.parents, vec![Version::new("alice-16"), Version::new("bob-0")]);
+        assert_eq!(update.version, doc.version());
+
+        doc.undo("alice").unwrap();
+        assert_eq!(doc.content(), "!");
+        assert!(doc.undo("alice").is_none());
+        doc.undo("bob").unwrap();
+        assert_eq!(doc.content(), "");
+    }
+
+    #[test]
+    fn test_undo_redo_tracks_restored_text() {
+        let mut doc = DiamondCRDT::new("alice");
+        doc.add_insert(0, "hello world");
+        doc.add_delete(0..6);
+
+        doc.undo("alice").unwrap();
+        doc.redo("alice").unwrap();
+        assert_eq!(doc.content(), "world");
+        doc.undo("alice").unwrap();
+        assert_eq!(doc.content(), "hello world");
+        // Removes the text restored by the undo as well as the original
+        doc.undo("alice").unwrap();
+        assert_eq!(doc.content(), "");
+
+        doc.redo("alice").unwrap();
+        assert_eq!(doc.content(), "hello world");
+        doc.redo("alice").unwrap();
+        assert_eq!(doc.content(), "world");
+        assert!(doc.redo("alice").is_none());
+    }
+
+    #[test]
+    fn test_undo_change_with_several_patches() {
+        let mut doc = DiamondCRDT::new("alice");
+        doc.add_insert(0, "abcde");
+        // Backspace twice, then type at the start, as one change
+        let patches = [
+            Patch::text("[4:5]", ""),
+            Patch::text("[3:4]", ""),
+            Patch::text("[0:0]", "X"),
+        ];
+        doc.apply_patches_at("alice", &doc.version(), &patches).unwrap();
+        assert_eq!(doc.content(), "Xabc");
+
+        doc.undo("alice").unwrap();
+        assert_eq!(doc.content(), "abcde");
+        doc.redo("alice").unwrap();
+        assert_eq!(doc.content(), "Xabc");
+    }
+
+    #[test]
+    fn test_undo_skips_changes_already_reverted() {
+        let mut doc = DiamondCRDT::new("alice");
+        doc.add_insert(0, "a");
+        doc.add_insert(1, "b");
+        doc.add_delete_remote("bob", 1..2).unwrap();
+
+        doc.undo("alice").unwrap();
+        assert_eq!(doc.content(), "");
+        assert!(doc.undo("alice").is_none());
+    }
+
+    #[test]
+    fn test_cached_replays_match_fresh_ones() {
+        // Characters in the text, in order; where deleted ones sit beside concurrent
+        // inserts depends on the order operations are replayed in
+        let visible = |replay: &ItemReplay| -> Vec<usize> {
+            replay.items.iter().filter(|item| item.visible).map(|item| item.time).collect()
+        };
+        let fresh = |doc: &DiamondCRDT, version: &[usize]| {
+            let mut replay = ItemReplay::default();
+            replay.advance(&doc.oplog, version);
+            visible(&replay)
+        };
+
+        let mut doc = DiamondCRDT::new("alice");
+        doc.add_insert(0, "hello world");
+        let seen = doc.version();
+        assert_eq!(doc.blame().len(), 1);
+        doc.add_delete(0..6);
+        doc.add_insert_at("bob", &seen, 11, "!").unwrap();
+        doc.undo("alice").unwrap();
+        doc.add_insert_at("carol", &seen, 5, ",").unwrap();
+        let tip = doc.oplog.local_version().to_vec();
+        assert_eq!(visible(&doc.replay_at(&tip)), fresh(&doc, &tip));
+        assert_eq!(doc.replay_at(&tip).content(), doc.content());
+        let mut uncached = doc.clone();
+        uncached.replays = ReplayCache::default();
+
+        // An older version than every cached replay starts over, then is reused
+        let old = doc.to_local_version(&seen).unwrap();
+        assert_eq!(visible(&doc.replay_at(&old)), fresh(&doc, &old));
+        doc.add_insert_at("bob", &seen, 0, ">").unwrap();
+        uncached.add_insert_at("bob", &seen, 0, ">").unwrap();
+        let newer = doc.to_local_version(&[Version::new("bob-1")]).unwrap();
+        assert_eq!(visible(&doc.replay_at(&newer)), fresh(&doc, &newer));
+        assert_eq!(doc.replays.0.lock().len(), REPLAY_CHECKPOINTS);
+
+        let tip = doc.oplog.local_version().to_vec();
+        assert_eq!(visible(&doc.replay_at(&tip)), fresh(&doc, &tip));
+        doc.undo("alice").unwrap();
+        uncached.undo("alice").unwrap();
+        assert_eq!(doc.content(), ">,!");
+        assert_eq!(uncached.content(), ">,!");
+        for _ in 0..2 {
+            doc.redo("alice").unwrap();
+            uncached.redo("alice").unwrap();
+            assert_eq!(doc.content(), uncached.content());
+        }
+    }
+
+    #[test]
+    fn test_transform_position_between_concurrent_versions() {
+        use Stickiness::{Left, Right};
+        let mut doc = DiamondCRDT::new("alice");
+        doc.add_insert(0, "hello world");
+        let seen = doc.version();
+        doc.add_delete(0..6);
+        let alice = doc.version();
+        doc.add_insert_at("bob", &seen, 11, "!").unwrap();
+        let bob = vec![Version::new("bob-0")];
+        assert_eq!(doc.content_at(&alice).unwrap(), "world");
+        assert_eq!(doc.content_at(&bob).unwrap(), "hello world!");
+
+        let transform = |pos, from: &[Version], to: &[Version], stickiness| {
+            doc.transform_position(pos, from, to, stickiness).unwrap()
+        };
+        assert_eq!(transform(0, &alice, &bob, Right), 6);
+        assert_eq!(transform(0, &alice, &bob, Left), 0);
+        assert_eq!(transform(5, &alice, &bob, Left), 11);
+        assert_eq!(transform(5, &alice, &bob, Right), 12);
+
+        // Deleted characters and ones `to` lacks map to where they'd be
+        assert_eq!(transform(3, &bob, &alice, Left), 0);
+        assert_eq!(transform(12, &bob, &alice, Left), 5);
+        assert_eq!(transform(12, &bob, &alice, Right), 5);
+        assert_eq!(transform(5, &bob, &doc.version(), Right), 0);
+        assert_eq!(doc.transform_range(6..11, &bob, &doc.version(), Left).unwrap(), 0..5);
+
+        assert!(matches!(
+            doc.transform_position(13, &bob, &alice, Left),
+            Err(BraidError::HeaderParse(_))
+        ));
+        assert!(matches!(
+            doc.transform_position(0, &[Version::new("zed-0")], &alice, Left),
+            Err(BraidError::InvalidVersion(_))
+        ));
+    }
+
+    #[test]
+    fn test_blame_attributes_spans_to_agents() {
+        let mut doc = DiamondCRDT::new("alice").with_position_unit(PositionUnit::Utf16);
+        doc.add_insert(0, "hello world");
+        let seen = doc.version();
+        doc.add_delete_remote("bob", 6..11).unwrap();
+        doc.add_insert_remote("bob", 6, "🌍").unwrap();
+        doc.add_insert(8, "!");
+
+        let spans: Vec<_> = doc
+            .blame()
+            .into_iter()
+            .map(|span| (span.start, span.end, span.agent, span.version.to_string()))
+            .collect();
+        assert_eq!(
+            spans,
+            vec![
+                (0, 6, "alice".to_string(), "alice-5".to_string()),
+                (6, 8, "bob".to_string(), "bob-5".to_string()),
+                (8, 9, "alice".to_string(), "alice-11".to_string()),
+            ]
+        );
+
+        let before = doc.blame_at(&seen).unwrap();
+        assert_eq!(before.len(), 1);
+        assert_eq!((before[0].end, before[0].version.clone()), (11, seen[0].clone()));
+        assert!(doc.blame_at(&[Version::new("zed-0")]).is_err());
+        assert!(DiamondCRDT::new("empty").blame().is_empty());
+    }
+
+    #[test]
+    fn test_blame_joins_runs_typed_backwards() {
+        let mut doc = DiamondCRDT::new("alice");
+        for ch in ["c", "b", "a"] {
+            doc.add_insert(0, ch);
+        }
+        doc.add_insert_remote("bob", 3, "d").unwrap();
+        let spans = doc.blame();
+        assert_eq!(spans.len(), 2);
+        assert_eq!((spans[0].end, spans[0].version.clone()), (3, Version::new("alice-2")));
+    }
+
+    #[test]
+    fn test_transform_position_agrees_with_patches() {
+        let mut doc = DiamondCRDT::new("alice").with_position_unit(PositionUnit::Utf16);
+        doc.add_insert(0, "a😀bcdef");
+        let seen = doc.version();
+        doc.add_insert_remote("bob", 0, ">> ").unwrap();
+        doc.add_delete_remote("bob", 6..8).unwrap();
+        doc.add_insert_remote("bob", 9, "🎉").unwrap();
+
+        let patches: Vec<Patch> = doc
+            .updates_since(&seen)
+            .unwrap()
+            .into_iter()
+            .flat_map(|update| update.patches.unwrap_or_default())
+            .collect();
+        for pos in [0, 1, 3, 4, 5, 6, 7, 8] {
+            for stickiness in [Stickiness::Left, Stickiness::Right] {
+                assert_eq!(
+                    doc.transform_position(pos, &seen, &doc.version(), stickiness).unwrap(),
+                    crate::merge::transform_position_in(pos, &patches, stickiness, PositionUnit::Utf16)
+                        .unwrap(),
+                    "{} sticking {:?}",
+                    pos,
+                    stickiness
+                );
+            }
+        }
+        // Inside the emoji
+        assert!(doc.transform_position(2, &seen, &doc.version(), Stickiness::Left).is_err());
+    }
+
+    #[test]
+    fn test_new_change_clears_redo() {
+        let mut doc = DiamondCRDT::new("alice");
+        doc.add_insert(0, "a");
+        doc.add_insert(1, "b");
+        doc.undo("alice").unwrap();
+        doc.add_insert(1, "c");
+        assert!(doc.redo("alice").is_none());
+        assert_eq!(doc.content(), "ac");
+    }
+
+    #[test]
+    fn test_encode_keeps_deleted_content() {
+        let mut doc = DiamondCRDT::new("alice");
+        doc.add_insert(0, "abc");
+        doc.add_delete(1..2);
+        let decoded = DiamondCRDT::from_encoded("bob", &doc.encode()).unwrap();
+        let delete = decoded.oplog.iter().last().unwrap();
+        assert_eq!(delete.kind, OpKind::Del);
+        assert_eq!(delete.content_as_str(), Some("b"));
     }
 
     #[test]
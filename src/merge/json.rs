@@ -0,0 +1,769 @@
+//! Path-level merging for JSON resources.
+//!
+//! This module provides [`JsonDocument`], a JSON value that is edited field by field
+//! through `json` range patches (e.g. `Content-Range: json .users[0].name`) and that
+//! merges concurrent edits deterministically.
+//!
+//! # Overview
+//!
+//! Every update is recorded together with its Braid `Version` and `Parents`, so the
+//! document knows the full version DAG. The merged value is defined as the result of
+//! replaying every update in a deterministic topological order of that DAG:
+//!
+//! - **Sequential edits** are applied in causal order, so later edits win
+//! - **Concurrent edits to different paths** both apply and are merged
+//! - **Concurrent edits to the same path** are ordered by version ID, so the edit
+//!   with the greater [`Version`] wins on every peer
+//!
+//! Updates whose parents are the current frontier (the common, non-concurrent case)
+//! are applied in place without replaying history.
+//!
+//! `JsonDocument` is registered as the `"json"` [`MergeType`], so JSON resources are
+//! merged, persisted and validated like those of any other merge type.
+//!
+//! # Range Syntax
+//!
+//! | Range | Meaning |
+//! |-------|---------|
+//! | *(empty)* | The whole document |
+//! | `.field` | Object member `field` |
+//! | `["some key"]` | Object member with arbitrary characters |
+//! | `[3]` | Array element 3 (appends when equal to the array length) |
+//! | `[1:3]` | Array splice replacing elements 1..3 with the patch content |
+//!
+//! Segments can be chained: `.messages[0].text`.
+//!
+//! # Examples
+//!
+//! ```
+//! use braid_axum_http::merge::JsonDocument;
+//! use braid_axum_http::{Patch, Version};
+//!
+//! let mut doc = JsonDocument::new();
+//! doc.apply_patches(Version::new("v1"), vec![], &[Patch::json("", r#"{"a": 1, "b": 1}"#)])
+//!     .unwrap();
+//!
+//! // Two concurrent edits to different fields
+//! doc.apply_patches(Version::new("v2"), vec![Version::new("v1")], &[Patch::json(".a", "2")])
+//!     .unwrap();
+//! doc.apply_patches(Version::new("v3"), vec![Version::new("v1")], &[Patch::json(".b", "3")])
+//!     .unwrap();
+//!
+//! assert_eq!(doc.value(), &serde_json::json!({"a": 2, "b": 3}));
+//! ```
+//!
+//! # Specification References
+//!
+//! - **draft-toomim-httpbis-braid-http**: Section 2 (Versioning), Section 3 (Patches)
+
+use super::merge_type::{same_versions, MergeType};
+use crate::error::{BraidError, Result};
+use crate::protocol::{self, merge_types};
+use crate::types::{Patch, Update, Version};
+use bytes::Bytes;
+use serde_json::{json, Value};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// One step of a parsed JSON range.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Segment {
+    /// Object member
+    Key(String),
+    /// Array element
+    Index(usize),
+    /// Array splice `[start:end]`
+    Slice(usize, usize),
+}
+
+/// A single recorded update in the document's history.
+#[derive(Clone, Debug)]
+struct JsonEdit {
+    /// Parent versions this edit was made against
+    parents: Vec<Version>,
+    /// Parsed `(path, value)` pairs, applied in order
+    patches: Vec<(Vec<Segment>, Value)>,
+}
+
+/// A JSON value with a version DAG and deterministic path-level merging.
+///
+/// `JsonDocument` stores the merged value along with every update it has seen, keyed
+/// by version. See the [module documentation](self) for the merge rules.
+///
+/// # Invariants
+///
+/// - Every recorded edit's parents are themselves recorded
+/// - `value` always equals the replay of all edits in merge order
+/// - `frontier` contains exactly the versions without recorded children
+#[derive(Clone, Debug, Default)]
+pub struct JsonDocument {
+    /// Current merged value
+    value: Value,
+
+    /// All edits seen so far, keyed by version
+    edits: HashMap<Version, JsonEdit>,
+
+    /// Versions with no children (the current version of the document)
+    frontier: Vec<Version>,
+}
+
+impl JsonDocument {
+    /// Create a new empty (`null`) JSON document.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use braid_axum_http::merge::JsonDocument;
+    ///
+    /// let doc = JsonDocument::new();
+    /// assert!(doc.is_empty());
+    /// assert!(doc.value().is_null());
+    /// ```
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply a versioned set of `json` range patches.
+    ///
+    /// A patch with an empty range replaces the whole document, which is how
+    /// snapshots are recorded. Applying a version that has already been seen is a
+    /// no-op, which makes retransmitted updates idempotent.
+    ///
+    /// # Arguments
+    ///
+    /// * `version` - Version ID of this update
+    /// * `parents` - Versions the update was made against (empty for the first update)
+    /// * `patches` - Patches with unit `json`
+    ///
+    /// # Errors
+    ///
+    /// - [`BraidError::InvalidVersion`] if a parent version is unknown
+    /// - [`BraidError::HeaderParse`] if a patch has a non-`json` unit or a malformed range
+    /// - [`BraidError::BodyParse`] if a patch body is not valid JSON
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use braid_axum_http::merge::JsonDocument;
+    /// use braid_axum_http::{Patch, Version};
+    ///
+    /// let mut doc = JsonDocument::new();
+    /// doc.apply_patches(Version::new("v1"), vec![], &[Patch::json(".name", r#""Alice""#)])
+    ///     .unwrap();
+    /// assert_eq!(doc.value()["name"], "Alice");
+    /// ```
+    pub fn apply_patches(
+        &mut self,
+        version: Version,
+        parents: Vec<Version>,
+        patches: &[Patch],
+    ) -> Result<()> {
+        let parsed = parse_patches(patches)?;
+        self.record(version, parents, parsed)
+    }
+
+    /// Replace the whole document with a snapshot value.
+    ///
+    /// Equivalent to a single patch with an empty range.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BraidError::InvalidVersion`] if a parent version is unknown.
+    pub fn apply_snapshot(
+        &mut self,
+        version: Version,
+        parents: Vec<Version>,
+        value: Value,
+    ) -> Result<()> {
+        self.record(version, parents, vec![(Vec::new(), value)])
+    }
+
+    /// Record an edit and bring the merged value up to date.
+    fn record(
+        &mut self,
+        version: Version,
+        parents: Vec<Version>,
+        patches: Vec<(Vec<Segment>, Value)>,
+    ) -> Result<()> {
+        if self.edits.contains_key(&version) {
+            return Ok(());
+        }
+        if let Some(missing) = parents.iter().find(|p| !self.edits.contains_key(*p)) {
+            return Err(BraidError::InvalidVersion(format!(
+                "Unknown parent version: {}",
+                missing
+            )));
+        }
+
+        let parent_set: HashSet<&Version> = parents.iter().collect();
+        let frontier_set: HashSet<&Version> = self.frontier.iter().collect();
+        let at_tip = parent_set == frontier_set;
+
+        if at_tip {
+            for (path, value) in &patches {
+                apply_at_path(&mut self.value, path, value.clone());
+            }
+        }
+
+        self.frontier.retain(|v| !parent_set.contains(v));
+        self.frontier.push(version.clone());
+        self.frontier.sort();
+        self.edits.insert(version, JsonEdit { parents, patches });
+
+        if !at_tip {
+            self.value = self.replay();
+        }
+        Ok(())
+    }
+
+    /// Replay every edit in merge order, starting from `null`.
+    fn replay(&self) -> Value {
+        let mut value = Value::Null;
+        for version in self.merge_order() {
+            for (path, patch_value) in &self.edits[&version].patches {
+                apply_at_path(&mut value, path, patch_value.clone());
+            }
+        }
+        value
+    }
+
+    /// Topological order of all edits, breaking ties by version order.
+    fn merge_order(&self) -> Vec<Version> {
+        let mut pending: HashMap<&Version, usize> = HashMap::new();
+        let mut children: HashMap<&Version, Vec<&Version>> = HashMap::new();
+        for (version, edit) in &self.edits {
+            pending.insert(version, edit.parents.len());
+            for parent in &edit.parents {
+                children.entry(parent).or_default().push(version);
+            }
+        }
+
+        let mut ready: BTreeSet<&Version> = pending
+            .iter()
+            .filter(|(_, count)| **count == 0)
+            .map(|(version, _)| *version)
+            .collect();
+        let mut order = Vec::with_capacity(self.edits.len());
+
+        while let Some(next) = ready.pop_first() {
+            order.push(next.clone());
+            for child in children.get(next).into_iter().flatten() {
+                let count = pending.get_mut(child).expect("child is recorded");
+                *count -= 1;
+                if *count == 0 {
+                    ready.insert(child);
+                }
+            }
+        }
+        order
+    }
+
+    // ========== Query Methods ==========
+
+    /// Get the current merged value.
+    #[inline]
+    #[must_use]
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+
+    /// Get the current merged value serialized as a JSON string.
+    #[must_use]
+    pub fn content(&self) -> String {
+        self.value.to_string()
+    }
+
+    /// Get the current version (the frontier of the version DAG).
+    ///
+    /// Contains more than one version when concurrent edits have not yet been
+    /// followed by a merging edit. Empty for a new document.
+    #[inline]
+    #[must_use]
+    pub fn version(&self) -> &[Version] {
+        &self.frontier
+    }
+
+    /// Check whether a version has been applied to this document.
+    #[inline]
+    #[must_use]
+    pub fn contains_version(&self, version: &Version) -> bool {
+        self.edits.contains_key(version)
+    }
+
+    /// Check if no updates have been applied.
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.edits.is_empty()
+    }
+
+    /// Create a checkpoint snapshot of the current state.
+    ///
+    /// Mirrors [`DiamondCRDT::checkpoint`](crate::merge::DiamondCRDT::checkpoint):
+    ///
+    /// - `content` (string): The merged value serialized as JSON
+    /// - `value` (object): The merged value
+    /// - `version` (string): The frontier formatted as a `Version` header
+    /// - `updates` (number): Number of recorded updates
+    #[must_use]
+    pub fn checkpoint(&self) -> Value {
+        json!({
+            "content": self.content(),
+            "value": self.value,
+            "version": protocol::format_version_header(&self.frontier),
+            "updates": self.edits.len(),
+        })
+    }
+}
+
+// ========== MergeType ==========
+
+impl MergeType for JsonDocument {
+    fn name(&self) -> &str {
+        merge_types::JSON
+    }
+
+    /// Apply an update carrying `json` range patches, a body with a `json` content
+    /// range, or a plain body replacing the whole document.
+    ///
+    /// A missing `Version` is assigned as `{agent_id}-{uuid}`, and missing `Parents`
+    /// default to the current version. Returns the patches if they were made against
+    /// the current version, and otherwise a snapshot of the merged value. An update
+    /// whose `Version` is already known is not re-applied and yields a snapshot.
+    fn apply(&mut self, update: &Update, agent_id: &str) -> Result<Update> {
+        let version = update.primary_version().cloned().unwrap_or_else(|| {
+            Version::new(format!("{}-{}", agent_id, uuid::Uuid::new_v4()))
+        });
+        if self.contains_version(&version) {
+            return Ok(MergeType::snapshot(self));
+        }
+        let parents = if update.parents.is_empty() {
+            self.frontier.clone()
+        } else {
+            update.parents.clone()
+        };
+
+        let patches = update_patches(update)?;
+        let at_tip = same_versions(&parents, &self.frontier);
+        self.apply_patches(version.clone(), parents.clone(), &patches)?;
+
+        let resolved = match (at_tip, &update.patches, &update.content_range) {
+            (true, Some(_), _) | (true, None, Some(_)) => Update::patched(version, patches),
+            _ => Update::snapshot(version, self.content()),
+        };
+        Ok(resolved
+            .with_parents(parents)
+            .with_merge_type(merge_types::JSON))
+    }
+
+    /// Previews patches or a body made against the current version.
+    fn preview(&self, update: &Update) -> Option<Bytes> {
+        let known = update.primary_version().is_some_and(|v| self.contains_version(v));
+        if known || (!update.parents.is_empty() && !same_versions(&update.parents, &self.frontier)) {
+            return None;
+        }
+        let mut value = self.value.clone();
+        for (path, patch_value) in parse_patches(&update_patches(update).ok()?).ok()? {
+            apply_at_path(&mut value, &path, patch_value);
+        }
+        Some(value.to_string().into())
+    }
+
+    fn snapshot(&self) -> Update {
+        Update {
+            version: self.frontier.clone(),
+            body: Some(self.content().into()),
+            merge_type: Some(merge_types::JSON.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn version(&self) -> Vec<Version> {
+        self.frontier.clone()
+    }
+
+    /// Only the merged value is kept, so a peer that isn't at the current version
+    /// gets a snapshot of it.
+    fn patches_since(&self, since: &[Version]) -> Result<Vec<Update>> {
+        if let Some(unknown) = since.iter().find(|v| !self.contains_version(v)) {
+            return Err(BraidError::InvalidVersion(format!(
+                "Unknown version: {}",
+                unknown
+            )));
+        }
+        if same_versions(since, &self.frontier) {
+            return Ok(Vec::new());
+        }
+        Ok(vec![MergeType::snapshot(self)])
+    }
+
+    fn contains_version(&self, version: &Version) -> bool {
+        JsonDocument::contains_version(self, version)
+    }
+
+    /// Only the current version can be checked out: earlier versions are known, but
+    /// their values are [dropped](BraidError::HistoryDropped).
+    fn snapshot_at(&self, version: &[Version]) -> Result<Update> {
+        if same_versions(version, &self.frontier) {
+            return Ok(Update {
+                version: version.to_vec(),
+                ..MergeType::snapshot(self)
+            });
+        }
+        if version.iter().all(|v| self.contains_version(v)) {
+            return Err(BraidError::HistoryDropped);
+        }
+        Err(BraidError::InvalidVersion(format!(
+            "Unknown versions: {}",
+            protocol::format_version_header(version)
+        )))
+    }
+
+    fn checkpoint(&self) -> Value {
+        JsonDocument::checkpoint(self)
+    }
+}
+
+/// The `json` patches an update carries: its patches, its body at its content range,
+/// or its body replacing the whole document.
+fn update_patches(update: &Update) -> Result<Vec<Patch>> {
+    match (&update.patches, &update.body) {
+        (Some(patches), _) => Ok(patches.clone()),
+        (None, Some(body)) => {
+            let range = update
+                .content_range
+                .as_ref()
+                .filter(|cr| cr.is_json())
+                .map(|cr| cr.range.clone())
+                .unwrap_or_default();
+            Ok(vec![Patch::json(range, body.clone())])
+        }
+        (None, None) => Err(BraidError::BodyParse(
+            "json update has no patches or body".to_string(),
+        )),
+    }
+}
+
+/// Parse `json` patches into `(path, value)` pairs.
+fn parse_patches(patches: &[Patch]) -> Result<Vec<(Vec<Segment>, Value)>> {
+    patches
+        .iter()
+        .map(|patch| {
+            if !patch.is_json() {
+                return Err(BraidError::HeaderParse(format!(
+                    "Expected json patch, got unit '{}'",
+                    patch.unit
+                )));
+            }
+            let value = serde_json::from_slice::<Value>(&patch.content).map_err(|e| {
+                BraidError::BodyParse(format!("Invalid JSON for {}: {}", patch.range, e))
+            })?;
+            Ok((parse_json_range(&patch.range)?, value))
+        })
+        .collect()
+}
+
+/// Parse a `json` Content-Range into path segments.
+fn parse_json_range(range: &str) -> Result<Vec<Segment>> {
+    let invalid = || BraidError::HeaderParse(format!("Invalid json range: {}", range));
+    let chars: Vec<char> = range.trim().chars().collect();
+    let mut segments = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '.' => {
+                let start = i + 1;
+                i = start;
+                while i < chars.len() && chars[i] != '.' && chars[i] != '[' {
+                    i += 1;
+                }
+                if i == start {
+                    return Err(invalid());
+                }
+                segments.push(Segment::Key(chars[start..i].iter().collect()));
+            }
+            '[' => {
+                let close = chars[i..]
+                    .iter()
+                    .position(|c| *c == ']')
+                    .map(|offset| i + offset)
+                    .ok_or_else(invalid)?;
+                let inner: String = chars[i + 1..close].iter().collect();
+                let inner = inner.trim();
+
+                if inner.starts_with('"') {
+                    let key = serde_json::from_str::<String>(inner).map_err(|_| invalid())?;
+                    segments.push(Segment::Key(key));
+                } else if let Some((start, end)) = inner.split_once(':') {
+                    let start = start.trim().parse().map_err(|_| invalid())?;
+                    let end = end.trim().parse().map_err(|_| invalid())?;
+                    if end < start {
+                        return Err(invalid());
+                    }
+                    segments.push(Segment::Slice(start, end));
+                } else {
+                    segments.push(Segment::Index(inner.parse().map_err(|_| invalid())?));
+                }
+                i = close + 1;
+            }
+            _ => return Err(invalid()),
+        }
+    }
+
+    if segments[..segments.len().saturating_sub(1)]
+        .iter()
+        .any(|s| matches!(s, Segment::Slice(..)))
+    {
+        return Err(invalid());
+    }
+    Ok(segments)
+}
+
+/// Write `new_value` at `path` inside `target`.
+///
+/// Missing object members are created and `null` is promoted to an object or array
+/// as needed. Paths that cannot be reached (e.g. indexing into a string, or an index
+/// past the end of an array) leave the document untouched, so replaying the same
+/// edits always produces the same result.
+fn apply_at_path(target: &mut Value, path: &[Segment], new_value: Value) {
+    let Some((last, parents)) = path.split_last() else {
+        *target = new_value;
+        return;
+    };
+
+    let mut node = target;
+    for segment in parents {
+        node = match (segment, node) {
+            (Segment::Key(key), node) => {
+                if node.is_null() {
+                    *node = Value::Object(Default::default());
+                }
+                match node {
+                    Value::Object(map) => map.entry(key.clone()).or_insert(Value::Null),
+                    _ => return,
+                }
+            }
+            (Segment::Index(index), Value::Array(items)) if *index < items.len() => {
+                &mut items[*index]
+            }
+            _ => return,
+        };
+    }
+
+    match last {
+        Segment::Key(key) => {
+            if node.is_null() {
+                *node = Value::Object(Default::default());
+            }
+            if let Value::Object(map) = node {
+                map.insert(key.clone(), new_value);
+            }
+        }
+        Segment::Index(index) => {
+            if node.is_null() {
+                *node = Value::Array(Vec::new());
+            }
+            if let Value::Array(items) = node {
+                if *index < items.len() {
+                    items[*index] = new_value;
+                } else if *index == items.len() {
+                    items.push(new_value);
+                }
+            }
+        }
+        Segment::Slice(start, end) => {
+            if node.is_null() {
+                *node = Value::Array(Vec::new());
+            }
+            if let Value::Array(items) = node {
+                if *end <= items.len() {
+                    let replacement = match new_value {
+                        Value::Array(values) => values,
+                        other => vec![other],
+                    };
+                    items.splice(*start..*end, replacement);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(s: &str) -> Version {
+        Version::new(s)
+    }
+
+    #[test]
+    fn test_parse_json_range() {
+        assert_eq!(parse_json_range("").unwrap(), vec![]);
+        assert_eq!(
+            parse_json_range(".users[0].name").unwrap(),
+            vec![
+                Segment::Key("users".into()),
+                Segment::Index(0),
+                Segment::Key("name".into())
+            ]
+        );
+        assert_eq!(
+            parse_json_range(r#"["a b"][1:2]"#).unwrap(),
+            vec![Segment::Key("a b".into()), Segment::Slice(1, 2)]
+        );
+        assert!(parse_json_range("field").is_err());
+        assert!(parse_json_range(".a[1:0]").is_err());
+        assert!(parse_json_range(".a[0:1].b").is_err());
+    }
+
+    #[test]
+    fn test_snapshot_then_field_patch() {
+        let mut doc = JsonDocument::new();
+        doc.apply_snapshot(v("v1"), vec![], json!({"name": "Alice", "age": 30}))
+            .unwrap();
+        doc.apply_patches(v("v2"), vec![v("v1")], &[Patch::json(".age", "31")])
+            .unwrap();
+
+        assert_eq!(doc.value(), &json!({"name": "Alice", "age": 31}));
+        assert_eq!(doc.version(), &[v("v2")]);
+    }
+
+    #[test]
+    fn test_array_splice_and_index() {
+        let mut doc = JsonDocument::new();
+        doc.apply_snapshot(v("v1"), vec![], json!({"messages": ["a", "c"]}))
+            .unwrap();
+        doc.apply_patches(v("v2"), vec![v("v1")], &[Patch::json(".messages[1:1]", r#"["b"]"#)])
+            .unwrap();
+        doc.apply_patches(v("v3"), vec![v("v2")], &[Patch::json(".messages[3]", r#""d""#)])
+            .unwrap();
+
+        assert_eq!(doc.value()["messages"], json!(["a", "b", "c", "d"]));
+    }
+
+    #[test]
+    fn test_concurrent_different_paths_merge() {
+        let mut doc = JsonDocument::new();
+        doc.apply_snapshot(v("v1"), vec![], json!({"a": 0, "b": 0})).unwrap();
+        doc.apply_patches(v("v2"), vec![v("v1")], &[Patch::json(".a", "1")])
+            .unwrap();
+        doc.apply_patches(v("v3"), vec![v("v1")], &[Patch::json(".b", "2")])
+            .unwrap();
+
+        assert_eq!(doc.value(), &json!({"a": 1, "b": 2}));
+        assert_eq!(doc.version(), &[v("v2"), v("v3")]);
+    }
+
+    #[test]
+    fn test_concurrent_same_key_greater_version_wins() {
+        let mut forward = JsonDocument::new();
+        let mut backward = JsonDocument::new();
+        for doc in [&mut forward, &mut backward] {
+            doc.apply_snapshot(v("v1"), vec![], json!({"title": "old"})).unwrap();
+        }
+
+        let alice = [Patch::json(".title", r#""from alice""#)];
+        let bob = [Patch::json(".title", r#""from bob""#)];
+
+        forward.apply_patches(v("alice-1"), vec![v("v1")], &alice).unwrap();
+        forward.apply_patches(v("bob-1"), vec![v("v1")], &bob).unwrap();
+        backward.apply_patches(v("bob-1"), vec![v("v1")], &bob).unwrap();
+        backward.apply_patches(v("alice-1"), vec![v("v1")], &alice).unwrap();
+
+        assert_eq!(forward.value(), backward.value());
+        assert_eq!(forward.value()["title"], "from bob");
+    }
+
+    #[test]
+    fn test_causal_edit_wins_over_version_order() {
+        let mut doc = JsonDocument::new();
+        doc.apply_snapshot(v("z"), vec![], json!({"x": 1})).unwrap();
+        doc.apply_patches(v("a"), vec![v("z")], &[Patch::json(".x", "2")])
+            .unwrap();
+        assert_eq!(doc.value()["x"], 2);
+    }
+
+    #[test]
+    fn test_overlapping_paths_converge() {
+        let base = json!({"a": {"b": 1, "c": 1}});
+        let replace_parent = [Patch::json(".a", r#"{"b": 10}"#)];
+        let edit_child = [Patch::json(".a.c", "20")];
+
+        let mut one = JsonDocument::new();
+        let mut two = JsonDocument::new();
+        for doc in [&mut one, &mut two] {
+            doc.apply_snapshot(v("v1"), vec![], base.clone()).unwrap();
+        }
+        one.apply_patches(v("p"), vec![v("v1")], &replace_parent).unwrap();
+        one.apply_patches(v("q"), vec![v("v1")], &edit_child).unwrap();
+        two.apply_patches(v("q"), vec![v("v1")], &edit_child).unwrap();
+        two.apply_patches(v("p"), vec![v("v1")], &replace_parent).unwrap();
+
+        assert_eq!(one.value(), two.value());
+        assert_eq!(one.value(), &json!({"a": {"b": 10, "c": 20}}));
+    }
+
+    #[test]
+    fn test_duplicate_version_is_idempotent() {
+        let mut doc = JsonDocument::new();
+        doc.apply_patches(v("v1"), vec![], &[Patch::json(".n", "1")]).unwrap();
+        doc.apply_patches(v("v1"), vec![], &[Patch::json(".n", "2")]).unwrap();
+        assert_eq!(doc.value()["n"], 1);
+    }
+
+    #[test]
+    fn test_unknown_parent_rejected() {
+        let mut doc = JsonDocument::new();
+        let result = doc.apply_patches(v("v2"), vec![v("missing")], &[Patch::json(".n", "1")]);
+        assert!(matches!(result, Err(BraidError::InvalidVersion(_))));
+        assert!(doc.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_patches_rejected() {
+        let mut doc = JsonDocument::new();
+        assert!(matches!(
+            doc.apply_patches(v("v1"), vec![], &[Patch::json(".n", "not json")]),
+            Err(BraidError::BodyParse(_))
+        ));
+        assert!(matches!(
+            doc.apply_patches(v("v1"), vec![], &[Patch::text(".n", "1")]),
+            Err(BraidError::HeaderParse(_))
+        ));
+        assert!(doc.is_empty());
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        let mut doc = JsonDocument::new();
+        doc.apply_snapshot(v("v1"), vec![], json!({"k": "v"})).unwrap();
+        let cp = doc.checkpoint();
+        assert_eq!(cp["content"], r#"{"k":"v"}"#);
+        assert_eq!(cp["version"], "\"v1\"");
+        assert_eq!(cp["updates"], 1);
+    }
+
+    #[test]
+    fn test_merge_type_apply_and_catch_up() {
+        let mut doc = JsonDocument::new();
+        let base = Update::snapshot(v("v1"), r#"{"a": 1}"#).with_merge_type("json");
+        let resolved = MergeType::apply(&mut doc, &base, "alice").unwrap();
+        assert_eq!(resolved.version, vec![v("v1")]);
+        assert_eq!(resolved.merge_type.as_deref(), Some("json"));
+
+        let edit = Update::patched(v("v2"), vec![Patch::json(".b", "2")]).with_parent(v("v1"));
+        assert_eq!(doc.preview(&edit).as_deref(), Some(&br#"{"a":1,"b":2}"#[..]));
+        let resolved = MergeType::apply(&mut doc, &edit, "bob").unwrap();
+        assert_eq!(resolved.patches, Some(vec![Patch::json(".b", "2")]));
+        assert_eq!(resolved.parents, vec![v("v1")]);
+
+        // Only the merged value is kept
+        assert!(doc.patches_since(&[v("v2")]).unwrap().is_empty());
+        let catch_up = doc.patches_since(&[v("v1")]).unwrap();
+        assert_eq!(catch_up[0].body_str(), Some(r#"{"a":1,"b":2}"#));
+        assert!(doc.patches_since(&[v("v9")]).is_err());
+        assert!(matches!(doc.snapshot_at(&[v("v1")]), Err(BraidError::HistoryDropped)));
+        assert_eq!(doc.snapshot_at(&[v("v2")]).unwrap().body_str(), Some(r#"{"a":1,"b":2}"#));
+    }
+}
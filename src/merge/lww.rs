@@ -0,0 +1,547 @@
+//! Last-writer-wins register for opaque resources.
+//!
+//! This module provides [`LwwRegister`], the `"lww"` merge type. It holds one opaque
+//! value (any bytes: an image, a zip archive, a serialized blob) and resolves
+//! concurrent writes by picking a single winner instead of merging their contents.
+//!
+//! # Choosing the Winner
+//!
+//! Every write is a node in the Braid version DAG. The register orders all writes by a
+//! total order that every peer computes identically:
+//!
+//! | Key | Rule |
+//! |-----|------|
+//! | Lamport depth | `1 + max(depth of parents)`; a deeper write wins |
+//! | Version ID | Ties are broken by the greater [`Version`] |
+//!
+//! A write always beats its ancestors, because it is deeper than all of them. Among
+//! concurrent writes the outcome is arbitrary but deterministic, so peers converge
+//! regardless of arrival order.
+//!
+//! # Byte Patches
+//!
+//! A write may also be `bytes` range patches instead of a body (see
+//! [`apply_byte_patches`]). The patches apply to the value at the write's parents, and
+//! the result is recorded as a write like any other, so a small edit to a large blob
+//! needn't resend it. A patched write made on top of the winning version is passed on
+//! as its patches; otherwise the new value is passed on as a snapshot.
+//!
+//! # Losing Writes
+//!
+//! A write that loses is still recorded in the DAG, but the value does not change. The
+//! update returned by [`MergeType::apply`] then carries status `293 Merge Conflict`,
+//! the winning value as its body, and a `Current-Version` naming the winning version,
+//! so the losing writer learns what the resource holds instead. The resource doesn't
+//! change, so the update is for the writer alone: the server doesn't notify observers
+//! of it, and it shouldn't be broadcast (see [`Update::is_conflict`]).
+//!
+//! # Examples
+//!
+//! ```
+//! use braid_axum_http::merge::LwwRegister;
+//! use braid_axum_http::Version;
+//!
+//! let mut register = LwwRegister::new();
+//! register.write(Version::new("a"), vec![], "first", None).unwrap();
+//!
+//! // Two concurrent writes on top of "a": the greater version ID wins
+//! assert!(register.write(Version::new("c"), vec![Version::new("a")], "mine", None).unwrap());
+//! assert!(!register.write(Version::new("b"), vec![Version::new("a")], "yours", None).unwrap());
+//!
+//! assert_eq!(register.value(), b"mine");
+//! assert_eq!(register.winner(), Some(&Version::new("c")));
+//! ```
+//!
+//! # Specification
+//!
+//! - [draft-toomim-httpbis-braid-http-04] Section 2.2 (Merge-Types)
+//!
+//! [draft-toomim-httpbis-braid-http-04]: https://datatracker.ietf.org/doc/html/draft-toomim-httpbis-braid-http
+
+use super::merge_type::same_versions;
+use super::{apply_byte_patches, MergeType};
+use crate::error::{BraidError, Result};
+use crate::protocol::{self, merge_types, STATUS_MERGE_CONFLICT};
+use crate::types::{Patch, Update, Version};
+use bytes::Bytes;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+
+/// One write recorded in the register.
+#[derive(Clone, Debug)]
+struct Write {
+    /// Parent versions
+    parents: Vec<Version>,
+    /// Lamport depth: 1 + max parent depth
+    depth: u64,
+    /// Value written
+    body: Bytes,
+    /// Content type of the value, if known
+    content_type: Option<String>,
+    /// The `bytes` patches the value was written as, if any
+    patches: Option<Vec<Patch>>,
+}
+
+/// Last-writer-wins register holding an opaque value.
+///
+/// See the [module documentation](self) for how the winner is chosen.
+#[derive(Clone, Debug, Default)]
+pub struct LwwRegister {
+    /// Every write ever seen, by version
+    writes: HashMap<Version, Write>,
+    /// The write whose value is current
+    winner: Option<Version>,
+}
+
+impl LwwRegister {
+    /// Create an empty register.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a write of `body` at `version`.
+    ///
+    /// Writing a version that is already known has no effect.
+    ///
+    /// # Arguments
+    ///
+    /// * `version` - Version of the write
+    /// * `parents` - Versions the writer had seen
+    /// * `body` - The new value
+    /// * `content_type` - Content type of the value, if known
+    ///
+    /// # Returns
+    ///
+    /// `true` if this write is now the winner, `false` if it lost to another write.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BraidError::InvalidVersion`] if a parent version is unknown.
+    pub fn write(
+        &mut self,
+        version: Version,
+        parents: Vec<Version>,
+        body: impl Into<Bytes>,
+        content_type: Option<String>,
+    ) -> Result<bool> {
+        if self.writes.contains_key(&version) {
+            return Ok(self.winner.as_ref() == Some(&version));
+        }
+
+        let mut depth = 0;
+        for parent in &parents {
+            let write = self.writes.get(parent).ok_or_else(|| {
+                BraidError::InvalidVersion(format!("Unknown parent version: {}", parent))
+            })?;
+            depth = depth.max(write.depth);
+        }
+
+        let key = (depth + 1, &version);
+        let wins = match &self.winner {
+            Some(winner) => key > (self.writes[winner].depth, winner),
+            None => true,
+        };
+
+        self.writes.insert(
+            version.clone(),
+            Write {
+                parents,
+                depth: depth + 1,
+                body: body.into(),
+                content_type,
+                patches: None,
+            },
+        );
+        if wins {
+            self.winner = Some(version);
+        }
+        Ok(wins)
+    }
+
+    // ========== Query Methods ==========
+
+    /// Get the current value (empty if nothing has been written).
+    #[inline]
+    #[must_use]
+    pub fn value(&self) -> &[u8] {
+        self.current().map_or(&[], |write| &write.body)
+    }
+
+    /// Get the content type of the current value.
+    #[inline]
+    #[must_use]
+    pub fn content_type(&self) -> Option<&str> {
+        self.current().and_then(|write| write.content_type.as_deref())
+    }
+
+    /// Get the version of the winning write.
+    #[inline]
+    #[must_use]
+    pub fn winner(&self) -> Option<&Version> {
+        self.winner.as_ref()
+    }
+
+    /// Get the parents of a known write.
+    #[must_use]
+    pub fn parents_of(&self, version: &Version) -> Option<&[Version]> {
+        self.writes.get(version).map(|write| write.parents.as_slice())
+    }
+
+    /// Check whether a version has been written.
+    #[inline]
+    #[must_use]
+    pub fn contains_version(&self, version: &Version) -> bool {
+        self.writes.contains_key(version)
+    }
+
+    /// Check whether nothing has been written yet.
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.writes.is_empty()
+    }
+
+    /// Create a JSON checkpoint of the register.
+    ///
+    /// `content` is the value decoded as UTF-8 (lossily); `length` is its size in bytes.
+    #[must_use]
+    pub fn checkpoint(&self) -> Value {
+        json!({
+            "content": String::from_utf8_lossy(self.value()),
+            "length": self.value().len(),
+            "content_type": self.content_type(),
+            "version": protocol::format_version_header(self.current_version()),
+            "writes": self.writes.len(),
+        })
+    }
+
+    /// The winning write among `versions` and their ancestors.
+    ///
+    /// `None` if a version is unknown; `Some(None)` if `versions` is empty.
+    fn winner_at(&self, versions: &[Version]) -> Option<Option<(&Version, &Write)>> {
+        let mut pending: Vec<&Version> = versions.iter().collect();
+        let mut seen = HashSet::new();
+        let mut winner: Option<(&Version, &Write)> = None;
+        while let Some(version) = pending.pop() {
+            if !seen.insert(version) {
+                continue;
+            }
+            let (version, write) = self.writes.get_key_value(version)?;
+            if winner.is_none_or(|(v, w)| (write.depth, version) > (w.depth, v)) {
+                winner = Some((version, write));
+            }
+            pending.extend(&write.parents);
+        }
+        Some(winner)
+    }
+
+    fn current(&self) -> Option<&Write> {
+        self.winner.as_ref().map(|winner| &self.writes[winner])
+    }
+
+    fn current_version(&self) -> &[Version] {
+        self.winner.as_slice()
+    }
+}
+
+impl MergeType for LwwRegister {
+    fn name(&self) -> &str {
+        merge_types::LWW
+    }
+
+    /// Apply an update whose body replaces the value, or whose `bytes` patches edit
+    /// the value at its parents.
+    ///
+    /// A missing `Version` is assigned as `{agent_id}-{uuid}`, and missing `Parents`
+    /// default to the winning version. Returns the write if it won (as its patches when
+    /// made on top of the previous winner, otherwise as a snapshot), or a
+    /// `293 Merge Conflict` update naming the winner if it lost.
+    fn apply(&mut self, update: &Update, agent_id: &str) -> Result<Update> {
+        let version = update.primary_version().cloned().unwrap_or_else(|| {
+            Version::new(format!("{}-{}", agent_id, uuid::Uuid::new_v4()))
+        });
+        let parents = if update.parents.is_empty() {
+            self.current_version().to_vec()
+        } else {
+            update.parents.clone()
+        };
+
+        let patches = match (&update.body, &update.patches, &update.content_range) {
+            (None, Some(patches), None) if patches.iter().all(Patch::is_bytes) => Some(patches.clone()),
+            (Some(body), None, Some(range)) if range.is_bytes() => {
+                Some(vec![Patch::bytes(range.range.clone(), body.clone())])
+            }
+            (Some(_), None, None) => None,
+            _ => {
+                return Err(BraidError::BodyParse(
+                    "lww updates must replace the whole value with a body, or patch it with bytes ranges"
+                        .to_string(),
+                ))
+            }
+        };
+        let (body, content_type) = match &patches {
+            Some(patches) => {
+                let base = self.winner_at(&parents).ok_or_else(|| {
+                    BraidError::InvalidVersion(format!(
+                        "Unknown parent versions: {}",
+                        protocol::format_version_header(&parents)
+                    ))
+                })?;
+                let body = apply_byte_patches(base.map_or(&[][..], |(_, write)| &write.body), patches)?;
+                // A patched value keeps the content type of the value it patched
+                let content_type = update
+                    .content_type
+                    .clone()
+                    .or_else(|| base.and_then(|(_, write)| write.content_type.clone()));
+                (body, content_type)
+            }
+            None => (update.body.clone().unwrap_or_default(), update.content_type.clone()),
+        };
+
+        let previous = self.winner.clone();
+        let known = self.contains_version(&version);
+        if self.write(version.clone(), parents.clone(), body, content_type)? {
+            if let (Some(patches), false) = (patches, known) {
+                if let Some(write) = self.writes.get_mut(&version) {
+                    write.patches = Some(patches.clone());
+                }
+                if same_versions(previous.as_slice(), &parents) {
+                    return Ok(Update::patched(version, patches)
+                        .with_parents(parents)
+                        .with_merge_type(merge_types::LWW));
+                }
+            }
+            return Ok(self.snapshot().with_parents(parents));
+        }
+
+        let mut lost = Update::snapshot(version, Bytes::copy_from_slice(self.value()))
+            .with_parents(parents)
+            .with_merge_type(merge_types::LWW)
+            .with_status(STATUS_MERGE_CONFLICT);
+        lost.current_version = Some(self.current_version().to_vec());
+        if let Some(content_type) = self.content_type() {
+            lost = lost.with_content_type(content_type);
+        }
+        Ok(lost)
+    }
+
+    fn snapshot(&self) -> Update {
+        Update {
+            version: self.current_version().to_vec(),
+            body: Some(Bytes::copy_from_slice(self.value())),
+            content_type: self.content_type().map(str::to_string),
+            merge_type: Some(merge_types::LWW.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn version(&self) -> Vec<Version> {
+        self.current_version().to_vec()
+    }
+
+    fn contains_version(&self, version: &Version) -> bool {
+        LwwRegister::contains_version(self, version)
+    }
+
+    /// Returns nothing if the peer already has the winning write, its patches if the
+    /// peer has exactly its parents and it was written as patches, and otherwise a
+    /// snapshot.
+    fn patches_since(&self, since: &[Version]) -> Result<Vec<Update>> {
+        if let Some(unknown) = since.iter().find(|v| !self.contains_version(v)) {
+            return Err(BraidError::InvalidVersion(format!(
+                "Unknown version: {}",
+                unknown
+            )));
+        }
+        let Some(winner) = self.winner.as_ref().filter(|winner| !since.contains(winner)) else {
+            return Ok(Vec::new());
+        };
+        let write = &self.writes[winner];
+        match &write.patches {
+            Some(patches) if !since.is_empty() && same_versions(since, &write.parents) => {
+                Ok(vec![Update::patched(winner.clone(), patches.clone())
+                    .with_parents(write.parents.clone())
+                    .with_merge_type(merge_types::LWW)])
+            }
+            _ => Ok(vec![self.snapshot()]),
+        }
+    }
+
+    /// Every write is kept, so any known version can be reconstructed: its value is
+    /// the winning write among the version and its ancestors.
+    fn snapshot_at(&self, version: &[Version]) -> Result<Update> {
+        let winner = self.winner_at(version).ok_or_else(|| {
+            BraidError::InvalidVersion(format!(
+                "Unknown versions: {}",
+                protocol::format_version_header(version)
+            ))
+        })?;
+        Ok(Update {
+            version: version.to_vec(),
+            body: Some(winner.map_or_else(Bytes::new, |(_, write)| write.body.clone())),
+            content_type: winner.and_then(|(_, write)| write.content_type.clone()),
+            merge_type: Some(merge_types::LWW.to_string()),
+            ..Default::default()
+        })
+    }
+
+    fn checkpoint(&self) -> Value {
+        LwwRegister::checkpoint(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put(version: &str, parents: &[&str], body: &'static str) -> Update {
+        Update::snapshot(Version::new(version), body)
+            .with_parents(parents.iter().map(|p| Version::new(*p)).collect())
+            .with_merge_type("lww")
+    }
+
+    #[test]
+    fn test_descendant_beats_greater_concurrent_id() {
+        let mut register = LwwRegister::new();
+        register.write(Version::new("a"), vec![], "1", None).unwrap();
+        register.write(Version::new("z"), vec![Version::new("a")], "2", None).unwrap();
+        // "b" is deeper than "z" because it has seen it
+        let wins = register
+            .write(Version::new("b"), vec![Version::new("z")], "3", None)
+            .unwrap();
+        assert!(wins);
+        assert_eq!(register.value(), b"3");
+    }
+
+    #[test]
+    fn test_concurrent_writes_converge_in_any_order() {
+        let base = put("base", &[], "0");
+        let alice = put("alice-1", &["base"], "from alice");
+        let bob = put("bob-1", &["base"], "from bob");
+
+        let mut one = LwwRegister::new();
+        let mut two = LwwRegister::new();
+        for update in [&base, &alice, &bob] {
+            one.apply(update, "x").unwrap();
+        }
+        for update in [&base, &bob, &alice] {
+            two.apply(update, "x").unwrap();
+        }
+
+        assert_eq!(one.value(), b"from bob");
+        assert_eq!(one.value(), two.value());
+        assert_eq!(one.winner(), two.winner());
+    }
+
+    #[test]
+    fn test_losing_write_gets_merge_conflict() {
+        let mut register = LwwRegister::new();
+        register.apply(&put("base", &[], "0"), "x").unwrap();
+        let won = register.apply(&put("bob-1", &["base"], "bob"), "bob").unwrap();
+        assert_eq!(won.status, 200);
+        assert_eq!(won.version, vec![Version::new("bob-1")]);
+
+        let lost = register.apply(&put("alice-1", &["base"], "alice"), "alice").unwrap();
+        assert_eq!(lost.status, STATUS_MERGE_CONFLICT);
+        assert_eq!(lost.version, vec![Version::new("alice-1")]);
+        assert_eq!(lost.current_version, Some(vec![Version::new("bob-1")]));
+        assert_eq!(lost.body_str(), Some("bob"));
+        assert_eq!(register.parents_of(&Version::new("alice-1")).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_binary_value_and_defaults() {
+        let mut register = LwwRegister::new();
+        let png: &'static [u8] = &[0x89, b'P', b'N', b'G', 0xff, 0x00];
+        let update = Update {
+            body: Some(Bytes::from_static(png)),
+            content_type: Some("image/png".to_string()),
+            ..Default::default()
+        };
+        let first = register.apply(&update, "alice").unwrap();
+        assert!(first.version[0].to_string().starts_with("alice-"));
+
+        // Missing parents default to the winner, so a second write always wins
+        let second = register.apply(&update, "bob").unwrap();
+        assert_eq!(second.parents, first.version);
+        assert_eq!(register.value(), png);
+        assert_eq!(register.snapshot().content_type.as_deref(), Some("image/png"));
+        assert_eq!(register.checkpoint()["length"], 6);
+    }
+
+    #[test]
+    fn test_bytes_patches_apply_at_parents() {
+        let mut register = LwwRegister::new();
+        let base = Update {
+            body: Some(Bytes::from_static(&[0, 1, 2, 3])),
+            content_type: Some("application/octet-stream".to_string()),
+            ..put("a", &[], "")
+        };
+        register.apply(&base, "x").unwrap();
+
+        // On top of the winner, the write is passed on as its patches
+        let patch = Patch::bytes("1:3", &[0xff][..]);
+        let edit = Update::patched(Version::new("b"), vec![patch.clone()])
+            .with_parent(Version::new("a"))
+            .with_merge_type("lww");
+        let resolved = register.apply(&edit, "x").unwrap();
+        assert_eq!(resolved.patches, Some(vec![patch.clone()]));
+        assert_eq!(register.value(), &[0, 0xff, 3]);
+        assert_eq!(register.content_type(), Some("application/octet-stream"));
+        assert_eq!(register.patches_since(&[Version::new("a")]).unwrap()[0].patches, Some(vec![patch]));
+        assert!(register.patches_since(&[]).unwrap()[0].is_snapshot());
+
+        // A deeper write patching an older version is passed on as a snapshot
+        let old = Update::patched(Version::new("a2"), vec![Patch::bytes("4:4", &[4][..])])
+            .with_parent(Version::new("a"));
+        let edit = Update::patched(Version::new("d"), vec![Patch::bytes("0:1", Bytes::new())])
+            .with_parent(Version::new("a2"));
+        assert_eq!(register.apply(&old, "x").unwrap().status, STATUS_MERGE_CONFLICT);
+        let resolved = register.apply(&edit, "x").unwrap();
+        assert_eq!(resolved.body.as_deref(), Some(&[1u8, 2, 3, 4][..]));
+
+        let out_of_range = Update::patched(Version::new("e"), vec![Patch::bytes("9:9", Bytes::new())]);
+        assert!(register.apply(&out_of_range, "x").is_err());
+        assert!(!register.contains_version(&Version::new("e")));
+    }
+
+    #[test]
+    fn test_patches_since() {
+        let mut register = LwwRegister::new();
+        register.apply(&put("a", &[], "1"), "x").unwrap();
+        register.apply(&put("b", &["a"], "2"), "x").unwrap();
+
+        assert!(register.patches_since(&[Version::new("b")]).unwrap().is_empty());
+        let updates = register.patches_since(&[Version::new("a")]).unwrap();
+        assert_eq!(updates[0].body_str(), Some("2"));
+        assert!(register.patches_since(&[Version::new("nope")]).is_err());
+    }
+
+    #[test]
+    fn test_snapshot_at_past_versions() {
+        let mut register = LwwRegister::new();
+        register.apply(&put("a", &[], "1"), "x").unwrap();
+        register.apply(&put("c", &["a"], "3"), "x").unwrap();
+        register.apply(&put("b", &["a"], "2"), "x").unwrap();
+
+        let at = |versions: &[&str]| {
+            let versions: Vec<Version> = versions.iter().map(|v| Version::new(*v)).collect();
+            register.snapshot_at(&versions).map(|u| u.body_str().unwrap().to_string())
+        };
+        assert_eq!(at(&["a"]).unwrap(), "1");
+        assert_eq!(at(&["b"]).unwrap(), "2");
+        assert_eq!(at(&["b", "c"]).unwrap(), "3");
+        assert_eq!(at(&[]).unwrap(), "");
+        assert!(matches!(at(&["nope"]), Err(BraidError::InvalidVersion(_))));
+    }
+
+    #[test]
+    fn test_rejects_patches_and_unknown_parents() {
+        let mut register = LwwRegister::new();
+        let patched = Update::patched(Version::new("a"), vec![crate::types::Patch::text("[0:0]", "x")]);
+        assert!(register.apply(&patched, "x").is_err());
+        assert!(register.apply(&put("b", &["missing"], "x"), "x").is_err());
+        assert!(register.is_empty());
+    }
+}
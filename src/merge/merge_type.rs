@@ -0,0 +1,456 @@
+//! Pluggable merge types and the registry that names them.
+//!
+//! A Braid resource declares how concurrent edits are reconciled through its
+//! `Merge-Type` header. This module defines the [`MergeType`] trait that every merge
+//! algorithm implements, and [`MergeTypeRegistry`], which maps merge-type names to
+//! factories creating one document per resource.
+//!
+//! # Built-in Merge Types
+//!
+//! | Name | Document |
+//! |------|----------|
+//! | `"diamond"` | [`DiamondCRDT`] |
+//! | `"sync9"` | [`Sync9`] |
+//! | `"ot-text"` | [`OtText`] |
+//! | `"lww"` | [`LwwRegister`] |
+//! | `"json"` | [`JsonDocument`] |
+//!
+//! # Custom Merge Types
+//!
+//! ```
+//! use braid_axum_http::merge::{MergeType, MergeTypeRegistry};
+//! use braid_axum_http::{Result, Update, Version};
+//!
+//! /// Keeps whatever was written last.
+//! #[derive(Clone, Debug, Default)]
+//! struct Overwrite {
+//!     body: String,
+//!     version: Vec<Version>,
+//! }
+//!
+//! impl MergeType for Overwrite {
+//!     fn name(&self) -> &str {
+//!         "overwrite"
+//!     }
+//!
+//!     fn apply(&mut self, update: &Update, _agent_id: &str) -> Result<Update> {
+//!         self.body = update.body_str().unwrap_or_default().to_string();
+//!         self.version = update.version.clone();
+//!         Ok(self.snapshot())
+//!     }
+//!
+//!     fn snapshot(&self) -> Update {
+//!         let mut update = Update::snapshot(Version::new("_"), self.body.clone());
+//!         update.version = self.version.clone();
+//!         update.with_merge_type("overwrite")
+//!     }
+//!
+//!     fn patches_since(&self, _since: &[Version]) -> Result<Vec<Update>> {
+//!         Ok(vec![self.snapshot()])
+//!     }
+//! }
+//!
+//! let registry = MergeTypeRegistry::new();
+//! registry.register("overwrite", || Box::new(Overwrite::default()));
+//! assert!(registry.contains("overwrite"));
+//! assert!(registry.contains("diamond"));
+//! ```
+//!
+//! # Specification
+//!
+//! - [draft-toomim-httpbis-braid-http-04] Section 2.2 (Merge-Types)
+//!
+//! [draft-toomim-httpbis-braid-http-04]: https://datatracker.ietf.org/doc/html/draft-toomim-httpbis-braid-http
+
+use super::{DiamondCRDT, JsonDocument, LwwRegister, OtText, Sync9};
+use crate::error::{BraidError, Result};
+use crate::protocol::{self, merge_types};
+use crate::types::{Patch, Update, Version};
+use bytes::Bytes;
+use parking_lot::RwLock;
+use serde_json::{json, Value};
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// A merge algorithm holding the state of one resource.
+///
+/// Each resource gets its own instance, created by a [`MergeTypeRegistry`] factory
+/// and then [initialized](MergeType::initialize). All incoming updates for the
+/// resource flow through [`apply`](MergeType::apply).
+///
+/// Implementations must be deterministic: peers that apply the same set of updates,
+/// in any causal order, must end up with the same snapshot.
+pub trait MergeType: Any + Send + Sync + fmt::Debug + MergeTypeClone {
+    /// The merge-type name this document is registered under (e.g. `"sync9"`).
+    fn name(&self) -> &str;
+
+    /// Prepare a freshly created document for a resource.
+    ///
+    /// Called once, before the first update is applied. The default does nothing.
+    ///
+    /// # Arguments
+    ///
+    /// * `resource_id` - The resource this document belongs to
+    /// * `agent_id` - The agent whose update caused the resource to be created
+    fn initialize(&mut self, resource_id: &str, agent_id: &str) -> Result<()> {
+        let _ = (resource_id, agent_id);
+        Ok(())
+    }
+
+    /// Apply an incoming update, merging it with concurrent history.
+    ///
+    /// The update's `Parents` identify the version it was made against.
+    ///
+    /// # Arguments
+    ///
+    /// * `update` - The incoming Braid update
+    /// * `agent_id` - Origin agent identifier
+    ///
+    /// # Returns
+    ///
+    /// The update to broadcast to subscribers: patches or a snapshot describing the
+    /// newly merged state.
+    fn apply(&mut self, update: &Update, agent_id: &str) -> Result<Update>;
+
+    /// Produce a snapshot of the current state, tagged with the current version.
+    fn snapshot(&self) -> Update;
+
+    /// Get the current version, as [`snapshot`](MergeType::snapshot) tags it.
+    ///
+    /// The default takes it from a snapshot; documents that track their version
+    /// return it without building one.
+    fn version(&self) -> Vec<Version> {
+        self.snapshot().version
+    }
+
+    /// Compute the content an update would leave, without applying it.
+    ///
+    /// Lets an update be [validated](crate::server::validation) without copying the
+    /// document. Returns `None` when the content depends on merging with concurrent
+    /// history, and the update is then merged into a copy instead. The default always
+    /// returns `None`.
+    fn preview(&self, update: &Update) -> Option<Bytes> {
+        let _ = update;
+        None
+    }
+
+    /// Produce the updates that bring a peer at `since` up to the current version.
+    ///
+    /// An empty `since` means the peer has nothing, so the result covers the whole
+    /// history (or a snapshot of it).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BraidError::InvalidVersion`](crate::BraidError::InvalidVersion) if
+    /// `since` names a version this document doesn't know.
+    fn patches_since(&self, since: &[Version]) -> Result<Vec<Update>>;
+
+    /// Check whether an update with `version` has been applied to this document.
+    ///
+    /// The default asks [`patches_since`](MergeType::patches_since) whether it knows
+    /// the version.
+    fn contains_version(&self, version: &Version) -> bool {
+        self.patches_since(std::slice::from_ref(version)).is_ok()
+    }
+
+    /// Produce a snapshot of the state as it was at `version`.
+    ///
+    /// An empty `version` means the state before any update. The snapshot is tagged
+    /// with `version`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BraidError::InvalidVersion`](crate::BraidError::InvalidVersion) if
+    /// `version` names a version this document doesn't know, or
+    /// [`BraidError::HistoryDropped`](crate::BraidError::HistoryDropped) if the state
+    /// at `version` is no longer kept. The default keeps no history: it returns the
+    /// current snapshot for the current version and `HistoryDropped` otherwise.
+    fn snapshot_at(&self, version: &[Version]) -> Result<Update> {
+        let snapshot = self.snapshot();
+        if same_versions(&snapshot.version, version) {
+            Ok(snapshot)
+        } else {
+            Err(BraidError::HistoryDropped)
+        }
+    }
+
+    /// Create a JSON checkpoint of the current state.
+    ///
+    /// Contains at least `content` (string) and `version` (formatted `Version`
+    /// header). The default derives both from [`snapshot`](MergeType::snapshot).
+    fn checkpoint(&self) -> Value {
+        let snapshot = self.snapshot();
+        json!({
+            "content": snapshot.body_str().unwrap_or_default(),
+            "version": protocol::format_version_header(&snapshot.version),
+        })
+    }
+
+    /// Encode the whole state, history included, so that [`decode`](MergeType::decode)
+    /// can restore it.
+    ///
+    /// Stores save this as a resource's snapshot (see
+    /// [`ResourceSnapshot`](crate::server::store::ResourceSnapshot)). Returns `None` if
+    /// the merge type can't be encoded, as the default does; its resources are then
+    /// always restored by replaying their stored updates.
+    fn encode(&self) -> Option<Bytes> {
+        None
+    }
+
+    /// Restore the state produced by [`encode`](MergeType::encode) into this newly
+    /// initialized document.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `data` isn't a valid encoding, or if the merge type can't be
+    /// encoded, as the default does.
+    fn decode(&mut self, data: &[u8]) -> Result<()> {
+        let _ = data;
+        Err(BraidError::BodyParse(format!(
+            "Merge type '{}' has no encoding to decode",
+            self.name()
+        )))
+    }
+}
+
+/// Preview `text` patches made against the current version of a text document.
+///
+/// Patches at the current version splice the current text in order, whichever merge
+/// type holds it. Updates at other versions, or carrying a body, aren't previewed.
+pub(crate) fn preview_text_patches(content: &str, current: &[Version], update: &Update) -> Option<Bytes> {
+    let patches = update
+        .patches
+        .as_ref()
+        .filter(|patches| !patches.is_empty() && patches.iter().all(Patch::is_text))?;
+    if !update.parents.is_empty() && !same_versions(&update.parents, current) {
+        return None;
+    }
+    super::text::apply_text_patches(content, patches).ok().map(Into::into)
+}
+
+/// Check whether two version sets are equal, ignoring order.
+pub(crate) fn same_versions(a: &[Version], b: &[Version]) -> bool {
+    a.len() == b.len() && a.iter().all(|v| b.contains(v))
+}
+
+/// Object-safe cloning for [`MergeType`] documents.
+///
+/// Implemented automatically for every `MergeType` that is `Clone`.
+pub trait MergeTypeClone {
+    /// Clone this document into a new box.
+    fn clone_box(&self) -> Box<dyn MergeType>;
+}
+
+impl<T: MergeType + Clone> MergeTypeClone for T {
+    fn clone_box(&self) -> Box<dyn MergeType> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn MergeType> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// Creates an empty document for a new resource.
+pub type MergeTypeFactory = Arc<dyn Fn() -> Box<dyn MergeType> + Send + Sync>;
+
+/// Thread-safe registry mapping merge-type names to document factories.
+///
+/// [`MergeTypeRegistry::new`] registers the built-in `"diamond"`, `"sync9"`, `"ot-text"`,
+/// `"lww"` and `"json"` merge types. Registering a name that already exists replaces its factory.
+/// Cloning creates a new handle to the same registry.
+///
+/// # Examples
+///
+/// ```
+/// use braid_axum_http::merge::MergeTypeRegistry;
+///
+/// let registry = MergeTypeRegistry::new();
+/// let doc = registry.create("sync9").unwrap();
+/// assert_eq!(doc.name(), "sync9");
+/// assert!(registry.create("unknown").is_none());
+/// ```
+#[derive(Clone)]
+pub struct MergeTypeRegistry {
+    /// Merge-type name → factory
+    factories: Arc<RwLock<HashMap<String, MergeTypeFactory>>>,
+}
+
+impl MergeTypeRegistry {
+    /// Create a registry with the built-in merge types.
+    #[must_use]
+    pub fn new() -> Self {
+        let registry = Self::empty();
+        registry.register(merge_types::DIAMOND, || Box::new(DiamondCRDT::new("server")));
+        registry.register(merge_types::SYNC9, || Box::new(Sync9::new()));
+        registry.register(merge_types::OT_TEXT, || Box::new(OtText::new()));
+        registry.register(merge_types::LWW, || Box::new(LwwRegister::new()));
+        registry.register(merge_types::JSON, || Box::new(JsonDocument::new()));
+        registry
+    }
+
+    /// Create a registry with no merge types.
+    #[must_use]
+    pub fn empty() -> Self {
+        Self {
+            factories: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// Register a merge type under a name.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Merge-type name as it appears in the `Merge-Type` header
+    /// * `factory` - Creates an empty document for each new resource
+    pub fn register<F>(&self, name: impl Into<String>, factory: F)
+    where
+        F: Fn() -> Box<dyn MergeType> + Send + Sync + 'static,
+    {
+        self.factories.write().insert(name.into(), Arc::new(factory));
+    }
+
+    /// Create an empty document for a merge type.
+    ///
+    /// # Returns
+    ///
+    /// `Some(Box<dyn MergeType>)` if the name is registered, `None` otherwise.
+    #[must_use]
+    pub fn create(&self, name: &str) -> Option<Box<dyn MergeType>> {
+        let factory = self.factories.read().get(name).cloned()?;
+        Some(factory())
+    }
+
+    /// Check whether a merge type is registered.
+    #[inline]
+    #[must_use]
+    pub fn contains(&self, name: &str) -> bool {
+        self.factories.read().contains_key(name)
+    }
+
+    /// List the registered merge-type names in sorted order.
+    #[must_use]
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.factories.read().keys().cloned().collect();
+        names.sort();
+        names
+    }
+}
+
+impl Default for MergeTypeRegistry {
+    /// Create a registry with the built-in merge types (equivalent to `new()`)
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for MergeTypeRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MergeTypeRegistry")
+            .field("names", &self.names())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preview_matches_apply_at_the_current_version() {
+        let registry = MergeTypeRegistry::new();
+        for name in ["diamond", "sync9", "ot-text"] {
+            let mut doc = registry.create(name).unwrap();
+            let hello = Update::patched(Version::new("a-4"), vec![Patch::text("[0:0]", "hello")]);
+            let base = doc.apply(&hello, "a").unwrap();
+            let edit = Update::patched(Version::new("b-0"), vec![Patch::text("[5:5]", "!")])
+                .with_parents(doc.snapshot().version);
+            let preview = doc.preview(&edit).unwrap();
+            doc.apply(&edit, "b").unwrap();
+            assert_eq!(Some(preview), doc.snapshot().body, "{}", name);
+
+            // Concurrent with the latest edit, so it depends on merging
+            let concurrent = Update::patched(Version::new("c-0"), vec![Patch::text("[0:0]", ">")])
+                .with_parents(base.version.clone());
+            assert!(doc.preview(&concurrent).is_none(), "{}", name);
+        }
+        let lww = registry.create("lww").unwrap();
+        assert!(lww.preview(&Update::snapshot(Version::new("v1"), "x")).is_none());
+    }
+
+    #[test]
+    fn test_builtin_merge_types() {
+        let registry = MergeTypeRegistry::new();
+        assert_eq!(registry.names(), vec!["diamond", "json", "lww", "ot-text", "sync9"]);
+        assert_eq!(registry.create("json").unwrap().name(), "json");
+        assert_eq!(registry.create("ot-text").unwrap().name(), "ot-text");
+        assert_eq!(registry.create("diamond").unwrap().name(), "diamond");
+        assert_eq!(registry.create("sync9").unwrap().name(), "sync9");
+        assert_eq!(registry.create("lww").unwrap().name(), "lww");
+        assert!(MergeTypeRegistry::empty().names().is_empty());
+    }
+
+    #[test]
+    fn test_register_replaces_and_is_shared() {
+        let registry = MergeTypeRegistry::empty();
+        let handle = registry.clone();
+        handle.register("text", || Box::new(Sync9::new()));
+        assert!(registry.contains("text"));
+
+        registry.register("text", || Box::new(DiamondCRDT::new("x")));
+        assert_eq!(handle.create("text").unwrap().name(), "diamond");
+    }
+
+    #[test]
+    fn test_boxed_document_clone_is_independent() {
+        let mut doc = MergeTypeRegistry::new().create("sync9").unwrap();
+        doc.apply(&Update::snapshot(Version::new("v1"), "a"), "alice")
+            .unwrap();
+        let copy = doc.clone();
+        doc.apply(
+            &Update::snapshot(Version::new("v2"), "b").with_parent(Version::new("v1")),
+            "alice",
+        )
+        .unwrap();
+
+        assert_eq!(copy.checkpoint()["content"], "a");
+        assert_eq!(doc.checkpoint()["content"], "b");
+    }
+
+    #[test]
+    fn test_default_snapshot_at_keeps_no_history() {
+        #[derive(Clone, Debug)]
+        struct Current;
+
+        impl MergeType for Current {
+            fn name(&self) -> &str {
+                "current"
+            }
+
+            fn apply(&mut self, update: &Update, _agent_id: &str) -> Result<Update> {
+                Ok(update.clone())
+            }
+
+            fn snapshot(&self) -> Update {
+                let mut update = Update::snapshot(Version::new("b"), "x");
+                update.version.push(Version::new("a"));
+                update
+            }
+
+            fn patches_since(&self, _since: &[Version]) -> Result<Vec<Update>> {
+                Ok(Vec::new())
+            }
+        }
+
+        let current = [Version::new("a"), Version::new("b")];
+        assert_eq!(Current.snapshot_at(&current).unwrap().body_str(), Some("x"));
+        assert!(matches!(
+            Current.snapshot_at(&[Version::new("a")]),
+            Err(BraidError::HistoryDropped)
+        ));
+    }
+}
@@ -11,8 +11,11 @@
 //!
 //! | Merge Type | Description |
 //! |------------|-------------|
-//! | `"sync9"` | Simple synchronization (last-write-wins) |
+//! | `"sync9"` | Braid sequence CRDT for text ([`Sync9`]) |
 //! | `"diamond"` | Diamond-types CRDT for text documents |
+//! | `"ot-text"` | Operational transform over a linear history ([`OtText`]) |
+//! | `"lww"` | Last-writer-wins register for opaque values and `bytes` patches ([`LwwRegister`]) |
+//! | `"json"` | JSON value merged per path with `json` range patches ([`JsonDocument`]) |
 //! | Custom | Application-defined merge algorithms |
 //!
 //! # Key Types
@@ -20,6 +23,16 @@
 //! | Type | Description |
 //! |------|-------------|
 //! | [`DiamondCRDT`] | High-performance text CRDT |
+//! | [`AuthorSpan`] | Text attributed to the agent that inserted it |
+//! | [`JsonDocument`] | JSON value with path-level merging |
+//! | [`Sync9`] | Braid sequence CRDT with version-DAG-aware merging |
+//! | [`OtText`] | Operational-transform text for legacy OT editors |
+//! | [`LwwRegister`] | Last-writer-wins register for blobs |
+//! | [`apply_byte_patches`] | Applies `bytes` range patches to binary content |
+//! | [`MergeType`] | Trait implemented by every merge algorithm |
+//! | [`MergeTypeRegistry`] | Maps `Merge-Type` names to document factories |
+//! | [`PositionUnit`] | How `text` range positions are counted |
+//! | [`Stickiness`] | Which way cursors move for text inserted at them |
 //!
 //! # Examples
 //!
@@ -43,7 +56,7 @@
 //! doc.add_insert(0, "hello");
 //!
 //! // Concurrent edit from another client
-//! doc.add_insert_remote("editor-2", 5, " world");
+//! doc.add_insert_remote("editor-2", 5, " world").unwrap();
 //!
 //! // Automatically merged without conflict
 //! assert_eq!(doc.content(), "hello world");
@@ -91,6 +104,18 @@
 //! assert_eq!(checkpoint["content"], "hello");
 //! ```
 //!
+//! ## JSON Resources
+//!
+//! ```
+//! use braid_axum_http::merge::JsonDocument;
+//! use braid_axum_http::{Patch, Version};
+//!
+//! let mut doc = JsonDocument::new();
+//! doc.apply_patches(Version::new("v1"), vec![], &[Patch::json(".count", "1")])
+//!     .unwrap();
+//! assert_eq!(doc.value()["count"], 1);
+//! ```
+//!
 //! # Specification
 //!
 //! - [draft-toomim-httpbis-braid-http-04] Section 2.2 (Merge-Types)
@@ -99,6 +124,23 @@
 //!
 //! [draft-toomim-httpbis-braid-http-04]: https://datatracker.ietf.org/doc/html/draft-toomim-httpbis-braid-http
 
+pub(crate) mod binary;
 pub mod diamond;
+pub mod json;
+pub mod lww;
+pub mod merge_type;
+pub mod ot;
+pub mod sync9;
+pub(crate) mod text;
 
-pub use diamond::DiamondCRDT;
+pub use binary::{apply_byte_patches, diff_bytes};
+pub use diamond::{AuthorSpan, DiamondCRDT};
+pub use json::JsonDocument;
+pub use lww::LwwRegister;
+pub use merge_type::{MergeType, MergeTypeClone, MergeTypeFactory, MergeTypeRegistry};
+pub use ot::OtText;
+pub use sync9::Sync9;
+pub use text::{
+    transform_position, transform_position_in, transform_range, transform_range_in, PositionUnit,
+    Stickiness,
+};
@@ -0,0 +1,651 @@
+//! Operational-transform text merge type for legacy OT editors.
+//!
+//! This module provides [`OtText`], registered as `"ot-text"`. Unlike the CRDTs in
+//! this crate, it keeps a single linear server history: every accepted update is
+//! appended to the end, and an update made against an older revision is
+//! *transformed* against everything accepted since before it is applied.
+//!
+//! # Overview
+//!
+//! - **Linear history**: each revision is a Braid `Version` plus the `text` patches
+//!   that were applied to the tip at the time
+//! - **Transformation**: patches whose `Parents` are behind the tip are rebased over
+//!   the concurrent revisions using inclusion transformation
+//! - **Broadcast**: the transformed patches (not the client's originals) are what
+//!   other subscribers receive, with the previous tip as their parent
+//!
+//! Inserts at the same position as an already-accepted insert are placed after it,
+//! so the server's order decides ties.
+//!
+//! # Patch Format
+//!
+//! Patches use the `text` unit with a `[start:end]` range measured in Unicode scalar
+//! values, applied in order. This is the same format as [`Sync9`](super::Sync9).
+//!
+//! # Examples
+//!
+//! ```
+//! use braid_axum_http::merge::OtText;
+//! use braid_axum_http::{Patch, Version};
+//!
+//! let mut doc = OtText::new();
+//! doc.apply_patches(Version::new("v1"), vec![], &[Patch::text("[0:0]", "hello")])
+//!     .unwrap();
+//! doc.apply_patches(Version::new("v2"), vec![Version::new("v1")], &[Patch::text("[0:0]", "oh, ")])
+//!     .unwrap();
+//!
+//! // Made against v1, so position 5 is transformed past "oh, "
+//! let transformed = doc
+//!     .apply_patches(Version::new("v3"), vec![Version::new("v1")], &[Patch::text("[5:5]", "!")])
+//!     .unwrap();
+//!
+//! assert_eq!(transformed, vec![Patch::text("[9:9]", "!")]);
+//! assert_eq!(doc.content(), "oh, hello!");
+//! ```
+//!
+//! # Specification References
+//!
+//! - **draft-toomim-httpbis-braid-http**: Section 2.2 (Merge-Types), Section 3 (Patches)
+
+use super::text::{apply_text_patches, format_text_range, parse_text_patch};
+use super::merge_type::preview_text_patches;
+use super::MergeType;
+use crate::error::{BraidError, Result};
+use crate::protocol::{self, merge_types};
+use crate::types::{Patch, Update, Version};
+use bytes::Bytes;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// A primitive text operation, positioned in Unicode scalar values.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Op {
+    /// Insert text at a position
+    Insert(usize, String),
+    /// Delete a number of characters starting at a position
+    Delete(usize, usize),
+}
+
+/// One accepted revision in the linear history.
+#[derive(Clone, Debug)]
+struct Revision {
+    /// Braid version of this revision
+    version: Version,
+    /// Operations as applied to the previous tip
+    ops: Vec<Op>,
+    /// Length of the text after this revision, in Unicode scalar values
+    len: usize,
+}
+
+/// Text document merged by operational transformation over a linear history.
+///
+/// See the [module documentation](self) for the merge semantics.
+///
+/// # Invariants
+///
+/// - `content` equals the result of applying every revision's operations in order
+/// - `index` maps each revision's version to its position in `history`
+///
+/// # Complexity
+///
+/// - **Apply update**: O(n + c·p) where n is the document length, c the number of
+///   concurrent revisions, and p the number of patches involved
+#[derive(Clone, Debug, Default)]
+pub struct OtText {
+    /// Current text at the tip
+    content: String,
+
+    /// Accepted revisions, oldest first
+    history: Vec<Revision>,
+
+    /// Version ID → index into `history`
+    index: HashMap<Version, usize>,
+}
+
+impl OtText {
+    /// Create a new empty document.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use braid_axum_http::merge::OtText;
+    ///
+    /// let doc = OtText::new();
+    /// assert!(doc.is_empty());
+    /// ```
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply `text` patches made against `parents`, transforming them if needed.
+    ///
+    /// The patches are rebased over every revision accepted after the latest of
+    /// `parents` (all revisions if `parents` is empty), then appended to the history.
+    /// Applying a version that has already been accepted is a no-op.
+    ///
+    /// # Arguments
+    ///
+    /// * `version` - Version ID of this update
+    /// * `parents` - Revisions the update was made against
+    /// * `patches` - Patches with unit `text` and `[start:end]` ranges
+    ///
+    /// # Returns
+    ///
+    /// The transformed patches, as applied to the previous tip.
+    ///
+    /// # Errors
+    ///
+    /// - [`BraidError::InvalidVersion`] if a parent version is unknown
+    /// - [`BraidError::HeaderParse`] if a patch has a non-`text` unit or a malformed
+    ///   range
+    /// - [`BraidError::InvalidPatch`] if a range falls outside the text at `parents`
+    /// - [`BraidError::BodyParse`] if a patch body is not valid UTF-8
+    pub fn apply_patches(
+        &mut self,
+        version: Version,
+        parents: Vec<Version>,
+        patches: &[Patch],
+    ) -> Result<Vec<Patch>> {
+        if self.index.contains_key(&version) {
+            return Ok(Vec::new());
+        }
+
+        let base = parents
+            .iter()
+            .map(|p| {
+                self.index.get(p).map(|i| i + 1).ok_or_else(|| {
+                    BraidError::InvalidVersion(format!("Unknown parent version: {}", p))
+                })
+            })
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .max()
+            .unwrap_or(0);
+
+        // Each patch applies to the result of the previous one
+        let mut len = base.checked_sub(1).map_or(0, |i| self.history[i].len);
+        let mut ops = Vec::new();
+        for patch in patches {
+            let (start, end, text) = parse_text_patch(patch)?;
+            if end > len {
+                return Err(BraidError::InvalidPatch(format!(
+                    "Range [{}:{}] exceeds document length {} at {}",
+                    start,
+                    end,
+                    len,
+                    protocol::format_version_header(&parents)
+                )));
+            }
+            len = len - (end - start) + char_len(text);
+            if end > start {
+                ops.push(Op::Delete(start, end - start));
+            }
+            if !text.is_empty() {
+                ops.push(Op::Insert(start, text.to_string()));
+            }
+        }
+
+        for revision in &self.history[base..] {
+            ops = transform(&ops, &revision.ops, false).0;
+        }
+
+        let transformed = ops_to_patches(&ops);
+        self.content = apply_text_patches(&self.content, &transformed)?;
+        self.index.insert(version.clone(), self.history.len());
+        let len = char_len(&self.content);
+        self.history.push(Revision { version, ops, len });
+
+        Ok(transformed)
+    }
+
+    // ========== Query Methods ==========
+
+    /// Get the current text.
+    #[inline]
+    #[must_use]
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    /// Get the text as it was at a set of versions.
+    ///
+    /// The history is linear, so this is the text after the latest of `versions`.
+    ///
+    /// # Returns
+    ///
+    /// `Some(String)` if every version is known, `None` otherwise. An empty slice
+    /// yields the empty text before any revision.
+    #[must_use]
+    pub fn content_at(&self, versions: &[Version]) -> Option<String> {
+        let end = versions
+            .iter()
+            .map(|v| self.index.get(v).map(|i| i + 1))
+            .collect::<Option<Vec<_>>>()?
+            .into_iter()
+            .max()
+            .unwrap_or(0);
+        self.history[..end]
+            .iter()
+            .try_fold(String::new(), |text, revision| {
+                apply_text_patches(&text, &ops_to_patches(&revision.ops)).ok()
+            })
+    }
+
+    /// Get the version at the tip of the history, if any.
+    #[inline]
+    #[must_use]
+    pub fn tip(&self) -> Option<&Version> {
+        self.history.last().map(|r| &r.version)
+    }
+
+    /// Get the number of accepted revisions.
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Check if no revisions have been accepted.
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.history.is_empty()
+    }
+
+    /// Create a checkpoint snapshot of the current state.
+    ///
+    /// - `content` (string): The current text
+    /// - `version` (string): The tip formatted as a `Version` header
+    /// - `revisions` (number): Number of accepted revisions
+    #[must_use]
+    pub fn checkpoint(&self) -> Value {
+        json!({
+            "content": self.content,
+            "version": protocol::format_version_header(self.tip().cloned().as_slice()),
+            "revisions": self.history.len(),
+        })
+    }
+}
+
+// ========== MergeType ==========
+
+impl MergeType for OtText {
+    fn name(&self) -> &str {
+        merge_types::OT_TEXT
+    }
+
+    /// Apply an update carrying `text` patches, or a body with a `text` content range.
+    ///
+    /// A missing `Version` is assigned as `{agent_id}-{uuid}`, and missing `Parents`
+    /// default to the tip. Returns the transformed patches with the previous tip as
+    /// their parent.
+    fn apply(&mut self, update: &Update, agent_id: &str) -> Result<Update> {
+        let version = update.primary_version().cloned().unwrap_or_else(|| {
+            Version::new(format!("{}-{}", agent_id, uuid::Uuid::new_v4()))
+        });
+        let parents = if update.parents.is_empty() {
+            self.tip().cloned().into_iter().collect()
+        } else {
+            update.parents.clone()
+        };
+
+        let patches = match (&update.patches, &update.body, &update.content_range) {
+            (Some(patches), _, _) => patches.clone(),
+            (None, Some(body), Some(range)) => {
+                vec![Patch::new(range.unit.clone(), range.range.clone(), body.clone())]
+            }
+            _ => {
+                return Err(BraidError::BodyParse(
+                    "ot-text updates require text patches".to_string(),
+                ))
+            }
+        };
+
+        let previous_tip: Vec<Version> = self.tip().cloned().into_iter().collect();
+        let transformed = self.apply_patches(version.clone(), parents, &patches)?;
+        Ok(Update::patched(version, transformed)
+            .with_parents(previous_tip)
+            .with_merge_type(merge_types::OT_TEXT))
+    }
+
+    /// Previews `text` patches at the tip.
+    fn preview(&self, update: &Update) -> Option<Bytes> {
+        if update.primary_version().is_some_and(|v| self.index.contains_key(v)) {
+            return None;
+        }
+        let tip: Vec<Version> = self.tip().cloned().into_iter().collect();
+        preview_text_patches(&self.content, &tip, update)
+    }
+
+    fn snapshot(&self) -> Update {
+        Update {
+            version: self.tip().cloned().into_iter().collect(),
+            body: Some(self.content.clone().into()),
+            merge_type: Some(merge_types::OT_TEXT.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn version(&self) -> Vec<Version> {
+        self.tip().cloned().into_iter().collect()
+    }
+
+    fn contains_version(&self, version: &Version) -> bool {
+        self.index.contains_key(version)
+    }
+
+    /// Returns one update per revision after `since`, each parented on the one before.
+    fn patches_since(&self, since: &[Version]) -> Result<Vec<Update>> {
+        let base = since
+            .iter()
+            .map(|v| {
+                self.index.get(v).map(|i| i + 1).ok_or_else(|| {
+                    BraidError::InvalidVersion(format!("Unknown version: {}", v))
+                })
+            })
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .max()
+            .unwrap_or(0);
+
+        Ok(self.history[base..]
+            .iter()
+            .enumerate()
+            .map(|(i, revision)| {
+                let parents = (base + i)
+                    .checked_sub(1)
+                    .map(|p| vec![self.history[p].version.clone()])
+                    .unwrap_or_default();
+                Update::patched(revision.version.clone(), ops_to_patches(&revision.ops))
+                    .with_parents(parents)
+                    .with_merge_type(merge_types::OT_TEXT)
+            })
+            .collect())
+    }
+
+    /// Replays the history up to any accepted revision; see
+    /// [`content_at`](OtText::content_at).
+    fn snapshot_at(&self, version: &[Version]) -> Result<Update> {
+        let content = self.content_at(version).ok_or_else(|| {
+            BraidError::InvalidVersion(format!(
+                "Unknown versions: {}",
+                protocol::format_version_header(version)
+            ))
+        })?;
+        Ok(Update {
+            version: version.to_vec(),
+            body: Some(content.into()),
+            merge_type: Some(merge_types::OT_TEXT.to_string()),
+            ..Default::default()
+        })
+    }
+
+    fn checkpoint(&self) -> Value {
+        OtText::checkpoint(self)
+    }
+}
+
+/// Convert operations back into `text` patches, joining a delete and an insert at
+/// the same position into one replacement.
+fn ops_to_patches(ops: &[Op]) -> Vec<Patch> {
+    let mut patches = Vec::with_capacity(ops.len());
+    let mut i = 0;
+    while i < ops.len() {
+        match (&ops[i], ops.get(i + 1)) {
+            (Op::Delete(start, len), Some(Op::Insert(pos, text))) if pos == start => {
+                patches.push(Patch::text(format_text_range(*start, start + len), text.as_str()));
+                i += 2;
+            }
+            (Op::Delete(start, len), _) => {
+                patches.push(Patch::text(format_text_range(*start, start + len), ""));
+                i += 1;
+            }
+            (Op::Insert(pos, text), _) => {
+                patches.push(Patch::text(format_text_range(*pos, *pos), text.as_str()));
+                i += 1;
+            }
+        }
+    }
+    patches
+}
+
+/// Transform two operation sequences made against the same text.
+///
+/// Returns `(a', b')` where `a'` applies after `b` and `b'` applies after `a`, so
+/// that `a · b' == b · a'`. `a_first` decides which insert goes first when both
+/// insert at the same position.
+fn transform(a: &[Op], b: &[Op], a_first: bool) -> (Vec<Op>, Vec<Op>) {
+    match (a, b) {
+        ([], _) => (Vec::new(), b.to_vec()),
+        (_, []) => (a.to_vec(), Vec::new()),
+        ([x], [y]) => transform_op(x, y, a_first),
+        ([x, rest @ ..], _) if !rest.is_empty() => {
+            let (x2, b2) = transform(std::slice::from_ref(x), b, a_first);
+            let (rest2, b3) = transform(rest, &b2, a_first);
+            ([x2, rest2].concat(), b3)
+        }
+        (_, [y, rest @ ..]) => {
+            let (a2, y2) = transform(a, std::slice::from_ref(y), a_first);
+            let (a3, rest2) = transform(&a2, rest, a_first);
+            (a3, [y2, rest2].concat())
+        }
+    }
+}
+
+/// Transform a pair of primitive operations made against the same text.
+fn transform_op(a: &Op, b: &Op, a_first: bool) -> (Vec<Op>, Vec<Op>) {
+    match (a, b) {
+        (Op::Insert(p1, t1), Op::Insert(p2, t2)) => {
+            if p1 < p2 || (p1 == p2 && a_first) {
+                (vec![a.clone()], vec![Op::Insert(p2 + char_len(t1), t2.clone())])
+            } else {
+                (vec![Op::Insert(p1 + char_len(t2), t1.clone())], vec![b.clone()])
+            }
+        }
+        (Op::Insert(p, t), Op::Delete(s, l)) => {
+            let (p, s, l) = (*p, *s, *l);
+            if p <= s {
+                (vec![a.clone()], vec![Op::Delete(s + char_len(t), l)])
+            } else if p >= s + l {
+                (vec![Op::Insert(p - l, t.clone())], vec![b.clone()])
+            } else {
+                // The insert lands inside the deleted range: keep it, and split the
+                // delete around it
+                let before = Op::Delete(s, p - s);
+                let after = Op::Delete(s + char_len(t), s + l - p);
+                (vec![Op::Insert(s, t.clone())], vec![before, after])
+            }
+        }
+        (Op::Delete(..), Op::Insert(..)) => {
+            let (b2, a2) = transform_op(b, a, !a_first);
+            (a2, b2)
+        }
+        (Op::Delete(s1, l1), Op::Delete(s2, l2)) => (
+            shrink_delete(*s1, *l1, *s2, *l2),
+            shrink_delete(*s2, *l2, *s1, *l1),
+        ),
+    }
+}
+
+/// Rebase delete `[s1, s1+l1)` over a concurrent delete `[s2, s2+l2)`.
+fn shrink_delete(s1: usize, l1: usize, s2: usize, l2: usize) -> Vec<Op> {
+    let (e1, e2) = (s1 + l1, s2 + l2);
+    let overlap = e1.min(e2).saturating_sub(s1.max(s2));
+    let start = if s1 < s2 {
+        s1
+    } else if s1 >= e2 {
+        s1 - l2
+    } else {
+        s2
+    };
+    let len = l1 - overlap;
+    if len == 0 {
+        Vec::new()
+    } else {
+        vec![Op::Delete(start, len)]
+    }
+}
+
+/// Length of a string in Unicode scalar values.
+#[inline]
+fn char_len(text: &str) -> usize {
+    text.chars().count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(s: &str) -> Version {
+        Version::new(s)
+    }
+
+    /// Apply operations to a string.
+    fn apply_ops(text: &str, ops: &[Op]) -> String {
+        apply_text_patches(text, &ops_to_patches(ops)).unwrap()
+    }
+
+    #[test]
+    fn test_transform_convergence() {
+        let base = "abcdefgh";
+        let cases: Vec<(Vec<Op>, Vec<Op>)> = vec![
+            (vec![Op::Insert(2, "X".into())], vec![Op::Insert(2, "Y".into())]),
+            (vec![Op::Insert(3, "X".into())], vec![Op::Delete(1, 4)]),
+            (vec![Op::Delete(0, 3)], vec![Op::Delete(2, 4)]),
+            (vec![Op::Delete(2, 2)], vec![Op::Delete(1, 5)]),
+            (
+                vec![Op::Delete(1, 2), Op::Insert(1, "Z".into())],
+                vec![Op::Insert(2, "Q".into()), Op::Delete(5, 2)],
+            ),
+        ];
+
+        for (a, b) in cases {
+            let (a2, b2) = transform(&a, &b, true);
+            let left = apply_ops(&apply_ops(base, &a), &b2);
+            let right = apply_ops(&apply_ops(base, &b), &a2);
+            assert_eq!(left, right, "a={:?} b={:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn test_insert_inside_concurrent_delete_survives() {
+        let mut doc = OtText::new();
+        doc.apply_patches(v("v1"), vec![], &[Patch::text("[0:0]", "abcdef")])
+            .unwrap();
+        doc.apply_patches(v("v2"), vec![v("v1")], &[Patch::text("[1:5]", "")])
+            .unwrap();
+        let out = doc
+            .apply_patches(v("v3"), vec![v("v1")], &[Patch::text("[3:3]", "XY")])
+            .unwrap();
+
+        assert_eq!(out, vec![Patch::text("[1:1]", "XY")]);
+        assert_eq!(doc.content(), "aXYf");
+    }
+
+    #[test]
+    fn test_tie_goes_to_accepted_insert() {
+        let mut doc = OtText::new();
+        doc.apply_patches(v("v1"), vec![], &[Patch::text("[0:0]", "ab")]).unwrap();
+        doc.apply_patches(v("alice"), vec![v("v1")], &[Patch::text("[1:1]", "A")])
+            .unwrap();
+        doc.apply_patches(v("bob"), vec![v("v1")], &[Patch::text("[1:1]", "B")])
+            .unwrap();
+        assert_eq!(doc.content(), "aABb");
+    }
+
+    #[test]
+    fn test_transform_over_several_revisions() {
+        let mut doc = OtText::new();
+        doc.apply_patches(v("v1"), vec![], &[Patch::text("[0:0]", "The cat")])
+            .unwrap();
+        doc.apply_patches(v("v2"), vec![v("v1")], &[Patch::text("[4:4]", "black ")])
+            .unwrap();
+        doc.apply_patches(v("v3"), vec![v("v2")], &[Patch::text("[0:4]", "")])
+            .unwrap();
+
+        // Edit made against v1, two revisions behind
+        let out = doc
+            .apply_patches(v("v4"), vec![v("v1")], &[Patch::text("[4:7]", "dog")])
+            .unwrap();
+        assert_eq!(out, vec![Patch::text("[6:9]", "dog")]);
+        assert_eq!(doc.content(), "black dog");
+    }
+
+    #[test]
+    fn test_merge_type_apply_and_patches_since() {
+        let mut doc = OtText::new();
+        doc.apply(&Update::patched(v("v1"), vec![Patch::text("[0:0]", "hi")]), "alice")
+            .unwrap();
+        let resolved = doc
+            .apply(&Update::patched(v("v2"), vec![Patch::text("[2:2]", "!")]), "bob")
+            .unwrap();
+
+        assert_eq!(resolved.parents, vec![v("v1")]);
+        assert_eq!(resolved.merge_type.as_deref(), Some("ot-text"));
+
+        let history = doc.patches_since(&[]).unwrap();
+        assert_eq!(history.len(), 2);
+        assert!(history[0].parents.is_empty());
+        assert_eq!(history[1].parents, vec![v("v1")]);
+        assert_eq!(doc.patches_since(&[v("v2")]).unwrap().len(), 0);
+        assert!(doc.patches_since(&[v("v9")]).is_err());
+        assert_eq!(doc.snapshot().body_str(), Some("hi!"));
+    }
+
+    #[test]
+    fn test_content_at_replays_history() {
+        let mut doc = OtText::new();
+        doc.apply_patches(v("v1"), vec![], &[Patch::text("[0:0]", "ab")]).unwrap();
+        doc.apply_patches(v("v2"), vec![v("v1")], &[Patch::text("[0:1]", "")]).unwrap();
+        // Made against v1 concurrently with v2, then transformed
+        doc.apply_patches(v("v3"), vec![v("v1")], &[Patch::text("[2:2]", "c")]).unwrap();
+
+        assert_eq!(doc.content_at(&[]).as_deref(), Some(""));
+        assert_eq!(doc.content_at(&[v("v1")]).as_deref(), Some("ab"));
+        assert_eq!(doc.content_at(&[v("v2")]).as_deref(), Some("b"));
+        assert_eq!(doc.content_at(&[v("v3")]).as_deref(), Some(doc.content()));
+        assert!(doc.content_at(&[v("v9")]).is_none());
+        assert!(doc.snapshot_at(&[v("v9")]).is_err());
+    }
+
+    #[test]
+    fn test_invalid_updates_rejected() {
+        let mut doc = OtText::new();
+        doc.apply_patches(v("v1"), vec![], &[Patch::text("[0:0]", "abc")]).unwrap();
+
+        assert!(matches!(
+            doc.apply_patches(v("v2"), vec![v("nope")], &[Patch::text("[0:0]", "x")]),
+            Err(BraidError::InvalidVersion(_))
+        ));
+        assert!(matches!(
+            doc.apply_patches(v("v2"), vec![v("v1")], &[Patch::text("[2:9]", "")]),
+            Err(BraidError::InvalidPatch(_))
+        ));
+        assert!(doc
+            .apply(&Update::snapshot(v("v2"), "whole body"), "alice")
+            .is_err());
+        assert_eq!(doc.len(), 1);
+        assert_eq!(doc.content(), "abc");
+    }
+
+    #[test]
+    fn test_ranges_are_checked_against_the_parents() {
+        let mut doc = OtText::new();
+        doc.apply_patches(v("v1"), vec![], &[Patch::text("[0:0]", "abc")]).unwrap();
+        doc.apply_patches(v("v2"), vec![v("v1")], &[Patch::text("[3:3]", "defgh")])
+            .unwrap();
+
+        // Fits the tip, but not the text at v1
+        assert!(matches!(
+            doc.apply_patches(v("v3"), vec![v("v1")], &[Patch::text("[2:6]", "")]),
+            Err(BraidError::InvalidPatch(_))
+        ));
+        assert_eq!(doc.content(), "abcdefgh");
+
+        // Later patches are measured after the earlier ones
+        let patches = [Patch::text("[0:0]", "xx"), Patch::text("[4:5]", "")];
+        doc.apply_patches(v("v3"), vec![v("v1")], &patches).unwrap();
+        assert_eq!(doc.content(), "xxabdefgh");
+    }
+}
@@ -0,0 +1,747 @@
+//! Sync9 sequence CRDT for Braid text resources.
+//!
+//! This module provides [`Sync9`], the Braid sequence CRDT. Every update is a set of
+//! `text` range patches tagged with a Braid `Version` and its `Parents`, and the
+//! document merges concurrent updates using the version DAG.
+//!
+//! # Overview
+//!
+//! Sync9 keeps every character ever inserted, in a single ordered list:
+//!
+//! - **Inserted characters** are identified by the version that inserted them and
+//!   their offset within that version
+//! - **Deleted characters** stay in the list as tombstones, marked with the versions
+//!   that deleted them
+//! - **Patch positions** are interpreted against the document *as seen by the
+//!   update's parents*, so an update made against an old version still lands in the
+//!   right place after concurrent edits have been merged
+//!
+//! Concurrent insertions at the same position are ordered by the Lamport depth of
+//! their version and then by version ID, so every peer converges to the same text no
+//! matter which order updates arrive in.
+//!
+//! # Patch Format
+//!
+//! Patches use the `text` unit with a `[start:end]` range measured in Unicode scalar
+//! values. Patches in one update are applied in order, each against the result of the
+//! previous one. An empty range (`[3:3]`) inserts; empty content deletes.
+//!
+//! # Examples
+//!
+//! ```
+//! use braid_axum_http::merge::Sync9;
+//! use braid_axum_http::{Patch, Version};
+//!
+//! let mut doc = Sync9::new();
+//! doc.apply_patches(Version::new("a1"), vec![], &[Patch::text("[0:0]", "hello")])
+//!     .unwrap();
+//!
+//! // Two peers edit concurrently against "a1"
+//! doc.apply_patches(Version::new("b1"), vec![Version::new("a1")], &[Patch::text("[0:0]", ">> ")])
+//!     .unwrap();
+//! doc.apply_patches(Version::new("c1"), vec![Version::new("a1")], &[Patch::text("[5:5]", "!")])
+//!     .unwrap();
+//!
+//! assert_eq!(doc.content(), ">> hello!");
+//! ```
+//!
+//! # Specification References
+//!
+//! - **draft-toomim-httpbis-braid-http**: Section 2 (Versioning), Section 2.2 (Merge-Types)
+//! - **Sync9**: <https://braid.org/sync9>
+
+use super::text::{diff_text, format_text_range, parse_text_patch};
+use super::merge_type::preview_text_patches;
+use super::MergeType;
+use crate::error::{BraidError, Result};
+use crate::protocol::{self, merge_types};
+use crate::types::{Patch, Update, Version};
+use bytes::Bytes;
+use serde_json::{json, Value};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+/// A version in the DAG, stored by index.
+#[derive(Clone, Debug)]
+struct VersionNode {
+    /// The Braid version ID
+    id: Version,
+    /// Indices of parent versions
+    parents: Vec<usize>,
+    /// Lamport depth: 1 + the maximum depth of the parents
+    depth: u64,
+}
+
+/// A single character in the sequence, possibly deleted.
+#[derive(Clone, Debug)]
+struct Element {
+    /// Index of the version that inserted this character
+    version: usize,
+    /// Offset of this character within its version's insertions
+    offset: usize,
+    /// The character itself
+    ch: char,
+    /// Indices of the versions that deleted this character
+    deleted_by: Vec<usize>,
+}
+
+/// The Braid sync9 sequence CRDT.
+///
+/// See the [module documentation](self) for the merge semantics.
+///
+/// # Invariants
+///
+/// - Every version's parents are recorded before the version itself
+/// - `elements` is ordered so that each character follows the character it was
+///   inserted after, with concurrent insertions ordered by descending priority
+/// - `frontier` contains exactly the versions without recorded children
+///
+/// # Complexity
+///
+/// - **Apply update**: O(n + v) where n is the number of characters ever inserted
+///   and v is the number of versions
+/// - **Content**: O(n)
+#[derive(Clone, Debug, Default)]
+pub struct Sync9 {
+    /// All versions, in the order they were applied
+    versions: Vec<VersionNode>,
+
+    /// Version ID → index into `versions`
+    index: HashMap<Version, usize>,
+
+    /// Every character ever inserted, including tombstones
+    elements: Vec<Element>,
+
+    /// Versions with no children (the current version of the document)
+    frontier: Vec<Version>,
+}
+
+impl Sync9 {
+    /// Create a new empty document.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use braid_axum_http::merge::Sync9;
+    ///
+    /// let doc = Sync9::new();
+    /// assert!(doc.is_empty());
+    /// assert_eq!(doc.content(), "");
+    /// ```
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply a versioned set of `text` range patches.
+    ///
+    /// Patch positions are relative to the document at `parents`. Applying a version
+    /// that has already been seen is a no-op.
+    ///
+    /// # Arguments
+    ///
+    /// * `version` - Version ID of this update
+    /// * `parents` - Versions the update was made against (empty for the first update)
+    /// * `patches` - Patches with unit `text` and `[start:end]` ranges
+    ///
+    /// # Returns
+    ///
+    /// Patches that bring the previously merged content up to date with the newly
+    /// merged content, suitable for sending to subscribers.
+    ///
+    /// # Errors
+    ///
+    /// - [`BraidError::InvalidVersion`] if a parent version is unknown
+    /// - [`BraidError::HeaderParse`] if a patch has a non-`text` unit, a malformed range,
+    ///   or a range outside the document
+    /// - [`BraidError::BodyParse`] if a patch body is not valid UTF-8
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use braid_axum_http::merge::Sync9;
+    /// use braid_axum_http::{Patch, Version};
+    ///
+    /// let mut doc = Sync9::new();
+    /// let out = doc
+    ///     .apply_patches(Version::new("v1"), vec![], &[Patch::text("[0:0]", "hi")])
+    ///     .unwrap();
+    /// assert_eq!(out[0].range, "[0:0]");
+    /// ```
+    pub fn apply_patches(
+        &mut self,
+        version: Version,
+        parents: Vec<Version>,
+        patches: &[Patch],
+    ) -> Result<Vec<Patch>> {
+        if self.index.contains_key(&version) {
+            return Ok(Vec::new());
+        }
+
+        let parent_indices = parents
+            .iter()
+            .map(|p| {
+                self.index.get(p).copied().ok_or_else(|| {
+                    BraidError::InvalidVersion(format!("Unknown parent version: {}", p))
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let edits = patches
+            .iter()
+            .map(parse_text_patch)
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut view = self.ancestors(&parent_indices);
+
+        // Validate every range before touching the document
+        let mut len = self.elements.iter().filter(|e| visible_in(e, &view)).count();
+        for (start, end, text) in &edits {
+            if *end > len {
+                return Err(BraidError::HeaderParse(format!(
+                    "Range [{}:{}] exceeds document length {}",
+                    start, end, len
+                )));
+            }
+            len = len - (end - start) + text.chars().count();
+        }
+
+        let idx = self.versions.len();
+        let depth = parent_indices
+            .iter()
+            .map(|p| self.versions[*p].depth)
+            .max()
+            .unwrap_or(0)
+            + 1;
+        self.versions.push(VersionNode {
+            id: version.clone(),
+            parents: parent_indices,
+            depth,
+        });
+        self.index.insert(version.clone(), idx);
+        view.insert(idx);
+
+        let mut offset = 0;
+        for (start, end, text) in edits {
+            let positions: Vec<usize> = self
+                .elements
+                .iter()
+                .enumerate()
+                .filter(|(_, e)| visible_in(e, &view))
+                .map(|(i, _)| i)
+                .collect();
+
+            for &pos in &positions[start..end] {
+                self.elements[pos].deleted_by.push(idx);
+            }
+
+            let mut origin = start.checked_sub(1).map(|i| positions[i]);
+            for ch in text.chars() {
+                let at = self.integrate(origin, idx, offset);
+                self.elements.insert(
+                    at,
+                    Element {
+                        version: idx,
+                        offset,
+                        ch,
+                        deleted_by: Vec::new(),
+                    },
+                );
+                origin = Some(at);
+                offset += 1;
+            }
+        }
+
+        let parent_ids: HashSet<&Version> = parents.iter().collect();
+        self.frontier.retain(|v| !parent_ids.contains(v));
+        self.frontier.push(version);
+        self.frontier.sort();
+
+        Ok(self.diff_patches(idx))
+    }
+
+    /// Replace the whole document, as seen by `parents`, with `content`.
+    ///
+    /// Equivalent to a single patch covering the full text at `parents`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BraidError::InvalidVersion`] if a parent version is unknown.
+    pub fn apply_snapshot(
+        &mut self,
+        version: Version,
+        parents: Vec<Version>,
+        content: &str,
+    ) -> Result<Vec<Patch>> {
+        let len = match self.content_at(&parents) {
+            Some(existing) => existing.chars().count(),
+            None => {
+                return Err(BraidError::InvalidVersion(format!(
+                    "Unknown parent versions: {}",
+                    protocol::format_version_header(&parents)
+                )))
+            }
+        };
+        self.apply_patches(
+            version,
+            parents,
+            &[Patch::text(format_text_range(0, len), content)],
+        )
+    }
+
+    /// Find where a new element belongs, given the element it was inserted after.
+    ///
+    /// Starting right after `origin`, skips every element with a higher priority
+    /// than the new one. Such elements were inserted concurrently at the same spot
+    /// (or after one of those), and higher-priority insertions come first.
+    fn integrate(&self, origin: Option<usize>, version: usize, offset: usize) -> usize {
+        let mut at = origin.map_or(0, |o| o + 1);
+        while at < self.elements.len()
+            && self.compare_priority(&self.elements[at], version, offset) == Ordering::Greater
+        {
+            at += 1;
+        }
+        at
+    }
+
+    /// Compare an existing element's priority with that of a new element.
+    fn compare_priority(&self, element: &Element, version: usize, offset: usize) -> Ordering {
+        let existing = &self.versions[element.version];
+        let new = &self.versions[version];
+        existing
+            .depth
+            .cmp(&new.depth)
+            .then_with(|| existing.id.cmp(&new.id))
+            .then_with(|| element.offset.cmp(&offset))
+    }
+
+    /// Collect the indices of the given versions and all of their ancestors.
+    fn ancestors(&self, versions: &[usize]) -> HashSet<usize> {
+        let mut seen = HashSet::new();
+        let mut stack = versions.to_vec();
+        while let Some(v) = stack.pop() {
+            if seen.insert(v) {
+                stack.extend(&self.versions[v].parents);
+            }
+        }
+        seen
+    }
+
+    /// Describe the effect of version `idx` on the merged content as text patches.
+    fn diff_patches(&self, idx: usize) -> Vec<Patch> {
+        let mut patches = Vec::new();
+        let mut pos = 0;
+        let mut deleted = 0;
+        let mut inserted = String::new();
+
+        let mut flush = |pos: &mut usize, deleted: &mut usize, inserted: &mut String| {
+            if *deleted > 0 || !inserted.is_empty() {
+                let range = format_text_range(*pos, *pos + *deleted);
+                *pos += inserted.chars().count();
+                *deleted = 0;
+                patches.push(Patch::text(range, std::mem::take(inserted)));
+            }
+        };
+
+        for element in &self.elements {
+            let before = element.version != idx && element.deleted_by.iter().all(|d| *d == idx);
+            let after = element.deleted_by.is_empty();
+            match (before, after) {
+                (true, false) => deleted += 1,
+                (false, true) => inserted.push(element.ch),
+                (true, true) => {
+                    flush(&mut pos, &mut deleted, &mut inserted);
+                    pos += 1;
+                }
+                (false, false) => {}
+            }
+        }
+        flush(&mut pos, &mut deleted, &mut inserted);
+        patches
+    }
+
+    // ========== Query Methods ==========
+
+    /// Get the current merged text.
+    #[must_use]
+    pub fn content(&self) -> String {
+        self.elements
+            .iter()
+            .filter(|e| e.deleted_by.is_empty())
+            .map(|e| e.ch)
+            .collect()
+    }
+
+    /// Get the text as it was at a set of versions.
+    ///
+    /// # Returns
+    ///
+    /// `Some(String)` if every version is known, `None` otherwise. An empty slice
+    /// yields the empty text before any update.
+    #[must_use]
+    pub fn content_at(&self, versions: &[Version]) -> Option<String> {
+        let indices = versions
+            .iter()
+            .map(|v| self.index.get(v).copied())
+            .collect::<Option<Vec<_>>>()?;
+        let view = self.ancestors(&indices);
+        Some(
+            self.elements
+                .iter()
+                .filter(|e| visible_in(e, &view))
+                .map(|e| e.ch)
+                .collect(),
+        )
+    }
+
+    /// Get the current version (the frontier of the version DAG).
+    #[inline]
+    #[must_use]
+    pub fn version(&self) -> &[Version] {
+        &self.frontier
+    }
+
+    /// Check whether a version has been applied to this document.
+    #[inline]
+    #[must_use]
+    pub fn contains_version(&self, version: &Version) -> bool {
+        self.index.contains_key(version)
+    }
+
+    /// Check if no updates have been applied.
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.versions.is_empty()
+    }
+
+    /// Create a checkpoint snapshot of the current state.
+    ///
+    /// - `content` (string): The merged text
+    /// - `version` (string): The frontier formatted as a `Version` header
+    /// - `versions` (number): Number of recorded versions
+    #[must_use]
+    pub fn checkpoint(&self) -> Value {
+        json!({
+            "content": self.content(),
+            "version": protocol::format_version_header(&self.frontier),
+            "versions": self.versions.len(),
+        })
+    }
+}
+
+// ========== MergeType ==========
+
+impl MergeType for Sync9 {
+    fn name(&self) -> &str {
+        merge_types::SYNC9
+    }
+
+    /// Apply an update carrying `text` patches, a body with a `text` content range,
+    /// or a plain body replacing the text seen at the parents.
+    ///
+    /// A missing `Version` is assigned as `{agent_id}-{uuid}`, and missing `Parents`
+    /// default to the current version. Returns the patches that bring subscribers up
+    /// to date, or a snapshot if the update changed nothing.
+    fn apply(&mut self, update: &Update, agent_id: &str) -> Result<Update> {
+        let version = update.primary_version().cloned().unwrap_or_else(|| {
+            Version::new(format!("{}-{}", agent_id, uuid::Uuid::new_v4()))
+        });
+        let parents = if update.parents.is_empty() {
+            self.frontier.clone()
+        } else {
+            update.parents.clone()
+        };
+
+        let patches = match (&update.patches, &update.body, &update.content_range) {
+            (Some(patches), _, _) => self.apply_patches(version.clone(), parents.clone(), patches)?,
+            (None, Some(body), Some(range)) => self.apply_patches(
+                version.clone(),
+                parents.clone(),
+                &[Patch::new(range.unit.clone(), range.range.clone(), body.clone())],
+            )?,
+            (None, Some(body), None) => {
+                let text = std::str::from_utf8(body).map_err(|e| {
+                    BraidError::BodyParse(format!("Invalid UTF-8 in sync9 body: {}", e))
+                })?;
+                self.apply_snapshot(version.clone(), parents.clone(), text)?
+            }
+            (None, None, _) => {
+                return Err(BraidError::BodyParse(
+                    "sync9 update has no patches or body".to_string(),
+                ))
+            }
+        };
+
+        let resolved = if patches.is_empty() {
+            Update::snapshot(version, self.content())
+        } else {
+            Update::patched(version, patches)
+        };
+        Ok(resolved
+            .with_parents(parents)
+            .with_merge_type(merge_types::SYNC9))
+    }
+
+    /// Previews `text` patches at the current version.
+    fn preview(&self, update: &Update) -> Option<Bytes> {
+        if update.primary_version().is_some_and(|v| self.index.contains_key(v)) {
+            return None;
+        }
+        preview_text_patches(&self.content(), &self.frontier, update)
+    }
+
+    fn snapshot(&self) -> Update {
+        Update {
+            version: self.frontier.clone(),
+            body: Some(self.content().into()),
+            merge_type: Some(merge_types::SYNC9.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn version(&self) -> Vec<Version> {
+        self.frontier.clone()
+    }
+
+    fn contains_version(&self, version: &Version) -> bool {
+        Sync9::contains_version(self, version)
+    }
+
+    /// Returns a single update patching the text at `since` into the current text.
+    fn patches_since(&self, since: &[Version]) -> Result<Vec<Update>> {
+        let old = self.content_at(since).ok_or_else(|| {
+            BraidError::InvalidVersion(format!(
+                "Unknown versions: {}",
+                protocol::format_version_header(since)
+            ))
+        })?;
+
+        Ok(diff_text(&old, &self.content())
+            .map(|patch| Update {
+                version: self.frontier.clone(),
+                parents: since.to_vec(),
+                patches: Some(vec![patch]),
+                merge_type: Some(merge_types::SYNC9.to_string()),
+                ..Default::default()
+            })
+            .into_iter()
+            .collect())
+    }
+
+    /// Any known version can be reconstructed; see [`content_at`](Sync9::content_at).
+    fn snapshot_at(&self, version: &[Version]) -> Result<Update> {
+        let content = self.content_at(version).ok_or_else(|| {
+            BraidError::InvalidVersion(format!(
+                "Unknown versions: {}",
+                protocol::format_version_header(version)
+            ))
+        })?;
+        Ok(Update {
+            version: version.to_vec(),
+            body: Some(content.into()),
+            merge_type: Some(merge_types::SYNC9.to_string()),
+            ..Default::default()
+        })
+    }
+
+    fn checkpoint(&self) -> Value {
+        Sync9::checkpoint(self)
+    }
+}
+
+/// Check whether an element is part of the text at a set of versions.
+#[inline]
+fn visible_in(element: &Element, view: &HashSet<usize>) -> bool {
+    view.contains(&element.version) && !element.deleted_by.iter().any(|d| view.contains(d))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merge::text::apply_text_patches;
+
+    fn v(s: &str) -> Version {
+        Version::new(s)
+    }
+
+    fn ins(pos: usize, text: &str) -> Patch {
+        Patch::text(format_text_range(pos, pos), text)
+    }
+
+    fn del(start: usize, end: usize) -> Patch {
+        Patch::text(format_text_range(start, end), "")
+    }
+
+    #[test]
+    fn test_sequential_edits() {
+        let mut doc = Sync9::new();
+        doc.apply_patches(v("v1"), vec![], &[ins(0, "hello world")]).unwrap();
+        doc.apply_patches(v("v2"), vec![v("v1")], &[del(5, 11), ins(5, "!")])
+            .unwrap();
+        assert_eq!(doc.content(), "hello!");
+        assert_eq!(doc.version(), &[v("v2")]);
+        assert_eq!(doc.content_at(&[v("v1")]).unwrap(), "hello world");
+    }
+
+    #[test]
+    fn test_concurrent_inserts_same_position_converge() {
+        let updates = [
+            (v("alice"), vec![ins(1, "A")]),
+            (v("bob"), vec![ins(1, "B")]),
+            (v("carol"), vec![ins(1, "C")]),
+        ];
+
+        let mut reference: Option<String> = None;
+        for order in [[0, 1, 2], [2, 1, 0], [1, 0, 2], [2, 0, 1]] {
+            let mut doc = Sync9::new();
+            doc.apply_patches(v("root"), vec![], &[ins(0, "xy")]).unwrap();
+            for i in order {
+                let (version, patches) = &updates[i];
+                doc.apply_patches(version.clone(), vec![v("root")], patches).unwrap();
+            }
+            let content = doc.content();
+            assert!(content.starts_with('x') && content.ends_with('y'));
+            assert_eq!(content.len(), 5);
+            match &reference {
+                Some(expected) => assert_eq!(&content, expected),
+                None => reference = Some(content),
+            }
+        }
+    }
+
+    #[test]
+    fn test_concurrent_insert_and_delete() {
+        let mut one = Sync9::new();
+        let mut two = Sync9::new();
+        for doc in [&mut one, &mut two] {
+            doc.apply_patches(v("v1"), vec![], &[ins(0, "abcdef")]).unwrap();
+        }
+
+        let delete = (v("del"), vec![del(1, 4)]);
+        let insert = (v("ins"), vec![ins(3, "XY")]);
+
+        one.apply_patches(delete.0.clone(), vec![v("v1")], &delete.1).unwrap();
+        one.apply_patches(insert.0.clone(), vec![v("v1")], &insert.1).unwrap();
+        two.apply_patches(insert.0, vec![v("v1")], &insert.1).unwrap();
+        two.apply_patches(delete.0, vec![v("v1")], &delete.1).unwrap();
+
+        assert_eq!(one.content(), "aXYef");
+        assert_eq!(two.content(), "aXYef");
+        assert_eq!(one.version(), &[v("del"), v("ins")]);
+    }
+
+    #[test]
+    fn test_multi_peer_convergence_with_merges() {
+        // alice and bob edit concurrently, carol merges both, dave edits alice's branch
+        let history: Vec<(Version, Vec<Version>, Vec<Patch>)> = vec![
+            (v("root"), vec![], vec![ins(0, "The cat sat")]),
+            (v("alice-1"), vec![v("root")], vec![ins(4, "black ")]),
+            (v("bob-1"), vec![v("root")], vec![del(8, 11), ins(8, "slept")]),
+            (v("carol-1"), vec![v("alice-1"), v("bob-1")], vec![ins(19, ".")]),
+            (v("dave-1"), vec![v("alice-1")], vec![del(0, 4)]),
+        ];
+
+        let orders: [&[usize]; 3] = [&[0, 1, 2, 3, 4], &[0, 2, 1, 4, 3], &[0, 1, 4, 2, 3]];
+        for order in orders {
+            let mut doc = Sync9::new();
+            let mut subscriber = String::new();
+            for &i in order {
+                let (version, parents, patches) = &history[i];
+                let out = doc
+                    .apply_patches(version.clone(), parents.clone(), patches)
+                    .unwrap();
+                subscriber = apply_text_patches(&subscriber, &out).unwrap();
+                assert_eq!(subscriber, doc.content());
+            }
+            assert_eq!(doc.content(), "black cat slept.");
+            assert_eq!(doc.version(), &[v("carol-1"), v("dave-1")]);
+        }
+    }
+
+    #[test]
+    fn test_snapshot_replaces_parent_view() {
+        let mut doc = Sync9::new();
+        doc.apply_snapshot(v("v1"), vec![], "draft").unwrap();
+        let out = doc.apply_snapshot(v("v2"), vec![v("v1")], "final").unwrap();
+        assert_eq!(doc.content(), "final");
+        assert_eq!(apply_text_patches("draft", &out).unwrap(), "final");
+    }
+
+    #[test]
+    fn test_unicode_positions() {
+        let mut doc = Sync9::new();
+        doc.apply_patches(v("v1"), vec![], &[ins(0, "héllo 🌍")]).unwrap();
+        doc.apply_patches(v("v2"), vec![v("v1")], &[ins(7, "!")]).unwrap();
+        assert_eq!(doc.content(), "héllo 🌍!");
+    }
+
+    #[test]
+    fn test_duplicate_version_is_noop() {
+        let mut doc = Sync9::new();
+        doc.apply_patches(v("v1"), vec![], &[ins(0, "a")]).unwrap();
+        let out = doc.apply_patches(v("v1"), vec![], &[ins(0, "a")]).unwrap();
+        assert!(out.is_empty());
+        assert_eq!(doc.content(), "a");
+    }
+
+    #[test]
+    fn test_invalid_updates_rejected() {
+        let mut doc = Sync9::new();
+        doc.apply_patches(v("v1"), vec![], &[ins(0, "abc")]).unwrap();
+
+        assert!(matches!(
+            doc.apply_patches(v("v2"), vec![v("nope")], &[ins(0, "x")]),
+            Err(BraidError::InvalidVersion(_))
+        ));
+        assert!(matches!(
+            doc.apply_patches(v("v2"), vec![v("v1")], &[del(2, 5)]),
+            Err(BraidError::HeaderParse(_))
+        ));
+        assert!(matches!(
+            doc.apply_patches(v("v2"), vec![v("v1")], &[Patch::json(".a", "1")]),
+            Err(BraidError::HeaderParse(_))
+        ));
+        assert!(!doc.contains_version(&v("v2")));
+        assert_eq!(doc.content(), "abc");
+    }
+
+    #[test]
+    fn test_merge_type_patches_since() {
+        let mut doc = Sync9::new();
+        doc.apply(&Update::snapshot(v("v1"), "hello"), "alice").unwrap();
+        doc.apply(&Update::patched(v("v2"), vec![ins(5, "!")]), "bob").unwrap();
+
+        let updates = doc.patches_since(&[v("v1")]).unwrap();
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].parents, vec![v("v1")]);
+        assert_eq!(updates[0].version, vec![v("v2")]);
+        assert_eq!(
+            apply_text_patches("hello", updates[0].patches.as_ref().unwrap()).unwrap(),
+            "hello!"
+        );
+
+        assert!(doc.patches_since(&[v("v2")]).unwrap().is_empty());
+        assert!(doc.patches_since(&[v("nope")]).is_err());
+        assert_eq!(doc.snapshot().body_str(), Some("hello!"));
+    }
+
+    #[test]
+    fn test_merge_type_apply_rejects_binary_bodies() {
+        let mut doc = Sync9::new();
+        let update = Update::snapshot(v("v1"), vec![b'h', 0xff]);
+        assert!(matches!(doc.apply(&update, "alice"), Err(BraidError::BodyParse(_))));
+        assert!(doc.content().is_empty());
+        assert!(!doc.contains_version(&v("v1")));
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        let mut doc = Sync9::new();
+        doc.apply_patches(v("v1"), vec![], &[ins(0, "abc")]).unwrap();
+        let cp = doc.checkpoint();
+        assert_eq!(cp["content"], "abc");
+        assert_eq!(cp["version"], "\"v1\"");
+        assert_eq!(cp["versions"], 1);
+    }
+}
@@ -0,0 +1,481 @@
+//! Helpers for `text` range patches shared by the text merge types.
+//!
+//! Text patches use the `text` unit with a `[start:end]` range measured in Unicode
+//! scalar values. Patches in one update are applied in order, each against the result
+//! of the previous one.
+//!
+//! Documents that accept other position units (see [`PositionUnit`]) also accept
+//! `text-chars`, `text-utf16`, and `text-utf8` ranges.
+//!
+//! # Cursors
+//!
+//! [`transform_position`] and [`transform_range`] map a cursor or selection through
+//! text patches, so that it stays on the same text while patches are applied around
+//! it. [`DiamondCRDT::transform_position`](crate::merge::DiamondCRDT::transform_position)
+//! does the same between any two versions of a document.
+//!
+//! ```
+//! use braid_axum_http::merge::{transform_position, Stickiness};
+//! use braid_axum_http::Patch;
+//!
+//! // The cursor is after "hello"; someone inserts a greeting at its position
+//! let patches = [Patch::text("[0:0]", ">> "), Patch::text("[8:8]", ", world")];
+//! assert_eq!(transform_position(5, &patches, Stickiness::Left).unwrap(), 8);
+//! assert_eq!(transform_position(5, &patches, Stickiness::Right).unwrap(), 15);
+//! ```
+
+use crate::error::{BraidError, Result};
+use crate::types::Patch;
+
+/// How positions in a `text` range are counted.
+///
+/// Editors disagree on what a position means: diamond-types and Rust `char`s count
+/// Unicode scalar values, browsers count UTF-16 code units, and many tools count UTF-8
+/// bytes. For `"a😀b"`, the position after the emoji is 2, 3, or 5 respectively.
+///
+/// The unit of a patch is negotiated through its `Content-Range` unit:
+///
+/// | Unit | Positions |
+/// |------|-----------|
+/// | `text` | The document's configured unit |
+/// | `text-chars` | [`PositionUnit::Char`] |
+/// | `text-utf16` | [`PositionUnit::Utf16`] |
+/// | `text-utf8` | [`PositionUnit::Utf8`] |
+///
+/// # Examples
+///
+/// ```
+/// use braid_axum_http::merge::PositionUnit;
+///
+/// let text = "a😀b";
+/// assert_eq!(PositionUnit::Utf16.to_char_offset(text, 3), Some(2));
+/// assert_eq!(PositionUnit::Utf16.to_char_offset(text, 2), None); // inside the emoji
+/// assert_eq!(PositionUnit::Utf8.to_unit_offset(text, 2), 5);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum PositionUnit {
+    /// Unicode scalar values (Rust `char`s)
+    #[default]
+    Char,
+    /// UTF-16 code units, as used by JavaScript strings
+    Utf16,
+    /// UTF-8 bytes
+    Utf8,
+}
+
+impl PositionUnit {
+    /// The explicit `Content-Range` unit naming this position unit.
+    #[must_use]
+    pub fn range_unit(self) -> &'static str {
+        match self {
+            PositionUnit::Char => "text-chars",
+            PositionUnit::Utf16 => "text-utf16",
+            PositionUnit::Utf8 => "text-utf8",
+        }
+    }
+
+    /// Parse an explicit `Content-Range` unit (`text-chars`, `text-utf16`, `text-utf8`).
+    ///
+    /// Returns `None` for any other unit, including plain `text`.
+    #[must_use]
+    pub fn from_range_unit(unit: &str) -> Option<Self> {
+        match unit {
+            "text-chars" => Some(PositionUnit::Char),
+            "text-utf16" => Some(PositionUnit::Utf16),
+            "text-utf8" => Some(PositionUnit::Utf8),
+            _ => None,
+        }
+    }
+
+    /// Resolve the unit of a `text` patch, with plain `text` meaning `default`.
+    pub(crate) fn resolve(unit: &str, default: PositionUnit) -> Result<Self> {
+        match unit {
+            "text" => Ok(default),
+            _ => Self::from_range_unit(unit).ok_or_else(|| {
+                BraidError::HeaderParse(format!("Expected text patch, got unit '{}'", unit))
+            }),
+        }
+    }
+
+    /// Measure `text` in this unit.
+    #[must_use]
+    pub fn len(self, text: &str) -> usize {
+        match self {
+            PositionUnit::Char => text.chars().count(),
+            PositionUnit::Utf16 => text.encode_utf16().count(),
+            PositionUnit::Utf8 => text.len(),
+        }
+    }
+
+    /// Convert a position in this unit into a Unicode scalar offset.
+    ///
+    /// Returns `None` if `pos` is past the end of `text` or falls inside a character.
+    #[must_use]
+    pub fn to_char_offset(self, text: &str, pos: usize) -> Option<usize> {
+        let mut offset = 0;
+        for (index, ch) in text.chars().enumerate() {
+            match offset.cmp(&pos) {
+                std::cmp::Ordering::Equal => return Some(index),
+                std::cmp::Ordering::Greater => return None,
+                std::cmp::Ordering::Less => offset += self.char_len(ch),
+            }
+        }
+        (offset == pos).then(|| text.chars().count())
+    }
+
+    /// Convert a Unicode scalar offset into a position in this unit.
+    ///
+    /// Offsets past the end of `text` are clamped to its length.
+    #[must_use]
+    pub fn to_unit_offset(self, text: &str, chars: usize) -> usize {
+        text.chars().take(chars).map(|ch| self.char_len(ch)).sum()
+    }
+
+    /// Convert a `[start:end]` range in this unit into Unicode scalar offsets in `text`.
+    pub(crate) fn range_to_chars(self, text: &str, start: usize, end: usize) -> Result<(usize, usize)> {
+        let len = self.len(text);
+        if end > len {
+            return Err(BraidError::HeaderParse(format!(
+                "Range [{}:{}] exceeds document length {}",
+                start, end, len
+            )));
+        }
+        match (self.to_char_offset(text, start), self.to_char_offset(text, end)) {
+            (Some(start), Some(end)) => Ok((start, end)),
+            _ => Err(BraidError::HeaderParse(format!(
+                "Range [{}:{}] splits a character ({} positions)",
+                start,
+                end,
+                self.range_unit()
+            ))),
+        }
+    }
+
+    fn char_len(self, ch: char) -> usize {
+        match self {
+            PositionUnit::Char => 1,
+            PositionUnit::Utf16 => ch.len_utf16(),
+            PositionUnit::Utf8 => ch.len_utf8(),
+        }
+    }
+}
+
+/// Which way a cursor moves when text is inserted exactly at its position.
+///
+/// A cursor sits between two characters and follows one of them:
+///
+/// | Stickiness | Follows | Text inserted at the cursor goes |
+/// |------------|---------|----------------------------------|
+/// | [`Stickiness::Left`] | The character before it | After the cursor |
+/// | [`Stickiness::Right`] | The character after it | Before the cursor |
+///
+/// A cursor whose character is deleted moves to where the character was: the end of
+/// any text replacing it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Stickiness {
+    /// Follow the character before the cursor
+    #[default]
+    Left,
+    /// Follow the character after the cursor
+    Right,
+}
+
+/// Map a position through `text` patches applied in order.
+///
+/// Positions and plain `text` patches count Unicode scalar values; see
+/// [`transform_position_in`] for other units.
+///
+/// # Arguments
+///
+/// * `pos` - Position in the text the first patch applies to
+/// * `patches` - Patches, each applying to the result of the previous one
+/// * `stickiness` - Which way the position moves for text inserted at it
+///
+/// # Returns
+///
+/// The position in the text the last patch produces.
+///
+/// # Errors
+///
+/// Returns [`BraidError::HeaderParse`] if a patch isn't a valid `text` patch.
+pub fn transform_position(pos: usize, patches: &[Patch], stickiness: Stickiness) -> Result<usize> {
+    transform_position_in(pos, patches, stickiness, PositionUnit::Char)
+}
+
+/// [`transform_position`] with positions measured in `unit`.
+///
+/// Plain `text` patches are measured in `unit` too, and patches naming another unit
+/// are rejected.
+///
+/// # Errors
+///
+/// Returns [`BraidError::HeaderParse`] if a patch isn't a valid `text` patch in `unit`.
+pub fn transform_position_in(
+    pos: usize,
+    patches: &[Patch],
+    stickiness: Stickiness,
+    unit: PositionUnit,
+) -> Result<usize> {
+    patches.iter().try_fold(pos, |pos, patch| {
+        let (patch_unit, start, end, content) = parse_text_patch_in(patch, unit)?;
+        if patch_unit != unit {
+            return Err(BraidError::HeaderParse(format!(
+                "Expected a patch in {} positions, got unit '{}'",
+                unit.range_unit(),
+                patch.unit
+            )));
+        }
+        Ok(transform_offset(pos, start, end, unit.len(content), stickiness))
+    })
+}
+
+/// Map a range, such as a selection, through `text` patches applied in order.
+///
+/// Both ends move with the same stickiness; map them with [`transform_position`]
+/// to treat them differently.
+///
+/// # Errors
+///
+/// Returns [`BraidError::HeaderParse`] if a patch isn't a valid `text` patch.
+pub fn transform_range(
+    range: std::ops::Range<usize>,
+    patches: &[Patch],
+    stickiness: Stickiness,
+) -> Result<std::ops::Range<usize>> {
+    transform_range_in(range, patches, stickiness, PositionUnit::Char)
+}
+
+/// [`transform_range`] with positions measured in `unit`.
+///
+/// # Errors
+///
+/// Returns [`BraidError::HeaderParse`] if a patch isn't a valid `text` patch in `unit`.
+pub fn transform_range_in(
+    range: std::ops::Range<usize>,
+    patches: &[Patch],
+    stickiness: Stickiness,
+    unit: PositionUnit,
+) -> Result<std::ops::Range<usize>> {
+    let start = transform_position_in(range.start, patches, stickiness, unit)?;
+    let end = transform_position_in(range.end, patches, stickiness, unit)?;
+    Ok(start..end)
+}
+
+/// Map `pos` through replacing `start..end` with `inserted` positions of text.
+fn transform_offset(pos: usize, start: usize, end: usize, inserted: usize, stickiness: Stickiness) -> usize {
+    if pos < start || (pos == start && stickiness == Stickiness::Left) {
+        // Follows a character before the patch
+        pos
+    } else if pos > end || (pos == end && stickiness == Stickiness::Right) {
+        // Follows a character after the patch
+        pos - (end - start) + inserted
+    } else {
+        // Follows a replaced character, or is pushed past an insertion
+        start + inserted
+    }
+}
+
+/// Parse a `text` range of the form `[start:end]` (brackets optional).
+pub(crate) fn parse_text_range(range: &str) -> Result<(usize, usize)> {
+    let invalid = || BraidError::HeaderParse(format!("Invalid text range: {}", range));
+    let trimmed = range.trim();
+    let inner = trimmed
+        .strip_prefix('[')
+        .and_then(|r| r.strip_suffix(']'))
+        .unwrap_or(trimmed);
+    let (start, end) = inner.split_once(':').ok_or_else(invalid)?;
+    let start: usize = start.trim().parse().map_err(|_| invalid())?;
+    let end: usize = end.trim().parse().map_err(|_| invalid())?;
+    if end < start {
+        return Err(invalid());
+    }
+    Ok((start, end))
+}
+
+/// Format a `text` range as `[start:end]`.
+pub(crate) fn format_text_range(start: usize, end: usize) -> String {
+    format!("[{}:{}]", start, end)
+}
+
+/// Parse a text patch into `(start, end, content)`.
+pub(crate) fn parse_text_patch(patch: &Patch) -> Result<(usize, usize, &str)> {
+    if !patch.is_text() {
+        return Err(BraidError::HeaderParse(format!(
+            "Expected text patch, got unit '{}'",
+            patch.unit
+        )));
+    }
+    let text = std::str::from_utf8(&patch.content)
+        .map_err(|e| BraidError::BodyParse(format!("Invalid UTF-8 in text patch: {}", e)))?;
+    let (start, end) = parse_text_range(&patch.range)?;
+    Ok((start, end, text))
+}
+
+/// Parse a `text` patch in any position unit into `(unit, start, end, content)`.
+///
+/// Plain `text` patches are measured in `default`.
+pub(crate) fn parse_text_patch_in(
+    patch: &Patch,
+    default: PositionUnit,
+) -> Result<(PositionUnit, usize, usize, &str)> {
+    let unit = PositionUnit::resolve(&patch.unit, default)?;
+    let text = std::str::from_utf8(&patch.content)
+        .map_err(|e| BraidError::BodyParse(format!("Invalid UTF-8 in text patch: {}", e)))?;
+    let (start, end) = parse_text_range(&patch.range)?;
+    Ok((unit, start, end, text))
+}
+
+/// Replace the Unicode scalar range `start..end` of `text` with `content`.
+pub(crate) fn splice_chars(text: &mut String, start: usize, end: usize, content: &str) {
+    let byte = |chars: usize| text.char_indices().nth(chars).map_or(text.len(), |(i, _)| i);
+    let range = byte(start)..byte(end);
+    text.replace_range(range, content);
+}
+
+/// Apply text patches in order to a string.
+///
+/// Fails without partial effects if any range falls outside the text.
+pub(crate) fn apply_text_patches(text: &str, patches: &[Patch]) -> Result<String> {
+    let mut chars: Vec<char> = text.chars().collect();
+    for patch in patches {
+        let (start, end, content) = parse_text_patch(patch)?;
+        if end > chars.len() {
+            return Err(BraidError::HeaderParse(format!(
+                "Range [{}:{}] exceeds document length {}",
+                start,
+                end,
+                chars.len()
+            )));
+        }
+        chars.splice(start..end, content.chars());
+    }
+    Ok(chars.into_iter().collect())
+}
+
+/// Describe the change from `old` to `new` as a single text patch.
+///
+/// Returns `None` if the texts are equal.
+pub(crate) fn diff_text(old: &str, new: &str) -> Option<Patch> {
+    if old == new {
+        return None;
+    }
+    let old: Vec<char> = old.chars().collect();
+    let new: Vec<char> = new.chars().collect();
+
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let inserted: String = new[prefix..new.len() - suffix].iter().collect();
+    Some(Patch::text(
+        format_text_range(prefix, old.len() - suffix),
+        inserted,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_text_range() {
+        assert_eq!(parse_text_range("[1:4]").unwrap(), (1, 4));
+        assert_eq!(parse_text_range("2:2").unwrap(), (2, 2));
+        assert!(parse_text_range("[4:1]").is_err());
+        assert!(parse_text_range(".title").is_err());
+    }
+
+    #[test]
+    fn test_apply_text_patches() {
+        let patches = [Patch::text("[0:1]", "J"), Patch::text("[5:5]", "!")];
+        assert_eq!(apply_text_patches("hello", &patches).unwrap(), "Jello!");
+        assert!(apply_text_patches("hi", &[Patch::text("[1:9]", "")]).is_err());
+    }
+
+    #[test]
+    fn test_position_unit_conversion() {
+        // "中" is 1 UTF-16 unit and 3 bytes; "🎉" is 2 UTF-16 units and 4 bytes
+        let text = "中🎉x";
+        assert_eq!(PositionUnit::Char.len(text), 3);
+        assert_eq!(PositionUnit::Utf16.len(text), 4);
+        assert_eq!(PositionUnit::Utf8.len(text), 8);
+
+        for unit in [PositionUnit::Char, PositionUnit::Utf16, PositionUnit::Utf8] {
+            for chars in 0..=3 {
+                let pos = unit.to_unit_offset(text, chars);
+                assert_eq!(unit.to_char_offset(text, pos), Some(chars));
+            }
+            assert_eq!(unit.to_char_offset(text, unit.len(text) + 1), None);
+        }
+        assert_eq!(PositionUnit::Utf8.to_char_offset(text, 2), None);
+        assert_eq!(PositionUnit::Utf16.to_char_offset(text, 2), None);
+    }
+
+    #[test]
+    fn test_position_unit_names() {
+        assert_eq!(PositionUnit::resolve("text", PositionUnit::Utf16).unwrap(), PositionUnit::Utf16);
+        assert_eq!(PositionUnit::resolve("text-utf8", PositionUnit::Utf16).unwrap(), PositionUnit::Utf8);
+        assert!(PositionUnit::resolve("json", PositionUnit::Char).is_err());
+        for unit in [PositionUnit::Char, PositionUnit::Utf16, PositionUnit::Utf8] {
+            assert_eq!(PositionUnit::from_range_unit(unit.range_unit()), Some(unit));
+        }
+    }
+
+    #[test]
+    fn test_transform_position_follows_its_character() {
+        use Stickiness::{Left, Right};
+        // "hello world": replace "world" with "there", then delete "hello "
+        let patches = [Patch::text("[6:11]", "there"), Patch::text("[0:6]", "")];
+        let cases = [
+            (0, Left, 0),
+            (0, Right, 0),
+            (3, Left, 0),   // in deleted text
+            (6, Left, 0),   // after the deleted space
+            (6, Right, 5),  // before the replaced "w"
+            (8, Left, 5),   // inside the replaced word
+            (11, Left, 5),  // after the replaced "d"
+            (11, Right, 5), // at the end
+        ];
+        for (pos, stickiness, expected) in cases {
+            assert_eq!(
+                transform_position(pos, &patches, stickiness).unwrap(),
+                expected,
+                "{} sticking {:?}",
+                pos,
+                stickiness
+            );
+        }
+
+        let insert = [Patch::text("[2:2]", "ab")];
+        assert_eq!(transform_range(2..4, &insert, Left).unwrap(), 2..6);
+        assert_eq!(transform_range(2..4, &insert, Right).unwrap(), 4..6);
+        assert!(transform_position(0, &[Patch::json(".a", "1")], Left).is_err());
+    }
+
+    #[test]
+    fn test_transform_position_in_other_units() {
+        // Inserting an emoji moves later positions by 2 UTF-16 units
+        let patches = [Patch::text("[1:1]", "🎉")];
+        assert_eq!(transform_position_in(3, &patches, Stickiness::Left, PositionUnit::Utf16).unwrap(), 5);
+        assert_eq!(transform_position_in(3, &patches, Stickiness::Left, PositionUnit::Utf8).unwrap(), 7);
+
+        let utf16 = [Patch::new("text-utf16", "[1:1]", "🎉")];
+        assert_eq!(transform_position_in(3, &utf16, Stickiness::Left, PositionUnit::Utf16).unwrap(), 5);
+        assert!(transform_position_in(3, &utf16, Stickiness::Left, PositionUnit::Utf8).is_err());
+    }
+
+    #[test]
+    fn test_diff_text() {
+        assert!(diff_text("same", "same").is_none());
+
+        for (old, new) in [("hello", "help"), ("", "🌍 world"), ("aaa", "aaaa"), ("abc", "")] {
+            let patch = diff_text(old, new).unwrap();
+            assert_eq!(apply_text_patches(old, &[patch]).unwrap(), new);
+        }
+        assert_eq!(diff_text("hello", "help").unwrap().range, "[3:5]");
+    }
+}
@@ -21,6 +21,7 @@
 //! | 206 | `status::PARTIAL_CONTENT` | Range-based patches |
 //! | 209 | `STATUS_SUBSCRIPTION` | Subscription update |
 //! | 293 | `STATUS_MERGE_CONFLICT` | Version conflicts |
+//! | 404 | `status::NOT_FOUND` | Unknown resource or version |
 //! | 410 | `STATUS_GONE` | History dropped |
 //! | 416 | `STATUS_RANGE_NOT_SATISFIABLE` | Invalid range |
 //!
@@ -133,11 +134,23 @@ pub mod status {
     /// 293 Merge Conflict - Version conflicts detected (Braid-HTTP)
     pub const MERGE_CONFLICT: u16 = 293;
 
+    /// 400 Bad Request - Update that can't be parsed or merged
+    pub const BAD_REQUEST: u16 = 400;
+
+    /// 404 Not Found - Unknown resource or version
+    pub const NOT_FOUND: u16 = 404;
+
     /// 410 Gone - History dropped, client must restart
     pub const GONE: u16 = 410;
 
     /// 416 Range Not Satisfiable - Invalid range request (RFC 7233)
     pub const RANGE_NOT_SATISFIABLE: u16 = 416;
+
+    /// 422 Unprocessable Entity - Update rejected by validation
+    pub const UNPROCESSABLE_ENTITY: u16 = 422;
+
+    /// 500 Internal Server Error - Update the server failed to apply or store
+    pub const INTERNAL_SERVER_ERROR: u16 = 500;
 }
 
 // =============================================================================
@@ -212,11 +225,20 @@ pub mod headers {
 /// assert_eq!(merge_types::SYNC9, "sync9");
 /// ```
 pub mod merge_types {
-    /// Simple synchronization merge type.
+    /// Braid sync9 sequence CRDT merge type (see [`crate::merge::Sync9`]).
     pub const SYNC9: &str = "sync9";
 
     /// Diamond-types CRDT merge type for collaborative text editing.
     pub const DIAMOND: &str = "diamond";
+
+    /// Operational-transform text merge type (see [`crate::merge::OtText`]).
+    pub const OT_TEXT: &str = "ot-text";
+
+    /// Last-writer-wins merge type for opaque values (see [`crate::merge::LwwRegister`]).
+    pub const LWW: &str = "lww";
+
+    /// Path-level JSON merge type (see [`crate::merge::JsonDocument`]).
+    pub const JSON: &str = "json";
 }
 
 #[cfg(test)]
@@ -237,6 +259,7 @@ mod tests {
         assert_eq!(status::PARTIAL_CONTENT, 206);
         assert_eq!(status::SUBSCRIPTION, 209);
         assert_eq!(status::MERGE_CONFLICT, 293);
+        assert_eq!(status::NOT_FOUND, 404);
     }
 
     #[test]
@@ -251,5 +274,6 @@ mod tests {
     fn test_merge_types() {
         assert_eq!(merge_types::SYNC9, "sync9");
         assert_eq!(merge_types::DIAMOND, "diamond");
+        assert_eq!(merge_types::OT_TEXT, "ot-text");
     }
 }
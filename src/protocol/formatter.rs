@@ -9,6 +9,9 @@ use crate::protocol;
 use crate::types::{Update, Patch};
 use bytes::{Bytes, BytesMut};
 
+/// Largest snapshot body copied into the same chunk as its message headers.
+pub const INLINE_BODY_LIMIT: usize = 16 * 1024;
+
 /// Format an Update into Braid protocol message bytes.
 ///
 /// Serializes the update including all headers and body/patches
@@ -22,6 +25,27 @@ use bytes::{Bytes, BytesMut};
 ///
 /// Bytes containing the formatted message.
 pub fn format_update(update: &Update) -> Result<Bytes> {
+    let chunks = format_update_chunks(update)?;
+    if let [message] = chunks.as_slice() {
+        return Ok(message.clone());
+    }
+    let mut buffer = BytesMut::with_capacity(chunks.iter().map(Bytes::len).sum());
+    for chunk in &chunks {
+        buffer.extend_from_slice(chunk);
+    }
+    Ok(buffer.freeze())
+}
+
+/// Format an Update into chunks of a Braid protocol message, to send in order.
+///
+/// The chunks make up the same bytes as [`format_update`], but a snapshot body larger
+/// than [`INLINE_BODY_LIMIT`] is a chunk of its own that shares the update's buffer,
+/// so large binary snapshots are streamed without being copied behind their headers.
+///
+/// # Returns
+///
+/// The message headers (and any patches), followed by the body if there is one.
+pub fn format_update_chunks(update: &Update) -> Result<Vec<Bytes>> {
     let mut buffer = BytesMut::new();
 
     // Format headers
@@ -48,6 +72,9 @@ pub fn format_update(update: &Update) -> Result<Bytes> {
             write_header(&mut buffer, headers::CONTENT_TYPE.as_str(), ct);
         }
         buffer.extend_from_slice(b"\r\n"); // End of headers
+        if body.len() > INLINE_BODY_LIMIT {
+            return Ok(vec![buffer.freeze(), body.clone()]);
+        }
         buffer.extend_from_slice(body);
     } else if let Some(patches) = &update.patches {
         if !patches.is_empty() {
@@ -68,6 +95,24 @@ pub fn format_update(update: &Update) -> Result<Bytes> {
         buffer.extend_from_slice(b"\r\n");
     }
 
+    Ok(vec![buffer.freeze()])
+}
+
+/// Format the body of a multi-patch message (Section 3.3).
+///
+/// Each patch is written with its own `Content-Length` and `Content-Range` headers,
+/// followed by its content. The message itself then carries a `Patches: N` header
+/// instead of a `Content-Length`. [`parse_patches`](crate::protocol::parse_patches)
+/// reads the body back.
+///
+/// # Returns
+///
+/// Bytes containing the patches, one after the other.
+pub fn format_patches(patches: &[Patch]) -> Result<Bytes> {
+    let mut buffer = BytesMut::new();
+    for patch in patches {
+        format_patch(&mut buffer, patch)?;
+    }
     Ok(buffer.freeze())
 }
 
@@ -105,4 +150,25 @@ mod tests {
         assert!(s.contains("content-length: 4"));
         assert!(s.ends_with("\r\ndata"));
     }
+
+    #[test]
+    fn test_format_patches_round_trip() {
+        let patches = vec![Patch::text("[0:0]", "hi"), Patch::text("[5:7]", "")];
+        let body = format_patches(&patches).unwrap();
+        assert_eq!(protocol::parse_patches(2, &body).unwrap(), patches);
+        assert!(protocol::parse_patches(3, &body).is_err());
+    }
+
+    #[test]
+    fn test_format_chunks_share_large_snapshot_body() {
+        let small = Update::snapshot(Version::new("v1"), vec![0u8, 0xff, 0x80]);
+        assert_eq!(format_update_chunks(&small).unwrap().len(), 1);
+
+        let body = Bytes::from(vec![0xffu8; INLINE_BODY_LIMIT + 1]);
+        let update = Update::snapshot(Version::new("v1"), body.clone());
+        let chunks = format_update_chunks(&update).unwrap();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[1].as_ptr(), body.as_ptr());
+        assert_eq!(format_update(&update).unwrap(), [chunks[0].clone(), body].concat());
+    }
 }
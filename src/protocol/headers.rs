@@ -243,7 +243,10 @@ pub fn parse_heartbeat(value: &str) -> Result<u64> {
 
 /// Parse and validate Merge-Type header.
 ///
-/// Ensures the merge type is one of the supported values (e.g., "sync9", "diamond").
+/// Ensures the value is a single merge-type name: a non-empty HTTP token such as
+/// "sync9", "diamond", "ot-text" or "lww". Whether a merge type of that name is
+/// available is up to the server's [`MergeTypeRegistry`](crate::merge::MergeTypeRegistry),
+/// which can also hold application-registered types.
 ///
 /// # Arguments
 ///
@@ -255,7 +258,7 @@ pub fn parse_heartbeat(value: &str) -> Result<u64> {
 ///
 /// # Errors
 ///
-/// Returns an error if the merge type is unknown or invalid.
+/// Returns an error if the value is empty or isn't a single token.
 ///
 /// # Examples
 ///
@@ -263,14 +266,18 @@ pub fn parse_heartbeat(value: &str) -> Result<u64> {
 /// use braid_axum_http::protocol::parse_merge_type;
 ///
 /// assert!(parse_merge_type("diamond").is_ok());
-/// assert!(parse_merge_type("invalid").is_err());
+/// assert!(parse_merge_type("diamond, sync9").is_err());
 /// ```
 pub fn parse_merge_type(value: &str) -> Result<String> {
     let trimmed = value.trim();
-    match trimmed {
-        crate::protocol::constants::merge_types::SYNC9 |
-        crate::protocol::constants::merge_types::DIAMOND => Ok(trimmed.to_string()),
-        _ => Err(BraidError::HeaderParse(format!("Unsupported merge-type: {}", value))),
+    let is_token = !trimmed.is_empty()
+        && trimmed
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b));
+    if is_token {
+        Ok(trimmed.to_string())
+    } else {
+        Err(BraidError::HeaderParse(format!("Invalid merge-type: {}", value)))
     }
 }
 
@@ -354,6 +361,16 @@ mod tests {
     fn test_parse_merge_type() {
         assert!(parse_merge_type("diamond").is_ok());
         assert!(parse_merge_type("sync9").is_ok());
-        assert!(parse_merge_type("unknown").is_err());
+        assert!(parse_merge_type("ot-text").is_ok());
+        assert!(parse_merge_type("lww").is_ok());
+        assert!(parse_merge_type("").is_err());
+        assert!(parse_merge_type("diamond sync9").is_err());
+        assert!(parse_merge_type("\"diamond\"").is_err());
+    }
+
+    #[test]
+    fn test_parse_merge_type_accepts_registered_types() {
+        // Names are resolved through the registry, not limited to the built-ins
+        assert_eq!(parse_merge_type(" notes ").unwrap(), "notes");
     }
 }
@@ -9,8 +9,9 @@
 //! ```text
 //! protocol/
 //! ├── constants   - Status codes, header names, merge types
+//! ├── formatter   - Message and multi-patch body formatting
 //! ├── headers     - Header parsing and formatting utilities
-//! └── parser      - HeaderParser type for structured access
+//! └── parser      - HeaderParser type and multi-patch body parsing
 //! ```
 //!
 //! # Key Components
@@ -22,6 +23,7 @@
 //! | [`parse_version_header`] | Parse Version header |
 //! | [`format_version_header`] | Format Version header |
 //! | [`parse_content_range`] | Parse Content-Range header |
+//! | [`format_patches`] / [`parse_patches`] | Multi-patch message bodies |
 //! | [`HeaderParser`] | Structured header parsing API |
 //!
 //! # Design Philosophy
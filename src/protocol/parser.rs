@@ -36,8 +36,9 @@
 //! let header = protocol::format_version_header(&versions);
 //! ```
 
-use crate::error::Result;
-use crate::types::Version;
+use crate::error::{BraidError, Result};
+use crate::types::{Patch, Version};
+use bytes::Bytes;
 
 /// Header parser for protocol messages.
 ///
@@ -120,3 +121,75 @@ impl HeaderParser {
     }
 }
 
+
+/// Parse the body of a multi-patch message (Section 3.3).
+///
+/// The body holds `count` patches, the number given by the message's `Patches`
+/// header, as written by [`format_patches`](crate::protocol::format_patches).
+///
+/// # Errors
+///
+/// Returns [`BraidError::BodyParse`] if the body holds fewer than `count` complete
+/// patches, or [`BraidError::HeaderParse`] if a patch's headers are malformed.
+pub fn parse_patches(count: usize, body: &[u8]) -> Result<Vec<Patch>> {
+    let mut patches = Vec::with_capacity(count);
+    let mut rest = body;
+    while patches.len() < count {
+        let (patch, len) = parse_patch(rest)?.ok_or_else(|| {
+            BraidError::BodyParse(format!("Expected {} patches, found {}", count, patches.len()))
+        })?;
+        patches.push(patch);
+        rest = &rest[len..];
+    }
+    Ok(patches)
+}
+
+/// Parse one patch of a multi-patch body from the start of `data`.
+///
+/// The patch's headers must include `Content-Length` and `Content-Range`. Blank lines
+/// before them are skipped.
+///
+/// # Returns
+///
+/// The patch and the number of bytes of `data` it took up, or `None` if `data`
+/// doesn't hold the whole patch yet.
+///
+/// # Errors
+///
+/// Returns [`BraidError::HeaderParse`] if the patch's headers are malformed or
+/// missing.
+pub fn parse_patch(data: &[u8]) -> Result<Option<(Patch, usize)>> {
+    let start = data.iter().take_while(|&&b| b == b'\r' || b == b'\n').count();
+    let Some(headers_len) = data[start..].windows(4).position(|w| w == b"\r\n\r\n") else {
+        return Ok(None);
+    };
+    let headers = std::str::from_utf8(&data[start..start + headers_len])
+        .map_err(|e| BraidError::HeaderParse(format!("Invalid patch headers: {}", e)))?;
+
+    let mut length = None;
+    let mut range = None;
+    for line in headers.lines() {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        match name.trim().to_ascii_lowercase().as_str() {
+            "content-length" => {
+                length = Some(value.trim().parse::<usize>().map_err(|_| {
+                    BraidError::HeaderParse(format!("Invalid patch content-length: {}", value.trim()))
+                })?);
+            }
+            "content-range" => range = Some(crate::protocol::parse_content_range(value.trim())?),
+            _ => {}
+        }
+    }
+    let length = length.ok_or_else(|| BraidError::HeaderParse("Patch has no content-length".to_string()))?;
+    let (unit, range) =
+        range.ok_or_else(|| BraidError::HeaderParse("Patch has no content-range".to_string()))?;
+
+    let content_start = start + headers_len + 4;
+    if data.len() < content_start + length {
+        return Ok(None);
+    }
+    let content = Bytes::copy_from_slice(&data[content_start..content_start + length]);
+    Ok(Some((Patch::new(unit, range, content), content_start + length)))
+}
@@ -0,0 +1,79 @@
+//! Authorship attribution ("blame") for text resources.
+//!
+//! [`ResourceStateManager::blame`] splits the text of a resource into
+//! [`AuthorSpan`]s, each naming the agent that inserted it and the Braid version that
+//! did. The result is served as JSON with the content type [`CONTENT_TYPE`]:
+//!
+//! ```json
+//! {
+//!   "version": ["bob-5"],
+//!   "spans": [
+//!     {"start": 0, "end": 6, "agent": "alice", "version": "alice-5"},
+//!     {"start": 6, "end": 11, "agent": "bob", "version": "bob-5"}
+//!   ]
+//! }
+//! ```
+//!
+//! Positions count Unicode scalar values, like `text` patches.
+//!
+//! # Serving Blame
+//!
+//! | Mode | Request |
+//! |------|---------|
+//! | Endpoint | Route [`handler`] under a prefix, e.g. `GET /blame/notes/today` for the resource `/notes/today` |
+//! | Header | A resource's own handler checks [`BraidState::wants_blame`](crate::server::BraidState::wants_blame), set by `Accept: application/blame+json` |
+//!
+//! Either way, a `Version` header asks for the text as it was at that version.
+//!
+//! # Examples
+//!
+//! ```
+//! use axum::routing::get;
+//! use axum::Router;
+//! use braid_axum_http::server::{blame, BraidLayer};
+//!
+//! let layer = BraidLayer::new();
+//! let app: Router = Router::new()
+//!     .nest("/blame", Router::new().route("/{*path}", get(blame::handler)))
+//!     .layer(axum::middleware::from_fn(layer.middleware()));
+//! ```
+//!
+//! [`ResourceStateManager::blame`]: crate::server::ResourceStateManager::blame
+
+use crate::merge::AuthorSpan;
+use crate::server::{BraidState, ResourceStateManager};
+use crate::types::{Update, Version};
+use axum::extract::Extension;
+use axum::http::Uri;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Content type of blame responses, and the `Accept` value that requests them.
+pub const CONTENT_TYPE: &str = "application/blame+json";
+
+/// The attribution of a resource's text, as served.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Blame {
+    /// Version of the attributed text
+    pub version: Vec<Version>,
+
+    /// Spans covering the text, in order
+    pub spans: Vec<AuthorSpan>,
+}
+
+/// Serve the blame of the resource named by the request path.
+///
+/// Requires the [`BraidLayer`](crate::server::BraidLayer) middleware. When nested
+/// under a prefix, the path after the prefix is the resource ID.
+///
+/// # Returns
+///
+/// The [`Blame`] as JSON, or an error update as from
+/// [`ResourceStateManager::blame`].
+pub async fn handler(
+    Extension(manager): Extension<Arc<ResourceStateManager>>,
+    Extension(braid): Extension<Arc<BraidState>>,
+    uri: Uri,
+) -> Update {
+    manager.blame(uri.path(), braid.version.as_deref())
+}
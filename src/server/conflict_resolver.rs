@@ -1,38 +1,71 @@
-//! Conflict resolution for Diamond-Types CRDT merges.
+//! Conflict resolution through registered merge types.
 //!
-//! This module handles incoming updates with the "diamond" merge-type, applying
-//! CRDT operations and returning merged results. It bridges Braid-HTTP protocol
-//! updates with the underlying diamond-types CRDT engine.
+//! This module handles incoming updates that declare a `Merge-Type`, applying them
+//! through the matching [`MergeType`](crate::merge::MergeType) document and returning
+//! merged results. It bridges Braid-HTTP protocol updates with the merge algorithms
+//! in [`crate::merge`].
 
+use crate::merge::MergeTypeRegistry;
+use crate::protocol;
 use crate::types::{Update, Version};
+use crate::server::validation::{Rejection, UpdateValidator, ValidatorRoutes};
 use crate::server::ResourceStateManager;
-use serde_json::{json, Value};
+use std::sync::Arc;
 
-/// Handles conflict resolution using Diamond-Types CRDT.
+/// Handles conflict resolution by dispatching to registered merge types.
 ///
-/// The conflict resolver intercepts updates marked with `merge-type: "diamond"`,
-/// applies them to the appropriate resource's CRDT, and returns the merged result.
-/// This ensures deterministic convergence across all peers.
+/// The conflict resolver looks up the update's `Merge-Type` in a
+/// [`MergeTypeRegistry`], applies the update to the resource's document, and returns
+/// the merged result. This ensures deterministic convergence across all peers.
+/// Updates without a merge type are returned unchanged; updates naming an
+/// unregistered merge type are rejected.
 ///
 /// # Request/Response Formats
 ///
-/// **Plain Text Updates:**
-/// - Inserts text at position 0
-/// - Body can be plain text or JSON
+/// **Diamond Updates (`"diamond"`):**
+/// - `text` range patches are applied as deletes and inserts at the update's
+///   `Parents`, so edits made against an older version land in the right place
+/// - Plain text bodies are inserted at position 0
+/// - JSON bodies with `"inserts"` (`{pos, text}`) and `"deletes"` (`{start, end}`)
+///   arrays are applied operation by operation
+/// - The response carries `text` patches tagged with the new Braid version and its
+///   parents, rather than a snapshot of the whole document
 ///
-/// **Structured JSON Updates:**
-/// - `"inserts"`: Array of `{pos, text}` objects
-/// - `"deletes"`: Array of `{start, end}` objects
-/// - All operations are applied and merged into the CRDT
+/// **Sync9 Updates (`"sync9"`):**
+/// - `text` range patches, or a body replacing the text seen at the parents
+/// - A missing `Version` is assigned by the server; missing `Parents` default to
+///   the resource's current version
+/// - The response carries the patches that bring subscribers up to date
+///
+/// **JSON Updates (`"json"`):**
+/// - `json` range patches, a body at a `json` Content-Range, or a body replacing
+///   the whole value; concurrent edits to different paths are all kept
+/// - Versions and parents default as for sync9
+/// - The response carries the patches if they were made against the current
+///   version, and a snapshot of the merged value otherwise
+///
+/// # Validation
+///
+/// Validators registered with [`with_validator`](Self::with_validator) check the
+/// merged state of matching resources before each update is committed; see
+/// [`validation`](crate::server::validation).
 #[derive(Clone)]
 pub struct ConflictResolver {
     /// Manages per-resource CRDT state
     resource_manager: ResourceStateManager,
+
+    /// Merge types available to new resources
+    merge_types: MergeTypeRegistry,
+
+    /// Validators by route
+    validators: ValidatorRoutes,
 }
 
 impl ConflictResolver {
     /// Create a new conflict resolver with the given resource manager.
     ///
+    /// Uses a registry containing only the built-in merge types.
+    ///
     /// # Arguments
     ///
     /// * `resource_manager` - The centralized resource state registry
@@ -47,13 +80,61 @@ impl ConflictResolver {
     /// ```
     #[must_use]
     pub fn new(resource_manager: ResourceStateManager) -> Self {
-        Self { resource_manager }
+        Self::with_merge_types(resource_manager, MergeTypeRegistry::new())
+    }
+
+    /// Create a conflict resolver that dispatches through a custom registry.
+    ///
+    /// # Arguments
+    ///
+    /// * `resource_manager` - The centralized resource state registry
+    /// * `merge_types` - Registry of merge types by name
+    #[must_use]
+    pub fn with_merge_types(
+        resource_manager: ResourceStateManager,
+        merge_types: MergeTypeRegistry,
+    ) -> Self {
+        Self {
+            resource_manager,
+            merge_types,
+            validators: ValidatorRoutes::new(),
+        }
+    }
+
+    /// Validate updates to the resources matching `route` (builder style).
+    ///
+    /// # Arguments
+    ///
+    /// * `route` - A resource ID, or a resource ID prefix ending in `*`
+    /// * `validator` - Check of the merged state of each update
+    #[must_use]
+    pub fn with_validator(self, route: impl Into<String>, validator: impl UpdateValidator + 'static) -> Self {
+        self.validators.insert(route, Arc::new(validator));
+        self
     }
 
-    /// Resolve an update by applying CRDT semantics if needed.
+    /// Get the validators this resolver checks updates with.
+    #[inline]
+    #[must_use]
+    pub fn validators(&self) -> &ValidatorRoutes {
+        &self.validators
+    }
+
+    /// Get the registry this resolver dispatches through.
+    #[inline]
+    #[must_use]
+    pub fn merge_types(&self) -> &MergeTypeRegistry {
+        &self.merge_types
+    }
+
+    /// Resolve an update by applying its merge type.
     ///
-    /// If the update has `merge-type: "diamond"`, it's applied to the resource's CRDT.
+    /// If the update names a merge type, it's applied to the resource's document of
+    /// that type, loading the resource from the manager's store and persisting the
+    /// update if it has one (see [`ResourceStateManager::apply_and_persist`]).
     /// Otherwise, the update is returned unchanged (no merge strategy applied).
+    /// Updates are validated as in [`resolve`](Self::resolve), which also reports
+    /// the status to respond with.
     ///
     /// # Arguments
     ///
@@ -63,7 +144,8 @@ impl ConflictResolver {
     ///
     /// # Returns
     ///
-    /// The resolved update with merged content and current version.
+    /// The resolved update with merged content and current version, or an error if
+    /// the merge type is not registered or the update cannot be merged.
     ///
     /// # Examples
     ///
@@ -79,170 +161,50 @@ impl ConflictResolver {
         update: &Update,
         agent_id: &str,
     ) -> Result<Update, String> {
-        match &update.merge_type {
-            Some(merge_type) if merge_type == "diamond" => {
-                self.resolve_diamond_merge(resource_id, update, agent_id)
-                    .await
-            }
-            _ => Ok(update.clone()),
-        }
+        self.resolve(resource_id, update, agent_id)
+            .await
+            .map_err(|rejection| rejection.message)
     }
 
-    /// Apply and merge a diamond-type update.
-    ///
-    /// Detects whether the body is:
-    /// - Plain text (applies as insertion at position 0)
-    /// - Structured JSON with operation arrays (applies each operation)
-    ///
-    /// # Arguments
+    /// Resolve an update, reporting failures with the status to respond with.
     ///
-    /// * `resource_id` - Resource to update
-    /// * `update` - The Braid update with body
-    /// * `agent_id` - Origin agent
+    /// Like [`resolve_update`](Self::resolve_update), but also checks the merged state
+    /// with the validator of the resource's route, if any. A rejected update changes
+    /// nothing and is never returned for broadcasting. Neither is a write that lost:
+    /// it's resolved to a `293 Merge Conflict` (see [`Update::is_conflict`]) to
+    /// respond to its writer with.
     ///
     /// # Returns
     ///
-    /// A new update with merged content and current version.
-    async fn resolve_diamond_merge(
-        &self,
-        resource_id: &str,
-        update: &Update,
-        agent_id: &str,
-    ) -> Result<Update, String> {
-        if let Some(body_bytes) = &update.body {
-            let body_str = String::from_utf8_lossy(body_bytes);
-
-            if body_str.starts_with('{') && body_str.ends_with('}') {
-                if let Ok(json_data) = serde_json::from_str::<Value>(&body_str) {
-                    if json_data.is_object() {
-                        return self
-                            .handle_diamond_json(resource_id, &json_data, agent_id)
-                            .await;
-                    }
-                }
-            }
-
-            let _ = self
-                .resource_manager
-                .apply_update(resource_id, &body_str, agent_id)?;
-        }
-
-        self.build_merged_response(resource_id, agent_id).await
-    }
-
-    /// Parse and apply structured JSON operations.
-    ///
-    /// Expected JSON format:
-    /// ```json
-    /// {
-    ///   "inserts": [{"pos": 0, "text": "hello"}],
-    ///   "deletes": [{"start": 5, "end": 6}]
-    /// }
-    /// ```
-    ///
-    /// # Arguments
-    ///
-    /// * `resource_id` - Resource to update
-    /// * `json_data` - Parsed JSON operations
-    /// * `agent_id` - Origin agent
-    ///
-    /// # Returns
+    /// The resolved update, or a [`Rejection`] that converts into the error response:
     ///
-    /// A response update with merged state.
-    async fn handle_diamond_json(
+    /// | Failure | Status |
+    /// |---------|--------|
+    /// | Unknown merge type, or the update can't be merged | 400 |
+    /// | The validator rejects the merged state | 422 |
+    /// | The update can't be stored | 500 |
+    pub async fn resolve(
         &self,
         resource_id: &str,
-        json_data: &Value,
+        update: &Update,
         agent_id: &str,
-    ) -> Result<Update, String> {
-        self.apply_insert_operations(resource_id, json_data, agent_id);
-        self.apply_delete_operations(resource_id, json_data, agent_id);
-
-        let merged_state = self
-            .resource_manager
-            .get_resource_state(resource_id)
-            .ok_or_else(|| "Failed to retrieve resource state after merge".to_string())?;
-
-        let merged_content = extract_string(&merged_state, "content", "");
-        let quality = self
-            .resource_manager
-            .get_merge_quality(resource_id)
-            .unwrap_or(0);
-        let version_str = extract_string(&merged_state, "version", &format!("merged-{}", agent_id));
-
-        let response_body = json!({
-            "content": merged_content,
-            "merge_quality": quality,
-            "agents": [agent_id],
-        })
-        .to_string();
-
-        Ok(Update::snapshot(Version::new(&version_str), response_body)
-            .with_merge_type("diamond"))
-    }
-
-    /// Apply insertion operations from JSON array.
-    ///
-    /// Silently skips malformed operations.
-    fn apply_insert_operations(&self, resource_id: &str, json_data: &Value, agent_id: &str) {
-        if let Some(inserts) = json_data.get("inserts").and_then(|v| v.as_array()) {
-            for insert in inserts {
-                if let (Some(pos), Some(text)) = (
-                    insert.get("pos").and_then(|v| v.as_u64()),
-                    insert.get("text").and_then(|v| v.as_str()),
-                ) {
-                    let _ = self.resource_manager.apply_remote_insert(
-                        resource_id,
-                        agent_id,
-                        pos as usize,
-                        text,
-                    );
-                }
-            }
-        }
-    }
-
-    /// Apply deletion operations from JSON array.
-    ///
-    /// Silently skips malformed operations.
-    fn apply_delete_operations(&self, resource_id: &str, json_data: &Value, agent_id: &str) {
-        if let Some(deletes) = json_data.get("deletes").and_then(|v| v.as_array()) {
-            for delete in deletes {
-                if let (Some(start), Some(end)) = (
-                    delete.get("start").and_then(|v| v.as_u64()),
-                    delete.get("end").and_then(|v| v.as_u64()),
-                ) {
-                    let _ = self.resource_manager.apply_remote_delete(
-                        resource_id,
-                        agent_id,
-                        start as usize,
-                        end as usize,
-                    );
-                }
+    ) -> Result<Update, Rejection> {
+        match &update.merge_type {
+            Some(merge_type) if !self.merge_types.contains(merge_type) => Err(Rejection::bad_request(format!(
+                "Unknown merge type: {} (registered: {})",
+                merge_type,
+                self.merge_types.names().join(", ")
+            ))),
+            Some(_) => {
+                let validator = self.validators.find(resource_id);
+                self.resource_manager
+                    .persist_update(resource_id, &self.merge_types, update, agent_id, validator.as_deref())
+                    .await
             }
+            None => Ok(update.clone()),
         }
     }
 
-    /// Build a Braid response with merged content.
-    ///
-    /// Extracts content and version from the current resource state.
-    async fn build_merged_response(
-        &self,
-        resource_id: &str,
-        agent_id: &str,
-    ) -> Result<Update, String> {
-        let merged_state = self
-            .resource_manager
-            .get_resource_state(resource_id)
-            .ok_or_else(|| "Failed to retrieve merged resource state".to_string())?;
-
-        let merged_content = extract_string(&merged_state, "content", "");
-        let version_str = extract_string(&merged_state, "version", &format!("merged-{}", agent_id));
-
-        Ok(Update::snapshot(Version::new(&version_str), merged_content)
-            .with_merge_type("diamond"))
-    }
-
     /// Get the current content of a resource.
     ///
     /// # Arguments
@@ -268,29 +230,27 @@ impl ConflictResolver {
     ///
     /// # Returns
     ///
-    /// Current version identifier, or `None` if the resource doesn't exist.
+    /// Current version identifier (the first one, if the resource has several
+    /// concurrent tips), or `None` if the resource doesn't exist.
     #[inline]
     #[must_use]
     pub fn get_resource_version(&self, resource_id: &str) -> Option<Version> {
         self.resource_manager
             .get_resource_state(resource_id)
-            .and_then(|state| state["version"].as_str().map(|s| Version::new(s)))
+            .and_then(|state| {
+                state["version"]
+                    .as_str()
+                    .and_then(|header| protocol::parse_version_header(header).ok())
+                    .and_then(|versions| versions.into_iter().next())
+            })
     }
 }
 
-/// Helper to safely extract string values from JSON.
-///
-/// Returns the specified default if the field is missing or not a string.
-fn extract_string(json: &Value, key: &str, default: &str) -> String {
-    json.get(key)
-        .and_then(|v| v.as_str())
-        .unwrap_or(default)
-        .to_string()
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::merge::MergeType;
+    use crate::types::Patch;
 
     #[tokio::test]
     async fn test_resolve_non_diamond_update() {
@@ -318,6 +278,105 @@ mod tests {
         assert_eq!(resolved.merge_type, Some("diamond".to_string()));
     }
 
+    #[tokio::test]
+    async fn test_resolve_sync9_concurrent_peers() {
+        let manager = ResourceStateManager::new();
+        let resolver = ConflictResolver::new(manager);
+
+        let base = Update::snapshot(Version::new("v1"), "hello")
+            .with_merge_type("sync9");
+        resolver.resolve_update("doc1", &base, "alice").await.unwrap();
+
+        let alice = Update::patched(Version::new("alice-1"), vec![Patch::text("[5:5]", " world")])
+            .with_parent(Version::new("v1"))
+            .with_merge_type("sync9");
+        let bob = Update::patched(Version::new("bob-1"), vec![Patch::text("[0:1]", "H")])
+            .with_parent(Version::new("v1"))
+            .with_merge_type("sync9");
+        resolver.resolve_update("doc1", &alice, "alice").await.unwrap();
+        let resolved = resolver.resolve_update("doc1", &bob, "bob").await.unwrap();
+
+        assert_eq!(resolved.merge_type, Some("sync9".to_string()));
+        assert_eq!(resolved.parents, vec![Version::new("v1")]);
+        assert_eq!(resolved.patches.unwrap(), vec![Patch::text("[0:1]", "H")]);
+        assert_eq!(resolver.get_resource_content("doc1").unwrap(), "Hello world");
+    }
+
+    #[tokio::test]
+    async fn test_resolve_sync9_defaults_version_and_parents() {
+        let manager = ResourceStateManager::new();
+        let resolver = ConflictResolver::new(manager);
+
+        let first = Update::snapshot(Version::new("v1"), "ab").with_merge_type("sync9");
+        resolver.resolve_update("doc1", &first, "alice").await.unwrap();
+
+        let next = Update {
+            patches: Some(vec![Patch::text("[2:2]", "c")]),
+            merge_type: Some("sync9".to_string()),
+            ..Default::default()
+        };
+        let resolved = resolver.resolve_update("doc1", &next, "bob").await.unwrap();
+
+        assert!(resolved.version[0].to_string().starts_with("bob-"));
+        assert_eq!(resolved.parents, vec![Version::new("v1")]);
+        assert_eq!(resolver.get_resource_content("doc1").unwrap(), "abc");
+    }
+
+    #[tokio::test]
+    async fn test_resolve_unknown_merge_type() {
+        let manager = ResourceStateManager::new();
+        let resolver = ConflictResolver::new(manager);
+
+        let update = Update::snapshot(Version::new("v1"), "x").with_merge_type("crdt-9000");
+        let err = resolver.resolve_update("doc1", &update, "alice").await.unwrap_err();
+        assert!(err.starts_with("Unknown merge type: crdt-9000"));
+        assert!(resolver.get_resource_content("doc1").is_none());
+    }
+
+    /// Appends every body it receives.
+    #[derive(Clone, Debug, Default)]
+    struct AppendLog {
+        entries: Vec<String>,
+    }
+
+    impl MergeType for AppendLog {
+        fn name(&self) -> &str {
+            "append"
+        }
+
+        fn apply(&mut self, update: &Update, _agent_id: &str) -> crate::Result<Update> {
+            self.entries.push(update.body_str().unwrap_or_default().to_string());
+            Ok(self.snapshot())
+        }
+
+        fn snapshot(&self) -> Update {
+            Update::snapshot(Version::new(self.entries.len().to_string()), self.entries.join("\n"))
+                .with_merge_type("append")
+        }
+
+        fn patches_since(&self, _since: &[Version]) -> crate::Result<Vec<Update>> {
+            Ok(vec![self.snapshot()])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resolve_custom_merge_type() {
+        let merge_types = MergeTypeRegistry::empty();
+        merge_types.register("append", || Box::new(AppendLog::default()));
+        let resolver = ConflictResolver::with_merge_types(ResourceStateManager::new(), merge_types);
+
+        for line in ["first", "second"] {
+            let update = Update::snapshot(Version::new(line), line).with_merge_type("append");
+            resolver.resolve_update("log", &update, "alice").await.unwrap();
+        }
+        assert_eq!(resolver.get_resource_content("log").unwrap(), "first\nsecond");
+        assert_eq!(resolver.get_resource_version("log").unwrap(), Version::new("2"));
+
+        // Built-ins are not available in an empty registry
+        let diamond = Update::snapshot(Version::new("v1"), "hi").with_merge_type("diamond");
+        assert!(resolver.resolve_update("doc", &diamond, "alice").await.is_err());
+    }
+
     #[tokio::test]
     async fn test_concurrent_diamond_merges() {
         let manager = ResourceStateManager::new();
@@ -0,0 +1,708 @@
+//! Serve the files of a directory as Braid text resources.
+//!
+//! A [`BraidDir`] maps each resource ID to a UTF-8 file under its root directory, so
+//! `/docs/intro.md` is `{root}/docs/intro.md`. It's meant for live editing of config
+//! files and docs: editors subscribe and `PUT` changes through Braid, while other
+//! programs keep reading and writing the files directly.
+//!
+//! | Request | Response |
+//! |---------|----------|
+//! | `GET` | The file, with its content hash as `Version` |
+//! | `GET` with `Subscribe` | The file, then a patch each time it changes |
+//! | `PUT` | Merges the update into the file and writes it back |
+//!
+//! # Versions
+//!
+//! The version of a file is a hash of its content (its length and CRC-32), so it can
+//! be computed by anyone holding the file. The history of each file is kept in a
+//! diamond-types document of the directory's [`ResourceStateManager`], and every
+//! content hash served is mapped to the document version it was read or written at.
+//! Edits go through [`ResourceStateManager::apply_and_persist`], so a manager with a
+//! store keeps the history across restarts.
+//!
+//! A `PUT` of patches names the version they were made against in `Parents`
+//! (defaulting to the current version). Patches against an older version are merged
+//! with the changes made since, including changes made on disk, so that no edit is
+//! lost. A `PUT` of a snapshot is turned into the patch from its parent version.
+//!
+//! # Watching
+//!
+//! Changes made on disk are picked up by polling each file that has subscribers,
+//! every second by default (see [`BraidDir::with_poll_interval`]), and before every
+//! request. Each change found is applied to the file's document as the agent `disk`
+//! and sent to subscribers as one patch. Files are replaced atomically when written,
+//! through a hidden temporary file beside them that takes the original's permissions
+//! and, where allowed, its owner.
+//!
+//! Only plain paths are served: resource IDs with `..`, or naming hidden files or
+//! directories, are rejected. Symbolic links are followed, to files under the root
+//! only, and writes go to the file a link points to, leaving the link in place.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use braid_axum_http::server::BraidDir;
+//! use std::time::Duration;
+//!
+//! # async fn run() -> std::io::Result<()> {
+//! let dir = BraidDir::new("/srv/config").with_poll_interval(Duration::from_millis(250));
+//!
+//! let listener = tokio::net::TcpListener::bind("0.0.0.0:8080").await?;
+//! axum::serve(listener, dir.router()).await
+//! # }
+//! ```
+
+use crate::error::{BraidError, Result};
+use crate::merge::text::diff_text;
+use crate::merge::MergeTypeRegistry;
+use crate::protocol::{merge_types, status};
+use crate::server::store::write_atomically;
+use crate::server::{BraidState, ResourceStateManager, SubscriptionResponse};
+use crate::types::{Patch, Update, Version};
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use futures::stream::{self, Stream, StreamExt};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// Default interval between checks of a subscribed file for changes on disk.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Agent ID of changes made to files on disk.
+pub const DISK_AGENT: &str = "disk";
+
+/// Agent ID of `PUT` requests without a `Peer` header
+const DEFAULT_AGENT: &str = "braid-dir";
+
+/// Changes buffered per file before slow subscribers are disconnected
+const CHANNEL_CAPACITY: usize = 64;
+
+/// Serves the UTF-8 files under a directory as Braid text resources.
+///
+/// Cloning is cheap; clones share the files' documents and subscribers.
+#[derive(Clone)]
+pub struct BraidDir {
+    root: PathBuf,
+    manager: ResourceStateManager,
+    merge_types: MergeTypeRegistry,
+    poll_interval: Duration,
+
+    /// Resource ID → its file, once requested
+    files: Arc<Mutex<HashMap<String, Arc<File>>>>,
+}
+
+/// A file being served.
+struct File {
+    resource_id: String,
+
+    /// Held while the file is read, merged into, or written
+    state: tokio::sync::Mutex<FileState>,
+
+    updates: broadcast::Sender<Arc<Update>>,
+
+    /// Whether a task is polling the file for changes
+    watching: AtomicBool,
+}
+
+struct FileState {
+    /// Content as last read or written
+    content: String,
+
+    /// Content hash of `content`
+    version: Version,
+
+    /// Content hash → version of the document with that content
+    versions: HashMap<Version, Vec<Version>>,
+}
+
+impl BraidDir {
+    /// Serve the files under `root`, keeping their history in a new manager.
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - Directory to serve
+    #[must_use]
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self::with_manager(root, ResourceStateManager::new())
+    }
+
+    /// Serve the files under `root`, keeping their history in `manager`.
+    ///
+    /// The document of each file is the manager's text resource with the same ID.
+    #[must_use]
+    pub fn with_manager(root: impl Into<PathBuf>, manager: ResourceStateManager) -> Self {
+        Self {
+            root: root.into(),
+            manager,
+            merge_types: MergeTypeRegistry::new(),
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            files: Arc::default(),
+        }
+    }
+
+    /// Check subscribed files for changes on disk every `interval` (builder style).
+    #[must_use]
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Get the directory served.
+    #[inline]
+    #[must_use]
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Get the manager holding the files' documents.
+    #[inline]
+    #[must_use]
+    pub fn manager(&self) -> &ResourceStateManager {
+        &self.manager
+    }
+
+    /// Get the content hash version of `content`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use braid_axum_http::server::BraidDir;
+    ///
+    /// assert_eq!(BraidDir::content_version(b"hello").to_string(), "5-3610a686");
+    /// ```
+    #[must_use]
+    pub fn content_version(content: &[u8]) -> Version {
+        Version::new(format!("{}-{:08x}", content.len(), crc32fast::hash(content)))
+    }
+
+    /// Get the path of the file of a resource, with symbolic links resolved.
+    ///
+    /// The file and its parent directories don't have to exist yet; the part of the
+    /// path that does is resolved.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`NotFound`](std::io::ErrorKind::NotFound) I/O error if the resource
+    /// ID isn't a plain relative path (it's empty, or has a `..` or hidden component),
+    /// or a symbolic link on the path leads outside the root or to a hidden file.
+    /// Returns other I/O errors if the root or a link can't be resolved.
+    pub fn path(&self, resource_id: &str) -> Result<PathBuf> {
+        let not_served = || {
+            BraidError::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("Not a served file: {}", resource_id),
+            ))
+        };
+        let relative = Path::new(resource_id.trim_start_matches('/'));
+        if !is_plain(relative) || relative.as_os_str().is_empty() {
+            return Err(not_served());
+        }
+
+        let root = std::fs::canonicalize(&self.root)?;
+        let mut path = root.clone();
+        let mut components = relative.components();
+        for component in components.by_ref() {
+            let next = path.join(component);
+            match std::fs::symlink_metadata(&next) {
+                // A dangling link fails here, rather than being written through later
+                Ok(_) => path = std::fs::canonicalize(&next)?,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    path = next;
+                    break;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        path.extend(components);
+
+        match path.strip_prefix(&root) {
+            Ok(resolved) if is_plain(resolved) && !resolved.as_os_str().is_empty() => Ok(path),
+            _ => Err(not_served()),
+        }
+    }
+
+    /// Get the current content of a file, with its version.
+    ///
+    /// Changes made on disk since the file was last read are applied first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the path isn't served, or the file can't be read or isn't
+    /// UTF-8.
+    pub async fn get(&self, resource_id: &str) -> Result<Update> {
+        let file = self.existing_file(resource_id).await?;
+        let mut state = file.state.lock().await;
+        self.sync(&file, &mut state, false).await?;
+        Ok(snapshot(&state))
+    }
+
+    /// Subscribe to a file.
+    ///
+    /// Must be called within a Tokio runtime. The stream starts with the file's
+    /// content, unless `since` is already its version, followed by a patch for each
+    /// change. Changes on disk are polled for while the file has subscribers.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the path isn't served, or the file can't be read or isn't
+    /// UTF-8.
+    pub async fn subscribe(
+        &self,
+        resource_id: &str,
+        since: &[Version],
+    ) -> Result<impl Stream<Item = Result<Update>> + Send + 'static> {
+        let file = self.existing_file(resource_id).await?;
+        let (first, live) = {
+            let mut state = file.state.lock().await;
+            self.sync(&file, &mut state, false).await?;
+            let first = (since != [state.version.clone()]).then(|| snapshot(&state));
+            (first, file.updates.subscribe())
+        };
+        self.watch(&file);
+
+        let live = stream::unfold(live, |mut live| async move {
+            let update = live.recv().await.ok()?;
+            Some((Ok((*update).clone()), live))
+        });
+        Ok(stream::iter(first.map(Ok)).chain(live))
+    }
+
+    /// Merge an update into a file and write it back.
+    ///
+    /// The update's patches, or the patch from its parents to its body, are applied
+    /// at its parents, or at the current version if it has none. Creates the file if
+    /// it doesn't exist.
+    ///
+    /// # Arguments
+    ///
+    /// * `resource_id` - Resource to edit
+    /// * `update` - Text patches or a snapshot
+    /// * `agent_id` - Agent the edit is recorded as
+    ///
+    /// # Returns
+    ///
+    /// The content hash version of the file written.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the path isn't served, the parents are unknown, the
+    /// patches don't apply, or the file can't be written.
+    pub async fn put(&self, resource_id: &str, update: &Update, agent_id: &str) -> Result<Version> {
+        let file = self.file(resource_id).await?;
+        let mut state = file.state.lock().await;
+        self.sync(&file, &mut state, true).await?;
+
+        let parents = if update.parents.is_empty() {
+            vec![state.version.clone()]
+        } else {
+            update.parents.clone()
+        };
+        let mut at = Vec::new();
+        for parent in &parents {
+            let version = state.versions.get(parent).ok_or_else(|| {
+                BraidError::InvalidVersion(format!("Unknown version of {}: {}", resource_id, parent))
+            })?;
+            for v in version {
+                if !at.contains(v) {
+                    at.push(v.clone());
+                }
+            }
+        }
+
+        let patches = match (&update.patches, &update.body) {
+            (Some(patches), _) => patches.clone(),
+            (None, Some(body)) => {
+                let body = std::str::from_utf8(body)
+                    .map_err(|e| BraidError::BodyParse(format!("Invalid UTF-8 body: {}", e)))?;
+                diff_text(&self.content_at(resource_id, &at)?, body).into_iter().collect()
+            }
+            (None, None) => Vec::new(),
+        };
+        let content = self.apply(resource_id, agent_id, at, patches).await?;
+
+        write_atomically(&self.path(resource_id)?, content.as_bytes()).await?;
+        self.record(&file, &mut state, content);
+        Ok(state.version.clone())
+    }
+
+    /// Respond to a request for a file.
+    ///
+    /// # Arguments
+    ///
+    /// * `resource_id` - Resource requested
+    /// * `braid` - Braid headers of the request
+    /// * `body` - Body of a `PUT` request, or `None` for a `GET`
+    pub async fn respond(&self, resource_id: &str, braid: &BraidState, body: Option<Bytes>) -> Response {
+        let result = match body {
+            Some(body) => {
+                let agent_id = braid.peer.clone().unwrap_or_else(|| DEFAULT_AGENT.to_string());
+                match braid.update(body) {
+                    Ok(update) => self
+                        .put(resource_id, &update, &agent_id)
+                        .await
+                        .map(|version| Update {
+                            version: vec![version],
+                            ..Default::default()
+                        }
+                        .into_response()),
+                    Err(e) => Err(e),
+                }
+            }
+            None if braid.subscribe => {
+                let since = braid.parents.clone().unwrap_or_default();
+                self.subscribe(resource_id, &since)
+                    .await
+                    .map(|updates| SubscriptionResponse::new(updates).into_response())
+            }
+            None => self.get(resource_id).await.map(IntoResponse::into_response),
+        };
+        result.unwrap_or_else(|e| error_response(&e))
+    }
+
+    /// Create a router serving every path from the directory.
+    ///
+    /// The path of a request is its resource ID.
+    pub fn router(&self) -> Router {
+        Router::new()
+            .route("/{*path}", get(handle_get).put(handle_put))
+            .with_state(self.clone())
+    }
+
+    /// Get the file of a resource, failing if it doesn't exist on disk.
+    async fn existing_file(&self, resource_id: &str) -> Result<Arc<File>> {
+        tokio::fs::metadata(self.path(resource_id)?).await?;
+        self.file(resource_id).await
+    }
+
+    /// Get the file of a resource, starting from its document's content the first time.
+    ///
+    /// The document is loaded from the manager's store if it isn't in memory yet.
+    async fn file(&self, resource_id: &str) -> Result<Arc<File>> {
+        self.path(resource_id)?;
+        self.manager
+            .load_resource(resource_id, &self.merge_types)
+            .await
+            .map_err(BraidError::Storage)?;
+        let file = self
+            .files
+            .lock()
+            .entry(resource_id.to_string())
+            .or_insert_with(|| {
+                let (content, at) = self
+                    .manager
+                    .get_resource(resource_id)
+                    .and_then(|resource| {
+                        let resource = resource.read();
+                        resource.crdt().map(|crdt| (crdt.content(), crdt.version()))
+                    })
+                    .unwrap_or_default();
+                let version = Self::content_version(content.as_bytes());
+                Arc::new(File {
+                    resource_id: resource_id.to_string(),
+                    state: tokio::sync::Mutex::new(FileState {
+                        content,
+                        version: version.clone(),
+                        versions: HashMap::from([(version, at)]),
+                    }),
+                    updates: broadcast::channel(CHANNEL_CAPACITY).0,
+                    watching: AtomicBool::new(false),
+                })
+            })
+            .clone();
+        Ok(file)
+    }
+
+    /// Apply the changes made to a file on disk since it was last read.
+    ///
+    /// A missing file is read as empty if `allow_missing` is set.
+    async fn sync(&self, file: &File, state: &mut FileState, allow_missing: bool) -> Result<()> {
+        // Resolved each time, in case a link was added or changed since
+        let data = match tokio::fs::read(self.path(&file.resource_id)?).await {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && allow_missing => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let disk = String::from_utf8(data)?;
+        if let Some(patch) = diff_text(&state.content, &disk) {
+            // No parents: the change is made against the document's current version
+            self.apply(&file.resource_id, DISK_AGENT, Vec::new(), vec![patch]).await?;
+        }
+        self.record(file, state, disk);
+        Ok(())
+    }
+
+    /// Get the text of a file's document at `at`; a document that doesn't exist yet is
+    /// empty.
+    fn content_at(&self, resource_id: &str, at: &[Version]) -> Result<String> {
+        let Some(resource) = self.manager.get_resource(resource_id) else {
+            return Ok(String::new());
+        };
+        let resource = resource.read();
+        match resource.crdt() {
+            Some(crdt) => crdt.content_at(at),
+            None => Err(BraidError::InvalidPatch(format!(
+                "Resource {} is not a text resource",
+                resource_id
+            ))),
+        }
+    }
+
+    /// Apply text patches to a file's document at `at`, through the manager so that
+    /// they're persisted if it has a store.
+    ///
+    /// # Returns
+    ///
+    /// The document's text after the patches.
+    async fn apply(
+        &self,
+        resource_id: &str,
+        agent_id: &str,
+        at: Vec<Version>,
+        patches: Vec<Patch>,
+    ) -> Result<String> {
+        if !patches.is_empty() {
+            let update = Update {
+                patches: Some(patches),
+                parents: at,
+                merge_type: Some(merge_types::DIAMOND.to_string()),
+                ..Default::default()
+            };
+            self.manager
+                .persist_update(resource_id, &self.merge_types, &update, agent_id, None)
+                .await
+                .map_err(|rejection| match rejection.status {
+                    status::INTERNAL_SERVER_ERROR => BraidError::Storage(rejection.message),
+                    _ => BraidError::InvalidPatch(rejection.message),
+                })?;
+        }
+        let content = self
+            .manager
+            .get_resource(resource_id)
+            .map(|resource| resource.read().content())
+            .unwrap_or_default();
+        Ok(String::from_utf8(content.to_vec())?)
+    }
+
+    /// Record a file's new content, and send the change to subscribers.
+    fn record(&self, file: &File, state: &mut FileState, content: String) {
+        let Some(patch) = diff_text(&state.content, &content) else {
+            return;
+        };
+        let version = Self::content_version(content.as_bytes());
+        let at = self
+            .manager
+            .get_resource(&file.resource_id)
+            .map(|resource| resource.read().version())
+            .unwrap_or_default();
+        let update = Update::patched(version.clone(), vec![patch]).with_parent(state.version.clone());
+        state.versions.insert(version.clone(), at);
+        state.version = version;
+        state.content = content;
+        let _ = file.updates.send(Arc::new(update));
+    }
+
+    /// Poll a file for changes on disk until it has no subscribers.
+    fn watch(&self, file: &Arc<File>) {
+        if file.watching.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let dir = self.clone();
+        let file = Arc::clone(file);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(dir.poll_interval);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                let mut state = file.state.lock().await;
+                if file.updates.receiver_count() == 0 {
+                    // Checked with the state locked, so a new subscriber restarts polling
+                    file.watching.store(false, Ordering::SeqCst);
+                    return;
+                }
+                if let Err(e) = dir.sync(&file, &mut state, false).await {
+                    tracing::warn!("Failed to read {}: {}", file.resource_id, e);
+                }
+            }
+        });
+    }
+}
+
+impl fmt::Debug for BraidDir {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BraidDir")
+            .field("root", &self.root)
+            .field("poll_interval", &self.poll_interval)
+            .field("files", &self.files.lock().len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Snapshot of a file's current content.
+fn snapshot(state: &FileState) -> Update {
+    Update::snapshot(state.version.clone(), state.content.clone())
+}
+
+/// Whether every component of `path` is a plain, visible name.
+fn is_plain(path: &Path) -> bool {
+    path.components().all(|component| match component {
+        Component::Normal(name) => !name.to_string_lossy().starts_with('.'),
+        _ => false,
+    })
+}
+
+/// Respond with the status of an error and its message.
+fn error_response(error: &BraidError) -> Response {
+    let status = match error {
+        BraidError::Io(e) if e.kind() == std::io::ErrorKind::NotFound => status::NOT_FOUND,
+        BraidError::Io(_) | BraidError::Storage(_) => status::INTERNAL_SERVER_ERROR,
+        BraidError::InvalidVersion(_) => status::NOT_FOUND,
+        _ => status::BAD_REQUEST,
+    };
+    Update {
+        status,
+        body: Some(error.to_string().into()),
+        ..Default::default()
+    }
+    .into_response()
+}
+
+async fn handle_get(State(dir): State<BraidDir>, uri: Uri, headers: HeaderMap) -> Response {
+    dir.respond(uri.path(), &BraidState::from_headers(&headers), None).await
+}
+
+async fn handle_put(State(dir): State<BraidDir>, uri: Uri, headers: HeaderMap, body: Bytes) -> Response {
+    dir.respond(uri.path(), &BraidState::from_headers(&headers), Some(body))
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_rejects_escapes_and_hidden_files() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = BraidDir::new(root.path());
+        let canonical = root.path().canonicalize().unwrap();
+        assert_eq!(dir.path("/docs/intro.md").unwrap(), canonical.join("docs/intro.md"));
+        assert!(dir.path("/../etc/passwd").is_err());
+        assert!(dir.path("/docs/../../x").is_err());
+        assert!(dir.path("/.git/config").is_err());
+        assert!(dir.path("/docs/.intro.md.braid-tmp").is_err());
+        assert!(dir.path("/").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_put_merges_with_changes_on_disk() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::write(root.path().join("notes.txt"), "hello").unwrap();
+        let dir = BraidDir::new(root.path());
+
+        let original = dir.get("/notes.txt").await.unwrap();
+        assert_eq!(original.body_str(), Some("hello"));
+        assert_eq!(original.version, vec![BraidDir::content_version(b"hello")]);
+
+        // Edited on disk, then through Braid against the original
+        std::fs::write(root.path().join("notes.txt"), ">> hello").unwrap();
+        let update = Update::patched(Version::new("ignored"), vec![Patch::text("[5:5]", " world")])
+            .with_parents(original.version.clone());
+        let version = dir.put("/notes.txt", &update, "alice").await.unwrap();
+
+        let written = std::fs::read_to_string(root.path().join("notes.txt")).unwrap();
+        assert_eq!(written, ">> hello world");
+        assert_eq!(version, BraidDir::content_version(written.as_bytes()));
+        assert_eq!(dir.get("/notes.txt").await.unwrap().body_str(), Some(">> hello world"));
+        assert!(!root.path().join(".notes.txt.braid-tmp").exists());
+    }
+
+    #[tokio::test]
+    async fn test_put_snapshot_creates_file() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = BraidDir::new(root.path());
+        assert!(matches!(dir.get("/new.txt").await, Err(BraidError::Io(_))));
+
+        let update = Update::snapshot(Version::new("ignored"), "created");
+        dir.put("/new.txt", &update, "alice").await.unwrap();
+        assert_eq!(std::fs::read_to_string(root.path().join("new.txt")).unwrap(), "created");
+        assert_eq!(dir.get("/new.txt").await.unwrap().body_str(), Some("created"));
+
+        let unknown = update.clone().with_parent(Version::new("3-deadbeef"));
+        assert!(matches!(
+            dir.put("/new.txt", &unknown, "alice").await,
+            Err(BraidError::InvalidVersion(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_edits_are_persisted_by_a_manager_with_a_store() {
+        use crate::server::store::MemoryStore;
+
+        let root = tempfile::tempdir().unwrap();
+        std::fs::write(root.path().join("notes.txt"), "hello").unwrap();
+        let store = Arc::new(MemoryStore::new());
+        let dir = BraidDir::with_manager(root.path(), ResourceStateManager::with_store(store.clone()));
+
+        dir.get("/notes.txt").await.unwrap();
+        let update = Update::patched(Version::new("ignored"), vec![Patch::text("[5:5]", " world")]);
+        dir.put("/notes.txt", &update, "alice").await.unwrap();
+
+        // A restarted directory picks up the history, and doesn't re-read the file as new
+        let restarted = BraidDir::with_manager(root.path(), ResourceStateManager::with_store(store));
+        assert_eq!(restarted.get("/notes.txt").await.unwrap().body_str(), Some("hello world"));
+        let resource = restarted.manager().get_resource("/notes.txt").unwrap();
+        assert_eq!(resource.read().content(), "hello world");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_path_follows_links_under_the_root_only() {
+        use std::os::unix::fs::symlink;
+
+        let outside = tempfile::tempdir().unwrap();
+        let root = tempfile::tempdir().unwrap();
+        std::fs::create_dir(root.path().join("docs")).unwrap();
+        std::fs::write(root.path().join("docs/intro.md"), "hi").unwrap();
+        symlink(root.path().join("docs/intro.md"), root.path().join("readme.md")).unwrap();
+        symlink(outside.path(), root.path().join("escape")).unwrap();
+        symlink(root.path().join("docs/.secret"), root.path().join("hidden")).unwrap();
+        symlink(root.path().join("gone"), root.path().join("dangling")).unwrap();
+        let dir = BraidDir::new(root.path());
+
+        let target = root.path().join("docs/intro.md").canonicalize().unwrap();
+        assert_eq!(dir.path("/readme.md").unwrap(), target);
+        assert!(dir.path("/escape/passwd").is_err());
+        assert!(dir.path("/hidden").is_err());
+        assert!(dir.path("/dangling").is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_put_keeps_permissions_and_writes_through_links() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let root = tempfile::tempdir().unwrap();
+        let target = root.path().join("config.toml");
+        std::fs::write(&target, "a = 1").unwrap();
+        std::fs::set_permissions(&target, std::fs::Permissions::from_mode(0o640)).unwrap();
+        symlink(&target, root.path().join("link.toml")).unwrap();
+        let dir = BraidDir::new(root.path());
+
+        let update = Update::snapshot(Version::new("ignored"), "a = 2");
+        dir.put("/link.toml", &update, "alice").await.unwrap();
+
+        assert!(std::fs::symlink_metadata(root.path().join("link.toml")).unwrap().is_symlink());
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "a = 2");
+        let mode = std::fs::metadata(&target).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+    }
+}
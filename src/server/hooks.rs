@@ -0,0 +1,322 @@
+//! Change hooks for reacting to resource updates.
+//!
+//! A [`ChangeObserver`] registered with
+//! [`ResourceStateManager::add_observer`](crate::server::ResourceStateManager::add_observer)
+//! is called with a [`ResourceChange`] after every update the manager applies, whether
+//! it came from an HTTP handler, a replicator, or application code. Typical observers
+//! reindex content, trigger builds, or write an audit log.
+//!
+//! # Delivery
+//!
+//! | Guarantee | |
+//! |-----------|---|
+//! | Ordering | Changes to one resource are delivered in the order they were applied, each to every observer before the next |
+//! | Isolation | Observers run in background tasks; a slow, failing or panicking observer never blocks or fails a merge |
+//! | Concurrency | Changes to different resources are delivered concurrently |
+//!
+//! Observers run on the Tokio runtime the change was made on, or else the one that
+//! was current when the first observer was added.
+//!
+//! # Examples
+//!
+//! ```
+//! use braid_axum_http::server::{ResourceChange, ResourceStateManager};
+//! use std::sync::Arc;
+//!
+//! # tokio_test::block_on(async {
+//! let manager = ResourceStateManager::new();
+//! manager.add_observer(Arc::new(|change: ResourceChange| async move {
+//!     println!("{} is now at {:?}", change.resource_id, change.version);
+//!     Ok(())
+//! }));
+//!
+//! manager.apply_update("doc", "hello", "alice").unwrap();
+//! manager.observers_idle().await;
+//! # });
+//! ```
+
+use crate::types::{Update, Version};
+use async_trait::async_trait;
+use futures::FutureExt;
+use parking_lot::{Mutex, RwLock};
+use std::collections::HashMap;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::runtime::Handle;
+use tokio::sync::{mpsc, Notify};
+
+/// An update applied to a resource.
+#[derive(Clone, Debug)]
+pub struct ResourceChange {
+    /// Resource the update was applied to
+    pub resource_id: String,
+
+    /// The update as merged, in the form broadcast to subscribers
+    pub update: Update,
+
+    /// Agent or peer the update came from
+    pub agent_id: String,
+
+    /// Version of the resource after the update
+    pub version: Vec<Version>,
+}
+
+/// Receives the changes made to resources.
+///
+/// Closures taking a [`ResourceChange`] and returning a future of
+/// `Result<(), String>` implement this trait.
+#[async_trait]
+pub trait ChangeObserver: Send + Sync {
+    /// Handle a change.
+    ///
+    /// # Errors
+    ///
+    /// An error is logged and otherwise ignored: the change is still delivered to
+    /// the other observers, and later changes to this one.
+    async fn on_change(&self, change: &ResourceChange) -> Result<(), String>;
+}
+
+#[async_trait]
+impl<F, Fut> ChangeObserver for F
+where
+    F: Fn(ResourceChange) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<(), String>> + Send,
+{
+    async fn on_change(&self, change: &ResourceChange) -> Result<(), String> {
+        self(change.clone()).await
+    }
+}
+
+/// The observers of a [`ResourceStateManager`](crate::server::ResourceStateManager)
+/// and their per-resource delivery queues.
+#[derive(Default)]
+pub(crate) struct ChangeHooks {
+    observers: RwLock<Vec<Arc<dyn ChangeObserver>>>,
+
+    /// Resource ID → queue of the task delivering its changes, while one runs
+    queues: Mutex<HashMap<String, mpsc::UnboundedSender<ResourceChange>>>,
+
+    /// Runtime to deliver on when a change is made outside of one
+    runtime: Mutex<Option<Handle>>,
+
+    /// Changes queued but not yet delivered to every observer
+    pending: AtomicUsize,
+    idle: Notify,
+}
+
+impl ChangeHooks {
+    pub(crate) fn add(&self, observer: Arc<dyn ChangeObserver>) {
+        if let Ok(handle) = Handle::try_current() {
+            self.runtime.lock().get_or_insert(handle);
+        }
+        self.observers.write().push(observer);
+    }
+
+    /// Unregister `observer`, returning whether it was registered.
+    pub(crate) fn remove(&self, observer: &Arc<dyn ChangeObserver>) -> bool {
+        let mut observers = self.observers.write();
+        let count = observers.len();
+        observers.retain(|o| !Arc::ptr_eq(o, observer));
+        observers.len() < count
+    }
+
+    /// Whether any observers are registered, so that changes need to be built.
+    pub(crate) fn is_active(&self) -> bool {
+        !self.observers.read().is_empty()
+    }
+
+    /// Queue a change for delivery.
+    ///
+    /// Call while holding the resource's lock, so that the queue order is the order
+    /// the resource's updates were applied in.
+    pub(crate) fn notify(self: &Arc<Self>, change: ResourceChange) {
+        let Some(handle) = Handle::try_current()
+            .ok()
+            .or_else(|| self.runtime.lock().clone())
+        else {
+            tracing::warn!(
+                "Dropping change to {}: no Tokio runtime to run observers on",
+                change.resource_id
+            );
+            return;
+        };
+
+        let mut queues = self.queues.lock();
+        self.pending.fetch_add(1, Ordering::SeqCst);
+        let change = match queues.get(&change.resource_id) {
+            Some(queue) => match queue.send(change) {
+                Ok(()) => return,
+                // The delivering task was stopped with its runtime; start another
+                Err(mpsc::error::SendError(change)) => change,
+            },
+            None => change,
+        };
+
+        let (queue, changes) = mpsc::unbounded_channel();
+        let resource_id = change.resource_id.clone();
+        let _ = queue.send(change);
+        queues.insert(resource_id.clone(), queue);
+        handle.spawn(Arc::clone(self).deliver(resource_id, changes));
+    }
+
+    /// Deliver a resource's queued changes, exiting once the queue is empty.
+    async fn deliver(
+        self: Arc<Self>,
+        resource_id: String,
+        mut changes: mpsc::UnboundedReceiver<ResourceChange>,
+    ) {
+        loop {
+            let change = match changes.try_recv() {
+                Ok(change) => change,
+                Err(_) => {
+                    // Checked again under the lock, so no change is queued after we exit
+                    let mut queues = self.queues.lock();
+                    match changes.try_recv() {
+                        Ok(change) => change,
+                        Err(_) => {
+                            queues.remove(&resource_id);
+                            return;
+                        }
+                    }
+                }
+            };
+
+            let observers = self.observers.read().clone();
+            for observer in observers {
+                match AssertUnwindSafe(observer.on_change(&change)).catch_unwind().await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => tracing::warn!("Observer of {} failed: {}", resource_id, e),
+                    Err(_) => tracing::warn!("Observer of {} panicked", resource_id),
+                }
+            }
+            if self.pending.fetch_sub(1, Ordering::SeqCst) == 1 {
+                self.idle.notify_waiters();
+            }
+        }
+    }
+
+    /// Wait until every queued change has been delivered.
+    pub(crate) async fn idle(&self) {
+        loop {
+            let idle = self.idle.notified();
+            if self.pending.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+            idle.await;
+        }
+    }
+}
+
+impl std::fmt::Debug for ChangeHooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChangeHooks")
+            .field("observers", &self.observers.read().len())
+            .field("pending", &self.pending.load(Ordering::SeqCst))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merge::MergeTypeRegistry;
+    use crate::server::ResourceStateManager;
+    use crate::types::Patch;
+    use std::time::Duration;
+
+    fn recorder() -> (Arc<dyn ChangeObserver>, Arc<Mutex<Vec<ResourceChange>>>) {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&seen);
+        let observer = move |change: ResourceChange| {
+            let log = Arc::clone(&log);
+            async move {
+                // Deliver the first change slowest, so that reordering would show
+                if log.lock().is_empty() {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                }
+                log.lock().push(change);
+                Ok(())
+            }
+        };
+        (Arc::new(observer), seen)
+    }
+
+    #[tokio::test]
+    async fn test_changes_delivered_in_order_per_resource() {
+        let manager = ResourceStateManager::new();
+        let (observer, seen) = recorder();
+        manager.add_observer(observer);
+
+        let merge_types = MergeTypeRegistry::new();
+        let mut parents = Vec::new();
+        for (i, word) in ["a", "b", "c"].iter().enumerate() {
+            let update = Update::patched(Version::new("_"), vec![Patch::text(format!("[{i}:{i}]"), *word)])
+                .with_parents(parents)
+                .with_merge_type("diamond");
+            parents = manager
+                .apply_merge_update("doc", &merge_types, &update, "alice")
+                .unwrap()
+                .version;
+        }
+        manager.apply_update("notes", "hi", "bob").unwrap();
+        manager.observers_idle().await;
+
+        let seen = seen.lock();
+        let doc: Vec<_> = seen.iter().filter(|c| c.resource_id == "doc").collect();
+        let bodies: Vec<_> = doc
+            .iter()
+            .map(|c| c.update.patches.as_ref().unwrap()[0].content_str().unwrap().to_string())
+            .collect();
+        assert_eq!(bodies, vec!["a", "b", "c"]);
+        assert!(doc.iter().all(|c| c.agent_id == "alice"));
+        assert_eq!(doc[2].version, parents);
+
+        let notes = seen.iter().find(|c| c.resource_id == "notes").unwrap();
+        assert_eq!(notes.agent_id, "bob");
+        assert_eq!(notes.version, manager.get_resource("notes").unwrap().read().version());
+    }
+
+    #[tokio::test]
+    async fn test_failing_observers_dont_block_merging() {
+        let manager = ResourceStateManager::new();
+        manager.add_observer(Arc::new(|_: ResourceChange| async { Err("index offline".to_string()) }));
+        manager.add_observer(Arc::new(|_: ResourceChange| async { panic!("observer bug") }));
+        let (observer, seen) = recorder();
+        manager.add_observer(observer);
+
+        manager.apply_update("doc", "one", "alice").unwrap();
+        manager.apply_update("doc", "two ", "alice").unwrap();
+        manager.observers_idle().await;
+
+        assert_eq!(manager.get_resource("doc").unwrap().read().crdt().unwrap().content(), "two one");
+        assert_eq!(seen.lock().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_removed_observers_get_no_more_changes() {
+        let manager = ResourceStateManager::new();
+        let (observer, seen) = recorder();
+        manager.add_observer(Arc::clone(&observer));
+        manager.apply_update("doc", "one", "alice").unwrap();
+        manager.observers_idle().await;
+
+        assert!(manager.remove_observer(&observer));
+        assert!(!manager.remove_observer(&observer));
+        manager.apply_update("doc", "two ", "alice").unwrap();
+        manager.observers_idle().await;
+        assert_eq!(seen.lock().len(), 1);
+    }
+
+    #[test]
+    fn test_changes_outside_a_runtime_dont_fail() {
+        let manager = ResourceStateManager::new();
+        manager.apply_update("doc", "hello", "alice").unwrap();
+
+        // With nowhere to run the observer, the change is only logged
+        manager.add_observer(Arc::new(|_: ResourceChange| async { Ok(()) }));
+        manager.apply_update("doc", "why ", "alice").unwrap();
+        assert_eq!(manager.get_resource("doc").unwrap().read().crdt().unwrap().content(), "why hello");
+    }
+}
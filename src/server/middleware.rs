@@ -27,7 +27,7 @@
 //! See draft-toomim-httpbis-braid-http sections 2, 3, and 4 for protocol details.
 
 use crate::protocol::{self, constants::headers};
-use crate::types::Version;
+use crate::types::{Patch, Update, Version};
 use axum::{
     middleware::Next,
     response::Response,
@@ -36,6 +36,8 @@ use axum::{
 use std::sync::Arc;
 use std::collections::BTreeMap;
 use super::resource_state::ResourceStateManager;
+use super::conflict_resolver::ConflictResolver;
+use crate::merge::{MergeType, MergeTypeRegistry};
 
 /// Braid protocol state extracted from HTTP request headers.
 ///
@@ -146,6 +148,61 @@ impl BraidState {
 
         braid_state
     }
+
+    /// Check whether the request asks for the authorship of a resource instead of its
+    /// content, with `Accept: application/blame+json`.
+    ///
+    /// See [`blame`](crate::server::blame).
+    #[must_use]
+    pub fn wants_blame(&self) -> bool {
+        self.headers
+            .get("accept")
+            .is_some_and(|accept| accept.split(',').any(|media| {
+                media.split(';').next().unwrap_or_default().trim() == super::blame::CONTENT_TYPE
+            }))
+    }
+
+    /// Build the update a request sends, from its Braid headers and `body`.
+    ///
+    /// | Request | Update |
+    /// |---------|--------|
+    /// | `Patches: N` | The N patches of the multi-patch body |
+    /// | `Content-Range` | The body as a single patch |
+    /// | Neither | The body as a snapshot |
+    ///
+    /// The update carries the request's `Version`, `Parents`, `Merge-Type` and
+    /// `Content-Type`, ready for [`ResourceStateManager::apply_merge_update`] or a
+    /// [`ConflictResolver`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `Patches` header, the multi-patch body, or the
+    /// `Content-Range` header is malformed.
+    pub fn update(&self, body: bytes::Bytes) -> crate::error::Result<Update> {
+        let patches = match (self.headers.get(headers::PATCHES.as_str()), &self.content_range) {
+            (Some(count), _) => {
+                let count = count.trim().parse().map_err(|_| {
+                    crate::error::BraidError::HeaderParse(format!("Invalid patches: {}", count))
+                })?;
+                Some(protocol::parse_patches(count, &body)?)
+            }
+            (None, Some(range)) => {
+                let (unit, range) = protocol::parse_content_range(range)?;
+                Some(vec![Patch::new(unit, range, body.clone())])
+            }
+            (None, None) => None,
+        };
+
+        Ok(Update {
+            version: self.version.clone().unwrap_or_default(),
+            parents: self.parents.clone().unwrap_or_default(),
+            merge_type: self.merge_type.clone(),
+            body: patches.is_none().then_some(body),
+            patches,
+            content_type: self.headers.get(headers::CONTENT_TYPE.as_str()).cloned(),
+            ..Default::default()
+        })
+    }
 }
 
 
@@ -160,6 +217,7 @@ impl BraidState {
 ///
 /// - Extracts Braid protocol headers from requests
 /// - Manages collaborative document state via Diamond-Types CRDT
+/// - Keeps a registry of merge types, extensible with custom algorithms
 /// - Configurable subscription limits and heartbeat intervals
 /// - Thread-safe, shareable across async tasks
 ///
@@ -215,6 +273,9 @@ pub struct BraidLayer {
 
     /// Shared resource state manager (CRDT instances per resource)
     pub resource_manager: Arc<ResourceStateManager>,
+
+    /// Merge types available by name (built-ins plus application-registered)
+    merge_types: MergeTypeRegistry,
 }
 
 impl BraidLayer {
@@ -238,6 +299,7 @@ impl BraidLayer {
         Self {
             config: super::config::ServerConfig::default(),
             resource_manager: Arc::new(ResourceStateManager::new()),
+            merge_types: MergeTypeRegistry::new(),
         }
     }
 
@@ -267,6 +329,7 @@ impl BraidLayer {
         Self {
             config,
             resource_manager: Arc::new(ResourceStateManager::new()),
+            merge_types: MergeTypeRegistry::new(),
         }
     }
 
@@ -281,16 +344,57 @@ impl BraidLayer {
         &self.config
     }
 
+    /// Get the layer's merge-type registry.
+    #[inline]
+    #[must_use]
+    pub fn merge_types(&self) -> &MergeTypeRegistry {
+        &self.merge_types
+    }
+
+    /// Register a custom merge type by name.
+    ///
+    /// Updates whose `Merge-Type` header matches `name` are resolved by documents
+    /// created with `factory`. Registering an existing name replaces it, so the
+    /// built-in `"diamond"` and `"sync9"` types can be overridden. The registry is
+    /// shared by all clones of this layer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use braid_axum_http::BraidLayer;
+    /// use braid_axum_http::merge::Sync9;
+    ///
+    /// let layer = BraidLayer::new();
+    /// layer.register_merge_type("braid-text", || Box::new(Sync9::new()));
+    /// assert!(layer.merge_types().contains("braid-text"));
+    /// ```
+    pub fn register_merge_type<F>(&self, name: impl Into<String>, factory: F)
+    where
+        F: Fn() -> Box<dyn MergeType> + Send + Sync + 'static,
+    {
+        self.merge_types.register(name, factory);
+    }
+
+    /// Create a conflict resolver sharing this layer's resources and merge types.
+    #[must_use]
+    pub fn conflict_resolver(&self) -> ConflictResolver {
+        ConflictResolver::with_merge_types(
+            (*self.resource_manager).clone(),
+            self.merge_types.clone(),
+        )
+    }
+
     /// Create the middleware function for use with Axum.
     ///
     /// Returns a middleware function that extracts Braid protocol information
-    /// from request headers and attaches it (and the resource manager) to
-    /// request extensions.
+    /// from request headers and attaches it, the resource manager, and the
+    /// [`MergeTypeRegistry`] to request extensions. Handlers can extract the
+    /// registry as `Extension<MergeTypeRegistry>` to resolve `Merge-Type` names.
     ///
     /// # Returns
     ///
     /// A middleware function compatible with `Router::layer()`.
-    #[must_use]
+    #[must_use = "the middleware does nothing until it's layered onto a router"]
     pub fn middleware(
         &self,
     ) -> impl Fn(Request, Next) -> std::pin::Pin<Box<dyn std::future::Future<Output = Response> + Send>>
@@ -298,13 +402,16 @@ impl BraidLayer {
              + Sync
              + Clone {
         let resource_manager = self.resource_manager.clone();
+        let merge_types = self.merge_types.clone();
 
         move |mut req: Request, next: Next| {
             let resource_manager = resource_manager.clone();
+            let merge_types = merge_types.clone();
             Box::pin(async move {
                 let braid_state = BraidState::from_headers(req.headers());
                 req.extensions_mut().insert(Arc::new(braid_state));
                 req.extensions_mut().insert(resource_manager);
+                req.extensions_mut().insert(merge_types);
                 next.run(req).await
             })
         }
@@ -337,4 +444,44 @@ mod tests {
             assert_eq!(val, 30);
         }
     }
+
+    #[tokio::test]
+    async fn test_conflict_resolver_uses_layer_registry() {
+        let layer = BraidLayer::new();
+        let resolver = layer.conflict_resolver();
+        let update = crate::Update::snapshot(Version::new("v1"), "x").with_merge_type("custom");
+        assert!(resolver.resolve_update("doc", &update, "alice").await.is_err());
+
+        layer.register_merge_type("custom", || Box::new(crate::merge::Sync9::new()));
+        assert!(resolver.merge_types().contains("custom"));
+        assert!(layer.resource_manager.get_resource("doc").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_middleware_provides_merge_types() {
+        use axum::extract::Extension;
+        use axum::routing::get;
+        use tower::ServiceExt;
+
+        async fn handler(
+            Extension(braid): Extension<Arc<BraidState>>,
+            Extension(merge_types): Extension<MergeTypeRegistry>,
+        ) -> String {
+            let name = braid.merge_type.as_deref().unwrap_or_default();
+            merge_types.contains(name).to_string()
+        }
+
+        let layer = BraidLayer::new();
+        layer.register_merge_type("custom", || Box::new(crate::merge::Sync9::new()));
+        let app = axum::Router::new()
+            .route("/doc", get(handler))
+            .layer(axum::middleware::from_fn(layer.middleware()));
+        let request = axum::http::Request::get("/doc")
+            .header("merge-type", "custom")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"true");
+    }
 }
@@ -18,6 +18,15 @@
 //! ├── parse_update      - ParseUpdateExt trait for requests
 //! ├── config            - ServerConfig options
 //! ├── resource_state    - ResourceStateManager for CRDT state
+//! ├── blame             - Authorship attribution of text resources
+//! ├── dir               - BraidDir serving a directory's files
+//! ├── hooks             - ChangeObserver hooks for applied updates
+//! ├── presence          - Ephemeral presence and cursors per resource
+//! ├── proxy             - BraidProxy fanning out upstream subscriptions
+//! ├── replicator        - Replicator syncing resources from an upstream server
+//! ├── store             - ResourceStore backends for persistence
+//! ├── upstream          - UpstreamConnection settings of the proxy and replicator
+//! ├── validation        - UpdateValidator checks before merging
 //! └── conflict_resolver - ConflictResolver for merging
 //! ```
 //!
@@ -29,7 +38,14 @@
 //! | [`BraidState`] | Extracted Braid request state |
 //! | [`ServerConfig`] | Server configuration options |
 //! | [`ResourceStateManager`] | CRDT-backed resource state |
+//! | [`BraidDir`] | Files of a directory as text resources |
+//! | [`ChangeObserver`] | Callback for applied updates |
+//! | [`Presence`] | Ephemeral presence states of peers |
+//! | [`BraidProxy`] | Fan-out caching proxy of an upstream server |
+//! | [`Replicator`] | Server-to-server replication of resources |
+//! | [`ResourceStore`] | Persistent storage for resources |
 //! | [`ConflictResolver`] | Version conflict resolution |
+//! | [`UpdateValidator`] | Check of updates before they're merged |
 //!
 //! # Examples
 //!
@@ -87,11 +103,11 @@
 //! // Access the resource through the lock
 //! {
 //!     let mut state = resource.write();
-//!     state.crdt.add_insert(0, "Hello");
+//!     state.crdt_mut().unwrap().add_insert(0, "Hello");
 //! }
 //!
 //! // Get current content
-//! let content = resource.read().crdt.content();
+//! let content = resource.read().crdt().unwrap().content();
 //! assert_eq!(content, "Hello");
 //! ```
 //!
@@ -132,18 +148,35 @@ mod middleware;
 mod parse_update;
 mod send_update;
 
+pub mod blame;
 pub mod conflict_resolver;
+pub mod dir;
+pub mod hooks;
+pub mod presence;
+pub mod proxy;
+pub mod replicator;
 pub mod resource_state;
+pub mod store;
+pub mod upstream;
+pub mod validation;
 
 #[cfg(test)]
 mod tests;
 
 pub use config::ServerConfig;
 pub use conflict_resolver::ConflictResolver;
+pub use dir::BraidDir;
+pub use hooks::{ChangeObserver, ResourceChange};
 pub use middleware::{BraidLayer, BraidState};
+pub use presence::{Presence, PresenceStream};
+pub use proxy::BraidProxy;
+pub use replicator::{Replication, Replicator};
 pub use parse_update::ParseUpdateExt;
-pub use resource_state::ResourceStateManager;
-pub use send_update::SendUpdateExt;
+pub use resource_state::{ResourceKind, ResourceState, ResourceStateManager};
+pub use send_update::{SendUpdateExt, SubscriptionResponse};
+pub use store::ResourceStore;
+pub use upstream::UpstreamConnection;
+pub use validation::{Rejection, UpdateValidator};
 
 use crate::types::Update;
 use std::sync::Arc;
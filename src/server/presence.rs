@@ -0,0 +1,585 @@
+//! Ephemeral presence and cursor states per resource.
+//!
+//! Collaborative editors show who else is viewing a resource and where their cursors
+//! are. That data changes constantly and means nothing once a peer leaves, so it's
+//! kept here, beside the resource, and never in its CRDT history: publishing a
+//! presence state creates no version, isn't stored, and isn't seen by
+//! [`ChangeObserver`](crate::server::ChangeObserver)s.
+//!
+//! Each peer, identified by its `Peer` header, publishes a small JSON state per
+//! resource. A subscriber receives presence as updates with the content type
+//! [`CONTENT_TYPE`] and no version, whose body is a JSON object of the peers that
+//! changed:
+//!
+//! ```text
+//! Content-Type: application/presence+json
+//! Content-Length: 34
+//!
+//! {"alice":{"cursor":12},"bob":null}
+//! ```
+//!
+//! A state replaces the peer's previous one, and `null` means the peer left. The first
+//! update of a [`PresenceStream`] holds every peer present, so merging the updates
+//! into an empty object in order always gives the current presence.
+//!
+//! Cursor positions in a state refer to the version of the text the peer last saw.
+//! Publish that version with them, and map them to the current text with
+//! [`DiamondCRDT::transform_position`](crate::merge::DiamondCRDT::transform_position).
+//!
+//! # Expiry
+//!
+//! | Entry of a peer | Removed |
+//! |-----------------|---------|
+//! | With a presence stream open on the resource | When its last stream is dropped, e.g. because the subscription closed |
+//! | Without a stream | When it hasn't been published for the time-to-live (30 seconds by default) |
+//!
+//! # Examples
+//!
+//! ```
+//! use braid_axum_http::server::ResourceStateManager;
+//! use futures::StreamExt;
+//! use serde_json::json;
+//!
+//! # tokio_test::block_on(async {
+//! let manager = ResourceStateManager::new();
+//! let presence = manager.presence();
+//!
+//! // Alice subscribes, holding her entry until the stream is dropped
+//! let mut alice = presence.subscribe("doc", Some("alice"));
+//! assert_eq!(alice.next().await.unwrap().unwrap().body_str(), Some("{}"));
+//!
+//! presence.publish("doc", "bob", json!({"cursor": 3})).unwrap();
+//! let update = alice.next().await.unwrap().unwrap();
+//! assert_eq!(update.body_str(), Some(r#"{"bob":{"cursor":3}}"#));
+//! assert!(update.version.is_empty());
+//! # });
+//! ```
+
+use crate::error::Result;
+use crate::server::Rejection;
+use crate::types::Update;
+use futures::stream::{self, BoxStream};
+use futures::{Stream, StreamExt};
+use parking_lot::Mutex;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::pin::Pin;
+use std::sync::{Arc, Weak};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::runtime::Handle;
+use tokio::sync::broadcast;
+use tokio::time::Instant;
+
+/// Content type of presence updates.
+pub const CONTENT_TYPE: &str = "application/presence+json";
+
+/// Default time a peer without a presence stream stays present after publishing.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(30);
+
+/// Maximum size of a serialized presence state, in bytes.
+pub const MAX_STATE_LEN: usize = 4096;
+
+/// Presence changes buffered per resource before slow streams resynchronize
+const CHANNEL_CAPACITY: usize = 256;
+
+/// A peer's state changing, or `None` when it left.
+type Change = Arc<(String, Option<Value>)>;
+
+/// Presence states of the peers of every resource.
+///
+/// Cloning shares the states, so every clone sees the same peers. Each
+/// [`ResourceStateManager`](crate::server::ResourceStateManager) has one, returned by
+/// its [`presence`](crate::server::ResourceStateManager::presence) method.
+#[derive(Clone)]
+pub struct Presence {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    ttl: Duration,
+
+    /// Resource ID → its peers
+    resources: Mutex<HashMap<String, ResourcePresence>>,
+
+    /// Whether a task is running to expire entries without streams
+    sweeping: Mutex<bool>,
+}
+
+struct ResourcePresence {
+    entries: BTreeMap<String, Entry>,
+
+    /// Peer → number of open streams holding its entry
+    holds: HashMap<String, usize>,
+
+    changes: broadcast::Sender<Change>,
+}
+
+struct Entry {
+    state: Value,
+    published: Instant,
+}
+
+impl ResourcePresence {
+    fn new() -> Self {
+        Self {
+            entries: BTreeMap::new(),
+            holds: HashMap::new(),
+            changes: broadcast::channel(CHANNEL_CAPACITY).0,
+        }
+    }
+
+    fn states(&self) -> BTreeMap<String, Value> {
+        self.entries
+            .iter()
+            .map(|(peer, entry)| (peer.clone(), entry.state.clone()))
+            .collect()
+    }
+
+    fn remove(&mut self, peer: &str) -> bool {
+        let removed = self.entries.remove(peer).is_some();
+        if removed {
+            let _ = self.changes.send(Arc::new((peer.to_string(), None)));
+        }
+        removed
+    }
+
+    /// Remove entries without streams that weren't published within `ttl`, returning
+    /// when the next of the others expires.
+    fn expire(&mut self, ttl: Duration, now: Instant) -> Option<Instant> {
+        let stale: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|(peer, entry)| !self.holds.contains_key(*peer) && entry.published + ttl <= now)
+            .map(|(peer, _)| peer.clone())
+            .collect();
+        for peer in stale {
+            self.remove(&peer);
+        }
+        self.entries
+            .iter()
+            .filter(|(peer, _)| !self.holds.contains_key(*peer))
+            .map(|(_, entry)| entry.published + ttl)
+            .min()
+    }
+
+    fn is_unused(&self) -> bool {
+        self.entries.is_empty() && self.holds.is_empty() && self.changes.receiver_count() == 0
+    }
+}
+
+impl Presence {
+    /// Create an empty set of presence states, expiring after [`DEFAULT_TTL`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_ttl(DEFAULT_TTL)
+    }
+
+    /// Create an empty set of presence states.
+    ///
+    /// # Arguments
+    ///
+    /// * `ttl` - How long a peer without a presence stream stays present after
+    ///   publishing
+    #[must_use]
+    pub fn with_ttl(ttl: Duration) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                ttl,
+                resources: Mutex::new(HashMap::new()),
+                sweeping: Mutex::new(false),
+            }),
+        }
+    }
+
+    /// Get how long a peer without a presence stream stays present after publishing.
+    #[inline]
+    #[must_use]
+    pub fn ttl(&self) -> Duration {
+        self.inner.ttl
+    }
+
+    /// Publish a peer's presence state, replacing its previous one.
+    ///
+    /// # Arguments
+    ///
+    /// * `resource_id` - Resource the peer is present on
+    /// * `peer` - The peer's `Peer` ID
+    /// * `state` - Its state, such as its name and cursor; `null` leaves
+    ///
+    /// # Errors
+    ///
+    /// Returns a 400 [`Rejection`] if the state is over [`MAX_STATE_LEN`] bytes.
+    pub fn publish(&self, resource_id: &str, peer: &str, state: Value) -> std::result::Result<(), Rejection> {
+        if state.is_null() {
+            self.leave(resource_id, peer);
+            return Ok(());
+        }
+        let len = state.to_string().len();
+        if len > MAX_STATE_LEN {
+            return Err(Rejection::bad_request(format!(
+                "Presence state of {} bytes is over the limit of {}",
+                len, MAX_STATE_LEN
+            )));
+        }
+
+        let held = {
+            let mut resources = self.inner.resources.lock();
+            let resource = resources
+                .entry(resource_id.to_string())
+                .or_insert_with(ResourcePresence::new);
+            if resource.entries.get(peer).is_some_and(|entry| entry.state == state) {
+                // Unchanged; only refresh it
+                resource.entries.get_mut(peer).expect("checked").published = Instant::now();
+            } else {
+                resource.entries.insert(
+                    peer.to_string(),
+                    Entry {
+                        state: state.clone(),
+                        published: Instant::now(),
+                    },
+                );
+                let _ = resource.changes.send(Arc::new((peer.to_string(), Some(state))));
+            }
+            resource.holds.contains_key(peer)
+        };
+        if !held {
+            self.start_sweeping();
+        }
+        Ok(())
+    }
+
+    /// Remove a peer's presence state.
+    ///
+    /// # Returns
+    ///
+    /// Whether the peer was present.
+    pub fn leave(&self, resource_id: &str, peer: &str) -> bool {
+        let mut resources = self.inner.resources.lock();
+        let Some(resource) = resources.get_mut(resource_id) else {
+            return false;
+        };
+        let removed = resource.remove(peer);
+        if resource.is_unused() {
+            resources.remove(resource_id);
+        }
+        removed
+    }
+
+    /// Get the presence states of a resource's peers.
+    #[must_use]
+    pub fn peers(&self, resource_id: &str) -> BTreeMap<String, Value> {
+        let mut resources = self.inner.resources.lock();
+        resources
+            .get_mut(resource_id)
+            .map(|resource| {
+                resource.expire(self.inner.ttl, Instant::now());
+                resource.states()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Remove every peer of a resource, ending its presence streams.
+    pub fn clear(&self, resource_id: &str) {
+        if let Some(mut resource) = self.inner.resources.lock().remove(resource_id) {
+            let peers: Vec<String> = resource.entries.keys().cloned().collect();
+            for peer in peers {
+                resource.remove(&peer);
+            }
+        }
+    }
+
+    /// Subscribe to the presence of a resource's peers.
+    ///
+    /// The stream first yields the peers present, then their changes. Combine it with
+    /// the resource's update stream to interleave the two in one subscription:
+    ///
+    /// ```ignore
+    /// let updates = futures::stream::select(document_updates, presence.subscribe(id, peer));
+    /// SubscriptionResponse::new(updates)
+    /// ```
+    ///
+    /// # Arguments
+    ///
+    /// * `resource_id` - Resource to follow
+    /// * `peer` - The subscriber's `Peer` ID; its entry is kept without expiring until
+    ///   the stream is dropped, and then removed
+    #[must_use]
+    pub fn subscribe(&self, resource_id: &str, peer: Option<&str>) -> PresenceStream {
+        let (states, changes) = {
+            let mut resources = self.inner.resources.lock();
+            let resource = resources
+                .entry(resource_id.to_string())
+                .or_insert_with(ResourcePresence::new);
+            if let Some(peer) = peer {
+                *resource.holds.entry(peer.to_string()).or_insert(0) += 1;
+            }
+            resource.expire(self.inner.ttl, Instant::now());
+            (resource.states(), resource.changes.subscribe())
+        };
+
+        let known: BTreeSet<String> = states.keys().cloned().collect();
+        let first = presence_update(states.into_iter().map(|(peer, state)| (peer, Some(state))));
+        let presence = self.clone();
+        let id = resource_id.to_string();
+        let rest = stream::unfold((changes, known), move |(mut changes, mut known)| {
+            let presence = presence.clone();
+            let id = id.clone();
+            async move {
+                let update = match changes.recv().await {
+                    Ok(change) => {
+                        let (peer, state) = &*change;
+                        match state {
+                            Some(_) => known.insert(peer.clone()),
+                            None => known.remove(peer),
+                        };
+                        presence_update([(peer.clone(), state.clone())])
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => {
+                        // Missed changes: send every peer again, and which are gone
+                        let states = presence.peers(&id);
+                        let present: BTreeSet<String> = states.keys().cloned().collect();
+                        let gone: Vec<String> = known.difference(&present).cloned().collect();
+                        known = present;
+                        presence_update(
+                            gone.into_iter()
+                                .map(|peer| (peer, None))
+                                .chain(states.into_iter().map(|(peer, state)| (peer, Some(state)))),
+                        )
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                };
+                Some((Ok(update), (changes, known)))
+            }
+        });
+
+        PresenceStream {
+            updates: stream::once(async move { Ok(first) }).chain(rest).boxed(),
+            hold: peer.map(|peer| Hold {
+                presence: self.clone(),
+                resource_id: resource_id.to_string(),
+                peer: peer.to_string(),
+            }),
+        }
+    }
+
+    /// Start a task expiring entries without streams, unless one is running.
+    fn start_sweeping(&self) {
+        let Ok(handle) = Handle::try_current() else {
+            // Expired entries are still removed whenever the peers are read
+            return;
+        };
+        {
+            let mut sweeping = self.inner.sweeping.lock();
+            if *sweeping {
+                return;
+            }
+            *sweeping = true;
+        }
+        handle.spawn(sweep(Arc::downgrade(&self.inner)));
+    }
+}
+
+/// Expire entries until none without streams remain.
+async fn sweep(inner: Weak<Inner>) {
+    let mut next = Instant::now();
+    loop {
+        tokio::time::sleep_until(next).await;
+        let Some(inner) = inner.upgrade() else {
+            return;
+        };
+        let mut resources = inner.resources.lock();
+        let now = Instant::now();
+        let deadline = resources
+            .values_mut()
+            .filter_map(|resource| resource.expire(inner.ttl, now))
+            .min();
+        resources.retain(|_, resource| !resource.is_unused());
+        match deadline {
+            Some(deadline) => next = deadline,
+            None => {
+                // Decided under the resources lock, so a publish after this starts a new task
+                *inner.sweeping.lock() = false;
+                return;
+            }
+        }
+    }
+}
+
+/// Build a presence update from peers' states.
+fn presence_update(states: impl IntoIterator<Item = (String, Option<Value>)>) -> Update {
+    let body: Map<String, Value> = states
+        .into_iter()
+        .map(|(peer, state)| (peer, state.unwrap_or(Value::Null)))
+        .collect();
+    Update {
+        body: Some(Value::Object(body).to_string().into()),
+        content_type: Some(CONTENT_TYPE.to_string()),
+        ..Default::default()
+    }
+}
+
+impl Default for Presence {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Presence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Presence")
+            .field("ttl", &self.inner.ttl)
+            .field("resources", &self.inner.resources.lock().len())
+            .finish()
+    }
+}
+
+/// Presence updates of a resource, from [`Presence::subscribe`].
+///
+/// Dropping the stream releases the subscriber's entry, removing it once no other
+/// stream of the same peer holds it.
+pub struct PresenceStream {
+    updates: BoxStream<'static, Result<Update>>,
+    hold: Option<Hold>,
+}
+
+impl Stream for PresenceStream {
+    type Item = Result<Update>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.updates.poll_next_unpin(cx)
+    }
+}
+
+impl fmt::Debug for PresenceStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PresenceStream")
+            .field("peer", &self.hold.as_ref().map(|hold| &hold.peer))
+            .finish()
+    }
+}
+
+/// A stream's hold on its peer's entry.
+struct Hold {
+    presence: Presence,
+    resource_id: String,
+    peer: String,
+}
+
+impl Drop for Hold {
+    fn drop(&mut self) {
+        let mut resources = self.presence.inner.resources.lock();
+        let Some(resource) = resources.get_mut(&self.resource_id) else {
+            return;
+        };
+        if let Some(holds) = resource.holds.get_mut(&self.peer) {
+            *holds -= 1;
+            if *holds == 0 {
+                resource.holds.remove(&self.peer);
+                resource.remove(&self.peer);
+            }
+        }
+        if resource.is_unused() {
+            resources.remove(&self.resource_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    async fn next_body(stream: &mut PresenceStream) -> Value {
+        let update = stream.next().await.unwrap().unwrap();
+        assert_eq!(update.content_type.as_deref(), Some(CONTENT_TYPE));
+        serde_json::from_str(update.body_str().unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_stream_starts_with_peers_then_follows_changes() {
+        let presence = Presence::new();
+        presence.publish("doc", "bob", json!({"cursor": 1})).unwrap();
+        presence.publish("other", "carol", json!({"cursor": 9})).unwrap();
+
+        let mut alice = presence.subscribe("doc", Some("alice"));
+        assert_eq!(next_body(&mut alice).await, json!({"bob": {"cursor": 1}}));
+
+        presence.publish("doc", "alice", json!({"cursor": 5})).unwrap();
+        assert_eq!(next_body(&mut alice).await, json!({"alice": {"cursor": 5}}));
+
+        // Republishing the same state only keeps it alive
+        presence.publish("doc", "bob", json!({"cursor": 1})).unwrap();
+        assert!(presence.leave("doc", "bob"));
+        assert_eq!(next_body(&mut alice).await, json!({"bob": null}));
+
+        assert_eq!(presence.peers("doc"), BTreeMap::from([("alice".to_string(), json!({"cursor": 5}))]));
+    }
+
+    #[tokio::test]
+    async fn test_dropping_the_last_stream_removes_the_peer() {
+        let presence = Presence::new();
+        let mut watcher = presence.subscribe("doc", None);
+        next_body(&mut watcher).await;
+
+        let first = presence.subscribe("doc", Some("alice"));
+        let second = presence.subscribe("doc", Some("alice"));
+        presence.publish("doc", "alice", json!({"cursor": 2})).unwrap();
+        next_body(&mut watcher).await;
+
+        drop(first);
+        assert!(presence.peers("doc").contains_key("alice"));
+        drop(second);
+        assert!(presence.peers("doc").is_empty());
+        assert_eq!(next_body(&mut watcher).await, json!({"alice": null}));
+    }
+
+    #[tokio::test]
+    async fn test_peers_without_streams_expire() {
+        let presence = Presence::with_ttl(Duration::from_millis(200));
+        let mut watcher = presence.subscribe("doc", None);
+        next_body(&mut watcher).await;
+        let _held = presence.subscribe("doc", Some("alice"));
+        presence.publish("doc", "alice", json!({"cursor": 0})).unwrap();
+        presence.publish("doc", "bob", json!({"cursor": 0})).unwrap();
+        next_body(&mut watcher).await;
+        next_body(&mut watcher).await;
+
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        presence.publish("doc", "bob", json!({"cursor": 0})).unwrap();
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        assert_eq!(presence.peers("doc").len(), 2);
+
+        // Bob's refresh expires; Alice is held by her stream
+        assert_eq!(next_body(&mut watcher).await, json!({"bob": null}));
+        assert_eq!(presence.peers("doc").keys().collect::<Vec<_>>(), vec!["alice"]);
+    }
+
+    #[tokio::test]
+    async fn test_lagging_stream_resynchronizes() {
+        let presence = Presence::new();
+        presence.publish("doc", "gone", json!(true)).unwrap();
+        let mut watcher = presence.subscribe("doc", None);
+        assert_eq!(next_body(&mut watcher).await, json!({"gone": true}));
+
+        presence.leave("doc", "gone");
+        for i in 0..=CHANNEL_CAPACITY {
+            presence.publish("doc", "bob", json!({"cursor": i})).unwrap();
+        }
+        assert_eq!(
+            next_body(&mut watcher).await,
+            json!({"gone": null, "bob": {"cursor": CHANNEL_CAPACITY}})
+        );
+    }
+
+    #[test]
+    fn test_large_states_are_rejected() {
+        let presence = Presence::new();
+        let rejection = presence
+            .publish("doc", "alice", json!("x".repeat(MAX_STATE_LEN)))
+            .unwrap_err();
+        assert_eq!(rejection.status, 400);
+        assert!(presence.peers("doc").is_empty());
+    }
+}
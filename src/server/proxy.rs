@@ -0,0 +1,611 @@
+//! Fan-out caching proxy for Braid subscriptions.
+//!
+//! Many clients subscribing to the same resource of an upstream server shouldn't each
+//! hold a connection to it. A [`BraidProxy`] subscribes upstream once per resource and
+//! serves every downstream subscriber of that resource from the one subscription:
+//!
+//! 1. The first downstream subscriber of a resource starts an upstream subscription,
+//!    which reconnects with exponential backoff (see [`upstream`](crate::server::upstream)),
+//!    resuming from the latest version seen
+//! 2. Each upstream update is recorded in the resource's cache, then sent to every
+//!    downstream subscriber
+//! 3. A new downstream subscriber first gets what it's missing from its `Parents`
+//! 4. When the last downstream subscriber leaves, the upstream subscription and the
+//!    cache of the resource are dropped
+//!
+//! # Cache
+//!
+//! Each resource keeps its latest version, the most recent updates (100 by default,
+//! see [`BraidProxy::with_history_limit`]), and a snapshot of its content. The
+//! snapshot follows the updates received: a snapshot update replaces it, and `text`
+//! and `bytes` patches whose `Parents` are the snapshot's version are applied to it.
+//! Other patches, including concurrent edits that only the merge type can combine,
+//! leave the proxy without a snapshot until the next snapshot update; plain `GET`
+//! requests are forwarded upstream meanwhile.
+//!
+//! A downstream subscriber catches up with:
+//!
+//! | `Parents` of the subscriber | Catch-up |
+//! |-----------------------------|----------|
+//! | The latest version | Nothing |
+//! | Versions in the cached history | The cached updates it's missing, in order |
+//! | None or unknown, with a snapshot | The snapshot |
+//! | None or unknown, with history back to the first version | Every cached update |
+//! | Otherwise | `410 Gone` |
+//!
+//! Updates without a version, like presence, are sent to current subscribers only.
+//! A subscriber too slow to keep up with live updates is disconnected, and catches
+//! up from its `Parents` when it subscribes again.
+//!
+//! # Examples
+//!
+//! ```
+//! use braid_axum_http::server::{BraidProxy, UpstreamConnection};
+//!
+//! let proxy = BraidProxy::new("http://upstream.example")
+//!     .with_peer("edge-1")
+//!     .with_history_limit(500);
+//! assert_eq!(proxy.url("/notes/today"), "http://upstream.example/notes/today");
+//!
+//! // Serve every path from upstream
+//! let app: axum::Router = proxy.router();
+//! ```
+//!
+//! Plain `GET` requests are answered from the cached snapshot while the resource has
+//! subscribers, and forwarded upstream otherwise. Writes aren't proxied: clients send
+//! them upstream directly, and get them back through their subscriptions.
+
+use crate::error::{BraidError, Result};
+use crate::merge::merge_type::same_versions;
+use crate::merge::apply_byte_patches;
+use crate::merge::text::apply_text_patches;
+use crate::protocol::status;
+use crate::server::upstream::{Upstream, UpstreamConnection};
+use crate::server::{presence, BraidState, SubscriptionResponse};
+use crate::types::{BraidRequest, BraidResponse, Update, Version};
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use futures::stream::{self, Stream, StreamExt};
+use parking_lot::Mutex;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+/// Default number of recent updates cached per resource.
+pub const DEFAULT_HISTORY_LIMIT: usize = 100;
+
+/// Live updates buffered per resource before slow subscribers are disconnected
+const CHANNEL_CAPACITY: usize = 256;
+
+/// Response headers not copied from a forwarded upstream response
+const HOP_BY_HOP_HEADERS: [&str; 4] = ["connection", "content-length", "keep-alive", "transfer-encoding"];
+
+/// Serves the resources of an upstream Braid server to many subscribers.
+///
+/// Its upstream connection is set up through [`UpstreamConnection`]. Cloning a proxy
+/// is cheap; clones share the upstream subscriptions and caches.
+#[derive(Clone)]
+pub struct BraidProxy {
+    connection: Upstream,
+    history_limit: usize,
+
+    /// Resource ID → its upstream subscription, while it has subscribers
+    channels: Arc<Mutex<HashMap<String, Arc<Channel>>>>,
+}
+
+/// The upstream subscription of a resource, and its downstream subscribers.
+struct Channel {
+    cache: Mutex<Cache>,
+    updates: broadcast::Sender<Arc<Update>>,
+    connected: AtomicBool,
+
+    /// Number of downstream subscribers, changed with the proxy's channels locked
+    subscribers: Mutex<usize>,
+
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl BraidProxy {
+    /// Create a proxy of the server at `upstream`.
+    ///
+    /// Uses a default [`BraidClient`](crate::client::BraidClient) and a random peer ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `upstream` - Base URL of the upstream server
+    #[must_use]
+    pub fn new(upstream: impl Into<String>) -> Self {
+        Self {
+            connection: Upstream::new(upstream, "proxy"),
+            history_limit: DEFAULT_HISTORY_LIMIT,
+            channels: Arc::default(),
+        }
+    }
+
+    /// Cache up to `limit` recent updates per resource (builder style).
+    ///
+    /// Subscribers whose `Parents` are older than the cached history catch up from
+    /// the snapshot instead.
+    #[must_use]
+    pub fn with_history_limit(mut self, limit: usize) -> Self {
+        self.history_limit = limit;
+        self
+    }
+
+    /// Get the number of downstream subscribers of a resource.
+    #[must_use]
+    pub fn subscriber_count(&self, resource_id: &str) -> usize {
+        self.channels
+            .lock()
+            .get(resource_id)
+            .map_or(0, |channel| *channel.subscribers.lock())
+    }
+
+    /// Check whether the upstream subscription of a resource is open.
+    #[must_use]
+    pub fn is_connected(&self, resource_id: &str) -> bool {
+        self.channels
+            .lock()
+            .get(resource_id)
+            .is_some_and(|channel| channel.connected.load(Ordering::SeqCst))
+    }
+
+    /// Subscribe to a resource through the proxy.
+    ///
+    /// Must be called within a Tokio runtime. The stream starts with the catch-up for
+    /// `since` (see the [module docs](self)), followed by the live updates from
+    /// upstream. Dropping it unsubscribes.
+    ///
+    /// # Arguments
+    ///
+    /// * `resource_id` - Resource ID, the same here and upstream
+    /// * `since` - Version the subscriber has, e.g. its `Parents` header
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BraidError::HistoryDropped`] when the cache can't catch the subscriber
+    /// up.
+    pub fn subscribe(
+        &self,
+        resource_id: &str,
+        since: &[Version],
+    ) -> Result<impl Stream<Item = Result<Update>> + Send + 'static> {
+        let lease = self.join(resource_id);
+        // Catch up and start listening at once, so no live update is missed or repeated
+        let (catch_up, live) = {
+            let cache = lease.channel.cache.lock();
+            (cache.catch_up(since), lease.channel.updates.subscribe())
+        };
+        let catch_up = catch_up.ok_or(BraidError::HistoryDropped)?;
+
+        let live = stream::unfold((live, lease), |(mut live, lease)| async move {
+            let update = live.recv().await.ok()?;
+            Some((Ok((*update).clone()), (live, lease)))
+        });
+        Ok(stream::iter(catch_up.into_iter().map(Ok)).chain(live))
+    }
+
+    /// Respond to a `GET` request for a resource.
+    ///
+    /// Subscriptions are served through [`subscribe`](Self::subscribe). Other requests
+    /// get the cached snapshot when there is one and no `Version` is requested, and
+    /// are forwarded upstream otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `resource_id` - Resource requested
+    /// * `braid` - Braid headers of the request
+    pub async fn respond(&self, resource_id: &str, braid: &BraidState) -> Response {
+        if braid.subscribe {
+            let since = braid.parents.clone().unwrap_or_default();
+            return match self.subscribe(resource_id, &since) {
+                Ok(updates) => SubscriptionResponse::new(updates).into_response(),
+                Err(_) => Update {
+                    status: status::GONE,
+                    body: Some(
+                        format!(
+                            "History of {} at {} is no longer cached",
+                            resource_id,
+                            crate::protocol::format_version_header(&since)
+                        )
+                        .into(),
+                    ),
+                    ..Default::default()
+                }
+                .into_response(),
+            };
+        }
+        if braid.version.is_none() {
+            if let Some(snapshot) = self.snapshot(resource_id) {
+                return snapshot.into_response();
+            }
+        }
+        self.forward(resource_id, braid).await
+    }
+
+    /// Create a router serving every path from upstream.
+    ///
+    /// The path of a request is its resource ID.
+    pub fn router(&self) -> Router {
+        Router::new()
+            .route("/{*path}", get(handle))
+            .with_state(self.clone())
+    }
+
+    /// Get the cached snapshot of a resource with subscribers.
+    fn snapshot(&self, resource_id: &str) -> Option<Update> {
+        let channel = self.channels.lock().get(resource_id).cloned()?;
+        let snapshot = channel.cache.lock().snapshot.clone();
+        snapshot
+    }
+
+    /// Forward a request upstream, and its response back.
+    async fn forward(&self, resource_id: &str, braid: &BraidState) -> Response {
+        let mut request = BraidRequest::new().with_peer(self.peer().to_string());
+        if let Some(version) = &braid.version {
+            request = request.with_versions(version.clone());
+        }
+        if let Some(parents) = &braid.parents {
+            request = request.with_parents(parents.clone());
+        }
+        match self.connection.client.fetch(&self.url(resource_id), request).await {
+            Ok(response) => upstream_response(response),
+            Err(e) => (StatusCode::BAD_GATEWAY, format!("Upstream request failed: {}", e)).into_response(),
+        }
+    }
+
+    /// Add a subscriber to a resource, subscribing upstream if it's the first.
+    fn join(&self, resource_id: &str) -> Lease {
+        let mut channels = self.channels.lock();
+        let channel = channels
+            .entry(resource_id.to_string())
+            .or_insert_with(|| {
+                let channel = Arc::new(Channel {
+                    cache: Mutex::new(Cache::new(self.history_limit)),
+                    updates: broadcast::channel(CHANNEL_CAPACITY).0,
+                    connected: AtomicBool::new(false),
+                    subscribers: Mutex::new(0),
+                    task: Mutex::new(None),
+                });
+                let task = tokio::spawn(listen(
+                    Arc::clone(&channel),
+                    self.connection.clone(),
+                    self.url(resource_id),
+                ));
+                *channel.task.lock() = Some(task);
+                channel
+            })
+            .clone();
+        *channel.subscribers.lock() += 1;
+        Lease {
+            channels: Arc::clone(&self.channels),
+            resource_id: resource_id.to_string(),
+            channel,
+        }
+    }
+}
+
+impl fmt::Debug for BraidProxy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BraidProxy")
+            .field("connection", &self.connection)
+            .field("history_limit", &self.history_limit)
+            .field("resources", &self.channels.lock().len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl UpstreamConnection for BraidProxy {
+    fn connection(&self) -> &Upstream {
+        &self.connection
+    }
+
+    fn connection_mut(&mut self) -> &mut Upstream {
+        &mut self.connection
+    }
+}
+
+impl Channel {
+    /// Record an update from upstream, and send it to the subscribers.
+    fn receive(&self, update: Update) {
+        let mut cache = self.cache.lock();
+        if cache.record(&update) {
+            let _ = self.updates.send(Arc::new(update));
+        }
+    }
+
+    fn stop(&self) {
+        if let Some(task) = self.task.lock().take() {
+            task.abort();
+        }
+    }
+}
+
+/// A downstream subscription, held by its stream.
+struct Lease {
+    channels: Arc<Mutex<HashMap<String, Arc<Channel>>>>,
+    resource_id: String,
+    channel: Arc<Channel>,
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        let mut channels = self.channels.lock();
+        let mut subscribers = self.channel.subscribers.lock();
+        *subscribers -= 1;
+        if *subscribers == 0 {
+            channels.remove(&self.resource_id);
+            self.channel.stop();
+        }
+    }
+}
+
+/// Subscribe upstream until stopped, reconnecting after each interruption.
+async fn listen(channel: Arc<Channel>, connection: Upstream, url: String) {
+    let (channel, connection, url) = (&channel, &connection, &url);
+    connection
+        .reconnect(|| async move {
+            let since = channel.cache.lock().version.clone();
+            let request = BraidRequest::new().with_parents(since).with_peer(connection.peer.clone());
+            match connection.client.subscribe(url, request).await {
+                Ok(mut subscription) => {
+                    channel.connected.store(true, Ordering::SeqCst);
+                    while let Some(next) = subscription.next().await {
+                        match next {
+                            Ok(update) => channel.receive(update),
+                            Err(e) => {
+                                tracing::warn!("Subscription to {} interrupted: {}", url, e);
+                                break;
+                            }
+                        }
+                    }
+                    channel.connected.store(false, Ordering::SeqCst);
+                    ControlFlow::Continue(true)
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to subscribe to {}: {}", url, e);
+                    ControlFlow::Continue(false)
+                }
+            }
+        })
+        .await;
+}
+
+/// The latest state and recent updates of a resource.
+struct Cache {
+    limit: usize,
+
+    /// Frontier of the updates received
+    version: Vec<Version>,
+
+    /// Most recent updates, oldest first
+    history: VecDeque<Update>,
+
+    /// Content at `version`, if known
+    snapshot: Option<Update>,
+}
+
+impl Cache {
+    fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            version: Vec::new(),
+            history: VecDeque::new(),
+            snapshot: None,
+        }
+    }
+
+    /// Record an update, returning whether subscribers should get it.
+    fn record(&mut self, update: &Update) -> bool {
+        if update.version.is_empty() || update.content_type.as_deref() == Some(presence::CONTENT_TYPE) {
+            return true;
+        }
+        if update.version.iter().all(|v| self.contains(v)) {
+            // Repeated after a reconnection
+            return false;
+        }
+
+        let base = match &self.snapshot {
+            // Patches only apply to the content they were made against
+            Some(snapshot) if same_versions(&update.parents, &snapshot.version) => snapshot.body.clone(),
+            Some(_) => None,
+            None if self.history.is_empty() && update.parents.is_empty() => Some(Default::default()),
+            None => None,
+        };
+        let content = match (&update.patches, &update.body) {
+            (Some(patches), _) if patches.iter().all(|p| p.is_text()) => base
+                .and_then(|base| String::from_utf8(base.to_vec()).ok())
+                .and_then(|base| apply_text_patches(&base, patches).ok())
+                .map(Into::into),
+            (Some(patches), _) if patches.iter().all(|p| p.is_bytes()) => {
+                base.and_then(|base| apply_byte_patches(&base, patches).ok())
+            }
+            (None, Some(body)) => Some(body.clone()),
+            _ => None,
+        };
+
+        if update.patches.is_some() {
+            self.version.retain(|v| !update.parents.contains(v));
+            self.version.extend(update.version.iter().cloned());
+        } else {
+            self.version = update.version.clone();
+        }
+        self.snapshot = content.map(|body| Update {
+            version: self.version.clone(),
+            body: Some(body),
+            merge_type: update.merge_type.clone(),
+            content_type: update.content_type.clone(),
+            ..Default::default()
+        });
+
+        self.history.push_back(update.clone());
+        while self.history.len() > self.limit {
+            self.history.pop_front();
+        }
+        true
+    }
+
+    /// Get the updates that bring a subscriber at `since` up to date, or `None` if
+    /// they're no longer cached.
+    fn catch_up(&self, since: &[Version]) -> Option<Vec<Update>> {
+        if !since.is_empty() && same_versions(since, &self.version) {
+            return Some(Vec::new());
+        }
+        let base = self.history.front().map_or(&[][..], |oldest| &oldest.parents[..]);
+        if !since.is_empty() && since.iter().all(|v| self.contains(v) || base.contains(v)) {
+            // Skip every update the subscriber has: its versions and their ancestors
+            let mut known: HashSet<&Version> = since.iter().collect();
+            for update in self.history.iter().rev() {
+                if update.version.iter().any(|v| known.contains(v)) {
+                    known.extend(&update.parents);
+                }
+            }
+            return Some(
+                self.history
+                    .iter()
+                    .filter(|update| !update.version.iter().any(|v| known.contains(v)))
+                    .cloned()
+                    .collect(),
+            );
+        }
+        if let Some(snapshot) = &self.snapshot {
+            return Some(vec![snapshot.clone()]);
+        }
+        base.is_empty().then(|| self.history.iter().cloned().collect())
+    }
+
+    fn contains(&self, version: &Version) -> bool {
+        self.history.iter().any(|update| update.version.contains(version))
+    }
+}
+
+async fn handle(State(proxy): State<BraidProxy>, uri: Uri, headers: HeaderMap) -> Response {
+    proxy.respond(uri.path(), &BraidState::from_headers(&headers)).await
+}
+
+/// Convert a response from upstream into one to send downstream.
+fn upstream_response(response: BraidResponse) -> Response {
+    let mut builder = Response::builder().status(response.status);
+    for (name, value) in &response.headers {
+        if !HOP_BY_HOP_HEADERS.contains(&name.to_ascii_lowercase().as_str()) {
+            builder = builder.header(name, value);
+        }
+    }
+    builder
+        .body(Body::from(response.body))
+        .unwrap_or_else(|_| StatusCode::BAD_GATEWAY.into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Patch;
+
+    fn text(version: &str, parents: &[&str], range: &str, content: &str) -> Update {
+        Update::patched(Version::new(version), vec![Patch::text(range, content)])
+            .with_parents(parents.iter().map(|p| Version::new(*p)).collect())
+    }
+
+    fn versions(updates: &[Update]) -> Vec<String> {
+        updates.iter().map(|u| u.version[0].to_string()).collect()
+    }
+
+    #[test]
+    fn test_cache_follows_text_patches() {
+        let mut cache = Cache::new(10);
+        assert!(cache.record(&text("a-4", &[], "[0:0]", "hello")));
+        assert!(cache.record(&text("b-5", &["a-4"], "[5:5]", " world")));
+        assert!(!cache.record(&text("b-5", &["a-4"], "[5:5]", " world")));
+
+        let snapshot = cache.snapshot.clone().unwrap();
+        assert_eq!(snapshot.body_str(), Some("hello world"));
+        assert_eq!(snapshot.version, vec![Version::new("b-5")]);
+        assert_eq!(versions(&cache.catch_up(&[Version::new("a-4")]).unwrap()), ["b-5"]);
+        assert!(cache.catch_up(&[Version::new("b-5")]).unwrap().is_empty());
+        assert_eq!(cache.catch_up(&[]).unwrap()[0].body_str(), Some("hello world"));
+    }
+
+    #[test]
+    fn test_cache_follows_bytes_patches() {
+        let mut cache = Cache::new(10);
+        cache.record(&Update::snapshot(Version::new("v0"), vec![0u8, 0xff, 0xfe]));
+        let patch = Patch::bytes("1:2", &[0x80, 0x81][..]);
+        cache.record(&Update::patched(Version::new("v1"), vec![patch]).with_parent(Version::new("v0")));
+
+        let snapshot = cache.snapshot.clone().unwrap();
+        assert_eq!(snapshot.body.as_deref(), Some(&[0u8, 0x80, 0x81, 0xfe][..]));
+        assert_eq!(snapshot.version, vec![Version::new("v1")]);
+    }
+
+    #[test]
+    fn test_cache_drops_snapshot_on_concurrent_patches() {
+        let mut cache = Cache::new(10);
+        cache.record(&Update::snapshot(Version::new("v0"), "hello"));
+        cache.record(&text("a-1", &["v0"], "[5:5]", " world"));
+        assert_eq!(cache.snapshot.as_ref().unwrap().body_str(), Some("hello world"));
+
+        // Made against "hello", so it can't be applied to "hello world"
+        cache.record(&text("b-1", &["v0"], "[0:5]", "howdy"));
+        assert!(cache.snapshot.is_none());
+        assert_eq!(versions(&cache.catch_up(&[]).unwrap()), ["v0", "a-1", "b-1"]);
+
+        // Nor can anything after it, until the next snapshot
+        cache.record(&text("c-1", &["a-1", "b-1"], "[0:0]", ">"));
+        assert!(cache.snapshot.is_none());
+        cache.record(&Update::snapshot(Version::new("d-1"), "howdy world"));
+        assert_eq!(cache.snapshot.as_ref().unwrap().body_str(), Some("howdy world"));
+    }
+
+    #[test]
+    fn test_cache_skips_ancestors_of_concurrent_versions() {
+        let mut cache = Cache::new(10);
+        cache.record(&text("a-0", &[], "[0:0]", "a"));
+        cache.record(&text("b-0", &["a-0"], "[1:1]", "b"));
+        cache.record(&text("c-0", &["a-0"], "[1:1]", "c"));
+        assert_eq!(cache.version, vec![Version::new("b-0"), Version::new("c-0")]);
+
+        assert_eq!(versions(&cache.catch_up(&[Version::new("b-0")]).unwrap()), ["c-0"]);
+        assert_eq!(versions(&cache.catch_up(&[Version::new("a-0")]).unwrap()), ["b-0", "c-0"]);
+    }
+
+    #[test]
+    fn test_cache_without_snapshot_or_history_is_gone() {
+        let mut cache = Cache::new(2);
+        let json = |version: &str, parent: &str| {
+            Update::patched(Version::new(version), vec![Patch::json(".n", version.to_string())])
+                .with_parent(Version::new(parent))
+        };
+        cache.record(&Update::snapshot(Version::new("v0"), "{}"));
+        cache.record(&json("v1", "v0"));
+        assert!(cache.snapshot.is_none());
+        // History still reaches back to the first snapshot
+        assert_eq!(versions(&cache.catch_up(&[]).unwrap()), ["v0", "v1"]);
+
+        cache.record(&json("v2", "v1"));
+        cache.record(&json("v3", "v2"));
+        assert_eq!(versions(&cache.catch_up(&[Version::new("v1")]).unwrap()), ["v2", "v3"]);
+        assert!(cache.catch_up(&[Version::new("v0")]).is_none());
+        assert!(cache.catch_up(&[]).is_none());
+    }
+
+    #[test]
+    fn test_cache_passes_versionless_updates() {
+        let mut cache = Cache::new(10);
+        let presence = Update {
+            body: Some("{}".into()),
+            content_type: Some(presence::CONTENT_TYPE.to_string()),
+            ..Default::default()
+        };
+        assert!(cache.record(&presence));
+        assert!(cache.history.is_empty());
+        assert!(cache.snapshot.is_none());
+    }
+}
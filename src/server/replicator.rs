@@ -0,0 +1,473 @@
+//! Server-to-server replication of Braid resources.
+//!
+//! A [`Replicator`] keeps resources of a local [`ResourceStateManager`] in sync with
+//! the same resources on an upstream Braid server. Each resource it
+//! [replicates](Replicator::replicate) gets a [`Replication`], a background task that:
+//!
+//! 1. Subscribes to the resource upstream with [`BraidClient::subscribe`], sending as
+//!    `Parents` the version upstream is known to have, so only what's missing is sent
+//! 2. Applies each incoming update through the resource's merge type, as the agent
+//!    named by its version, so that it gets the same version and parents locally
+//! 3. Pushes local edits upstream with `PUT`, each with its own version and parents
+//! 4. Reconnects with exponential backoff when the subscription ends or fails (see
+//!    [`upstream`](crate::server::upstream)), resuming from the last version both
+//!    sides share
+//!
+//! # Upstream Requirements
+//!
+//! The upstream server must send the updates since a subscription's `Parents` before
+//! live ones, and apply a `PUT` update as the agent of its version too (see
+//! [`DiamondCRDT::version_agent`]). A `PUT` of a version it already has must change
+//! nothing: pushes may repeat after a reconnect. [`BraidState::update`] builds the
+//! update of a `PUT`, and [`ResourceStateManager::updates_since`] the updates a
+//! subscription starts with.
+//!
+//! # Failures
+//!
+//! | Failure | Result |
+//! |---------|--------|
+//! | Upstream unreachable, subscription closed, or 5xx on `PUT` | Reconnect after a delay |
+//! | Incoming update can't be applied, or doesn't reproduce its version | Replication stops with an [`error`](Replication::error) |
+//! | Upstream rejects a `PUT` with a 4xx status | Replication stops with an error |
+//!
+//! Diamond updates are pushed as [`DiamondCRDT::updates_since`] describes them, with
+//! one operation per character, so applying one upstream reproduces its version. Text
+//! typed and deleted in one local change is pushed as two updates.
+//!
+//! # Examples
+//!
+//! ```
+//! use braid_axum_http::server::{Replicator, ResourceStateManager, UpstreamConnection};
+//! use std::time::Duration;
+//!
+//! # tokio_test::block_on(async {
+//! let manager = ResourceStateManager::new();
+//! let replicator = Replicator::new("http://upstream.example", manager)
+//!     .with_peer("edge-1")
+//!     .with_retry_delay(Duration::from_secs(2));
+//!
+//! let replication = replicator.replicate("/notes/today");
+//! assert_eq!(replication.resource_id(), "/notes/today");
+//! assert_eq!(replicator.url("/notes/today"), "http://upstream.example/notes/today");
+//!
+//! // Dropping the handle stops replicating
+//! drop(replication);
+//! # });
+//! ```
+//!
+//! [`BraidClient::subscribe`]: crate::client::BraidClient::subscribe
+//! [`BraidState::update`]: crate::server::BraidState::update
+
+use crate::client::is_retryable_status;
+use crate::error::BraidError;
+use crate::merge::{DiamondCRDT, MergeTypeRegistry};
+use crate::protocol::merge_types;
+use crate::server::upstream::{Upstream, UpstreamConnection};
+use crate::server::{presence, ChangeObserver, ResourceChange, ResourceStateManager};
+use crate::types::{BraidRequest, Update, Version};
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use std::fmt;
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+
+/// Replicates resources of a local manager from an upstream Braid server.
+///
+/// Its upstream connection is set up through [`UpstreamConnection`]. Cloning a
+/// replicator is cheap; clones share the client and the local manager.
+#[derive(Clone)]
+pub struct Replicator {
+    connection: Upstream,
+    manager: ResourceStateManager,
+    merge_types: MergeTypeRegistry,
+    merge_type: String,
+}
+
+impl Replicator {
+    /// Create a replicator into `manager` from the server at `upstream`.
+    ///
+    /// Uses a default [`BraidClient`](crate::client::BraidClient), the built-in merge
+    /// types, and a random peer ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `upstream` - Base URL of the upstream server
+    /// * `manager` - Local resources to keep in sync
+    #[must_use]
+    pub fn new(upstream: impl Into<String>, manager: ResourceStateManager) -> Self {
+        Self {
+            connection: Upstream::new(upstream, "replicator"),
+            manager,
+            merge_types: MergeTypeRegistry::new(),
+            merge_type: merge_types::DIAMOND.to_string(),
+        }
+    }
+
+    /// Create and load local resources with `merge_types` (builder style).
+    ///
+    /// Pass the registry the local server merges with, e.g. from
+    /// [`BraidLayer::merge_types`](crate::server::BraidLayer::merge_types).
+    #[must_use]
+    pub fn with_merge_types(mut self, merge_types: MergeTypeRegistry) -> Self {
+        self.merge_types = merge_types;
+        self
+    }
+
+    /// Apply incoming updates that name no `Merge-Type` with `merge_type` (builder
+    /// style). Defaults to `"diamond"`.
+    #[must_use]
+    pub fn with_merge_type(mut self, merge_type: impl Into<String>) -> Self {
+        self.merge_type = merge_type.into();
+        self
+    }
+
+    /// Start replicating a resource.
+    ///
+    /// Must be called within a Tokio runtime. The replication runs until the returned
+    /// handle is dropped or it fails for good; see the [module docs](self).
+    ///
+    /// # Arguments
+    ///
+    /// * `resource_id` - Resource ID, the same locally and upstream
+    pub fn replicate(&self, resource_id: impl Into<String>) -> Replication {
+        let shared = Arc::new(Shared {
+            resource_id: resource_id.into(),
+            upstream_version: Mutex::new(Vec::new()),
+            connected: AtomicBool::new(false),
+            error: Mutex::new(None),
+            local_changes: Notify::new(),
+        });
+        let observer: Arc<dyn ChangeObserver> = Arc::new(LocalChanges(Arc::downgrade(&shared)));
+        self.manager.add_observer(Arc::clone(&observer));
+        let task = tokio::spawn(self.clone().run(Arc::clone(&shared)));
+        Replication {
+            shared,
+            task,
+            manager: self.manager.clone(),
+            observer,
+        }
+    }
+
+    /// Replicate until failing for good, reconnecting after each interruption.
+    async fn run(self, shared: Arc<Shared>) {
+        let (this, shared) = (&self, &shared);
+        self.connection
+            .reconnect(|| async move {
+                let result = this.session(shared).await;
+                let connected = shared.connected.swap(false, Ordering::SeqCst);
+                match result {
+                    Ok(()) => tracing::debug!("Subscription to {} ended", this.url(&shared.resource_id)),
+                    Err(Interruption::Disconnected(e)) => {
+                        tracing::warn!("Replication of {} interrupted: {}", this.url(&shared.resource_id), e)
+                    }
+                    Err(Interruption::Failed(message)) => {
+                        tracing::error!("Replication of {} stopped: {}", this.url(&shared.resource_id), message);
+                        *shared.error.lock() = Some(message);
+                        return ControlFlow::Break(());
+                    }
+                }
+                ControlFlow::Continue(connected)
+            })
+            .await;
+    }
+
+    /// Subscribe once, then apply incoming updates and push local edits until the
+    /// subscription ends.
+    async fn session(&self, shared: &Shared) -> Result<(), Interruption> {
+        let resource_id = &shared.resource_id;
+        self.manager
+            .load_resource(resource_id, &self.merge_types)
+            .await
+            .map_err(Interruption::Failed)?;
+
+        let since = shared.upstream_version.lock().clone();
+        let request = BraidRequest::new()
+            .with_parents(since)
+            .with_peer(self.peer().to_string());
+        let mut subscription = self
+            .connection
+            .client
+            .subscribe(&self.url(resource_id), request)
+            .await
+            .map_err(Interruption::Disconnected)?;
+        shared.connected.store(true, Ordering::SeqCst);
+
+        // Edits made while disconnected go first
+        self.push(shared).await?;
+        loop {
+            tokio::select! {
+                next = subscription.next() => match next {
+                    Some(Ok(update)) => self.receive(shared, update).await?,
+                    Some(Err(e)) => return Err(Interruption::Disconnected(e)),
+                    None => return Ok(()),
+                },
+                () = shared.local_changes.notified() => self.push(shared).await?,
+            }
+        }
+    }
+
+    /// Apply an update received from upstream, unless it's already here.
+    async fn receive(&self, shared: &Shared, mut update: Update) -> Result<(), Interruption> {
+        if update.content_type.as_deref() == Some(presence::CONTENT_TYPE) {
+            return Ok(());
+        }
+        let resource_id = &shared.resource_id;
+        if !self.knows(resource_id, &update.version) {
+            update.merge_type.get_or_insert_with(|| self.merge_type.clone());
+            let agent_id = update
+                .primary_version()
+                .and_then(DiamondCRDT::version_agent)
+                .unwrap_or(self.peer())
+                .to_string();
+            self.manager
+                .apply_and_persist(resource_id, &self.merge_types, &update, &agent_id)
+                .await
+                .map_err(|e| {
+                    Interruption::Failed(format!("Failed to apply {}: {}", versions(&update.version), e))
+                })?;
+            if !self.knows(resource_id, &update.version) {
+                return Err(Interruption::Failed(format!(
+                    "Applying {} didn't reproduce its version",
+                    versions(&update.version)
+                )));
+            }
+        }
+        advance(&mut shared.upstream_version.lock(), &update);
+        Ok(())
+    }
+
+    /// Push every local update upstream doesn't have yet, in order.
+    async fn push(&self, shared: &Shared) -> Result<(), Interruption> {
+        let resource_id = &shared.resource_id;
+        let since = shared.upstream_version.lock().clone();
+        let updates = self
+            .manager
+            .updates_since(resource_id, &since)
+            .await
+            .map_err(Interruption::Failed)?;
+
+        for update in updates {
+            let mut request = BraidRequest::new()
+                .with_method("PUT")
+                .with_versions(update.version.clone())
+                .with_parents(update.parents.clone())
+                .with_peer(self.peer().to_string())
+                .with_merge_type(update.merge_type.clone().unwrap_or_else(|| self.merge_type.clone()));
+            if let Some(patches) = &update.patches {
+                request = request.with_patches(patches.clone());
+            } else if let Some(body) = &update.body {
+                request = request.with_body(body.clone());
+            }
+            if let Some(content_type) = &update.content_type {
+                request = request.with_content_type(content_type.clone());
+            }
+
+            let response = self
+                .connection
+                .client
+                .fetch(&self.url(resource_id), request)
+                .await
+                .map_err(Interruption::Disconnected)?;
+            if !(200..300).contains(&response.status) {
+                let message = format!(
+                    "Upstream rejected {} with {}: {}",
+                    versions(&update.version),
+                    response.status,
+                    String::from_utf8_lossy(&response.body)
+                );
+                return Err(if response.status >= 500 || is_retryable_status(response.status) {
+                    Interruption::Disconnected(BraidError::Http(message))
+                } else {
+                    Interruption::Failed(message)
+                });
+            }
+            advance(&mut shared.upstream_version.lock(), &update);
+        }
+        Ok(())
+    }
+
+    /// Check whether the local resource has every version in `version`.
+    fn knows(&self, resource_id: &str, version: &[Version]) -> bool {
+        if version.is_empty() {
+            return false;
+        }
+        self.manager.get_resource(resource_id).is_some_and(|resource| {
+            let state = resource.read();
+            version.iter().all(|v| state.document.contains_version(v))
+        })
+    }
+}
+
+impl fmt::Debug for Replicator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Replicator")
+            .field("connection", &self.connection)
+            .field("merge_type", &self.merge_type)
+            .finish_non_exhaustive()
+    }
+}
+
+impl UpstreamConnection for Replicator {
+    fn connection(&self) -> &Upstream {
+        &self.connection
+    }
+
+    fn connection_mut(&mut self) -> &mut Upstream {
+        &mut self.connection
+    }
+}
+
+/// A resource being replicated by a [`Replicator`].
+///
+/// Dropping the handle stops the replication and unregisters its observer of local
+/// changes. Local edits made after that are no longer pushed upstream.
+pub struct Replication {
+    shared: Arc<Shared>,
+    task: JoinHandle<()>,
+    manager: ResourceStateManager,
+
+    /// Observer of the local manager, waking `task` on local edits
+    observer: Arc<dyn ChangeObserver>,
+}
+
+/// State shared by a replication's handle, task, and change observer.
+struct Shared {
+    resource_id: String,
+
+    /// Versions upstream is known to have: the frontier of the updates received
+    /// from it and pushed to it
+    upstream_version: Mutex<Vec<Version>>,
+
+    connected: AtomicBool,
+
+    /// Why the replication stopped for good
+    error: Mutex<Option<String>>,
+
+    /// Signalled when the local resource changes
+    local_changes: Notify,
+}
+
+impl Replication {
+    /// Get the ID of the replicated resource.
+    #[inline]
+    #[must_use]
+    pub fn resource_id(&self) -> &str {
+        &self.shared.resource_id
+    }
+
+    /// Check whether the subscription upstream is open.
+    #[must_use]
+    pub fn is_connected(&self) -> bool {
+        self.shared.connected.load(Ordering::SeqCst)
+    }
+
+    /// Get the version upstream is known to have, from which a reconnection resumes.
+    #[must_use]
+    pub fn upstream_version(&self) -> Vec<Version> {
+        self.shared.upstream_version.lock().clone()
+    }
+
+    /// Get the error the replication stopped with, if it failed for good.
+    #[must_use]
+    pub fn error(&self) -> Option<String> {
+        self.shared.error.lock().clone()
+    }
+}
+
+impl Drop for Replication {
+    fn drop(&mut self) {
+        self.task.abort();
+        self.manager.remove_observer(&self.observer);
+    }
+}
+
+impl fmt::Debug for Replication {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Replication")
+            .field("resource_id", &self.shared.resource_id)
+            .field("connected", &self.is_connected())
+            .field("upstream_version", &self.upstream_version())
+            .finish()
+    }
+}
+
+/// Wakes a replication's task when its local resource changes.
+///
+/// Holds the shared state weakly, so that a change delivered after the replication
+/// is dropped does nothing.
+struct LocalChanges(Weak<Shared>);
+
+#[async_trait]
+impl ChangeObserver for LocalChanges {
+    async fn on_change(&self, change: &ResourceChange) -> Result<(), String> {
+        if let Some(shared) = self.0.upgrade() {
+            if change.resource_id == shared.resource_id {
+                shared.local_changes.notify_one();
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Why a replication session ended.
+enum Interruption {
+    /// Upstream couldn't be reached; reconnect later
+    Disconnected(BraidError),
+
+    /// Replicating can't go on
+    Failed(String),
+}
+
+/// Advance the frontier `known` past an update both sides now have.
+fn advance(known: &mut Vec<Version>, update: &Update) {
+    known.retain(|v| !update.parents.contains(v));
+    for version in &update.version {
+        if !known.contains(version) {
+            known.push(version.clone());
+        }
+    }
+}
+
+/// Format versions for messages.
+fn versions(version: &[Version]) -> String {
+    crate::protocol::format_version_header(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_advance_replaces_parents_with_version() {
+        let mut known = vec![Version::new("a-1"), Version::new("b-0")];
+        let update = Update::patched(Version::new("a-3"), vec![]).with_parent(Version::new("a-1"));
+        advance(&mut known, &update);
+        assert_eq!(known, vec![Version::new("b-0"), Version::new("a-3")]);
+
+        // Receiving an update twice changes nothing
+        advance(&mut known, &update);
+        assert_eq!(known.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_dropping_replication_removes_its_observer() {
+        let manager = ResourceStateManager::new();
+        let replicator = Replicator::new("http://127.0.0.1:9", manager.clone());
+        let replication = replicator.replicate("doc");
+        let observer = Arc::clone(&replication.observer);
+
+        drop(replication);
+        assert!(!manager.remove_observer(&observer));
+    }
+
+    #[test]
+    fn test_upstream_url() {
+        let replicator = Replicator::new("http://upstream.example/", ResourceStateManager::new());
+        assert_eq!(replicator.url("/doc"), "http://upstream.example/doc");
+        assert_eq!(replicator.url("doc"), "http://upstream.example/doc");
+        assert!(replicator.peer().starts_with("replicator-"));
+    }
+}
@@ -3,33 +3,175 @@
 //! This module provides centralized state management for collaborative document editing.
 //! It maintains an in-memory registry of documents, each with its own CRDT instance and
 //! metadata. All access is thread-safe via `Arc<RwLock<>>`.
+//!
+//! # Resource Kinds
+//!
+//! | Kind | Storage | Patches |
+//! |------|---------|---------|
+//! | [`ResourceKind::Text`] | [`DiamondCRDT`] | Inserts and deletes |
+//! | [`ResourceKind::Json`] | [`JsonDocument`] (merge type `json`) | `json` range patches merged per path |
+//! | [`ResourceKind::Sync9`] | [`Sync9`] | `text` range patches merged over the version DAG |
+//! | [`ResourceKind::Registered`] | Any other [`MergeType`] | Defined by the merge type |
+//!
+//! Resources created through [`ResourceStateManager::apply_merge_update`] use the
+//! document produced by the [`MergeTypeRegistry`] for the update's `Merge-Type`.
+//!
+//! # Persistence
+//!
+//! A manager created with [`ResourceStateManager::with_store`] keeps merge-type
+//! resources in a [`ResourceStore`]. Resources are loaded from the store the first time
+//! they're used, and [`ResourceStateManager::apply_and_persist`] appends every update to
+//! the store before returning the merged result. The manager's other edit methods
+//! return an error instead, since they'd change resources without storing the change.
+//! Resources whose merge type can [encode](MergeType::encode) them, such as text
+//! resources, are also snapshotted every
+//! [`snapshot_interval`](ResourceStateManager::with_snapshot_interval) updates, so
+//! loading them doesn't replay the whole log.
+//!
+//! # Change Hooks
+//!
+//! Observers added with [`ResourceStateManager::add_observer`] are notified of every
+//! update the manager applies, in order per resource, without holding up the merge
+//! (see [`hooks`](crate::server::hooks)). Updates applied by `apply_and_persist` are
+//! notified once they're stored.
+//!
+//! # Presence
+//!
+//! Each resource also has ephemeral presence states, such as its peers' cursors, in
+//! [`ResourceStateManager::presence`]. They're never merged into the resource, so
+//! they don't affect its version or history (see [`presence`](crate::server::presence)).
 
+use std::any::Any;
 use std::sync::Arc;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 use parking_lot::RwLock;
 use std::collections::HashMap;
-use crate::merge::DiamondCRDT;
+use crate::error::BraidError;
+use crate::merge::{DiamondCRDT, JsonDocument, MergeType, MergeTypeRegistry, Sync9};
+use crate::protocol::status;
+use crate::server::blame::{self, Blame};
+use crate::server::hooks::{ChangeHooks, ChangeObserver, ResourceChange};
+use crate::server::presence::Presence;
+use crate::server::store::{ResourceSnapshot, ResourceStore, StoredResource, StoredUpdate};
+use crate::server::validation::{Candidate, Rejection, UpdateValidator};
+use crate::types::{Update, Version};
+use bytes::Bytes;
 use serde_json::Value;
 
+/// Updates persisted per resource between oplog snapshots, unless configured otherwise.
+pub const DEFAULT_SNAPSHOT_INTERVAL: u64 = 100;
+
+/// The kind of content a resource holds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ResourceKind {
+    /// Plain text merged with the Diamond-types CRDT
+    Text,
+    /// Structured JSON merged per path
+    Json,
+    /// Plain text merged with the sync9 sequence CRDT
+    Sync9,
+    /// Content held by another registered merge type
+    Registered,
+}
+
 /// The state of a single collaborative resource.
 ///
-/// Each resource maintains its own CRDT instance along with synchronization metadata.
-/// State is protected by a RwLock for safe concurrent access from multiple async tasks.
+/// Each resource holds one merge-type document, which keeps its content and history,
+/// along with synchronization metadata. State is protected by a RwLock for safe
+/// concurrent access from multiple async tasks.
 ///
 /// # Invariants
 ///
-/// - The CRDT is always at the tip of its operation log
+/// - The document is always at the tip of its history
 /// - `last_sync` is updated whenever operations are applied
 /// - State is never invalid or inconsistent
 #[derive(Debug, Clone)]
 pub struct ResourceState {
-    /// The document's CRDT with full operation history
-    pub crdt: DiamondCRDT,
+    /// The resource's document; its merge type resolves every edit
+    pub document: Box<dyn MergeType>,
 
     /// When this resource was last modified
     pub last_sync: SystemTime,
 }
 
+impl ResourceState {
+    /// Create a resource holding a merge-type document.
+    fn new(document: Box<dyn MergeType>) -> Self {
+        Self {
+            document,
+            last_sync: SystemTime::now(),
+        }
+    }
+
+    /// Get the kind of content this resource holds.
+    #[inline]
+    #[must_use]
+    pub fn kind(&self) -> ResourceKind {
+        let document = self.document.as_ref() as &dyn Any;
+        if document.is::<DiamondCRDT>() {
+            ResourceKind::Text
+        } else if document.is::<JsonDocument>() {
+            ResourceKind::Json
+        } else if document.is::<Sync9>() {
+            ResourceKind::Sync9
+        } else {
+            ResourceKind::Registered
+        }
+    }
+
+    /// Get the name of the merge type that resolves this resource's edits.
+    #[inline]
+    #[must_use]
+    pub fn merge_type_name(&self) -> &str {
+        self.document.name()
+    }
+
+    /// Get the CRDT of a text resource.
+    ///
+    /// Returns `None` for resources of other kinds.
+    #[inline]
+    #[must_use]
+    pub fn crdt(&self) -> Option<&DiamondCRDT> {
+        (self.document.as_ref() as &dyn Any).downcast_ref()
+    }
+
+    /// Get the CRDT of a text resource for modification.
+    #[inline]
+    #[must_use]
+    pub fn crdt_mut(&mut self) -> Option<&mut DiamondCRDT> {
+        (self.document.as_mut() as &mut dyn Any).downcast_mut()
+    }
+
+    /// Get the document of a JSON resource.
+    ///
+    /// Returns `None` for resources of other kinds.
+    #[inline]
+    #[must_use]
+    pub fn json(&self) -> Option<&JsonDocument> {
+        (self.document.as_ref() as &dyn Any).downcast_ref()
+    }
+
+    /// Get the current version of the resource's document.
+    #[inline]
+    #[must_use]
+    pub fn version(&self) -> Vec<Version> {
+        self.document.version()
+    }
+
+    /// Get the resource's current content, as a snapshot of its document carries it.
+    #[must_use]
+    pub fn content(&self) -> Bytes {
+        self.document.snapshot().body.unwrap_or_default()
+    }
+
+    /// Create a checkpoint of the resource's document.
+    #[inline]
+    #[must_use]
+    pub fn checkpoint(&self) -> Value {
+        self.document.checkpoint()
+    }
+}
+
 /// Thread-safe registry of collaborative document resources.
 ///
 /// `ResourceStateManager` maintains the canonical state for all active resources in the
@@ -46,6 +188,15 @@ pub struct ResourceState {
 /// Resources are created lazily on first access and remain in memory. There is currently
 /// no automatic cleanup; consider implementing time-based eviction for long-running servers.
 ///
+/// # Persistence
+///
+/// Without a store, resources live only in memory. With one (see
+/// [`with_store`](Self::with_store)), [`apply_and_persist`](Self::apply_and_persist)
+/// and [`load_resource`](Self::load_resource) load resources from it on first use, and
+/// every update applied by `apply_and_persist` is persisted before it returns. The
+/// other edit methods can't persist their edits, so they fail on a manager with a
+/// store.
+///
 /// # Examples
 ///
 /// ```ignore
@@ -60,6 +211,33 @@ pub struct ResourceStateManager {
     /// Resource ID → Arc<RwLock<ResourceState>>
     /// Using Arc allows multiple concurrent tasks to reference the same resource
     resources: Arc<RwLock<HashMap<String, Arc<RwLock<ResourceState>>>>>,
+
+    /// Backend resources are loaded from and persisted to
+    store: Option<Arc<dyn ResourceStore>>,
+
+    /// Updates persisted per resource between snapshots
+    snapshot_interval: u64,
+
+    /// Resource ID → persistence progress; its lock keeps the order updates are
+    /// applied in the same as the order they're stored in
+    logs: Arc<RwLock<HashMap<String, Arc<tokio::sync::Mutex<LogState>>>>>,
+
+    /// Observers notified of every applied update
+    hooks: Arc<ChangeHooks>,
+
+    /// Ephemeral presence states of each resource's peers
+    presence: Presence,
+}
+
+/// How much of a resource's history is in its store.
+#[derive(Debug, Default)]
+struct LogState {
+    /// Whether the resource has been loaded from the store
+    loaded: bool,
+    /// `seq` of the last update stored
+    seq: u64,
+    /// Updates stored since the last snapshot
+    since_snapshot: u64,
 }
 
 impl ResourceStateManager {
@@ -76,9 +254,67 @@ impl ResourceStateManager {
     pub fn new() -> Self {
         Self {
             resources: Arc::new(RwLock::new(HashMap::new())),
+            store: None,
+            snapshot_interval: DEFAULT_SNAPSHOT_INTERVAL,
+            logs: Arc::new(RwLock::new(HashMap::new())),
+            hooks: Arc::new(ChangeHooks::default()),
+            presence: Presence::new(),
+        }
+    }
+
+    /// Create a resource manager backed by a store.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - Backend to load resources from and persist updates to
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use braid_axum_http::server::store::MemoryStore;
+    /// use braid_axum_http::server::ResourceStateManager;
+    /// use std::sync::Arc;
+    ///
+    /// let manager = ResourceStateManager::with_store(Arc::new(MemoryStore::new()));
+    /// assert!(manager.store().is_some());
+    /// ```
+    #[must_use]
+    pub fn with_store(store: Arc<dyn ResourceStore>) -> Self {
+        Self {
+            store: Some(store),
+            ..Self::new()
         }
     }
 
+    /// Set how many updates are persisted per resource between oplog snapshots
+    /// (builder style).
+    ///
+    /// Defaults to [`DEFAULT_SNAPSHOT_INTERVAL`]. Only resources whose merge type can
+    /// [encode](MergeType::encode) them are snapshotted; the others are always
+    /// restored by replaying their updates.
+    #[must_use]
+    pub fn with_snapshot_interval(mut self, updates: u64) -> Self {
+        self.snapshot_interval = updates.max(1);
+        self
+    }
+
+    /// Set how long peers without a presence stream stay present after publishing
+    /// (builder style).
+    ///
+    /// Defaults to [`presence::DEFAULT_TTL`](crate::server::presence::DEFAULT_TTL).
+    #[must_use]
+    pub fn with_presence_ttl(mut self, ttl: Duration) -> Self {
+        self.presence = Presence::with_ttl(ttl);
+        self
+    }
+
+    /// Get the store backing this manager, if any.
+    #[inline]
+    #[must_use]
+    pub fn store(&self) -> Option<&Arc<dyn ResourceStore>> {
+        self.store.as_ref()
+    }
+
     // ========== Resource Lifecycle ==========
 
     /// Get or create a resource, initializing its CRDT if needed.
@@ -108,17 +344,35 @@ impl ResourceStateManager {
         &self,
         resource_id: &str,
         initial_agent_id: &str,
+    ) -> Arc<RwLock<ResourceState>> {
+        self.get_or_create_with(resource_id, || Box::new(DiamondCRDT::new(initial_agent_id)))
+    }
+
+    /// Get or create a JSON resource.
+    ///
+    /// New resources start as an empty (`null`) [`JsonDocument`]. An existing text
+    /// resource is returned unchanged; check [`ResourceState::kind`] if the distinction
+    /// matters.
+    ///
+    /// # Arguments
+    ///
+    /// * `resource_id` - Unique resource identifier
+    #[must_use]
+    pub fn get_or_create_json_resource(&self, resource_id: &str) -> Arc<RwLock<ResourceState>> {
+        self.get_or_create_with(resource_id, || Box::new(JsonDocument::new()))
+    }
+
+    /// Get a resource, creating it with a new document if it doesn't exist.
+    fn get_or_create_with(
+        &self,
+        resource_id: &str,
+        document: impl FnOnce() -> Box<dyn MergeType>,
     ) -> Arc<RwLock<ResourceState>> {
         let mut resources = self.resources.write();
 
         resources
             .entry(resource_id.to_string())
-            .or_insert_with(|| {
-                Arc::new(RwLock::new(ResourceState {
-                    crdt: DiamondCRDT::new(initial_agent_id),
-                    last_sync: SystemTime::now(),
-                }))
-            })
+            .or_insert_with(|| Arc::new(RwLock::new(ResourceState::new(document()))))
             .clone()
     }
 
@@ -166,24 +420,18 @@ impl ResourceStateManager {
     ///
     /// # Returns
     ///
-    /// Current resource state exported as JSON, or error if the operation failed.
-    /// 
-    /// # Note
-    ///
-    /// Errors currently never occur (Result wrapper is for future extensibility).
+    /// Current resource state exported as JSON, or an error if the resource isn't a
+    /// text resource or the manager has a store.
     pub fn apply_update(
         &self,
         resource_id: &str,
         content: &str,
         agent_id: &str,
     ) -> Result<Value, String> {
-        let resource = self.get_or_create_resource(resource_id, agent_id);
-        let mut state = resource.write();
-
-        state.crdt.add_insert(0, content);
-        state.last_sync = SystemTime::now();
-
-        Ok(state.crdt.export_operations())
+        self.edit_text(resource_id, agent_id, |crdt| {
+            crdt.add_insert(0, content);
+            Ok(())
+        })
     }
 
     /// Apply a remote insertion operation.
@@ -199,7 +447,8 @@ impl ResourceStateManager {
     ///
     /// # Returns
     ///
-    /// Current resource state after merge.
+    /// Current resource state after merge, or an error if `pos` is past the end of the
+    /// text or falls inside a character, or the manager has a store.
     pub fn apply_remote_insert(
         &self,
         resource_id: &str,
@@ -207,13 +456,7 @@ impl ResourceStateManager {
         pos: usize,
         text: &str,
     ) -> Result<Value, String> {
-        let resource = self.get_or_create_resource(resource_id, agent_id);
-        let mut state = resource.write();
-
-        state.crdt.add_insert_remote(agent_id, pos, text);
-        state.last_sync = SystemTime::now();
-
-        Ok(state.crdt.export_operations())
+        self.edit_text(resource_id, agent_id, |crdt| crdt.add_insert_remote(agent_id, pos, text))
     }
 
     /// Apply a remote deletion operation.
@@ -229,7 +472,8 @@ impl ResourceStateManager {
     ///
     /// # Returns
     ///
-    /// Current resource state after merge.
+    /// Current resource state after merge, or an error if the range exceeds the text
+    /// or splits a character, or the manager has a store.
     pub fn apply_remote_delete(
         &self,
         resource_id: &str,
@@ -237,20 +481,655 @@ impl ResourceStateManager {
         start: usize,
         end: usize,
     ) -> Result<Value, String> {
+        self.edit_text(resource_id, agent_id, |crdt| crdt.add_delete_remote(agent_id, start..end))
+    }
+
+    /// Make an edit to a text resource, creating it if needed, and notify observers.
+    fn edit_text(
+        &self,
+        resource_id: &str,
+        agent_id: &str,
+        edit: impl FnOnce(&mut DiamondCRDT) -> crate::error::Result<()>,
+    ) -> Result<Value, String> {
+        self.ensure_unpersisted(resource_id)?;
         let resource = self.get_or_create_resource(resource_id, agent_id);
         let mut state = resource.write();
+        let crdt = state
+            .crdt_mut()
+            .ok_or_else(|| format!("Resource {} is not a text resource", resource_id))?;
 
-        state.crdt.add_delete_remote(agent_id, start..end);
+        let before = self.hooks.is_active().then(|| crdt.version());
+        edit(crdt).map_err(|e| e.to_string())?;
+        let operations = crdt.export_operations();
         state.last_sync = SystemTime::now();
+        self.notify_text_change(resource_id, &state, before, agent_id);
+
+        Ok(operations)
+    }
+
+    /// Fail if this manager has a store, which edits to `resource_id` would bypass.
+    fn ensure_unpersisted(&self, resource_id: &str) -> Result<(), String> {
+        match self.store {
+            Some(_) => Err(format!(
+                "Resource {} is persisted; edits to it must go through apply_and_persist",
+                resource_id
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Apply a Braid update through the resource's merge type.
+    ///
+    /// If the resource doesn't exist, it's created with a document from `merge_types`
+    /// for the update's `Merge-Type` and initialized for `agent_id`. The update is then
+    /// merged by that document.
+    ///
+    /// # Arguments
+    ///
+    /// * `resource_id` - Resource to update
+    /// * `merge_types` - Registry used to create new resources
+    /// * `update` - The incoming update; its `merge_type` must be set
+    /// * `agent_id` - Origin agent
+    ///
+    /// # Returns
+    ///
+    /// The update to broadcast to subscribers, or an error if the merge type is
+    /// missing or unknown, differs from the resource's existing merge type, or the
+    /// document rejects the update. A write that lost comes back as a `293 Merge
+    /// Conflict` (see [`Update::is_conflict`]) for the writer alone; observers aren't
+    /// notified of it. Managers with a store refuse the update; use
+    /// [`apply_and_persist`](Self::apply_and_persist) with them.
+    pub fn apply_merge_update(
+        &self,
+        resource_id: &str,
+        merge_types: &MergeTypeRegistry,
+        update: &Update,
+        agent_id: &str,
+    ) -> Result<Update, String> {
+        self.ensure_unpersisted(resource_id)?;
+        self.merge_update(resource_id, merge_types, update, agent_id, None, true)
+            .map_err(|rejection| rejection.message)
+    }
+
+    /// [`apply_merge_update`](Self::apply_merge_update), checking the merged state
+    /// with `validator` if one is given, and notifying observers only if `notify` is
+    /// set.
+    ///
+    /// A validated update is checked against its previewed content when the merge
+    /// type can preview it, and merged into a copy of the resource otherwise, which
+    /// replaces the resource only once the validator accepts it. A resource created
+    /// for an update that's rejected or fails to merge is removed again.
+    fn merge_update(
+        &self,
+        resource_id: &str,
+        merge_types: &MergeTypeRegistry,
+        update: &Update,
+        agent_id: &str,
+        validator: Option<&dyn UpdateValidator>,
+        notify: bool,
+    ) -> Result<Update, Rejection> {
+        let name = update
+            .merge_type
+            .as_deref()
+            .ok_or_else(|| Rejection::bad_request("Update has no merge type"))?;
+
+        let mut created = false;
+        let resource = match self.get_resource(resource_id) {
+            Some(resource) => resource,
+            None => {
+                let mut document = merge_types
+                    .create(name)
+                    .ok_or_else(|| Rejection::bad_request(format!("Unknown merge type: {}", name)))?;
+                document
+                    .initialize(resource_id, agent_id)
+                    .map_err(|e| Rejection::bad_request(e.to_string()))?;
+
+                let mut resources = self.resources.write();
+                resources
+                    .entry(resource_id.to_string())
+                    .or_insert_with(|| {
+                        created = true;
+                        Arc::new(RwLock::new(ResourceState::new(document)))
+                    })
+                    .clone()
+            }
+        };
+
+        let mut state = resource.write();
+        let resolved = match validate_and_merge(resource_id, name, &mut state, update, agent_id, validator) {
+            Ok(resolved) => resolved,
+            Err(rejection) => {
+                if created {
+                    let mut resources = self.resources.write();
+                    if resources.get(resource_id).is_some_and(|r| Arc::ptr_eq(r, &resource)) {
+                        resources.remove(resource_id);
+                    }
+                }
+                return Err(rejection);
+            }
+        };
+        state.last_sync = SystemTime::now();
+        if notify && !resolved.is_conflict() {
+            self.notify_change(resource_id, &state, &resolved, agent_id);
+        }
+        Ok(resolved)
+    }
+
+    /// Undo an agent's most recent change to a text resource.
+    ///
+    /// See [`DiamondCRDT::undo`]. The inverse edit is made by `agent_id` and merged
+    /// like any other, so concurrent edits by other agents are kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `resource_id` - Resource to modify
+    /// * `agent_id` - Agent whose change to undo
+    ///
+    /// # Returns
+    ///
+    /// The update to broadcast to subscribers, `None` if the resource doesn't exist or
+    /// the agent has nothing to undo, or an error if the resource isn't a text
+    /// resource or the manager has a store.
+    pub fn undo(&self, resource_id: &str, agent_id: &str) -> Result<Option<Update>, String> {
+        self.revert(resource_id, agent_id, DiamondCRDT::undo)
+    }
+
+    /// Redo an agent's most recently undone change to a text resource.
+    ///
+    /// See [`DiamondCRDT::redo`] and [`undo`](Self::undo).
+    pub fn redo(&self, resource_id: &str, agent_id: &str) -> Result<Option<Update>, String> {
+        self.revert(resource_id, agent_id, DiamondCRDT::redo)
+    }
+
+    fn revert(
+        &self,
+        resource_id: &str,
+        agent_id: &str,
+        revert: fn(&mut DiamondCRDT, &str) -> Option<Update>,
+    ) -> Result<Option<Update>, String> {
+        self.ensure_unpersisted(resource_id)?;
+        let Some(resource) = self.get_resource(resource_id) else {
+            return Ok(None);
+        };
+        let mut state = resource.write();
+        let crdt = state
+            .crdt_mut()
+            .ok_or_else(|| format!("Resource {} is not a text resource", resource_id))?;
+
+        let update = revert(crdt, agent_id);
+        if let Some(update) = &update {
+            state.last_sync = SystemTime::now();
+            self.notify_change(resource_id, &state, update, agent_id);
+        }
+        Ok(update)
+    }
+
+    // ========== Presence ==========
+
+    /// Get the ephemeral presence states of the resources' peers.
+    ///
+    /// Presence is shared by all clones of this manager, and kept apart from the
+    /// resources: it never changes their versions or history. See
+    /// [`presence`](crate::server::presence) for the wire format and expiry.
+    #[inline]
+    #[must_use]
+    pub fn presence(&self) -> &Presence {
+        &self.presence
+    }
+
+    // ========== Change Hooks ==========
+
+    /// Register an observer to be notified of every update applied from now on.
+    ///
+    /// Observers are shared by all clones of this manager. See [`hooks`](crate::server::hooks)
+    /// for the delivery guarantees.
+    ///
+    /// # Arguments
+    ///
+    /// * `observer` - Receives a [`ResourceChange`] per applied update
+    pub fn add_observer(&self, observer: Arc<dyn ChangeObserver>) {
+        self.hooks.add(observer);
+    }
+
+    /// Unregister an observer added with [`add_observer`](Self::add_observer).
+    ///
+    /// Changes already queued may still be delivered to it.
+    ///
+    /// # Returns
+    ///
+    /// Whether `observer` was registered.
+    pub fn remove_observer(&self, observer: &Arc<dyn ChangeObserver>) -> bool {
+        self.hooks.remove(observer)
+    }
 
-        Ok(state.crdt.export_operations())
+    /// Wait until every change made so far has been delivered to the observers.
+    pub async fn observers_idle(&self) {
+        self.hooks.idle().await;
+    }
+
+    /// Notify observers of an update applied to a resource, whose lock is held as `state`.
+    fn notify_change(&self, resource_id: &str, state: &ResourceState, update: &Update, agent_id: &str) {
+        if self.hooks.is_active() {
+            self.hooks.notify(ResourceChange {
+                resource_id: resource_id.to_string(),
+                update: update.clone(),
+                agent_id: agent_id.to_string(),
+                version: state.version(),
+            });
+        }
+    }
+
+    /// Notify observers of a text edit made since the CRDT was at `before`.
+    fn notify_text_change(
+        &self,
+        resource_id: &str,
+        state: &ResourceState,
+        before: Option<Vec<Version>>,
+        agent_id: &str,
+    ) {
+        let Some(before) = before else {
+            return;
+        };
+        let Some(crdt) = state.crdt() else {
+            return;
+        };
+        for update in crdt.updates_since(&before).unwrap_or_default() {
+            self.notify_change(resource_id, state, &update, agent_id);
+        }
+    }
+
+    // ========== Persistence Methods ==========
+
+    /// Get a resource, loading it from the store if it isn't in memory yet.
+    ///
+    /// The resource's document is rebuilt from its latest snapshot and the updates
+    /// stored after it, using `merge_types` to create it.
+    ///
+    /// # Arguments
+    ///
+    /// * `resource_id` - Resource to load
+    /// * `merge_types` - Registry used to recreate the resource's document
+    ///
+    /// # Returns
+    ///
+    /// The resource, `None` if it's neither in memory nor in the store, or an error
+    /// if the store fails or its contents can't be replayed.
+    pub async fn load_resource(
+        &self,
+        resource_id: &str,
+        merge_types: &MergeTypeRegistry,
+    ) -> Result<Option<Arc<RwLock<ResourceState>>>, String> {
+        if let Some(store) = &self.store {
+            let log = self.log_state(resource_id);
+            let mut log = log.lock().await;
+            self.ensure_loaded(store.as_ref(), resource_id, merge_types, &mut log)
+                .await?;
+        }
+        Ok(self.get_resource(resource_id))
+    }
+
+    /// Apply a Braid update through the resource's merge type and persist it.
+    ///
+    /// Behaves like [`apply_merge_update`](Self::apply_merge_update), but first loads
+    /// the resource from the store if needed, and doesn't return until the update has
+    /// been appended to the store. Updates to one resource are applied and stored one
+    /// at a time, in the same order. Without a store, this is `apply_merge_update`.
+    ///
+    /// # Arguments
+    ///
+    /// * `resource_id` - Resource to update
+    /// * `merge_types` - Registry used to create or load resources
+    /// * `update` - The incoming update; its `merge_type` must be set
+    /// * `agent_id` - Origin agent
+    ///
+    /// # Returns
+    ///
+    /// The update to broadcast to subscribers, or, for a write that lost, the
+    /// `293 Merge Conflict` to send to its writer alone, as in `apply_merge_update`.
+    /// Returns an error if the update can't be applied or stored. When storing fails, the resource is dropped from memory so
+    /// that it's reloaded from what the store holds.
+    pub async fn apply_and_persist(
+        &self,
+        resource_id: &str,
+        merge_types: &MergeTypeRegistry,
+        update: &Update,
+        agent_id: &str,
+    ) -> Result<Update, String> {
+        self.persist_update(resource_id, merge_types, update, agent_id, None)
+            .await
+            .map_err(|rejection| rejection.message)
+    }
+
+    /// Apply and persist a Braid update if `validator` accepts the result.
+    ///
+    /// Behaves like [`apply_and_persist`](Self::apply_and_persist), but merges the
+    /// update into a copy of the resource first and passes it to `validator` (see
+    /// [`validation`](crate::server::validation)). A rejected update leaves the
+    /// resource as it was, and isn't stored or passed to observers.
+    ///
+    /// # Arguments
+    ///
+    /// * `resource_id` - Resource to update
+    /// * `merge_types` - Registry used to create or load resources
+    /// * `update` - The incoming update; its `merge_type` must be set
+    /// * `agent_id` - Origin agent
+    /// * `validator` - Check of the merged state
+    ///
+    /// # Returns
+    ///
+    /// The update to broadcast to subscribers, or a [`Rejection`] with the status to
+    /// respond with: 422 with the validator's message if it rejects the update, 400
+    /// if the update can't be merged, or 500 if it can't be stored.
+    pub async fn apply_validated(
+        &self,
+        resource_id: &str,
+        merge_types: &MergeTypeRegistry,
+        update: &Update,
+        agent_id: &str,
+        validator: &dyn UpdateValidator,
+    ) -> Result<Update, Rejection> {
+        self.persist_update(resource_id, merge_types, update, agent_id, Some(validator))
+            .await
+    }
+
+    /// [`apply_validated`](Self::apply_validated), or [`apply_and_persist`](Self::apply_and_persist)
+    /// with its errors' statuses if there's no `validator`.
+    pub(crate) async fn persist_update(
+        &self,
+        resource_id: &str,
+        merge_types: &MergeTypeRegistry,
+        update: &Update,
+        agent_id: &str,
+        validator: Option<&dyn UpdateValidator>,
+    ) -> Result<Update, Rejection> {
+        let log = self.log_state(resource_id);
+        let mut log = log.lock().await;
+        let Some(store) = &self.store else {
+            return self.merge_update(resource_id, merge_types, update, agent_id, validator, true);
+        };
+        self.ensure_loaded(store.as_ref(), resource_id, merge_types, &mut log)
+            .await
+            .map_err(Rejection::internal)?;
+
+        let resolved = self.merge_update(resource_id, merge_types, update, agent_id, validator, false)?;
+        let stored = StoredUpdate {
+            seq: log.seq + 1,
+            agent_id: agent_id.to_string(),
+            version: resolved.version.clone(),
+            parents: resolved.parents.clone(),
+            update: update.clone(),
+        };
+        if let Err(e) = store.append_update(resource_id, &stored).await {
+            self.resources.write().remove(resource_id);
+            log.loaded = false;
+            return Err(Rejection::internal(format!(
+                "Failed to store update to {}: {}",
+                resource_id, e
+            )));
+        }
+        log.seq = stored.seq;
+        log.since_snapshot += 1;
+        if let Some(resource) = self.get_resource(resource_id).filter(|_| !resolved.is_conflict()) {
+            self.notify_change(resource_id, &resource.read(), &resolved, agent_id);
+        }
+
+        if log.since_snapshot >= self.snapshot_interval {
+            match self.oplog_snapshot(resource_id, log.seq) {
+                // The update is already stored, so a failed snapshot is retried later
+                Some(snapshot) => match store.save_snapshot(resource_id, &snapshot).await {
+                    Ok(()) => log.since_snapshot = 0,
+                    Err(e) => tracing::warn!("Failed to snapshot {}: {}", resource_id, e),
+                },
+                // Documents that can't be encoded are never snapshotted
+                None => log.since_snapshot = 0,
+            }
+        }
+        Ok(resolved)
+    }
+
+    /// Delete a resource from memory and from the store.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the resource existed in either, or an error if the store fails.
+    pub async fn delete_resource(&self, resource_id: &str) -> Result<bool, String> {
+        let log = self.log_state(resource_id);
+        let _log = log.lock().await;
+        let in_memory = self.resources.write().remove(resource_id).is_some();
+        let stored = match &self.store {
+            Some(store) => store.delete(resource_id).await.map_err(|e| e.to_string())?,
+            None => false,
+        };
+        self.logs.write().remove(resource_id);
+        self.presence.clear(resource_id);
+        Ok(in_memory || stored)
+    }
+
+    /// List the IDs of all resources, in memory or in the store.
+    ///
+    /// # Returns
+    ///
+    /// Resource IDs in arbitrary order, or an error if the store fails.
+    pub async fn list_stored_resources(&self) -> Result<Vec<String>, String> {
+        let mut ids = self.list_resources();
+        if let Some(store) = &self.store {
+            ids.extend(store.list().await.map_err(|e| e.to_string())?);
+            ids.sort();
+            ids.dedup();
+        }
+        Ok(ids)
+    }
+
+    fn log_state(&self, resource_id: &str) -> Arc<tokio::sync::Mutex<LogState>> {
+        if let Some(log) = self.logs.read().get(resource_id) {
+            return log.clone();
+        }
+        self.logs
+            .write()
+            .entry(resource_id.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// Load a resource from `store` unless that's been done already.
+    ///
+    /// A resource already in memory is kept, and only its stored `seq` is read.
+    async fn ensure_loaded(
+        &self,
+        store: &dyn ResourceStore,
+        resource_id: &str,
+        merge_types: &MergeTypeRegistry,
+        log: &mut LogState,
+    ) -> Result<(), String> {
+        if log.loaded {
+            return Ok(());
+        }
+        let stored = store
+            .load(resource_id)
+            .await
+            .map_err(|e| format!("Failed to load {}: {}", resource_id, e))?;
+        if let Some(stored) = stored {
+            log.seq = stored.last_seq();
+            log.since_snapshot = stored.updates.len() as u64;
+            if self.get_resource(resource_id).is_none() {
+                let state = restore_resource(resource_id, merge_types, stored)?;
+                self.resources
+                    .write()
+                    .entry(resource_id.to_string())
+                    .or_insert_with(|| Arc::new(RwLock::new(state)));
+            }
+        }
+        log.loaded = true;
+        Ok(())
+    }
+
+    /// Snapshot a resource's document as of update `seq`, if its merge type can
+    /// encode it.
+    fn oplog_snapshot(&self, resource_id: &str, seq: u64) -> Option<ResourceSnapshot> {
+        let resource = self.get_resource(resource_id)?;
+        let state = resource.read();
+        state.document.encode().map(|data| ResourceSnapshot {
+            seq,
+            merge_type: state.merge_type_name().to_string(),
+            version: state.version(),
+            data,
+        })
     }
 
     // ========== Query Methods ==========
 
+    /// Get a snapshot of a resource as it was at `version`, ready to send as a response.
+    ///
+    /// Text resources and documents of the built-in merge types keep their full
+    /// history, so any version they know can be checked out. JSON resources keep only
+    /// the merged value, so only their current version is available.
+    ///
+    /// # Arguments
+    ///
+    /// * `resource_id` - Resource to query
+    /// * `version` - Version to check out, e.g. from a GET request's `Version` header
+    ///
+    /// # Returns
+    ///
+    /// | Outcome | Status | Body |
+    /// |---------|--------|------|
+    /// | Found | 200 | The content at `version` |
+    /// | Unknown resource or version | 404 | Error message |
+    /// | History no longer kept | 410 | Error message |
+    #[must_use]
+    pub fn snapshot_at(&self, resource_id: &str, version: &[Version]) -> Update {
+        let Some(resource) = self.get_resource(resource_id) else {
+            return error_update(status::NOT_FOUND, format!("Unknown resource: {}", resource_id));
+        };
+        let state = resource.read();
+        match state.document.snapshot_at(version) {
+            Ok(snapshot) => snapshot,
+            Err(BraidError::HistoryDropped) => error_update(
+                status::GONE,
+                format!(
+                    "History of {} at {} has been dropped",
+                    resource_id,
+                    crate::protocol::format_version_header(version)
+                ),
+            ),
+            Err(e) => error_update(status::NOT_FOUND, e.to_string()),
+        }
+    }
+
+    /// Get the updates that bring a peer at `since` up to a resource's current version.
+    ///
+    /// This is what a subscription sends first: `since` is the subscriber's `Parents`
+    /// header, or empty for a subscriber that has nothing yet. Documents of merge
+    /// types return their [`patches_since`](MergeType::patches_since), each with its
+    /// own version and parents.
+    ///
+    /// Some documents fold the history after `since` into a snapshot of the current
+    /// value, as JSON resources do. With a store, a peer that has some of the
+    /// resource is then sent the updates it's missing from the store's log instead,
+    /// as they were applied (see [`ResourceStore::updates_since`]), and gets the
+    /// snapshot only if the store has folded them too.
+    ///
+    /// # Arguments
+    ///
+    /// * `resource_id` - Resource to query
+    /// * `since` - Version the peer has
+    ///
+    /// # Returns
+    ///
+    /// The updates in the order to apply them, none for an unknown resource, or an
+    /// error if `since` names a version the resource doesn't know.
+    pub async fn updates_since(&self, resource_id: &str, since: &[Version]) -> Result<Vec<Update>, String> {
+        // Held so that the store has every update the document has merged
+        let log = self.log_state(resource_id);
+        let _log = log.lock().await;
+        let (updates, folded) = {
+            let Some(resource) = self.get_resource(resource_id) else {
+                return Ok(Vec::new());
+            };
+            let state = resource.read();
+            let updates = state.document.patches_since(since).map_err(|e| e.to_string())?;
+            let folded = !since.is_empty()
+                && matches!(state.document.snapshot_at(since), Err(BraidError::HistoryDropped));
+            (updates, folded)
+        };
+
+        let Some(store) = self.store.as_ref().filter(|_| folded) else {
+            return Ok(updates);
+        };
+        match store.updates_since(resource_id, since).await {
+            Ok(stored) => Ok(stored.iter().map(StoredUpdate::as_applied).collect()),
+            Err(BraidError::HistoryDropped) => Ok(updates),
+            Err(e) => {
+                tracing::warn!("Failed to read the history of {}: {}", resource_id, e);
+                Ok(updates)
+            }
+        }
+    }
+
+    /// Get the authorship of a text resource's content, ready to send as a response.
+    ///
+    /// See [`blame`](crate::server::blame) for the JSON format.
+    ///
+    /// # Arguments
+    ///
+    /// * `resource_id` - Resource to query
+    /// * `version` - Version of the text to attribute, or `None` for the current text
+    ///
+    /// # Returns
+    ///
+    /// | Outcome | Status | Body |
+    /// |---------|--------|------|
+    /// | Found | 200 | [`Blame`](crate::server::blame::Blame) JSON |
+    /// | Not a text resource | 400 | Error message |
+    /// | Unknown resource or version | 404 | Error message |
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use braid_axum_http::server::blame::Blame;
+    /// use braid_axum_http::server::ResourceStateManager;
+    ///
+    /// let manager = ResourceStateManager::new();
+    /// manager.apply_update("doc", "hello", "alice").unwrap();
+    ///
+    /// let response = manager.blame("doc", None);
+    /// let blame: Blame = serde_json::from_slice(response.body.as_ref().unwrap()).unwrap();
+    /// assert_eq!(blame.spans[0].agent, "alice");
+    /// ```
+    #[must_use]
+    pub fn blame(&self, resource_id: &str, version: Option<&[Version]>) -> Update {
+        let Some(resource) = self.get_resource(resource_id) else {
+            return error_update(status::NOT_FOUND, format!("Unknown resource: {}", resource_id));
+        };
+        let state = resource.read();
+        let Some(crdt) = state.crdt() else {
+            return error_update(
+                status::BAD_REQUEST,
+                format!("Resource {} is not a text resource", resource_id),
+            );
+        };
+
+        let version = version.map_or_else(|| crdt.version(), <[Version]>::to_vec);
+        let spans = match crdt.blame_at(&version) {
+            Ok(spans) => spans,
+            Err(e) => return error_update(status::NOT_FOUND, e.to_string()),
+        };
+        let blame = Blame {
+            version: version.clone(),
+            spans,
+        };
+        Update {
+            version,
+            body: Some(serde_json::to_vec(&blame).unwrap_or_default().into()),
+            content_type: Some(blame::CONTENT_TYPE.to_string()),
+            ..Default::default()
+        }
+    }
+
     /// Get a snapshot of a resource's current state.
     ///
-    /// Returns a JSON checkpoint containing content, version, agent ID, and operation count.
+    /// Returns a JSON checkpoint containing content and version. Text resources also
+    /// report agent ID and operation count; JSON resources report the merged value.
     ///
     /// # Arguments
     ///
@@ -264,10 +1143,22 @@ impl ResourceStateManager {
     pub fn get_resource_state(&self, resource_id: &str) -> Option<Value> {
         self.get_resource(resource_id).map(|resource| {
             let state = resource.read();
-            state.crdt.checkpoint()
+            state.checkpoint()
         })
     }
 
+    /// Get the merged value of a JSON resource.
+    ///
+    /// # Returns
+    ///
+    /// `Some(Value)` if the resource exists and is a JSON resource, `None` otherwise.
+    #[must_use]
+    pub fn get_json_value(&self, resource_id: &str) -> Option<Value> {
+        let resource = self.get_resource(resource_id)?;
+        let state = resource.read();
+        state.json().map(|doc| doc.value().clone())
+    }
+
     /// Get the merge quality score for a resource.
     ///
     /// Returns a heuristic score (0-100) indicating how well concurrent edits have converged.
@@ -278,13 +1169,13 @@ impl ResourceStateManager {
     ///
     /// # Returns
     ///
-    /// `Some(u32)` if the resource exists, `None` otherwise.
+    /// `Some(u32)` if the resource exists and is a text resource, `None` otherwise.
     #[inline]
     #[must_use]
     pub fn get_merge_quality(&self, resource_id: &str) -> Option<u32> {
-        self.get_resource(resource_id).map(|resource| {
+        self.get_resource(resource_id).and_then(|resource| {
             let state = resource.read();
-            state.crdt.merge_quality()
+            state.crdt().map(DiamondCRDT::merge_quality)
         })
     }
 }
@@ -298,10 +1189,111 @@ impl Clone for ResourceStateManager {
     fn clone(&self) -> Self {
         Self {
             resources: Arc::clone(&self.resources),
+            store: self.store.clone(),
+            snapshot_interval: self.snapshot_interval,
+            logs: Arc::clone(&self.logs),
+            hooks: Arc::clone(&self.hooks),
+            presence: self.presence.clone(),
         }
     }
 }
 
+/// Merge an update of merge type `name` into a resource of the same type, checking the
+/// merged state with `validator` if one is given.
+fn validate_and_merge(
+    resource_id: &str,
+    name: &str,
+    state: &mut ResourceState,
+    update: &Update,
+    agent_id: &str,
+    validator: Option<&dyn UpdateValidator>,
+) -> Result<Update, Rejection> {
+    if state.merge_type_name() != name {
+        return Err(Rejection::bad_request(format!(
+            "Resource {} uses merge type '{}', not '{}'",
+            resource_id,
+            state.merge_type_name(),
+            name
+        )));
+    }
+
+    let Some(validator) = validator else {
+        return merge_into(state, update, agent_id);
+    };
+    if let Some(body) = state.document.preview(update) {
+        validator
+            .validate(&Candidate::new(resource_id, update, state, body))
+            .map_err(Rejection::invalid)?;
+        return merge_into(state, update, agent_id);
+    }
+    let mut merged = state.clone();
+    let resolved = merge_into(&mut merged, update, agent_id)?;
+    validator
+        .validate(&Candidate::new(resource_id, update, state, merged.content()))
+        .map_err(Rejection::invalid)?;
+    *state = merged;
+    Ok(resolved)
+}
+
+/// Merge an update through the merge-type document of a resource.
+fn merge_into(state: &mut ResourceState, update: &Update, agent_id: &str) -> Result<Update, Rejection> {
+    state
+        .document
+        .apply(update, agent_id)
+        .map_err(|e| Rejection::bad_request(e.to_string()))
+}
+
+/// Rebuild a resource from its stored snapshot and updates.
+fn restore_resource(
+    resource_id: &str,
+    merge_types: &MergeTypeRegistry,
+    stored: StoredResource,
+) -> Result<ResourceState, String> {
+    let name = stored
+        .snapshot
+        .as_ref()
+        .map(|snapshot| snapshot.merge_type.clone())
+        .or_else(|| stored.updates.iter().find_map(|s| s.update.merge_type.clone()))
+        .ok_or_else(|| format!("Stored resource {} has no merge type", resource_id))?;
+    let agent_id = stored
+        .updates
+        .first()
+        .map_or("server", |stored| stored.agent_id.as_str());
+
+    let mut document = merge_types
+        .create(&name)
+        .ok_or_else(|| format!("Unknown merge type: {}", name))?;
+    document
+        .initialize(resource_id, agent_id)
+        .map_err(|e| e.to_string())?;
+    let mut state = ResourceState::new(document);
+
+    if let Some(snapshot) = &stored.snapshot {
+        state
+            .document
+            .decode(&snapshot.data)
+            .map_err(|e| format!("Invalid snapshot of {}: {}", resource_id, e))?;
+    }
+    for stored in &stored.updates {
+        // Replay as the version and parents it was merged as, so that versions the
+        // merge type assigned come out the same
+        state
+            .document
+            .apply(&stored.as_applied(), &stored.agent_id)
+            .map_err(|e| format!("Failed to replay update {} of {}: {}", stored.seq, resource_id, e))?;
+    }
+    Ok(state)
+}
+
+/// An update carrying an error status and message.
+fn error_update(status: u16, message: String) -> Update {
+    Update {
+        status,
+        body: Some(message.into()),
+        ..Default::default()
+    }
+}
+
 impl Default for ResourceStateManager {
     /// Create a new resource manager (equivalent to `new()`)
     fn default() -> Self {
@@ -312,12 +1304,13 @@ impl Default for ResourceStateManager {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::types::{ContentRange, Patch, Version};
 
     #[test]
     fn test_create_resource() {
         let manager = ResourceStateManager::new();
         let resource = manager.get_or_create_resource("doc1", "alice");
-        assert!(resource.read().crdt.is_empty());
+        assert!(resource.read().crdt().unwrap().is_empty());
     }
 
     #[test]
@@ -370,6 +1363,152 @@ mod tests {
         assert_eq!(resources.len(), 2);
     }
 
+    #[test]
+    fn test_json_resource_concurrent_paths() {
+        let manager = ResourceStateManager::new();
+        let merge_types = MergeTypeRegistry::new();
+        let base = Update::snapshot(Version::new("v1"), r#"{"title": "", "done": false}"#)
+            .with_merge_type("json");
+        manager.apply_merge_update("todo", &merge_types, &base, "alice").unwrap();
+
+        let alice = Update::patched(Version::new("alice-1"), vec![Patch::json(".title", r#""Ship it""#)])
+            .with_parent(Version::new("v1"))
+            .with_merge_type("json");
+        let bob = Update::patched(Version::new("bob-1"), vec![Patch::json(".done", "true")])
+            .with_parent(Version::new("v1"))
+            .with_merge_type("json");
+        manager.apply_merge_update("todo", &merge_types, &alice, "alice").unwrap();
+        let merged = manager.apply_merge_update("todo", &merge_types, &bob, "bob").unwrap();
+
+        let value = serde_json::json!({"title": "Ship it", "done": true});
+        assert_eq!(merged.body_str().map(|body| serde_json::from_str(body).unwrap()), Some(value.clone()));
+        assert_eq!(merged.version, vec![Version::new("bob-1")]);
+        assert_eq!(manager.get_json_value("todo"), Some(value));
+
+        let resource = manager.get_resource("todo").unwrap();
+        assert_eq!(resource.read().kind(), ResourceKind::Json);
+        assert_eq!(resource.read().merge_type_name(), "json");
+        let state = manager.get_resource_state("todo").unwrap();
+        assert_eq!(state["version"], r#""alice-1", "bob-1""#);
+    }
+
+    #[test]
+    fn test_json_body_with_content_range() {
+        let manager = ResourceStateManager::new();
+        let merge_types = MergeTypeRegistry::new();
+        let update = Update::snapshot(Version::new("v1"), "{}").with_merge_type("json");
+        manager.apply_merge_update("cfg", &merge_types, &update, "alice").unwrap();
+
+        let update = Update::snapshot(Version::new("v2"), "42")
+            .with_parent(Version::new("v1"))
+            .with_content_range(ContentRange::json(".limits.max"))
+            .with_merge_type("json");
+        let resolved = manager.apply_merge_update("cfg", &merge_types, &update, "alice").unwrap();
+        assert_eq!(resolved.patches, Some(vec![Patch::json(".limits.max", "42")]));
+        assert_eq!(manager.get_json_value("cfg"), Some(serde_json::json!({"limits": {"max": 42}})));
+    }
+
+    #[test]
+    fn test_json_update_errors() {
+        let manager = ResourceStateManager::new();
+        let merge_types = MergeTypeRegistry::new();
+        let _ = manager.apply_update("text-doc", "hello", "alice");
+
+        let update = Update::snapshot(Version::new("v1"), "{}").with_merge_type("json");
+        assert!(manager.apply_merge_update("text-doc", &merge_types, &update, "alice").is_err());
+        assert!(manager.get_json_value("text-doc").is_none());
+
+        let invalid = Update::snapshot(Version::new("v1"), "{").with_merge_type("json");
+        assert!(manager.apply_merge_update("json-doc", &merge_types, &invalid, "alice").is_err());
+        // A resource created for an update that fails to merge is removed again
+        assert!(manager.get_resource("json-doc").is_none());
+
+        // Updates sent without a version are given one by the sending agent
+        let unversioned = Update {
+            body: Some("{}".into()),
+            merge_type: Some("json".to_string()),
+            ..Default::default()
+        };
+        let resolved = manager.apply_merge_update("json-doc", &merge_types, &unversioned, "bob").unwrap();
+        assert!(resolved.version[0].to_string().starts_with("bob-"));
+    }
+
+    #[test]
+    fn test_merge_update_creates_registered_document() {
+        let manager = ResourceStateManager::new();
+        let merge_types = MergeTypeRegistry::new();
+        let base = Update::snapshot(Version::new("v1"), "abc").with_merge_type("sync9");
+        manager.apply_merge_update("notes", &merge_types, &base, "alice").unwrap();
+
+        let alice = Update::patched(Version::new("alice-1"), vec![Patch::text("[0:0]", "<")])
+            .with_parent(Version::new("v1"))
+            .with_merge_type("sync9");
+        let bob = Update::patched(Version::new("bob-1"), vec![Patch::text("[3:3]", ">")])
+            .with_parent(Version::new("v1"))
+            .with_merge_type("sync9");
+        manager.apply_merge_update("notes", &merge_types, &alice, "alice").unwrap();
+        let resolved = manager.apply_merge_update("notes", &merge_types, &bob, "bob").unwrap();
+
+        assert_eq!(resolved.patches, Some(vec![Patch::text("[4:4]", ">")]));
+        assert_eq!(manager.get_resource_state("notes").unwrap()["content"], "<abc>");
+
+        let resource = manager.get_resource("notes").unwrap();
+        assert_eq!(resource.read().kind(), ResourceKind::Sync9);
+        assert_eq!(resource.read().merge_type_name(), "sync9");
+    }
+
+    #[test]
+    fn test_merge_update_diamond_uses_crdt() {
+        let manager = ResourceStateManager::new();
+        let merge_types = MergeTypeRegistry::new();
+        let update = Update::snapshot(Version::new("v1"), "hello").with_merge_type("diamond");
+        manager.apply_merge_update("doc1", &merge_types, &update, "alice").unwrap();
+
+        let resource = manager.get_resource("doc1").unwrap();
+        assert_eq!(resource.read().kind(), ResourceKind::Text);
+        assert_eq!(resource.read().crdt().unwrap().content(), "hello");
+        assert_eq!(resource.read().crdt().unwrap().agent_id(), "alice");
+    }
+
+    #[test]
+    fn test_merge_update_rejects_mismatched_merge_type() {
+        let manager = ResourceStateManager::new();
+        let merge_types = MergeTypeRegistry::new();
+        let _ = manager.apply_update("doc1", "text", "alice");
+
+        let update = Update::snapshot(Version::new("v1"), "x").with_merge_type("sync9");
+        let err = manager.apply_merge_update("doc1", &merge_types, &update, "bob").unwrap_err();
+        assert!(err.contains("'diamond'"));
+
+        let update = Update::snapshot(Version::new("v1"), "x").with_merge_type("nope");
+        let err = manager.apply_merge_update("doc2", &merge_types, &update, "bob").unwrap_err();
+        assert_eq!(err, "Unknown merge type: nope");
+        assert!(manager.get_resource("doc2").is_none());
+    }
+
+    #[test]
+    fn test_undo_redo_returns_update_to_broadcast() {
+        let manager = ResourceStateManager::new();
+        let merge_types = MergeTypeRegistry::new();
+        let update = Update::patched(Version::new("v1"), vec![Patch::text("[0:0]", "hello")])
+            .with_merge_type("diamond");
+        manager.apply_merge_update("doc1", &merge_types, &update, "alice").unwrap();
+        let _ = manager.apply_remote_insert("doc1", "bob", 5, "!");
+
+        let undone = manager.undo("doc1", "alice").unwrap().unwrap();
+        assert_eq!(undone.patches, Some(vec![Patch::text("[0:5]", "")]));
+        assert_eq!(manager.get_resource_state("doc1").unwrap()["content"], "!");
+
+        let redone = manager.redo("doc1", "alice").unwrap().unwrap();
+        assert_eq!(redone.parents, undone.version);
+        assert_eq!(manager.get_resource_state("doc1").unwrap()["content"], "hello!");
+
+        assert!(manager.redo("doc1", "alice").unwrap().is_none());
+        assert!(manager.undo("missing", "alice").unwrap().is_none());
+        let _ = manager.get_or_create_json_resource("json");
+        assert!(manager.undo("json", "alice").is_err());
+    }
+
     #[test]
     fn test_clone_shares_state() {
         let manager1 = ResourceStateManager::new();
@@ -379,4 +1518,256 @@ mod tests {
         let state = manager2.get_resource_state("doc1");
         assert_eq!(state.unwrap()["content"], "original");
     }
+
+    fn text_edit(range: &str, text: &str, parents: Vec<Version>) -> Update {
+        Update::patched(Version::new("_"), vec![Patch::text(range, text)])
+            .with_parents(parents)
+            .with_merge_type("diamond")
+    }
+
+    #[tokio::test]
+    async fn test_persisted_updates_reload_after_restart() {
+        use crate::server::store::MemoryStore;
+
+        let store = Arc::new(MemoryStore::new());
+        let merge_types = MergeTypeRegistry::new();
+        let manager = ResourceStateManager::with_store(store.clone()).with_snapshot_interval(2);
+        let mut version = Vec::new();
+        for (i, agent) in ["alice", "bob", "alice", "carol", "bob"].into_iter().enumerate() {
+            let range = format!("[{}:{}]", i, i);
+            let update = text_edit(&range, "x", version);
+            version = manager
+                .apply_and_persist("doc", &merge_types, &update, agent)
+                .await
+                .unwrap()
+                .version;
+        }
+
+        // Four updates went into snapshots; the fifth is replayed on top
+        let stored = store.load("doc").await.unwrap().unwrap();
+        assert_eq!(stored.snapshot.as_ref().unwrap().seq, 4);
+        assert_eq!(stored.updates.len(), 1);
+
+        let restarted = ResourceStateManager::with_store(store.clone());
+        assert!(restarted.get_resource("doc").is_none());
+        let resource = restarted.load_resource("doc", &merge_types).await.unwrap().unwrap();
+        assert_eq!(resource.read().crdt().unwrap().content(), "xxxxx");
+        assert_eq!(resource.read().crdt().unwrap().version(), version);
+
+        // New updates continue the stored sequence
+        let update = text_edit("[5:5]", "!", version);
+        restarted.apply_and_persist("doc", &merge_types, &update, "dave").await.unwrap();
+        assert_eq!(store.load("doc").await.unwrap().unwrap().last_seq(), 6);
+        assert!(restarted.load_resource("missing", &merge_types).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_unpersisted_edits_are_refused_with_a_store() {
+        use crate::server::store::MemoryStore;
+
+        let store = Arc::new(MemoryStore::new());
+        let merge_types = MergeTypeRegistry::new();
+        let manager = ResourceStateManager::with_store(store.clone());
+        let update = text_edit("[0:0]", "hello", Vec::new());
+        manager.apply_and_persist("doc", &merge_types, &update, "alice").await.unwrap();
+
+        assert!(manager.apply_update("doc", "x", "alice").is_err());
+        assert!(manager.apply_remote_insert("doc", "bob", 0, "x").is_err());
+        assert!(manager.apply_remote_delete("doc", "bob", 0, 1).is_err());
+        assert!(manager.apply_merge_update("doc", &merge_types, &update, "bob").is_err());
+        assert!(manager.undo("doc", "alice").is_err());
+        assert!(manager.redo("doc", "alice").is_err());
+
+        // Everything in memory is in the store
+        assert_eq!(manager.get_resource_state("doc").unwrap()["content"], "hello");
+        assert_eq!(store.load("doc").await.unwrap().unwrap().last_seq(), 1);
+        assert!(manager.apply_update("other", "x", "alice").is_err());
+        assert!(manager.get_resource("other").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_folded_history_is_caught_up_from_the_store() {
+        use crate::server::store::RedbStore;
+
+        let dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(RedbStore::open(dir.path().join("braid.redb")).unwrap());
+        let merge_types = MergeTypeRegistry::new();
+        let edit = |version: &str, path: &str, value: &'static str, parent: &str| {
+            Update::patched(Version::new(version), vec![Patch::json(path, value)])
+                .with_parent(Version::new(parent))
+                .with_merge_type("json")
+        };
+        let updates = [
+            Update::snapshot(Version::new("v1"), r#"{"a": 1}"#).with_merge_type("json"),
+            edit("v2", ".b", "2", "v1"),
+            edit("v3", ".c", "3", "v2"),
+        ];
+
+        let unstored = ResourceStateManager::new();
+        let manager = ResourceStateManager::with_store(store);
+        for update in &updates {
+            unstored.apply_merge_update("cfg", &merge_types, update, "alice").unwrap();
+            manager.apply_and_persist("cfg", &merge_types, update, "alice").await.unwrap();
+        }
+
+        // The document only has the merged value to send
+        let folded = unstored.updates_since("cfg", &[Version::new("v1")]).await.unwrap();
+        assert_eq!(folded.len(), 1);
+        assert_eq!(folded[0].body_str(), Some(r#"{"a":1,"b":2,"c":3}"#));
+
+        let caught_up = manager.updates_since("cfg", &[Version::new("v1")]).await.unwrap();
+        let versions: Vec<_> = caught_up.iter().map(|update| update.version.clone()).collect();
+        assert_eq!(versions, vec![vec![Version::new("v2")], vec![Version::new("v3")]]);
+        assert_eq!(caught_up[1].patches, Some(vec![Patch::json(".c", "3")]));
+        assert_eq!(caught_up[1].parents, vec![Version::new("v2")]);
+
+        assert!(manager.updates_since("cfg", &[Version::new("v3")]).await.unwrap().is_empty());
+        // A peer with nothing gets the value
+        let snapshot = manager.updates_since("cfg", &[]).await.unwrap();
+        assert_eq!(snapshot[0].body_str(), folded[0].body_str());
+        assert!(manager.updates_since("cfg", &[Version::new("nope")]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_persisted_registered_document_replays_log() {
+        use crate::server::store::MemoryStore;
+
+        let store = Arc::new(MemoryStore::new());
+        let merge_types = MergeTypeRegistry::new();
+        let manager = ResourceStateManager::with_store(store.clone()).with_snapshot_interval(1);
+        let update = Update::snapshot(Version::new("v1"), "first").with_merge_type("lww");
+        manager.apply_and_persist("reg", &merge_types, &update, "alice").await.unwrap();
+        let update = Update::snapshot(Version::new("v2"), "second")
+            .with_parent(Version::new("v1"))
+            .with_merge_type("lww");
+        manager.apply_and_persist("reg", &merge_types, &update, "bob").await.unwrap();
+        assert!(store.load("reg").await.unwrap().unwrap().snapshot.is_none());
+
+        let restarted = ResourceStateManager::with_store(store);
+        let resource = restarted.load_resource("reg", &merge_types).await.unwrap().unwrap();
+        let snapshot = resource.read().document.snapshot();
+        assert_eq!(snapshot.body.as_deref(), Some(&b"second"[..]));
+        assert_eq!(snapshot.version, vec![Version::new("v2")]);
+
+        assert_eq!(restarted.list_stored_resources().await.unwrap(), vec!["reg".to_string()]);
+        assert!(restarted.delete_resource("reg").await.unwrap());
+        assert!(restarted.get_resource("reg").is_none());
+        assert!(restarted.list_stored_resources().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_encodable_registered_document_is_snapshotted() {
+        use crate::server::store::MemoryStore;
+
+        /// Keeps the last body written, and encodes as its version and body.
+        #[derive(Clone, Debug, Default)]
+        struct Overwrite {
+            version: Vec<Version>,
+            body: String,
+        }
+
+        impl MergeType for Overwrite {
+            fn name(&self) -> &str {
+                "overwrite"
+            }
+
+            fn apply(&mut self, update: &Update, _agent_id: &str) -> crate::error::Result<Update> {
+                self.version = update.version.clone();
+                self.body = update.body_str().unwrap_or_default().to_string();
+                Ok(self.snapshot())
+            }
+
+            fn snapshot(&self) -> Update {
+                let mut update = Update::snapshot(Version::new("_"), self.body.clone());
+                update.version = self.version.clone();
+                update.with_merge_type("overwrite")
+            }
+
+            fn patches_since(&self, _since: &[Version]) -> crate::error::Result<Vec<Update>> {
+                Ok(vec![self.snapshot()])
+            }
+
+            fn encode(&self) -> Option<Bytes> {
+                let version = crate::protocol::format_version_header(&self.version);
+                Some(format!("{}\n{}", version, self.body).into())
+            }
+
+            fn decode(&mut self, data: &[u8]) -> crate::error::Result<()> {
+                let data = String::from_utf8_lossy(data);
+                let (version, body) = data.split_once('\n').unwrap_or_default();
+                self.version = crate::protocol::parse_version_header(version)?;
+                self.body = body.to_string();
+                Ok(())
+            }
+        }
+
+        let store = Arc::new(MemoryStore::new());
+        let merge_types = MergeTypeRegistry::new();
+        merge_types.register("overwrite", || Box::new(Overwrite::default()));
+        let manager = ResourceStateManager::with_store(store.clone()).with_snapshot_interval(2);
+        for (version, body) in [("v1", "one"), ("v2", "two"), ("v3", "three")] {
+            let update = Update::snapshot(Version::new(version), body).with_merge_type("overwrite");
+            manager.apply_and_persist("doc", &merge_types, &update, "alice").await.unwrap();
+        }
+
+        let stored = store.load("doc").await.unwrap().unwrap();
+        let snapshot = stored.snapshot.as_ref().unwrap();
+        assert_eq!((snapshot.seq, snapshot.merge_type.as_str()), (2, "overwrite"));
+        assert_eq!(stored.updates.len(), 1);
+
+        let restarted = ResourceStateManager::with_store(store);
+        let resource = restarted.load_resource("doc", &merge_types).await.unwrap().unwrap();
+        let restored = resource.read().document.snapshot();
+        assert_eq!(restored.body_str(), Some("three"));
+        assert_eq!(restored.version, vec![Version::new("v3")]);
+    }
+
+    #[tokio::test]
+    async fn test_assigned_versions_survive_restart() {
+        use crate::server::store::MemoryStore;
+
+        let store = Arc::new(MemoryStore::new());
+        let merge_types = MergeTypeRegistry::new();
+        let manager = ResourceStateManager::with_store(store.clone());
+        // Sent without a Version, so sync9 assigns one
+        let update = Update {
+            body: Some("hello".into()),
+            merge_type: Some("sync9".to_string()),
+            ..Default::default()
+        };
+        let version = manager
+            .apply_and_persist("doc", &merge_types, &update, "alice")
+            .await
+            .unwrap()
+            .version;
+        assert!(version[0].to_string().starts_with("alice-"));
+
+        let restarted = ResourceStateManager::with_store(store);
+        let child = Update::patched(Version::new("bob-1"), vec![Patch::text("[5:5]", "!")])
+            .with_parents(version.clone())
+            .with_merge_type("sync9");
+        restarted.apply_and_persist("doc", &merge_types, &child, "bob").await.unwrap();
+        let resource = restarted.get_resource("doc").unwrap();
+        let snapshot = resource.read().document.snapshot();
+        assert_eq!(snapshot.body_str(), Some("hello!"));
+        assert_eq!(snapshot.version, vec![Version::new("bob-1")]);
+    }
+
+    #[tokio::test]
+    async fn test_rejected_updates_are_not_persisted() {
+        use crate::server::store::MemoryStore;
+
+        let store = Arc::new(MemoryStore::new());
+        let merge_types = MergeTypeRegistry::new();
+        let manager = ResourceStateManager::with_store(store.clone());
+        let bad = text_edit("[3:3]", "x", Vec::new());
+        assert!(manager.apply_and_persist("doc", &merge_types, &bad, "alice").await.is_err());
+        assert!(store.load("doc").await.unwrap().is_none());
+
+        // Without a store, nothing is persisted anywhere
+        let manager = ResourceStateManager::new();
+        let update = text_edit("[0:0]", "x", Vec::new());
+        manager.apply_and_persist("doc", &merge_types, &update, "alice").await.unwrap();
+        assert!(manager.store().is_none());
+    }
 }
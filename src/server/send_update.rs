@@ -249,18 +249,20 @@ where
     S: Stream<Item = Result<Update>> + Send + 'static,
 {
     fn into_response(self) -> Response {
-        let stream = self.stream.map(|result| {
-            match result {
-                Ok(update) => {
-                    protocol::format_update(&update)
-                        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))
-                }
+        let stream = self.stream.flat_map(|result| {
+            let chunks = match result {
+                // Large snapshot bodies are sent as their own chunks, without being copied
+                Ok(update) => match protocol::format_update_chunks(&update) {
+                    Ok(chunks) => chunks.into_iter().map(Ok).collect(),
+                    Err(e) => vec![Err(std::io::Error::other(e.to_string()))],
+                },
                 Err(e) => {
                     // Log error or send error frame if protocol supports it
                     // For now, just terminate stream with error
-                    Err(std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))
+                    vec![Err(std::io::Error::other(e.to_string()))]
                 }
-            }
+            };
+            futures::stream::iter(chunks)
         });
 
         let mut builder = Response::builder()
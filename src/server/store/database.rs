@@ -0,0 +1,555 @@
+//! Embedded database resource store.
+//!
+//! Resources are kept in a single [redb](https://docs.rs/redb) database file with one
+//! table per kind of record:
+//!
+//! | Table | Key | Value |
+//! |-------|-----|-------|
+//! | `resources` | Resource ID | Latest `seq` and the heads of the version DAG |
+//! | `updates` | (Resource ID, `seq`) | The update as applied |
+//! | `dag` | (Resource ID, `seq`) | Merged version and parents of the update |
+//! | `versions` | (Resource ID, version) | `seq` of the update that produced it |
+//! | `snapshots` | Resource ID | The latest oplog snapshot |
+//!
+//! Each append writes its rows to every table in one transaction. Snapshots discard
+//! the updates they include but keep their DAG entries, so versions from before the
+//! snapshot are still recognised.
+//!
+//! # Updates Since a Version
+//!
+//! Every DAG entry records whether the update was made on top of all earlier ones,
+//! i.e. whether its parents were the heads of the DAG at the time. Walking back from
+//! the newest update, reaching such an update that the peer has means every older
+//! update is known too, so [`updates_since`](ResourceStore::updates_since) only visits
+//! updates newer than that point instead of the whole history.
+
+use super::record::{SnapshotRecord, UpdateRecord};
+use super::{FoldedUpdate, ResourceSnapshot, ResourceStore, StoredResource, StoredUpdate};
+use crate::error::{BraidError, Result};
+use crate::merge::merge_type::same_versions;
+use crate::protocol;
+use crate::types::Version;
+use async_trait::async_trait;
+use redb::{Database, ReadableTable, TableDefinition};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Arc;
+
+const RESOURCES: TableDefinition<&str, &[u8]> = TableDefinition::new("resources");
+const UPDATES: TableDefinition<(&str, u64), &[u8]> = TableDefinition::new("updates");
+const DAG: TableDefinition<(&str, u64), &[u8]> = TableDefinition::new("dag");
+const VERSIONS: TableDefinition<(&str, &str), u64> = TableDefinition::new("versions");
+const SNAPSHOTS: TableDefinition<&str, &[u8]> = TableDefinition::new("snapshots");
+
+/// A [`ResourceStore`] backed by an embedded redb database.
+///
+/// Writes are transactional: an append either stores the update with its DAG entry
+/// or nothing at all, and is durable once it returns.
+///
+/// # Examples
+///
+/// ```no_run
+/// use braid_axum_http::server::store::RedbStore;
+/// use braid_axum_http::server::ResourceStateManager;
+/// use std::sync::Arc;
+///
+/// let store = RedbStore::open("/var/lib/braid/resources.redb").unwrap();
+/// let manager = ResourceStateManager::with_store(Arc::new(store));
+/// ```
+#[derive(Clone)]
+pub struct RedbStore {
+    db: Arc<Database>,
+}
+
+impl RedbStore {
+    /// Open the database at `path`, creating it if it doesn't exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BraidError::Storage`] if the file can't be opened as a database.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let db = Database::create(path).map_err(storage_error)?;
+
+        // Create the tables up front, so that readers never find them missing
+        let txn = db.begin_write().map_err(storage_error)?;
+        txn.open_table(RESOURCES).map_err(storage_error)?;
+        txn.open_table(UPDATES).map_err(storage_error)?;
+        txn.open_table(DAG).map_err(storage_error)?;
+        txn.open_table(VERSIONS).map_err(storage_error)?;
+        txn.open_table(SNAPSHOTS).map_err(storage_error)?;
+        txn.commit().map_err(storage_error)?;
+
+        Ok(Self { db: Arc::new(db) })
+    }
+
+    /// Run blocking database work off the async runtime.
+    async fn run<T, F>(&self, work: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Database) -> Result<T> + Send + 'static,
+    {
+        let db = Arc::clone(&self.db);
+        tokio::task::spawn_blocking(move || work(&db))
+            .await
+            .map_err(|e| BraidError::Internal(format!("Database task failed: {}", e)))?
+    }
+}
+
+impl std::fmt::Debug for RedbStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedbStore").finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl ResourceStore for RedbStore {
+    async fn load(&self, resource_id: &str) -> Result<Option<StoredResource>> {
+        let id = resource_id.to_string();
+        self.run(move |db| {
+            let txn = db.begin_read().map_err(storage_error)?;
+            let resources = txn.open_table(RESOURCES).map_err(storage_error)?;
+            if resources.get(id.as_str()).map_err(storage_error)?.is_none() {
+                return Ok(None);
+            }
+
+            let snapshots = txn.open_table(SNAPSHOTS).map_err(storage_error)?;
+            let snapshot = match snapshots.get(id.as_str()).map_err(storage_error)? {
+                Some(data) => {
+                    Some(serde_json::from_slice::<SnapshotRecord>(data.value())?.into_snapshot()?)
+                }
+                None => None,
+            };
+            let updates = txn.open_table(UPDATES).map_err(storage_error)?;
+            let updates = updates
+                .range((id.as_str(), 0)..=(id.as_str(), u64::MAX))
+                .map_err(storage_error)?
+                .map(|row| {
+                    let (_, data) = row.map_err(storage_error)?;
+                    serde_json::from_slice::<UpdateRecord>(data.value())?.into_stored()
+                })
+                .collect::<Result<_>>()?;
+
+            // DAG entries of the updates the snapshot includes
+            let included = snapshot.as_ref().map_or(0, |snapshot| snapshot.seq);
+            let dag = txn.open_table(DAG).map_err(storage_error)?;
+            let folded = dag
+                .range((id.as_str(), 0)..=(id.as_str(), included))
+                .map_err(storage_error)?
+                .map(|row| {
+                    let (key, data) = row.map_err(storage_error)?;
+                    let entry: DagRecord = serde_json::from_slice(data.value())?;
+                    Ok(FoldedUpdate {
+                        seq: key.value().1,
+                        version: entry.version,
+                        parents: entry.parents,
+                    })
+                })
+                .collect::<Result<_>>()?;
+            Ok(Some(StoredResource {
+                snapshot,
+                folded,
+                updates,
+            }))
+        })
+        .await
+    }
+
+    async fn append_update(&self, resource_id: &str, update: &StoredUpdate) -> Result<()> {
+        let id = resource_id.to_string();
+        let record = serde_json::to_vec(&UpdateRecord::from_stored(update))?;
+        let (seq, version, parents) = (update.seq, update.version.clone(), update.parents.clone());
+        self.run(move |db| {
+            let txn = db.begin_write().map_err(storage_error)?;
+            {
+                let mut resources = txn.open_table(RESOURCES).map_err(storage_error)?;
+                let mut resource: ResourceRecord =
+                    match resources.get(id.as_str()).map_err(storage_error)? {
+                        Some(data) => serde_json::from_slice(data.value())?,
+                        None => ResourceRecord::default(),
+                    };
+
+                let entry = DagRecord {
+                    complete: same_versions(&parents, &resource.heads),
+                    version,
+                    parents,
+                };
+                resource.heads.retain(|head| !entry.parents.contains(head));
+                for v in &entry.version {
+                    if !resource.heads.contains(v) {
+                        resource.heads.push(v.clone());
+                    }
+                }
+                resource.last_seq = resource.last_seq.max(seq);
+                resources
+                    .insert(id.as_str(), serde_json::to_vec(&resource)?.as_slice())
+                    .map_err(storage_error)?;
+
+                let mut versions = txn.open_table(VERSIONS).map_err(storage_error)?;
+                for v in &entry.version {
+                    let key = version_key(v);
+                    if versions
+                        .get((id.as_str(), key.as_str()))
+                        .map_err(storage_error)?
+                        .is_none()
+                    {
+                        versions
+                            .insert((id.as_str(), key.as_str()), seq)
+                            .map_err(storage_error)?;
+                    }
+                }
+                txn.open_table(DAG)
+                    .map_err(storage_error)?
+                    .insert((id.as_str(), seq), serde_json::to_vec(&entry)?.as_slice())
+                    .map_err(storage_error)?;
+                txn.open_table(UPDATES)
+                    .map_err(storage_error)?
+                    .insert((id.as_str(), seq), record.as_slice())
+                    .map_err(storage_error)?;
+            }
+            txn.commit().map_err(storage_error)
+        })
+        .await
+    }
+
+    async fn save_snapshot(&self, resource_id: &str, snapshot: &ResourceSnapshot) -> Result<()> {
+        let id = resource_id.to_string();
+        let record = serde_json::to_vec(&SnapshotRecord::from_snapshot(snapshot))?;
+        let seq = snapshot.seq;
+        self.run(move |db| {
+            let txn = db.begin_write().map_err(storage_error)?;
+            {
+                let mut resources = txn.open_table(RESOURCES).map_err(storage_error)?;
+                if resources.get(id.as_str()).map_err(storage_error)?.is_none() {
+                    let resource = ResourceRecord {
+                        last_seq: seq,
+                        heads: Vec::new(),
+                    };
+                    resources
+                        .insert(id.as_str(), serde_json::to_vec(&resource)?.as_slice())
+                        .map_err(storage_error)?;
+                }
+                txn.open_table(SNAPSHOTS)
+                    .map_err(storage_error)?
+                    .insert(id.as_str(), record.as_slice())
+                    .map_err(storage_error)?;
+                txn.open_table(UPDATES)
+                    .map_err(storage_error)?
+                    .retain_in((id.as_str(), 0)..=(id.as_str(), seq), |_, _| false)
+                    .map_err(storage_error)?;
+            }
+            txn.commit().map_err(storage_error)
+        })
+        .await
+    }
+
+    async fn updates_since(
+        &self,
+        resource_id: &str,
+        since: &[Version],
+    ) -> Result<Vec<StoredUpdate>> {
+        let id = resource_id.to_string();
+        let since = since.to_vec();
+        self.run(move |db| {
+            let txn = db.begin_read().map_err(storage_error)?;
+            let versions = txn.open_table(VERSIONS).map_err(storage_error)?;
+            for v in &since {
+                if versions
+                    .get((id.as_str(), version_key(v).as_str()))
+                    .map_err(storage_error)?
+                    .is_none()
+                {
+                    return Err(BraidError::InvalidVersion(format!(
+                        "Unknown version: {}",
+                        version_key(v)
+                    )));
+                }
+            }
+
+            // Newest first; every descendant of an update comes before it
+            let dag = txn.open_table(DAG).map_err(storage_error)?;
+            let mut known: HashSet<Version> = since.into_iter().collect();
+            let mut missing = Vec::new();
+            for row in dag
+                .range((id.as_str(), 0)..=(id.as_str(), u64::MAX))
+                .map_err(storage_error)?
+                .rev()
+            {
+                let (key, data) = row.map_err(storage_error)?;
+                let seq = key.value().1;
+                let entry: DagRecord = serde_json::from_slice(data.value())?;
+                if entry.version.iter().any(|v| known.contains(v)) {
+                    if entry.complete {
+                        // Everything older is an ancestor of this update
+                        break;
+                    }
+                    known.extend(entry.parents);
+                } else {
+                    missing.push(seq);
+                }
+            }
+
+            let updates = txn.open_table(UPDATES).map_err(storage_error)?;
+            missing
+                .into_iter()
+                .rev()
+                .map(|seq| {
+                    let data = updates
+                        .get((id.as_str(), seq))
+                        .map_err(storage_error)?
+                        .ok_or(BraidError::HistoryDropped)?;
+                    serde_json::from_slice::<UpdateRecord>(data.value())?.into_stored()
+                })
+                .collect()
+        })
+        .await
+    }
+
+    async fn list(&self) -> Result<Vec<String>> {
+        self.run(|db| {
+            let txn = db.begin_read().map_err(storage_error)?;
+            let resources = txn.open_table(RESOURCES).map_err(storage_error)?;
+            let ids = resources
+                .iter()
+                .map_err(storage_error)?
+                .map(|row| Ok(row.map_err(storage_error)?.0.value().to_string()))
+                .collect::<Result<_>>()?;
+            Ok(ids)
+        })
+        .await
+    }
+
+    async fn delete(&self, resource_id: &str) -> Result<bool> {
+        let id = resource_id.to_string();
+        self.run(move |db| {
+            let txn = db.begin_write().map_err(storage_error)?;
+            let found;
+            {
+                found = txn
+                    .open_table(RESOURCES)
+                    .map_err(storage_error)?
+                    .remove(id.as_str())
+                    .map_err(storage_error)?
+                    .is_some();
+                txn.open_table(SNAPSHOTS)
+                    .map_err(storage_error)?
+                    .remove(id.as_str())
+                    .map_err(storage_error)?;
+                for table in [UPDATES, DAG] {
+                    txn.open_table(table)
+                        .map_err(storage_error)?
+                        .retain_in((id.as_str(), 0)..=(id.as_str(), u64::MAX), |_, _| false)
+                        .map_err(storage_error)?;
+                }
+
+                let mut versions = txn.open_table(VERSIONS).map_err(storage_error)?;
+                let mut keys = Vec::new();
+                for row in versions.range((id.as_str(), "")..).map_err(storage_error)? {
+                    let (key, _) = row.map_err(storage_error)?;
+                    let (resource, version) = key.value();
+                    if resource != id {
+                        break;
+                    }
+                    keys.push(version.to_string());
+                }
+                for key in keys {
+                    versions
+                        .remove((id.as_str(), key.as_str()))
+                        .map_err(storage_error)?;
+                }
+            }
+            txn.commit().map_err(storage_error)?;
+            Ok(found)
+        })
+        .await
+    }
+}
+
+/// The `resources` row of a resource.
+#[derive(Default, Serialize, Deserialize)]
+struct ResourceRecord {
+    last_seq: u64,
+    /// Versions no stored update has as a parent
+    heads: Vec<Version>,
+}
+
+/// The `dag` row of an update.
+#[derive(Serialize, Deserialize)]
+struct DagRecord {
+    version: Vec<Version>,
+    parents: Vec<Version>,
+    /// Whether every earlier update is an ancestor of this one
+    complete: bool,
+}
+
+/// Key of a version in the `versions` table.
+fn version_key(version: &Version) -> String {
+    protocol::format_version_header(std::slice::from_ref(version))
+}
+
+fn storage_error(e: impl Into<redb::Error>) -> BraidError {
+    BraidError::Storage(e.into().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Update;
+    use bytes::Bytes;
+
+    fn stored(seq: u64, version: &str, parents: &[&str]) -> StoredUpdate {
+        let parents: Vec<Version> = parents.iter().map(|p| Version::new(*p)).collect();
+        StoredUpdate {
+            seq,
+            agent_id: "alice".to_string(),
+            version: vec![Version::new(version)],
+            parents: parents.clone(),
+            update: Update::snapshot(Version::new(version), version.to_string()).with_parents(parents),
+        }
+    }
+
+    fn seqs(updates: &[StoredUpdate]) -> Vec<u64> {
+        updates.iter().map(|update| update.seq).collect()
+    }
+
+    #[tokio::test]
+    async fn test_updates_and_snapshot_survive_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("braid.redb");
+        let store = RedbStore::open(&path).unwrap();
+        assert!(store.load("doc").await.unwrap().is_none());
+        for (seq, version, parents) in [
+            (1, "v1", vec![]),
+            (2, "v2", vec!["v1"]),
+            (3, "v3", vec!["v2"]),
+        ] {
+            store
+                .append_update("doc", &stored(seq, version, &parents))
+                .await
+                .unwrap();
+        }
+        store
+            .append_update("other", &stored(1, "o1", &[]))
+            .await
+            .unwrap();
+        drop(store);
+
+        let store = RedbStore::open(&path).unwrap();
+        let loaded = store.load("doc").await.unwrap().unwrap();
+        assert_eq!(seqs(&loaded.updates), vec![1, 2, 3]);
+        assert_eq!(loaded.updates[1].parents, vec![Version::new("v1")]);
+        assert_eq!(loaded.updates[2].update.body.as_deref(), Some(&b"v3"[..]));
+
+        let snapshot = ResourceSnapshot {
+            seq: 2,
+            merge_type: "diamond".to_string(),
+            version: vec![Version::new("v2")],
+            data: Bytes::from_static(b"\x00oplog"),
+        };
+        store.save_snapshot("doc", &snapshot).await.unwrap();
+        let loaded = store.load("doc").await.unwrap().unwrap();
+        assert_eq!(loaded.snapshot, Some(snapshot));
+        assert_eq!(seqs(&loaded.updates), vec![3]);
+        assert_eq!(loaded.folded.iter().map(|f| f.seq).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(loaded.last_seq(), 3);
+
+        let mut ids = store.list().await.unwrap();
+        ids.sort();
+        assert_eq!(ids, vec!["doc".to_string(), "other".to_string()]);
+        assert!(store.delete("doc").await.unwrap());
+        assert!(!store.delete("doc").await.unwrap());
+        assert!(store.load("doc").await.unwrap().is_none());
+        assert!(store
+            .updates_since("doc", &[Version::new("v3")])
+            .await
+            .is_err());
+        assert_eq!(store.load("other").await.unwrap().unwrap().updates.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_updates_since_follows_concurrent_branches() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = RedbStore::open(dir.path().join("braid.redb")).unwrap();
+        // v1 <- a1 <- a2, v1 <- b1, then m merging a2 and b1
+        for (seq, version, parents) in [
+            (1, "v1", vec![]),
+            (2, "a1", vec!["v1"]),
+            (3, "b1", vec!["v1"]),
+            (4, "a2", vec!["a1"]),
+            (5, "m", vec!["a2", "b1"]),
+        ] {
+            store
+                .append_update("doc", &stored(seq, version, &parents))
+                .await
+                .unwrap();
+        }
+
+        let since = |versions: &[&str]| {
+            versions
+                .iter()
+                .map(|v| Version::new(*v))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            seqs(&store.updates_since("doc", &[]).await.unwrap()),
+            vec![1, 2, 3, 4, 5]
+        );
+        assert_eq!(
+            seqs(&store.updates_since("doc", &since(&["v1"])).await.unwrap()),
+            vec![2, 3, 4, 5]
+        );
+        assert_eq!(
+            seqs(&store.updates_since("doc", &since(&["a2"])).await.unwrap()),
+            vec![3, 5]
+        );
+        assert_eq!(
+            seqs(&store.updates_since("doc", &since(&["b1"])).await.unwrap()),
+            vec![2, 4, 5]
+        );
+        assert_eq!(
+            seqs(
+                &store
+                    .updates_since("doc", &since(&["a1", "b1"]))
+                    .await
+                    .unwrap()
+            ),
+            vec![4, 5]
+        );
+        assert!(store
+            .updates_since("doc", &since(&["m"]))
+            .await
+            .unwrap()
+            .is_empty());
+
+        // The default implementation agrees
+        let loaded = store.load("doc").await.unwrap().unwrap();
+        let expected = super::super::missing_updates(&loaded, &since(&["b1"])).unwrap();
+        assert_eq!(seqs(&expected), vec![2, 4, 5]);
+
+        assert!(matches!(
+            store.updates_since("doc", &since(&["nope"])).await,
+            Err(BraidError::InvalidVersion(_))
+        ));
+
+        let snapshot = ResourceSnapshot {
+            seq: 3,
+            merge_type: "diamond".to_string(),
+            version: since(&["a1", "b1"]),
+            data: Bytes::from_static(b"oplog"),
+        };
+        store.save_snapshot("doc", &snapshot).await.unwrap();
+        assert_eq!(
+            seqs(
+                &store
+                    .updates_since("doc", &since(&["a1", "b1"]))
+                    .await
+                    .unwrap()
+            ),
+            vec![4, 5]
+        );
+        assert!(matches!(
+            store.updates_since("doc", &since(&["a2"])).await,
+            Err(BraidError::HistoryDropped)
+        ));
+    }
+}
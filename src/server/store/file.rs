@@ -0,0 +1,464 @@
+//! Filesystem resource store.
+//!
+//! Each resource is kept in two files under the store's directory, named after the
+//! resource ID with every character outside `[A-Za-z0-9_-]` percent-encoded:
+//!
+//! | File | Contents |
+//! |------|----------|
+//! | `{name}.log` | One checksummed JSON record per applied update, appended in order |
+//! | `{name}.snapshot` | The latest oplog snapshot as a JSON record |
+//!
+//! Binary content is base64-encoded.
+//!
+//! # Crash Safety
+//!
+//! Log records are framed with their length, a CRC-32 checksum of the length, and a
+//! CRC-32 checksum of both. An append interrupted by a crash leaves a torn record at
+//! the end of the log, which is detected and truncated the next time the log is read,
+//! before anything else is appended to it. Every update acknowledged before the crash
+//! is kept. A damaged record anywhere before the last one fails the read and leaves
+//! the file untouched.
+//!
+//! # Compaction
+//!
+//! Saving a snapshot replaces the snapshot file atomically (write, sync, rename) and
+//! then rewrites the log with the updates the snapshot includes folded: only their
+//! `seq`, version and parents are kept. A crash in between leaves those updates in
+//! the log, where the `seq` numbers identify them as already included.
+
+use super::log::{encode_frame, scan};
+use super::record::{LogRecord, SnapshotRecord, UpdateRecord};
+use super::{FoldedUpdate, ResourceSnapshot, ResourceStore, StoredResource, StoredUpdate};
+use crate::error::Result;
+use async_trait::async_trait;
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+const LOG_EXTENSION: &str = "log";
+const SNAPSHOT_EXTENSION: &str = "snapshot";
+
+/// A [`ResourceStore`] writing an append-only update log and a snapshot file per
+/// resource.
+///
+/// Every append is synced to disk before it returns.
+///
+/// # Examples
+///
+/// ```no_run
+/// use braid_axum_http::server::store::FileStore;
+/// use braid_axum_http::server::ResourceStateManager;
+/// use std::sync::Arc;
+///
+/// let store = FileStore::open("/var/lib/braid").unwrap();
+/// let manager = ResourceStateManager::with_store(Arc::new(store))
+///     .with_snapshot_interval(500);
+/// ```
+#[derive(Debug)]
+pub struct FileStore {
+    /// Directory holding the files
+    root: PathBuf,
+
+    /// Serializes access to the logs, so that a snapshot's log rewrite never loses
+    /// an append, and torn records are truncated before anything follows them
+    write_lock: Mutex<()>,
+
+    /// Resources whose logs have been checked for torn records since opening
+    recovered: parking_lot::Mutex<HashSet<String>>,
+}
+
+impl FileStore {
+    /// Open a store in `root`, creating the directory if needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BraidError::Io`](crate::BraidError::Io) if the directory can't be created.
+    pub fn open(root: impl Into<PathBuf>) -> Result<Self> {
+        let root = root.into();
+        std::fs::create_dir_all(&root)?;
+        Ok(Self {
+            root,
+            write_lock: Mutex::new(()),
+            recovered: parking_lot::Mutex::new(HashSet::new()),
+        })
+    }
+
+    /// Get the directory the store writes to.
+    #[inline]
+    #[must_use]
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn path(&self, resource_id: &str, extension: &str) -> PathBuf {
+        self.root
+            .join(format!("{}.{}", encode_file_name(resource_id), extension))
+    }
+
+    /// Read the log records of a resource, oldest first, truncating a torn record
+    /// left at the end by a crash.
+    ///
+    /// Must be called with `write_lock` held.
+    async fn read_log(&self, resource_id: &str) -> Result<Option<Vec<LogRecord>>> {
+        let path = self.path(resource_id, LOG_EXTENSION);
+        let Some(data) = read_if_exists(&path).await? else {
+            self.recovered.lock().insert(resource_id.to_string());
+            return Ok(None);
+        };
+        let log = scan(&data)?;
+        let records = log
+            .records
+            .iter()
+            .map(|payload| serde_json::from_slice(payload))
+            .collect::<std::result::Result<_, _>>()?;
+
+        if log.valid_len < data.len() {
+            tracing::warn!(
+                "Truncating {} bytes of torn records from the log of {}",
+                data.len() - log.valid_len,
+                resource_id
+            );
+            let file = fs::OpenOptions::new().write(true).open(&path).await?;
+            file.set_len(log.valid_len as u64).await?;
+            file.sync_all().await?;
+        }
+        self.recovered.lock().insert(resource_id.to_string());
+        Ok(Some(records))
+    }
+}
+
+#[async_trait]
+impl ResourceStore for FileStore {
+    async fn load(&self, resource_id: &str) -> Result<Option<StoredResource>> {
+        let _guard = self.write_lock.lock().await;
+        let snapshot = match read_if_exists(&self.path(resource_id, SNAPSHOT_EXTENSION)).await? {
+            Some(data) => Some(serde_json::from_slice::<SnapshotRecord>(&data)?.into_snapshot()?),
+            None => None,
+        };
+        let records = self.read_log(resource_id).await?;
+        if snapshot.is_none() && records.is_none() {
+            return Ok(None);
+        }
+
+        let included = snapshot.as_ref().map_or(0, |snapshot| snapshot.seq);
+        let mut resource = StoredResource {
+            snapshot,
+            ..Default::default()
+        };
+        for record in records.unwrap_or_default() {
+            match record.fold_through(included) {
+                LogRecord::Update(record) => resource.updates.push(record.into_stored()?),
+                LogRecord::Folded(record) => resource.folded.push(FoldedUpdate::from(record)),
+            }
+        }
+        Ok(Some(resource))
+    }
+
+    async fn append_update(&self, resource_id: &str, update: &StoredUpdate) -> Result<()> {
+        let record = LogRecord::Update(Box::new(UpdateRecord::from_stored(update)));
+        let frame = encode_frame(&serde_json::to_vec(&record)?);
+
+        let _guard = self.write_lock.lock().await;
+        if !self.recovered.lock().contains(resource_id) {
+            self.read_log(resource_id).await?;
+        }
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path(resource_id, LOG_EXTENSION))
+            .await?;
+        file.write_all(&frame).await?;
+        file.sync_data().await?;
+        Ok(())
+    }
+
+    async fn save_snapshot(&self, resource_id: &str, snapshot: &ResourceSnapshot) -> Result<()> {
+        let data = serde_json::to_vec(&SnapshotRecord::from_snapshot(snapshot))?;
+
+        let _guard = self.write_lock.lock().await;
+        write_atomically(&self.path(resource_id, SNAPSHOT_EXTENSION), &data).await?;
+
+        let compacted: Vec<u8> = self
+            .read_log(resource_id)
+            .await?
+            .unwrap_or_default()
+            .into_iter()
+            .map(|record| Ok(encode_frame(&serde_json::to_vec(&record.fold_through(snapshot.seq))?)))
+            .collect::<Result<Vec<_>>>()?
+            .concat();
+        write_atomically(&self.path(resource_id, LOG_EXTENSION), &compacted).await
+    }
+
+    async fn list(&self) -> Result<Vec<String>> {
+        let mut ids = BTreeSet::new();
+        let mut entries = fs::read_dir(&self.root).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            let is_resource = path
+                .extension()
+                .is_some_and(|ext| ext == LOG_EXTENSION || ext == SNAPSHOT_EXTENSION);
+            if let (true, Some(stem)) = (is_resource, path.file_stem().and_then(|s| s.to_str())) {
+                if let Some(id) = decode_file_name(stem) {
+                    ids.insert(id);
+                }
+            }
+        }
+        Ok(ids.into_iter().collect())
+    }
+
+    async fn delete(&self, resource_id: &str) -> Result<bool> {
+        let _guard = self.write_lock.lock().await;
+        self.recovered.lock().remove(resource_id);
+        let mut found = false;
+        for extension in [LOG_EXTENSION, SNAPSHOT_EXTENSION] {
+            match fs::remove_file(self.path(resource_id, extension)).await {
+                Ok(()) => found = true,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(found)
+    }
+}
+
+async fn read_if_exists(path: &Path) -> Result<Option<Vec<u8>>> {
+    match fs::read(path).await {
+        Ok(data) => Ok(Some(data)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Replace the file at `path` so that readers see either the old or the new contents.
+///
+/// `path` must not be a symbolic link, or the link is replaced. The new file gets the
+/// permissions of the one it replaces, and its owner if the process may change it.
+/// The temporary file beside it is hidden, named `.{name}.braid-tmp`.
+pub(crate) async fn write_atomically(path: &Path, data: &[u8]) -> Result<()> {
+    let original = match fs::metadata(path).await {
+        Ok(metadata) => Some(metadata),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+    let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    let tmp = path.with_file_name(format!(".{}.braid-tmp", name));
+    let mut file = fs::File::create(&tmp).await?;
+    if let Some(original) = &original {
+        file.set_permissions(original.permissions()).await?;
+        #[cfg(unix)]
+        copy_owner(&file, original).await?;
+    }
+    file.write_all(data).await?;
+    file.sync_all().await?;
+    fs::rename(&tmp, path).await?;
+    Ok(())
+}
+
+/// Give `file` the owner and group of `original`, if they differ and the process may.
+#[cfg(unix)]
+async fn copy_owner(file: &fs::File, original: &std::fs::Metadata) -> Result<()> {
+    use std::os::unix::fs::MetadataExt;
+
+    let created = file.metadata().await?;
+    if (created.uid(), created.gid()) == (original.uid(), original.gid()) {
+        return Ok(());
+    }
+    match std::os::unix::fs::fchown(file, Some(original.uid()), Some(original.gid())) {
+        Ok(()) => Ok(()),
+        // Only privileged processes may give a file away
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Percent-encode every byte of `resource_id` outside `[A-Za-z0-9_-]`.
+fn encode_file_name(resource_id: &str) -> String {
+    resource_id
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'_' | b'-' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Reverse [`encode_file_name`], or `None` for names it can't have produced.
+fn decode_file_name(name: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(name.len());
+    let mut rest = name.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        if b == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(b);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Patch, Update, Version};
+    use bytes::Bytes;
+
+    fn stored(seq: u64, update: Update) -> StoredUpdate {
+        StoredUpdate {
+            seq,
+            agent_id: "alice".to_string(),
+            version: update.version.clone(),
+            parents: update.parents.clone(),
+            update,
+        }
+    }
+
+    #[test]
+    fn test_file_names_roundtrip() {
+        for id in ["doc", "notes/today.md", "100%", "ünïcode", ".."] {
+            let name = encode_file_name(id);
+            assert!(name
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b"_-%".contains(&b)));
+            assert_eq!(decode_file_name(&name).as_deref(), Some(id));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_log_and_snapshot_survive_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileStore::open(dir.path()).unwrap();
+        assert!(store.load("a/b").await.unwrap().is_none());
+
+        let patched = Update::patched(Version::new("v1"), vec![Patch::text("[0:0]", "hi")])
+            .with_merge_type("diamond")
+            .with_parent(Version::new("v0"));
+        let binary = Update {
+            body: Some(Bytes::from_static(&[0, 159, 255])),
+            ..Update::snapshot(Version::new("v2"), "")
+        };
+        store
+            .append_update("a/b", &stored(1, patched))
+            .await
+            .unwrap();
+        store
+            .append_update("a/b", &stored(2, binary))
+            .await
+            .unwrap();
+
+        let store = FileStore::open(dir.path()).unwrap();
+        let loaded = store.load("a/b").await.unwrap().unwrap();
+        assert!(loaded.snapshot.is_none());
+        assert_eq!(loaded.updates.len(), 2);
+        let first = &loaded.updates[0].update;
+        assert_eq!(first.parents, vec![Version::new("v0")]);
+        assert_eq!(first.merge_type.as_deref(), Some("diamond"));
+        assert_eq!(first.patches, Some(vec![Patch::text("[0:0]", "hi")]));
+        assert_eq!(
+            loaded.updates[1].update.body.as_deref(),
+            Some(&[0, 159, 255][..])
+        );
+
+        let snapshot = ResourceSnapshot {
+            seq: 1,
+            merge_type: "diamond".to_string(),
+            version: vec![Version::new("v1")],
+            data: Bytes::from_static(b"\x00oplog"),
+        };
+        store.save_snapshot("a/b", &snapshot).await.unwrap();
+        let loaded = store.load("a/b").await.unwrap().unwrap();
+        assert_eq!(loaded.snapshot, Some(snapshot));
+        assert_eq!(loaded.updates.len(), 1);
+        assert_eq!(loaded.last_seq(), 2);
+        assert_eq!(
+            loaded.folded,
+            vec![FoldedUpdate {
+                seq: 1,
+                version: vec![Version::new("v1")],
+                parents: vec![Version::new("v0")],
+            }]
+        );
+
+        assert_eq!(store.list().await.unwrap(), vec!["a/b".to_string()]);
+        assert!(store.delete("a/b").await.unwrap());
+        assert!(store.load("a/b").await.unwrap().is_none());
+        assert!(store.list().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_log_truncated_at_every_offset_recovers() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("doc.log");
+        let store = FileStore::open(dir.path()).unwrap();
+        let mut ends = Vec::new();
+        for seq in 1..=3 {
+            let update = Update::snapshot(Version::new(format!("v{}", seq)), format!("body {}", seq));
+            store.append_update("doc", &stored(seq, update)).await.unwrap();
+            ends.push(std::fs::metadata(&path).unwrap().len() as usize);
+        }
+        let log = std::fs::read(&path).unwrap();
+
+        for cut in 0..=log.len() {
+            std::fs::write(&path, &log[..cut]).unwrap();
+            let intact = ends.iter().filter(|&&end| end <= cut).count();
+
+            let store = FileStore::open(dir.path()).unwrap();
+            let loaded = store.load("doc").await.unwrap().unwrap_or_default();
+            let seqs: Vec<u64> = loaded.updates.iter().map(|update| update.seq).collect();
+            assert_eq!(seqs, (1..=intact as u64).collect::<Vec<_>>(), "cut at {}", cut);
+
+            // Appending after recovery continues the intact records
+            let next = intact as u64 + 1;
+            let update = Update::snapshot(Version::new("next"), "next");
+            FileStore::open(dir.path())
+                .unwrap()
+                .append_update("doc", &stored(next, update))
+                .await
+                .unwrap();
+            let reloaded = FileStore::open(dir.path()).unwrap().load("doc").await.unwrap().unwrap();
+            assert_eq!(reloaded.updates.len(), intact + 1, "cut at {}", cut);
+            assert_eq!(reloaded.updates[intact].update.body.as_deref(), Some(&b"next"[..]));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_record_before_the_tail_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileStore::open(dir.path()).unwrap();
+        for seq in 1..=2 {
+            let update = Update::snapshot(Version::new(format!("v{}", seq)), "body");
+            store.append_update("doc", &stored(seq, update)).await.unwrap();
+        }
+        let path = dir.path().join("doc.log");
+        let mut log = std::fs::read(&path).unwrap();
+        log[10] ^= 0xff;
+        std::fs::write(&path, &log).unwrap();
+
+        let store = FileStore::open(dir.path()).unwrap();
+        assert!(matches!(store.load("doc").await, Err(crate::BraidError::Storage(_))));
+        assert_eq!(std::fs::read(&path).unwrap(), log);
+    }
+
+    #[tokio::test]
+    async fn test_damaged_length_before_the_tail_keeps_the_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileStore::open(dir.path()).unwrap();
+        for seq in 1..=3 {
+            let update = Update::snapshot(Version::new(format!("v{}", seq)), "body");
+            store.append_update("doc", &stored(seq, update)).await.unwrap();
+        }
+        let path = dir.path().join("doc.log");
+        let mut log = std::fs::read(&path).unwrap();
+        // The first record's length now points past the end of the log
+        log[3] = 0x7f;
+        std::fs::write(&path, &log).unwrap();
+
+        let store = FileStore::open(dir.path()).unwrap();
+        assert!(matches!(store.load("doc").await, Err(crate::BraidError::Storage(_))));
+        assert_eq!(std::fs::read(&path).unwrap(), log);
+    }
+}
@@ -0,0 +1,163 @@
+//! Checksummed record framing for append-only logs.
+//!
+//! Each record is written as one frame:
+//!
+//! ```text
+//! +-------------+-------------+-------------+-------------------+
+//! | length: u32 | hcrc32: u32 | crc32: u32  | payload           |
+//! +-------------+-------------+-------------+-------------------+
+//!   little-endian, `hcrc32`     covering      `length` bytes
+//!   covering the length         the length
+//!                               and payload
+//! ```
+//!
+//! A crash during an append can leave the last frame torn: cut short, or with its
+//! bytes not all written. [`scan`] finds where the intact frames end, so the tail can
+//! be truncated before appending again. A bad frame anywhere else is corruption
+//! rather than a torn write, and is reported as an error instead of being dropped.
+//! The header checksum tells the two apart: a frame is only taken for the torn last
+//! one if its length is intact and reaches the end of the log.
+
+use crate::error::{BraidError, Result};
+
+/// Bytes of a frame before its payload
+const HEADER_LEN: usize = 12;
+
+/// Frame a record's payload.
+pub(super) fn encode_frame(payload: &[u8]) -> Vec<u8> {
+    let len = (payload.len() as u32).to_le_bytes();
+    let mut frame = Vec::with_capacity(HEADER_LEN + payload.len());
+    frame.extend_from_slice(&len);
+    frame.extend_from_slice(&crc32fast::hash(&len).to_le_bytes());
+    frame.extend_from_slice(&checksum(&len, payload).to_le_bytes());
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// The intact frames of a log.
+#[derive(Debug)]
+pub(super) struct Scan<'a> {
+    /// Payloads of the intact frames, in order
+    pub(super) records: Vec<&'a [u8]>,
+
+    /// Length of the intact frames; anything after it is a torn write
+    pub(super) valid_len: usize,
+}
+
+/// Split a log into its intact frames.
+///
+/// # Errors
+///
+/// Returns [`BraidError::Storage`] if a frame other than the last is damaged.
+pub(super) fn scan(data: &[u8]) -> Result<Scan<'_>> {
+    let mut records = Vec::new();
+    let mut offset = 0;
+    while offset < data.len() {
+        match read_frame(&data[offset..]) {
+            Some(payload) => {
+                records.push(payload);
+                offset += HEADER_LEN + payload.len();
+            }
+            None if is_torn_tail(&data[offset..]) => break,
+            None => {
+                return Err(BraidError::Storage(format!(
+                    "Corrupt log record at offset {}",
+                    offset
+                )))
+            }
+        }
+    }
+    Ok(Scan {
+        records,
+        valid_len: offset,
+    })
+}
+
+/// Read the frame at the start of `data`, if it's intact.
+fn read_frame(data: &[u8]) -> Option<&[u8]> {
+    let len = read_len(data)?;
+    let crc = u32::from_le_bytes(data.get(8..HEADER_LEN)?.try_into().ok()?);
+    let payload = data.get(HEADER_LEN..HEADER_LEN.checked_add(len)?)?;
+    // Empty records are never written, and a zeroed header would otherwise match
+    (len > 0 && checksum(&data[..4], payload) == crc).then_some(payload)
+}
+
+/// Read the length of the frame at the start of `data`, if its header is intact.
+fn read_len(data: &[u8]) -> Option<usize> {
+    let len_bytes: [u8; 4] = data.get(..4)?.try_into().ok()?;
+    let header_crc = u32::from_le_bytes(data.get(4..8)?.try_into().ok()?);
+    (crc32fast::hash(&len_bytes) == header_crc).then_some(u32::from_le_bytes(len_bytes) as usize)
+}
+
+/// Whether a damaged frame at the start of `data` can only be a torn final append.
+///
+/// That's the case when its header is cut short, its intact header gives a length
+/// reaching the end of the log, or everything from it on is zeroed, as when the file
+/// grew but the data never reached the disk. A damaged header is never taken for a
+/// torn one if the log goes on past it, since its length can't be trusted.
+fn is_torn_tail(data: &[u8]) -> bool {
+    let reaches_end = if data.len() < HEADER_LEN {
+        true
+    } else {
+        read_len(data).is_some_and(|len| HEADER_LEN.saturating_add(len) >= data.len())
+    };
+    reaches_end || data.iter().all(|&b| b == 0)
+}
+
+fn checksum(len: &[u8], payload: &[u8]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(len);
+    hasher.update(payload);
+    hasher.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(payloads: &[&[u8]]) -> Vec<u8> {
+        payloads.iter().flat_map(|payload| encode_frame(payload)).collect()
+    }
+
+    #[test]
+    fn test_scan_stops_at_torn_tail() {
+        let data = log(&[b"first", b"second", b"third"]);
+        let ends = [17, 35, 52];
+        assert_eq!(data.len(), 52);
+
+        for cut in 0..=data.len() {
+            let scan = scan(&data[..cut]).unwrap();
+            let intact = ends.iter().filter(|&&end| end <= cut).count();
+            assert_eq!(scan.records.len(), intact, "cut at {}", cut);
+            assert_eq!(scan.valid_len, ends[..intact].last().copied().unwrap_or(0));
+        }
+
+        let mut zeroed = data.clone();
+        zeroed.extend_from_slice(&[0; 64]);
+        assert_eq!(scan(&zeroed).unwrap().valid_len, 52);
+    }
+
+    #[test]
+    fn test_scan_rejects_damage_before_the_tail() {
+        let mut data = log(&[b"first", b"second", b"third"]);
+        data[30] ^= 1;
+        assert!(matches!(scan(&data), Err(BraidError::Storage(_))));
+
+        // The same damage in the last frame is a torn write
+        let last = data.len() - 1;
+        data[30] ^= 1;
+        data[last] ^= 1;
+        assert_eq!(scan(&data).unwrap().records.len(), 2);
+    }
+
+    #[test]
+    fn test_scan_rejects_damaged_lengths_before_the_tail() {
+        let data = log(&[b"first", b"second", b"third"]);
+        for byte in 0..4 {
+            // A length pointing past the end must not discard the frames after it
+            let mut damaged = data.clone();
+            damaged[byte] = 0xff;
+            assert!(matches!(scan(&damaged), Err(BraidError::Storage(_))), "byte {}", byte);
+        }
+    }
+}
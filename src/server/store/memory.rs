@@ -0,0 +1,134 @@
+//! In-memory resource store.
+
+use super::{FoldedUpdate, ResourceSnapshot, ResourceStore, StoredResource, StoredUpdate};
+use crate::error::Result;
+use async_trait::async_trait;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+
+/// A [`ResourceStore`] that keeps everything in memory.
+///
+/// Nothing survives the process, but resources do survive their
+/// [`ResourceStateManager`](crate::server::ResourceStateManager): managers sharing
+/// one store see the same histories.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    resources: RwLock<HashMap<String, StoredResource>>,
+}
+
+impl MemoryStore {
+    /// Create an empty store.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl ResourceStore for MemoryStore {
+    async fn load(&self, resource_id: &str) -> Result<Option<StoredResource>> {
+        Ok(self.resources.read().get(resource_id).cloned())
+    }
+
+    async fn append_update(&self, resource_id: &str, update: &StoredUpdate) -> Result<()> {
+        self.resources
+            .write()
+            .entry(resource_id.to_string())
+            .or_default()
+            .updates
+            .push(update.clone());
+        Ok(())
+    }
+
+    async fn save_snapshot(&self, resource_id: &str, snapshot: &ResourceSnapshot) -> Result<()> {
+        let mut resources = self.resources.write();
+        let resource = resources.entry(resource_id.to_string()).or_default();
+        let (folded, kept) = std::mem::take(&mut resource.updates)
+            .into_iter()
+            .partition::<Vec<_>, _>(|update| update.seq <= snapshot.seq);
+        resource.folded.extend(folded.iter().map(FoldedUpdate::from));
+        resource.updates = kept;
+        resource.snapshot = Some(snapshot.clone());
+        Ok(())
+    }
+
+    async fn list(&self) -> Result<Vec<String>> {
+        Ok(self.resources.read().keys().cloned().collect())
+    }
+
+    async fn delete(&self, resource_id: &str) -> Result<bool> {
+        Ok(self.resources.write().remove(resource_id).is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Update, Version};
+
+    fn stored(seq: u64) -> StoredUpdate {
+        StoredUpdate {
+            seq,
+            agent_id: "alice".to_string(),
+            version: vec![Version::new(format!("v{}", seq))],
+            parents: Vec::new(),
+            update: Update::snapshot(Version::new(format!("v{}", seq)), "x"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_discards_included_updates() {
+        let store = MemoryStore::new();
+        assert!(store.load("doc").await.unwrap().is_none());
+        for seq in 1..=3 {
+            store.append_update("doc", &stored(seq)).await.unwrap();
+        }
+
+        let snapshot = ResourceSnapshot {
+            seq: 2,
+            merge_type: "diamond".to_string(),
+            version: vec![Version::new("v2")],
+            data: "oplog".into(),
+        };
+        store.save_snapshot("doc", &snapshot).await.unwrap();
+        let resource = store.load("doc").await.unwrap().unwrap();
+        assert_eq!(resource.snapshot, Some(snapshot));
+        assert_eq!(resource.updates.len(), 1);
+        assert_eq!(resource.folded.iter().map(|f| f.seq).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(resource.last_seq(), 3);
+
+        assert_eq!(store.list().await.unwrap(), vec!["doc".to_string()]);
+        assert!(store.delete("doc").await.unwrap());
+        assert!(!store.delete("doc").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_updates_since_knows_folded_versions() {
+        let store = MemoryStore::new();
+        for seq in 1..=4 {
+            let mut update = stored(seq);
+            update.parents = (seq > 1).then(|| Version::new(format!("v{}", seq - 1))).into_iter().collect();
+            store.append_update("doc", &update).await.unwrap();
+        }
+        let snapshot = ResourceSnapshot {
+            seq: 2,
+            merge_type: "diamond".to_string(),
+            version: vec![Version::new("v2")],
+            data: "oplog".into(),
+        };
+        store.save_snapshot("doc", &snapshot).await.unwrap();
+
+        let seqs = |updates: Vec<StoredUpdate>| updates.iter().map(|u| u.seq).collect::<Vec<_>>();
+        let since = |v: &str| vec![Version::new(v)];
+        assert_eq!(seqs(store.updates_since("doc", &since("v2")).await.unwrap()), vec![3, 4]);
+        assert_eq!(seqs(store.updates_since("doc", &since("v3")).await.unwrap()), vec![4]);
+        assert!(matches!(
+            store.updates_since("doc", &since("v1")).await,
+            Err(crate::BraidError::HistoryDropped)
+        ));
+        assert!(matches!(
+            store.updates_since("doc", &since("v9")).await,
+            Err(crate::BraidError::InvalidVersion(_))
+        ));
+    }
+}
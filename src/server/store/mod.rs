@@ -0,0 +1,281 @@
+//! Persistent storage for resources.
+//!
+//! A [`ResourceStore`] keeps the history of every resource so that documents survive
+//! a restart. Each resource is stored as an optional oplog snapshot followed by the
+//! updates applied since, in the order they were applied:
+//!
+//! ```text
+//! [ snapshot (seq 40) ] [ update 41 ] [ update 42 ] ...
+//! ```
+//!
+//! Replaying the updates on top of the snapshot rebuilds the document exactly, since
+//! every merge type resolves the same updates in the same order to the same versions.
+//!
+//! Updates included in the snapshot are folded: their content is dropped, but their
+//! versions and parents are kept, so that the store still knows the shape of the
+//! version DAG when catching a peer up with [`ResourceStore::updates_since`].
+//!
+//! # Backends
+//!
+//! | Store | Persistence |
+//! |-------|-------------|
+//! | [`MemoryStore`] | None; useful in tests and as a reference implementation |
+//! | [`FileStore`] | An append-only log and a snapshot file per resource |
+//! | [`RedbStore`] | Tables in an embedded database, with an index of the version DAG |
+//!
+//! # Usage
+//!
+//! Give a store to [`ResourceStateManager::with_store`](crate::server::ResourceStateManager::with_store).
+//! The manager then loads resources on first use and persists every update it
+//! applies through [`apply_and_persist`](crate::server::ResourceStateManager::apply_and_persist)
+//! before returning, so an acknowledged update is never lost.
+//!
+//! ```
+//! use braid_axum_http::merge::MergeTypeRegistry;
+//! use braid_axum_http::server::store::MemoryStore;
+//! use braid_axum_http::server::ResourceStateManager;
+//! use braid_axum_http::{Patch, Update, Version};
+//! use std::sync::Arc;
+//!
+//! # tokio_test::block_on(async {
+//! let store = Arc::new(MemoryStore::new());
+//! let merge_types = MergeTypeRegistry::new();
+//! let update = Update::patched(Version::new("v1"), vec![Patch::text("[0:0]", "hello")])
+//!     .with_merge_type("diamond");
+//!
+//! let manager = ResourceStateManager::with_store(store.clone());
+//! manager.apply_and_persist("doc", &merge_types, &update, "alice").await.unwrap();
+//!
+//! // A new manager over the same store loads the document back
+//! let restarted = ResourceStateManager::with_store(store);
+//! restarted.load_resource("doc", &merge_types).await.unwrap();
+//! assert_eq!(restarted.get_resource_state("doc").unwrap()["content"], "hello");
+//! # });
+//! ```
+
+mod database;
+mod file;
+mod log;
+mod memory;
+mod record;
+
+pub use database::RedbStore;
+pub use file::FileStore;
+pub use memory::MemoryStore;
+
+pub(crate) use file::write_atomically;
+
+use crate::error::{BraidError, Result};
+use crate::merge::merge_type::same_versions;
+use crate::types::{Update, Version};
+use async_trait::async_trait;
+use bytes::Bytes;
+use std::collections::HashSet;
+
+/// An update as it was applied to a resource.
+#[derive(Clone, Debug)]
+pub struct StoredUpdate {
+    /// Position in the resource's history, starting at 1 and increasing by one
+    pub seq: u64,
+
+    /// Agent the update was applied as
+    pub agent_id: String,
+
+    /// Version the update was merged as
+    pub version: Vec<Version>,
+
+    /// Parents of the merged version
+    pub parents: Vec<Version>,
+
+    /// The update as received, before it was merged
+    pub update: Update,
+}
+
+impl StoredUpdate {
+    /// Get the update tagged with the version and parents it was merged as, where it
+    /// didn't carry its own.
+    ///
+    /// Applying these in `seq` order to a new document of the resource's merge type
+    /// rebuilds it with the same versions.
+    #[must_use]
+    pub fn as_applied(&self) -> Update {
+        let mut update = self.update.clone();
+        if update.version.is_empty() {
+            update.version = self.version.clone();
+        }
+        if update.parents.is_empty() {
+            update.parents = self.parents.clone();
+        }
+        update
+    }
+}
+
+/// An update folded into a snapshot, kept for its place in the version DAG.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FoldedUpdate {
+    /// Position in the resource's history
+    pub seq: u64,
+
+    /// Version the update was merged as
+    pub version: Vec<Version>,
+
+    /// Parents of the merged version
+    pub parents: Vec<Version>,
+}
+
+impl From<&StoredUpdate> for FoldedUpdate {
+    fn from(stored: &StoredUpdate) -> Self {
+        Self {
+            seq: stored.seq,
+            version: stored.version.clone(),
+            parents: stored.parents.clone(),
+        }
+    }
+}
+
+/// A snapshot of a resource's oplog.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResourceSnapshot {
+    /// `seq` of the last update the snapshot includes
+    pub seq: u64,
+
+    /// Merge type of the resource
+    pub merge_type: String,
+
+    /// Version of the resource when the snapshot was taken
+    pub version: Vec<Version>,
+
+    /// The document as its merge type [encodes](crate::merge::MergeType::encode) it,
+    /// e.g. a diamond oplog from [`DiamondCRDT::encode`](crate::merge::DiamondCRDT::encode)
+    pub data: Bytes,
+}
+
+/// Everything a store holds for one resource.
+#[derive(Clone, Debug, Default)]
+pub struct StoredResource {
+    /// The latest snapshot, if one has been saved
+    pub snapshot: Option<ResourceSnapshot>,
+
+    /// Updates the snapshot includes, in order, where the store keeps them
+    pub folded: Vec<FoldedUpdate>,
+
+    /// Updates applied after the snapshot, in order
+    pub updates: Vec<StoredUpdate>,
+}
+
+impl StoredResource {
+    /// Get the `seq` of the latest update held, or 0 if there are none.
+    #[must_use]
+    pub fn last_seq(&self) -> u64 {
+        self.updates
+            .last()
+            .map(|update| update.seq)
+            .or(self.snapshot.as_ref().map(|snapshot| snapshot.seq))
+            .unwrap_or(0)
+    }
+}
+
+/// Storage backend for resource histories.
+///
+/// Implementations must keep every update appended for a resource, in order, until a
+/// snapshot including it is saved. Calls for one resource are made one at a time by
+/// [`ResourceStateManager`](crate::server::ResourceStateManager); calls for different
+/// resources may run concurrently.
+#[async_trait]
+pub trait ResourceStore: Send + Sync {
+    /// Load a resource's snapshot and the updates after it.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the store holds nothing for `resource_id`.
+    async fn load(&self, resource_id: &str) -> Result<Option<StoredResource>>;
+
+    /// Durably append an applied update to a resource's history.
+    ///
+    /// The update must be persisted by the time this returns.
+    async fn append_update(&self, resource_id: &str, update: &StoredUpdate) -> Result<()>;
+
+    /// Replace a resource's snapshot.
+    ///
+    /// Updates with a `seq` up to the snapshot's are no longer needed and may be
+    /// discarded, or kept as [`FoldedUpdate`]s.
+    async fn save_snapshot(&self, resource_id: &str, snapshot: &ResourceSnapshot) -> Result<()>;
+
+    /// Get the stored updates a peer at version `since` is missing, oldest first.
+    ///
+    /// An update is missing unless its merged version is `since` or an ancestor of
+    /// it. The default implementation loads the resource and walks its whole history;
+    /// backends with an index of the version DAG should answer directly.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BraidError::HistoryDropped`] if some missing updates are only
+    /// held in the snapshot, or [`BraidError::InvalidVersion`] if `since` names a
+    /// version the resource never had.
+    async fn updates_since(&self, resource_id: &str, since: &[Version]) -> Result<Vec<StoredUpdate>> {
+        let stored = self.load(resource_id).await?.unwrap_or_default();
+        missing_updates(&stored, since)
+    }
+
+    /// List the IDs of all stored resources, in arbitrary order.
+    async fn list(&self) -> Result<Vec<String>>;
+
+    /// Delete everything stored for a resource.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the resource was stored.
+    async fn delete(&self, resource_id: &str) -> Result<bool>;
+}
+
+/// The updates of `stored` that aren't `since` or its ancestors, oldest first.
+///
+/// Walks the updates newest first, so every descendant of an update is seen before
+/// it, adding the parents of each known update to the known versions. Missing
+/// updates that were folded into the snapshot can't be returned.
+fn missing_updates(stored: &StoredResource, since: &[Version]) -> Result<Vec<StoredUpdate>> {
+    let mut known: HashSet<&Version> = since.iter().collect();
+    let mut found: HashSet<&Version> = HashSet::new();
+    let mut missing = Vec::new();
+    for update in stored.updates.iter().rev() {
+        found.extend(&update.version);
+        if update.version.iter().any(|version| known.contains(version)) {
+            known.extend(&update.parents);
+        } else {
+            missing.push(update.clone());
+        }
+    }
+    missing.reverse();
+
+    let mut dropped = false;
+    for folded in stored.folded.iter().rev() {
+        found.extend(&folded.version);
+        if folded.version.iter().any(|version| known.contains(version)) {
+            known.extend(&folded.parents);
+        } else {
+            dropped = true;
+        }
+    }
+
+    // Known versions no stored update produced must come from before the first one
+    let remaining: Vec<Version> = known
+        .into_iter()
+        .filter(|version| !found.contains(version))
+        .cloned()
+        .collect();
+    let folded_all = stored
+        .snapshot
+        .as_ref()
+        .is_some_and(|snapshot| stored.folded.len() as u64 == snapshot.seq);
+    match &stored.snapshot {
+        _ if remaining.is_empty() && !dropped => Ok(missing),
+        Some(snapshot) if stored.folded.is_empty() && same_versions(&remaining, &snapshot.version) => {
+            Ok(missing)
+        }
+        Some(_) if !folded_all || remaining.is_empty() => Err(BraidError::HistoryDropped),
+        _ => Err(BraidError::InvalidVersion(format!(
+            "Unknown versions: {}",
+            crate::protocol::format_version_header(&remaining)
+        ))),
+    }
+}
@@ -0,0 +1,194 @@
+//! Serialized forms of stored updates and snapshots.
+//!
+//! Records are JSON, with binary content base64-encoded.
+
+use super::{FoldedUpdate, ResourceSnapshot, StoredUpdate};
+use crate::error::{BraidError, Result};
+use crate::types::{ContentRange, Patch, Update, Version};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// An entry of a resource's update log.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(super) enum LogRecord {
+    /// An update applied after the snapshot
+    Update(Box<UpdateRecord>),
+
+    /// An update the snapshot includes
+    Folded(FoldedRecord),
+}
+
+impl LogRecord {
+    /// Fold the record if the snapshot at `seq` includes it.
+    pub(super) fn fold_through(self, seq: u64) -> Self {
+        match self {
+            Self::Update(record) if record.seq <= seq => Self::Folded(FoldedRecord {
+                seq: record.seq,
+                version: record.merged_version,
+                parents: record.merged_parents,
+            }),
+            record => record,
+        }
+    }
+}
+
+/// A [`FoldedUpdate`] as written to the log.
+#[derive(Serialize, Deserialize)]
+pub(super) struct FoldedRecord {
+    seq: u64,
+    version: Vec<Version>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    parents: Vec<Version>,
+}
+
+impl From<FoldedRecord> for FoldedUpdate {
+    fn from(record: FoldedRecord) -> Self {
+        Self {
+            seq: record.seq,
+            version: record.version,
+            parents: record.parents,
+        }
+    }
+}
+
+/// A [`StoredUpdate`] as written to the log.
+#[derive(Serialize, Deserialize)]
+pub(super) struct UpdateRecord {
+    pub(super) seq: u64,
+    agent: String,
+    /// Version and parents the update was merged as
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    merged_version: Vec<Version>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    merged_parents: Vec<Version>,
+    version: Vec<Version>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    parents: Vec<Version>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    merge_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    patches: Option<Vec<PatchRecord>>,
+    /// Base64-encoded body
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    body: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    content_range: Option<ContentRange>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    content_type: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    headers: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PatchRecord {
+    unit: String,
+    range: String,
+    /// Base64-encoded content
+    content: String,
+}
+
+impl UpdateRecord {
+    pub(super) fn from_stored(stored: &StoredUpdate) -> Self {
+        let update = &stored.update;
+        Self {
+            seq: stored.seq,
+            agent: stored.agent_id.clone(),
+            merged_version: stored.version.clone(),
+            merged_parents: stored.parents.clone(),
+            version: update.version.clone(),
+            parents: update.parents.clone(),
+            merge_type: update.merge_type.clone(),
+            patches: update.patches.as_ref().map(|patches| {
+                patches
+                    .iter()
+                    .map(|patch| PatchRecord {
+                        unit: patch.unit.clone(),
+                        range: patch.range.clone(),
+                        content: STANDARD.encode(&patch.content),
+                    })
+                    .collect()
+            }),
+            body: update.body.as_ref().map(|body| STANDARD.encode(body)),
+            content_range: update.content_range.clone(),
+            content_type: update.content_type.clone(),
+            headers: update.extra_headers.clone(),
+        }
+    }
+
+    pub(super) fn into_stored(self) -> Result<StoredUpdate> {
+        let patches = match self.patches {
+            Some(patches) => Some(
+                patches
+                    .into_iter()
+                    .map(|patch| {
+                        Ok(Patch::new(
+                            patch.unit,
+                            patch.range,
+                            decode_base64(&patch.content)?,
+                        ))
+                    })
+                    .collect::<Result<_>>()?,
+            ),
+            None => None,
+        };
+        let body = self.body.as_deref().map(decode_base64).transpose()?;
+        Ok(StoredUpdate {
+            seq: self.seq,
+            agent_id: self.agent,
+            version: self.merged_version,
+            parents: self.merged_parents,
+            update: Update {
+                version: self.version,
+                parents: self.parents,
+                merge_type: self.merge_type,
+                patches,
+                body,
+                content_range: self.content_range,
+                content_type: self.content_type,
+                extra_headers: self.headers,
+                ..Default::default()
+            },
+        })
+    }
+}
+
+/// A [`ResourceSnapshot`] as written to the snapshot file.
+#[derive(Serialize, Deserialize)]
+pub(super) struct SnapshotRecord {
+    seq: u64,
+    merge_type: String,
+    version: Vec<Version>,
+    /// Base64-encoded oplog
+    data: String,
+}
+
+impl SnapshotRecord {
+    pub(super) fn from_snapshot(snapshot: &ResourceSnapshot) -> Self {
+        Self {
+            seq: snapshot.seq,
+            merge_type: snapshot.merge_type.clone(),
+            version: snapshot.version.clone(),
+            data: STANDARD.encode(&snapshot.data),
+        }
+    }
+
+    pub(super) fn into_snapshot(self) -> Result<ResourceSnapshot> {
+        Ok(ResourceSnapshot {
+            seq: self.seq,
+            merge_type: self.merge_type,
+            version: self.version,
+            data: decode_base64(&self.data)?,
+        })
+    }
+}
+
+fn decode_base64(data: &str) -> Result<Bytes> {
+    STANDARD
+        .decode(data)
+        .map(Bytes::from)
+        .map_err(|e| BraidError::BodyParse(format!("Invalid base64 in stored record: {}", e)))
+}
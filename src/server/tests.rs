@@ -10,6 +10,15 @@
 //! - ParseUpdate functionality
 //! - ResourceStateManager
 //! - ConflictResolver
+//! - Historical snapshots
+//! - Persistent resource stores
+//! - Update validation
+//! - Presence
+//! - Authorship attribution
+//! - Server-to-server replication
+//! - Fan-out caching proxy
+//! - Directories of files as resources
+//! - Binary resources with byte-range patches
 
 #[cfg(test)]
 mod config_tests {
@@ -700,8 +709,8 @@ mod resource_state_manager_extended_tests {
 
         // Both should reference the same resource
         // Adding content via one should be visible in the other
-        resource1.write().crdt.add_insert(0, "test");
-        let content = resource2.read().crdt.content();
+        resource1.write().crdt_mut().unwrap().add_insert(0, "test");
+        let content = resource2.read().crdt().unwrap().content();
         assert_eq!(content, "test");
     }
 
@@ -727,7 +736,7 @@ mod resource_state_manager_extended_tests {
 #[cfg(test)]
 mod conflict_resolver_extended_tests {
     use crate::server::{ConflictResolver, ResourceStateManager};
-    use crate::types::{Update, Version};
+    use crate::types::{Patch, Update, Version};
 
     #[tokio::test]
     async fn test_resolve_non_diamond_passthrough() {
@@ -741,8 +750,104 @@ mod conflict_resolver_extended_tests {
         assert!(result.is_ok());
 
         let resolved = result.unwrap();
-        // Should be unchanged since it's not diamond merge type
+        // Merged by sync9 rather than diamond
         assert_eq!(resolved.merge_type, Some("sync9".to_string()));
+        assert_eq!(resolved.version, vec![Version::new("v1")]);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_ot_text_transforms_stale_patch() {
+        let resolver = ConflictResolver::new(ResourceStateManager::new());
+        let edit = |version: &str, parent: Option<&str>, range: &str, text: &str| {
+            let update = Update::patched(Version::new(version), vec![Patch::text(range, text)])
+                .with_merge_type("ot-text");
+            match parent {
+                Some(parent) => update.with_parent(Version::new(parent)),
+                None => update,
+            }
+        };
+
+        resolver.resolve_update("doc1", &edit("v1", None, "[0:0]", "world"), "alice").await.unwrap();
+        resolver.resolve_update("doc1", &edit("v2", Some("v1"), "[0:0]", "hello "), "alice").await.unwrap();
+
+        // bob hasn't seen v2 yet
+        let resolved = resolver
+            .resolve_update("doc1", &edit("v3", Some("v1"), "[5:5]", "!"), "bob")
+            .await
+            .unwrap();
+
+        assert_eq!(resolved.parents, vec![Version::new("v2")]);
+        assert_eq!(resolved.patches.unwrap(), vec![Patch::text("[11:11]", "!")]);
+        assert_eq!(resolver.get_resource_content("doc1").unwrap(), "hello world!");
+    }
+
+    #[tokio::test]
+    async fn test_resolve_diamond_patch_at_parents() {
+        let resolver = ConflictResolver::new(ResourceStateManager::new());
+        let first = resolver
+            .resolve_update("doc1", &Update::snapshot(Version::new("v1"), "world").with_merge_type("diamond"), "alice")
+            .await
+            .unwrap();
+        let edit = |range: &str, text: &str| {
+            Update::patched(Version::new("_"), vec![Patch::text(range, text)])
+                .with_parents(first.version.clone())
+                .with_merge_type("diamond")
+        };
+
+        resolver.resolve_update("doc1", &edit("[0:0]", "hello "), "alice").await.unwrap();
+        // bob hasn't seen "hello " yet
+        let resolved = resolver.resolve_update("doc1", &edit("[5:5]", "!"), "bob").await.unwrap();
+
+        assert_eq!(resolved.version, vec![Version::new("bob-0")]);
+        assert_eq!(resolved.parents, first.version);
+        assert_eq!(resolved.patches.unwrap(), vec![Patch::text("[5:5]", "!")]);
+        assert!(resolved.body.is_none());
+        assert_eq!(resolver.get_resource_content("doc1").unwrap(), "hello world!");
+    }
+
+    #[tokio::test]
+    async fn test_resolve_diamond_content_range_unit() {
+        use crate::types::ContentRange;
+
+        let resolver = ConflictResolver::new(ResourceStateManager::new());
+        resolver
+            .resolve_update("doc1", &Update::snapshot(Version::new("v1"), "👋世界").with_merge_type("diamond"), "alice")
+            .await
+            .unwrap();
+
+        // A browser sends a UTF-16 offset: after "👋世"
+        let update = Update {
+            body: Some("!".into()),
+            content_range: Some(ContentRange::new("text-utf16", "[3:3]")),
+            merge_type: Some("diamond".to_string()),
+            ..Default::default()
+        };
+        let resolved = resolver.resolve_update("doc1", &update, "browser").await.unwrap();
+
+        assert_eq!(resolver.get_resource_content("doc1").unwrap(), "👋世!界");
+        assert_eq!(resolved.patches.unwrap()[0].unit, "text-utf16");
+    }
+
+    #[tokio::test]
+    async fn test_resolve_lww_reports_winner_to_loser() {
+        let resolver = ConflictResolver::new(ResourceStateManager::new());
+        let put = |version: &str, body: &'static str| {
+            Update::snapshot(Version::new(version), body)
+                .with_parent(Version::new("v1"))
+                .with_merge_type("lww")
+        };
+
+        resolver
+            .resolve_update("blob", &Update::snapshot(Version::new("v1"), "old").with_merge_type("lww"), "alice")
+            .await
+            .unwrap();
+        resolver.resolve_update("blob", &put("v2-bob", "bob"), "bob").await.unwrap();
+        let lost = resolver.resolve_update("blob", &put("v2-alice", "alice"), "alice").await.unwrap();
+
+        assert_eq!(lost.status, crate::protocol::STATUS_MERGE_CONFLICT);
+        assert_eq!(lost.current_version, Some(vec![Version::new("v2-bob")]));
+        assert_eq!(resolver.get_resource_content("blob").unwrap(), "bob");
+        assert_eq!(resolver.get_resource_version("blob"), Some(Version::new("v2-bob")));
     }
 
     #[tokio::test]
@@ -898,10 +1003,12 @@ mod conflict_resolver_extended_tests {
         let manager = ResourceStateManager::new();
         let resolver = ConflictResolver::new(manager);
 
-        let mut update = Update::default();
-        update.merge_type = Some("diamond".to_string());
-        update.version = vec![Version::new("v1")];
         // No body set
+        let update = Update {
+            merge_type: Some("diamond".to_string()),
+            version: vec![Version::new("v1")],
+            ..Default::default()
+        };
 
         let result = resolver.resolve_update("doc1", &update, "alice").await;
         // Should handle gracefully even with no body
@@ -922,3 +1029,1166 @@ mod conflict_resolver_extended_tests {
         assert!(result.is_ok());
     }
 }
+
+#[cfg(test)]
+mod time_travel_tests {
+    use super::test_server::oneshot;
+    use crate::merge::{MergeType, MergeTypeRegistry};
+    use crate::server::{BraidLayer, BraidState, ResourceStateManager};
+    use crate::types::{Patch, Update, Version};
+    use axum::extract::Extension;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use axum::Router;
+    use std::sync::Arc;
+
+    async fn get_doc(
+        Extension(braid): Extension<Arc<BraidState>>,
+        Extension(manager): Extension<Arc<ResourceStateManager>>,
+    ) -> Update {
+        let resource = manager.get_resource("doc").unwrap();
+        match &braid.version {
+            Some(version) => manager.snapshot_at("doc", version),
+            None => resource.read().crdt().unwrap().snapshot(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_with_version_returns_historical_snapshot() {
+        let layer = BraidLayer::new();
+        let merge_types = MergeTypeRegistry::new();
+        let edit = |range: &str, text: &str| {
+            Update::patched(Version::new("_"), vec![Patch::text(range, text)]).with_merge_type("diamond")
+        };
+        let first = layer
+            .resource_manager
+            .apply_merge_update("doc", &merge_types, &edit("[0:0]", "hello"), "alice")
+            .unwrap();
+        layer
+            .resource_manager
+            .apply_merge_update("doc", &merge_types, &edit("[5:5]", " world"), "alice")
+            .unwrap();
+        let app = Router::new()
+            .route("/doc", get(get_doc))
+            .layer(axum::middleware::from_fn(layer.middleware()));
+
+        let fetched = oneshot(&app, "GET", "/doc", &[], "").await;
+        assert_eq!(fetched, (StatusCode::OK, "hello world".to_string()));
+        let version = crate::protocol::format_version_header(&first.version);
+        let fetched = oneshot(&app, "GET", "/doc", &[("version", &version)], "").await;
+        assert_eq!(fetched, (StatusCode::OK, "hello".to_string()));
+        let unknown = [("version", "\"carol-0\"")];
+        assert_eq!(oneshot(&app, "GET", "/doc", &unknown, "").await.0, StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_snapshot_at_statuses() {
+        let manager = ResourceStateManager::new();
+        assert_eq!(manager.snapshot_at("missing", &[]).status, 404);
+
+        let merge_types = MergeTypeRegistry::new();
+        let json = Update::snapshot(Version::new("j1"), "{\"a\": 1}").with_merge_type("json");
+        manager.apply_merge_update("settings", &merge_types, &json, "alice").unwrap();
+        let json = Update::snapshot(Version::new("j2"), "{\"a\": 2}")
+            .with_parent(Version::new("j1"))
+            .with_merge_type("json");
+        manager.apply_merge_update("settings", &merge_types, &json, "alice").unwrap();
+
+        let current = manager.snapshot_at("settings", &[Version::new("j2")]);
+        assert_eq!(current.status, 200);
+        assert_eq!(current.body_str(), Some("{\"a\":2}"));
+        // JSON resources keep only the merged value
+        assert_eq!(manager.snapshot_at("settings", &[Version::new("j1")]).status, 410);
+        assert_eq!(manager.snapshot_at("settings", &[Version::new("j9")]).status, 404);
+    }
+}
+
+#[cfg(test)]
+mod persistence_tests {
+    use crate::merge::MergeTypeRegistry;
+    use crate::server::store::{FileStore, MemoryStore};
+    use crate::server::{ConflictResolver, ResourceChange, ResourceStateManager, ResourceStore};
+    use crate::types::{Patch, Update, Version};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_file_store_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let open = || {
+            let store = FileStore::open(dir.path()).unwrap();
+            ConflictResolver::new(ResourceStateManager::with_store(Arc::new(store)).with_snapshot_interval(3))
+        };
+
+        let resolver = open();
+        let mut parents = Vec::new();
+        for (i, word) in ["one ", "two ", "three ", "four"].iter().enumerate() {
+            let at = ["", "one ", "one two ", "one two three "][i].len();
+            let update = Update::patched(Version::new("_"), vec![Patch::text(format!("[{at}:{at}]"), *word)])
+                .with_parents(parents)
+                .with_merge_type("diamond");
+            parents = resolver.resolve_update("notes/today", &update, "alice").await.unwrap().version;
+        }
+        drop(resolver);
+
+        let resolver = open();
+        assert_eq!(resolver.get_resource_content("notes/today"), None);
+        let update = Update::patched(Version::new("_"), vec![Patch::text("[0:3]", "ONE")])
+            .with_parents(parents)
+            .with_merge_type("diamond");
+        let resolved = resolver.resolve_update("notes/today", &update, "bob").await.unwrap();
+        assert_eq!(resolved.version, vec![Version::new("bob-5")]);
+        assert_eq!(
+            resolver.get_resource_content("notes/today").as_deref(),
+            Some("ONE two three four")
+        );
+
+        let manager = ResourceStateManager::with_store(Arc::new(FileStore::open(dir.path()).unwrap()));
+        let loaded = manager
+            .load_resource("notes/today", &MergeTypeRegistry::new())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(loaded.read().crdt().unwrap().content(), "ONE two three four");
+    }
+
+    #[tokio::test]
+    async fn test_observers_see_updates_once_stored() {
+        let store = Arc::new(MemoryStore::new());
+        let manager = ResourceStateManager::with_store(store.clone());
+        let seen = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let log = seen.clone();
+        manager.add_observer(Arc::new(move |change: ResourceChange| {
+            let (store, log) = (store.clone(), log.clone());
+            async move {
+                let stored = store.load(&change.resource_id).await.map_err(|e| e.to_string())?;
+                log.lock().push((change, stored.map_or(0, |s| s.last_seq())));
+                Ok(())
+            }
+        }));
+
+        let resolver = ConflictResolver::new(manager.clone());
+        let update = Update::snapshot(Version::new("_"), "hi").with_merge_type("diamond");
+        let resolved = resolver.resolve_update("doc", &update, "alice").await.unwrap();
+        manager.observers_idle().await;
+
+        let seen = seen.lock();
+        let (change, stored_seq) = &seen[0];
+        assert_eq!(seen.len(), 1);
+        assert_eq!(*stored_seq, 1);
+        assert_eq!(change.resource_id, "doc");
+        assert_eq!(change.agent_id, "alice");
+        assert_eq!(change.version, resolved.version);
+        assert_eq!(change.update.version, resolved.version);
+    }
+
+    #[tokio::test]
+    async fn test_compacted_log_truncated_at_every_offset_recovers() {
+        let dir = tempfile::tempdir().unwrap();
+        let merge_types = MergeTypeRegistry::new();
+        let manager = ResourceStateManager::with_store(Arc::new(FileStore::open(dir.path()).unwrap()))
+            .with_snapshot_interval(3);
+
+        let mut states = Vec::new();
+        let mut parents = Vec::new();
+        for (i, word) in ["a", "b", "c", "d", "e"].iter().enumerate() {
+            let update = Update::patched(Version::new("_"), vec![Patch::text(format!("[{i}:{i}]"), *word)])
+                .with_parents(parents)
+                .with_merge_type("diamond");
+            parents = manager
+                .apply_and_persist("doc", &merge_types, &update, "alice")
+                .await
+                .unwrap()
+                .version;
+            let resource = manager.get_resource("doc").unwrap();
+            let content = resource.read().crdt().unwrap().content();
+            states.push((content, parents.clone()));
+        }
+        drop(manager);
+
+        // The snapshot holds the first three updates; the log, the last two
+        let path = dir.path().join("doc.log");
+        let log = std::fs::read(&path).unwrap();
+        let mut seen = 2;
+        for cut in 0..=log.len() {
+            std::fs::write(&path, &log[..cut]).unwrap();
+            let manager = ResourceStateManager::with_store(Arc::new(FileStore::open(dir.path()).unwrap()));
+            let loaded = manager.load_resource("doc", &merge_types).await.unwrap().unwrap();
+            let state = (loaded.read().crdt().unwrap().content(), loaded.read().crdt().unwrap().version());
+
+            let at = states.iter().position(|s| *s == state).expect("a state the document had");
+            assert!(at >= seen, "cut at {} went back to state {}", cut, at);
+            seen = at;
+        }
+        assert_eq!(seen, states.len() - 1);
+    }
+}
+
+#[cfg(test)]
+mod validation_tests {
+    use super::test_server::oneshot;
+    use crate::server::store::MemoryStore;
+    use crate::server::validation::Candidate;
+    use crate::server::{
+        BraidLayer, BraidState, ConflictResolver, Rejection, ResourceChange, ResourceStateManager,
+        ResourceStore, UpdateBroadcast,
+    };
+    use crate::types::{Patch, Update, Version};
+    use axum::body::Bytes;
+    use axum::extract::{Extension, State};
+    use axum::http::StatusCode;
+    use axum::routing::put;
+    use axum::Router;
+    use std::sync::Arc;
+    use tokio::sync::broadcast;
+
+    fn parses_as_json(candidate: &Candidate<'_>) -> Result<(), String> {
+        serde_json::from_slice::<serde_json::Value>(&candidate.body())
+            .map(|_| ())
+            .map_err(|e| format!("Config is not valid JSON: {}", e))
+    }
+
+    fn at_most_lines(lines: usize) -> impl Fn(&Candidate<'_>) -> Result<(), String> {
+        move |candidate| {
+            let count = candidate.body().split(|&b| b == b'\n').count();
+            if count > lines {
+                return Err(format!("At most {} lines allowed, got {}", lines, count));
+            }
+            Ok(())
+        }
+    }
+
+    async fn put_config(
+        Extension(braid): Extension<Arc<BraidState>>,
+        State((resolver, updates)): State<(ConflictResolver, UpdateBroadcast)>,
+        body: Bytes,
+    ) -> Result<Update, Rejection> {
+        let update = Update {
+            version: braid.version.clone().unwrap_or_default(),
+            body: Some(body),
+            merge_type: Some("lww".to_string()),
+            ..Default::default()
+        };
+        let resolved = resolver.resolve("/config/app.json", &update, "client").await?;
+        let _ = updates.send(Arc::new(resolved.clone()));
+        Ok(resolved)
+    }
+
+    #[tokio::test]
+    async fn test_rejected_put_returns_422_and_is_never_broadcast() {
+        let layer = BraidLayer::new();
+        let resolver = layer.conflict_resolver().with_validator("/config/*", parses_as_json);
+        let (updates, mut subscriber) = broadcast::channel(16);
+        let app = Router::new()
+            .route("/config/app.json", put(put_config))
+            .with_state((resolver.clone(), updates))
+            .layer(axum::middleware::from_fn(layer.middleware()));
+
+        let send = |version: &str, body: &'static str| {
+            let (app, version) = (&app, format!("\"{}\"", version));
+            async move { oneshot(app, "PUT", "/config/app.json", &[("version", &version)], body).await }
+        };
+
+        assert_eq!(send("v1", r#"{"port": 80}"#).await.0, StatusCode::OK);
+
+        let (status, body) = send("v2", r#"{"port": "#).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(body.starts_with("Config is not valid JSON"));
+
+        assert_eq!(send("v3", r#"{"port": 8080}"#).await.0, StatusCode::OK);
+
+        let versions: Vec<Vec<Version>> = std::iter::from_fn(|| subscriber.try_recv().ok())
+            .map(|update| update.version.clone())
+            .collect();
+        assert_eq!(versions, vec![vec![Version::new("v1")], vec![Version::new("v3")]]);
+        assert_eq!(
+            resolver.get_resource_content("/config/app.json").as_deref(),
+            Some(r#"{"port": 8080}"#)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rejected_update_is_rolled_back_and_not_stored() {
+        let store = Arc::new(MemoryStore::new());
+        let manager = ResourceStateManager::with_store(store.clone());
+        let seen = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let log = seen.clone();
+        manager.add_observer(Arc::new(move |change: ResourceChange| {
+            let log = log.clone();
+            async move {
+                log.lock().push(change.update.version);
+                Ok(())
+            }
+        }));
+        let resolver = ConflictResolver::new(manager.clone()).with_validator("notes", at_most_lines(2));
+
+        let edit = |range: &str, text: &str, parents: Vec<Version>| {
+            Update::patched(Version::new("_"), vec![Patch::text(range, text)])
+                .with_parents(parents)
+                .with_merge_type("diamond")
+        };
+        let first = resolver.resolve("notes", &edit("[0:0]", "a\nb", vec![]), "alice").await.unwrap();
+
+        let rejection = resolver
+            .resolve("notes", &edit("[3:3]", "\nc", first.version.clone()), "bob")
+            .await
+            .unwrap_err();
+        assert_eq!(rejection, Rejection::invalid("At most 2 lines allowed, got 3"));
+        assert_eq!(resolver.get_resource_content("notes").as_deref(), Some("a\nb"));
+        assert_eq!(manager.get_resource("notes").unwrap().read().version(), first.version);
+
+        // The rolled-back version is free to be used by the next update
+        let next = resolver
+            .resolve("notes", &edit("[3:3]", "!", first.version.clone()), "bob")
+            .await
+            .unwrap();
+        assert_eq!(resolver.get_resource_content("notes").as_deref(), Some("a\nb!"));
+        manager.observers_idle().await;
+
+        assert_eq!(*seen.lock(), vec![first.version, next.version]);
+        assert_eq!(store.load("notes").await.unwrap().unwrap().updates.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_edits_are_validated_as_merged() {
+        let manager = ResourceStateManager::new();
+        let resolver = ConflictResolver::new(manager.clone()).with_validator("notes", at_most_lines(2));
+        let edit = |version: &str, range: &str, text: &str, parents: Vec<Version>| {
+            Update::patched(Version::new(version), vec![Patch::text(range, text)])
+                .with_parents(parents)
+                .with_merge_type("sync9")
+        };
+        let base = resolver.resolve("notes", &edit("a-1", "[0:0]", "ab", vec![]), "alice").await.unwrap();
+        resolver
+            .resolve("notes", &edit("a-2", "[2:2]", "\nc", base.version.clone()), "alice")
+            .await
+            .unwrap();
+
+        // Two lines where it was made, but three once merged with alice's edit
+        let rejection = resolver
+            .resolve("notes", &edit("b-1", "[0:0]", "x\n", base.version.clone()), "bob")
+            .await
+            .unwrap_err();
+        assert_eq!(rejection, Rejection::invalid("At most 2 lines allowed, got 3"));
+        assert_eq!(resolver.get_resource_content("notes").as_deref(), Some("ab\nc"));
+    }
+
+    #[tokio::test]
+    async fn test_validators_check_json_resources() {
+        let numeric_port = |candidate: &Candidate<'_>| {
+            let value: serde_json::Value = serde_json::from_slice(&candidate.body()).map_err(|e| e.to_string())?;
+            if !value["port"].is_u64() {
+                return Err("port must be a number".to_string());
+            }
+            Ok(())
+        };
+        let resolver = ConflictResolver::new(ResourceStateManager::new()).with_validator("*", numeric_port);
+        let json = |version: &str, patch: Patch, parent: &str| {
+            Update::patched(Version::new(version), vec![patch])
+                .with_parent(Version::new(parent))
+                .with_merge_type("json")
+        };
+
+        let base = Update::snapshot(Version::new("v1"), r#"{"port": 80}"#).with_merge_type("json");
+        resolver.resolve("cfg", &base, "alice").await.unwrap();
+        // At the current version, the patch is previewed
+        let rejection = resolver
+            .resolve("cfg", &json("v2", Patch::json(".port", r#""http""#), "v1"), "bob")
+            .await
+            .unwrap_err();
+        assert_eq!(rejection, Rejection::invalid("port must be a number"));
+        resolver
+            .resolve("cfg", &json("v3", Patch::json(".port", "8080"), "v1"), "bob")
+            .await
+            .unwrap();
+        // Behind it, the patch is merged into a copy of the resource
+        let rejection = resolver
+            .resolve("cfg", &json("v4", Patch::json(".port", "null"), "v1"), "carol")
+            .await
+            .unwrap_err();
+        assert_eq!(rejection.status, 422);
+        assert_eq!(resolver.get_resource_content("cfg").as_deref(), Some(r#"{"port":8080}"#));
+    }
+
+    #[tokio::test]
+    async fn test_rejected_first_update_creates_no_resource() {
+        let resolver =
+            ConflictResolver::new(ResourceStateManager::new()).with_validator("*", at_most_lines(1));
+        let update = Update::snapshot(Version::new("v1"), "a\nb").with_merge_type("sync9");
+        let rejection = resolver.resolve("doc", &update, "alice").await.unwrap_err();
+
+        assert_eq!(rejection.status, 422);
+        assert!(resolver.get_resource_content("doc").is_none());
+        assert_eq!(
+            resolver.resolve_update("doc", &update, "alice").await.unwrap_err(),
+            "At most 1 lines allowed, got 2"
+        );
+    }
+}
+
+#[cfg(test)]
+mod presence_tests {
+    use super::test_server::oneshot;
+    use crate::server::{BraidLayer, BraidState, Rejection, ResourceStateManager, SubscriptionResponse, UpdateBroadcast};
+    use crate::types::{Update, Version};
+    use axum::body::{Body, Bytes};
+    use axum::extract::{Extension, Request, State};
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse;
+    use axum::routing::get;
+    use axum::Router;
+    use futures::{stream, StreamExt};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::broadcast;
+    use tower::ServiceExt;
+
+    type AppState = (ResourceStateManager, UpdateBroadcast);
+
+    async fn subscribe_doc(
+        Extension(braid): Extension<Arc<BraidState>>,
+        State((manager, updates)): State<AppState>,
+    ) -> impl IntoResponse {
+        let document = stream::unfold(updates.subscribe(), |mut updates| async move {
+            let update = updates.recv().await.ok()?;
+            Some((Ok((*update).clone()), updates))
+        });
+        let presence = manager.presence().subscribe("/doc", braid.peer.as_deref());
+        SubscriptionResponse::new(stream::select(document, presence))
+    }
+
+    async fn put_presence(
+        Extension(braid): Extension<Arc<BraidState>>,
+        State((manager, _)): State<AppState>,
+        body: Bytes,
+    ) -> Result<StatusCode, Rejection> {
+        let peer = braid
+            .peer
+            .as_deref()
+            .ok_or_else(|| Rejection::bad_request("Presence needs a Peer header"))?;
+        let state = serde_json::from_slice(&body).map_err(|e| Rejection::bad_request(e.to_string()))?;
+        manager.presence().publish("/doc", peer, state)?;
+        Ok(StatusCode::OK)
+    }
+
+    fn app(manager: &ResourceStateManager, updates: &UpdateBroadcast) -> Router {
+        let layer = BraidLayer::new();
+        Router::new()
+            .route("/doc", get(subscribe_doc).put(put_presence))
+            .with_state((manager.clone(), updates.clone()))
+            .layer(axum::middleware::from_fn(layer.middleware()))
+    }
+
+    async fn next_frame(body: &mut (impl futures::Stream<Item = Result<Bytes, axum::Error>> + Unpin)) -> String {
+        let frame = tokio::time::timeout(Duration::from_secs(5), body.next())
+            .await
+            .expect("no frame within 5s")
+            .unwrap()
+            .unwrap();
+        String::from_utf8(frame.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_presence_is_streamed_beside_updates_and_kept_out_of_history() {
+        let manager = ResourceStateManager::new();
+        let (updates, _) = broadcast::channel(16);
+        let app = app(&manager, &updates);
+        manager.apply_update("/doc", "hello", "alice").unwrap();
+        let version = manager.get_resource("/doc").unwrap().read().version();
+
+        let request = Request::builder().uri("/doc").header("peer", "alice").body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status().as_u16(), 209);
+        let mut body = response.into_body().into_data_stream();
+        assert!(next_frame(&mut body).await.ends_with("\r\n\r\n{}"));
+
+        let (status, _) = oneshot(&app, "PUT", "/doc", &[("peer", "bob")], r#"{"cursor": 3}"#).await;
+        assert_eq!(status, StatusCode::OK);
+        let frame = next_frame(&mut body).await;
+        let headers = frame.to_lowercase();
+        assert!(headers.contains("content-type: application/presence+json"));
+        assert!(!headers.contains("version"));
+        assert!(frame.ends_with(r#"{"bob":{"cursor":3}}"#));
+
+        let _ = updates.send(Arc::new(Update::snapshot(Version::new("v2"), "world")));
+        assert!(next_frame(&mut body).await.ends_with("world"));
+
+        // Presence never reaches the resource's history
+        assert_eq!(manager.get_resource("/doc").unwrap().read().version(), version);
+        assert_eq!(manager.get_resource("/doc").unwrap().read().crdt().unwrap().content(), "hello");
+        assert_eq!(manager.presence().peers("/doc").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_closing_the_subscription_removes_the_peer() {
+        let manager = ResourceStateManager::new();
+        let (updates, _) = broadcast::channel(16);
+        let app = app(&manager, &updates);
+        let mut watcher = manager.presence().subscribe("/doc", None);
+        watcher.next().await;
+
+        let request = Request::builder().uri("/doc").header("peer", "alice").body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        manager.presence().publish("/doc", "alice", serde_json::json!({"cursor": 0})).unwrap();
+        assert!(manager.presence().peers("/doc").contains_key("alice"));
+        watcher.next().await;
+
+        drop(response);
+        assert!(manager.presence().peers("/doc").is_empty());
+        let left = watcher.next().await.unwrap().unwrap();
+        assert_eq!(left.body_str(), Some(r#"{"alice":null}"#));
+    }
+}
+
+#[cfg(test)]
+mod blame_tests {
+    use super::test_server::oneshot;
+    use crate::merge::MergeTypeRegistry;
+    use crate::server::blame::{self, Blame};
+    use crate::server::{BraidLayer, BraidState, ResourceStateManager};
+    use crate::types::{Update, Version};
+    use axum::extract::Extension;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::response::{IntoResponse, Response};
+    use axum::routing::get;
+    use axum::Router;
+    use std::sync::Arc;
+
+    async fn get_doc(
+        Extension(manager): Extension<Arc<ResourceStateManager>>,
+        Extension(braid): Extension<Arc<BraidState>>,
+    ) -> Response {
+        if braid.wants_blame() {
+            return manager.blame("/doc", braid.version.as_deref()).into_response();
+        }
+        manager.get_resource("/doc").unwrap().read().crdt().unwrap().content().into_response()
+    }
+
+    fn app(layer: &BraidLayer) -> Router {
+        Router::new()
+            .route("/doc", get(get_doc))
+            .nest("/blame", Router::new().route("/{*path}", get(blame::handler)))
+            .layer(axum::middleware::from_fn(layer.middleware()))
+    }
+
+    #[tokio::test]
+    async fn test_blame_endpoint_and_header_mode() {
+        let layer = BraidLayer::new();
+        let manager = &layer.resource_manager;
+        manager.apply_update("/doc", "hello", "alice").unwrap();
+        let first = manager.get_resource("/doc").unwrap().read().version();
+        manager.apply_remote_insert("/doc", "bob", 5, " world").unwrap();
+        let app = app(&layer);
+
+        let (status, body) = oneshot(&app, "GET", "/blame/doc", &[], "").await;
+        assert_eq!(status, StatusCode::OK);
+        let blame: Blame = serde_json::from_str(&body).unwrap();
+        let spans: Vec<_> = blame.spans.iter().map(|s| (s.start, s.end, s.agent.as_str())).collect();
+        assert_eq!(spans, vec![(0, 5, "alice"), (5, 11, "bob")]);
+        assert_eq!(blame.version, manager.get_resource("/doc").unwrap().read().version());
+
+        // The same through the resource's own URL, at the first version
+        let version = format!("\"{}\"", first[0]);
+        let accept = "text/html, application/blame+json;q=0.9";
+        let headers = [("accept", accept), ("version", &version)];
+        let (status, body) = oneshot(&app, "GET", "/doc", &headers, "").await;
+        assert_eq!(status, StatusCode::OK);
+        let blame: Blame = serde_json::from_str(&body).unwrap();
+        assert_eq!(blame.version, first);
+        assert_eq!(blame.spans.len(), 1);
+
+        assert_eq!(oneshot(&app, "GET", "/doc", &[], "").await.1, "hello world");
+        let unknown = [("accept", blame::CONTENT_TYPE), ("version", "\"zed-0\"")];
+        assert_eq!(oneshot(&app, "GET", "/doc", &unknown, "").await.0, StatusCode::NOT_FOUND);
+        assert_eq!(oneshot(&app, "GET", "/blame/missing", &[], "").await.0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_blame_of_non_text_resource_is_rejected() {
+        let manager = ResourceStateManager::new();
+        let update = Update::snapshot(Version::new("v1"), r#"{"a": 1}"#).with_merge_type("json");
+        manager.apply_merge_update("config", &MergeTypeRegistry::new(), &update, "alice").unwrap();
+        assert_eq!(manager.blame("config", None).status, 400);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("accept", "application/json".parse().unwrap());
+        assert!(!BraidState::from_headers(&headers).wants_blame());
+    }
+}
+
+#[cfg(test)]
+mod test_server {
+    //! A Braid server of text resources, as replicators and proxies expect upstream.
+
+    use crate::client::{BraidClient, Subscription};
+    use crate::merge::text::apply_text_patches;
+    use crate::merge::{DiamondCRDT, MergeTypeRegistry};
+    use crate::server::{
+        BraidLayer, BraidState, Rejection, ResourceStateManager, SubscriptionResponse, UpdateBroadcast,
+    };
+    use crate::types::{BraidRequest, Patch, Update, Version};
+    use axum::body::{to_bytes, Body, Bytes};
+    use axum::extract::{Extension, Request, State};
+    use axum::http::{StatusCode, Uri};
+    use axum::response::{IntoResponse, Response};
+    use axum::routing::get;
+    use axum::Router;
+    use futures::{stream, StreamExt};
+    use parking_lot::{Mutex, RwLock};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::broadcast;
+    use tower::ServiceExt;
+
+    #[derive(Clone)]
+    pub(super) struct Server {
+        pub(super) manager: ResourceStateManager,
+        updates: Arc<RwLock<UpdateBroadcast>>,
+        /// Parents of each subscription, in order
+        pub(super) subscriptions: Arc<Mutex<Vec<Vec<Version>>>>,
+    }
+
+    impl Server {
+        pub(super) fn new() -> Self {
+            Self {
+                manager: ResourceStateManager::new(),
+                updates: Arc::new(RwLock::new(broadcast::channel(16).0)),
+                subscriptions: Arc::default(),
+            }
+        }
+
+        pub(super) async fn serve(&self) -> String {
+            let app = Router::new()
+                .route("/{*path}", get(subscribe).put(put))
+                .with_state(self.clone())
+                .layer(axum::middleware::from_fn(BraidLayer::new().middleware()));
+            serve_router(app).await
+        }
+
+        /// End every open subscription.
+        pub(super) fn disconnect(&self) {
+            *self.updates.write() = broadcast::channel(16).0;
+        }
+
+        pub(super) fn content(&self) -> Option<String> {
+            self.manager.get_resource("/doc").map(|r| r.read().crdt().unwrap().content())
+        }
+
+        pub(super) fn version(&self) -> Vec<Version> {
+            self.manager.get_resource("/doc").map(|r| r.read().version()).unwrap_or_default()
+        }
+    }
+
+    async fn subscribe(
+        Extension(braid): Extension<Arc<BraidState>>,
+        State(server): State<Server>,
+        uri: Uri,
+    ) -> Response {
+        let parents = braid.parents.clone().unwrap_or_default();
+        server.subscriptions.lock().push(parents.clone());
+        let live = server.updates.read().subscribe();
+        let catch_up = match server.manager.updates_since(uri.path(), &parents).await {
+            Ok(updates) => updates,
+            Err(e) => return Rejection::bad_request(e).into_response(),
+        };
+        let live = stream::unfold(live, |mut updates| async move {
+            let update = updates.recv().await.ok()?;
+            Some((Ok((*update).clone()), updates))
+        });
+        SubscriptionResponse::new(stream::iter(catch_up.into_iter().map(Ok)).chain(live)).into_response()
+    }
+
+    async fn put(
+        Extension(braid): Extension<Arc<BraidState>>,
+        State(server): State<Server>,
+        uri: Uri,
+        body: Bytes,
+    ) -> Result<Response, Rejection> {
+        let mut update = braid.update(body).map_err(|e| Rejection::bad_request(e.to_string()))?;
+        update.merge_type.get_or_insert_with(|| "diamond".to_string());
+        // Replicated updates are applied as the agent of their version
+        let agent_id = update
+            .primary_version()
+            .and_then(DiamondCRDT::version_agent)
+            .or(braid.peer.as_deref())
+            .unwrap_or("server")
+            .to_string();
+        let known = update.primary_version().is_some_and(|version| {
+            server
+                .manager
+                .get_resource(uri.path())
+                .is_some_and(|r| r.read().document.contains_version(version))
+        });
+        if !known {
+            let resolved = server
+                .manager
+                .apply_merge_update(uri.path(), &MergeTypeRegistry::new(), &update, &agent_id)
+                .map_err(Rejection::bad_request)?;
+            // A write that lost is answered to its writer alone
+            if resolved.is_conflict() {
+                return Ok(resolved.into_response());
+            }
+            let _ = server.updates.read().send(Arc::new(resolved));
+        }
+        Ok(StatusCode::OK.into_response())
+    }
+
+    /// Send a request straight to `app`, returning the status and body of its response.
+    pub(super) async fn oneshot(
+        app: &Router,
+        method: &str,
+        uri: &str,
+        headers: &[(&str, &str)],
+        body: &str,
+    ) -> (StatusCode, String) {
+        let mut request = Request::builder().method(method).uri(uri);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let request = request.body(Body::from(body.to_string())).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    /// Serve `app` on a free local port, returning its base URL.
+    pub(super) async fn serve_router(app: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        url
+    }
+
+    /// Receive the next update, failing if none arrives within five seconds.
+    pub(super) async fn next_update(subscription: &mut Subscription) -> Update {
+        tokio::time::timeout(Duration::from_secs(5), subscription.next())
+            .await
+            .expect("no update received")
+            .unwrap()
+            .unwrap()
+    }
+
+    /// Receive the next update, applying it to `content` as a subscriber would.
+    pub(super) async fn receive(subscription: &mut Subscription, content: &mut String) -> Update {
+        let update = next_update(subscription).await;
+        if let Some(patches) = &update.patches {
+            *content = apply_text_patches(content, patches).unwrap();
+        } else if let Some(body) = update.body_str() {
+            *content = body.to_string();
+        }
+        update
+    }
+
+    pub(super) async fn edit(client: &BraidClient, url: &str, peer: &str, range: &str, text: &str) {
+        let request = BraidRequest::new()
+            .with_method("PUT")
+            .with_peer(peer.to_string())
+            .with_merge_type("diamond")
+            .with_patches(vec![Patch::text(range, text)]);
+        let response = client.fetch(&format!("{}/doc", url), request).await.unwrap();
+        assert_eq!(response.status, 200, "{}", String::from_utf8_lossy(&response.body));
+    }
+}
+
+#[cfg(test)]
+mod replication_tests {
+    use super::test_server::{edit, Server};
+    use crate::client::BraidClient;
+    use crate::server::{Replication, Replicator, UpstreamConnection};
+    use crate::types::{BraidRequest, Patch, Version};
+    use std::time::Duration;
+
+    /// Wait until both servers hold `content` at the same version, and the replication
+    /// knows upstream has it.
+    async fn converge(upstream: &Server, downstream: &Server, replication: &Replication, content: &str) {
+        let synced = || {
+            let mut known = replication.upstream_version();
+            known.sort();
+            upstream.content().as_deref() == Some(content)
+                && downstream.content().as_deref() == Some(content)
+                && upstream.version() == downstream.version()
+                && known == upstream.version()
+        };
+        let waited = tokio::time::timeout(Duration::from_secs(5), async {
+            while !synced() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await;
+        assert!(
+            waited.is_ok(),
+            "expected {:?}, upstream has {:?}, downstream {:?}, error {:?}",
+            content,
+            upstream.content(),
+            downstream.content(),
+            replication.error()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replicates_both_ways_and_resumes_after_disconnect() {
+        let client = BraidClient::new();
+        let upstream = Server::new();
+        let upstream_url = upstream.serve().await;
+        let downstream = Server::new();
+        let downstream_url = downstream.serve().await;
+        edit(&client, &upstream_url, "alice", "[0:0]", "hello").await;
+
+        let replicator = Replicator::new(upstream_url.clone(), downstream.manager.clone())
+            .with_retry_delay(Duration::from_millis(50));
+        let replication = replicator.replicate("/doc");
+        converge(&upstream, &downstream, &replication, "hello").await;
+        assert_eq!(downstream.version(), vec![Version::new("alice-4")]);
+        assert!(replication.is_connected());
+
+        // A local edit is pushed upstream under its own version
+        edit(&client, &downstream_url, "bob", "[5:5]", " world").await;
+        converge(&upstream, &downstream, &replication, "hello world").await;
+        assert_eq!(upstream.version(), vec![Version::new("bob-5")]);
+
+        // Edits on both sides while the subscription is down
+        upstream.disconnect();
+        edit(&client, &upstream_url, "carol", "[0:0]", ">> ").await;
+        edit(&client, &downstream_url, "dave", "[11:11]", "!").await;
+        converge(&upstream, &downstream, &replication, ">> hello world!").await;
+
+        // The second subscription resumed from a version both sides had
+        let subscriptions = upstream.subscriptions.lock().clone();
+        assert_eq!(subscriptions.len(), 2);
+        assert!(subscriptions[0].is_empty());
+        assert!(!subscriptions[1].is_empty());
+        assert!(replication.error().is_none());
+
+        // Once stopped, local edits stay local
+        drop(replication);
+        edit(&client, &downstream_url, "erin", "[0:0]", "#").await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(upstream.content().as_deref(), Some(">> hello world!"));
+    }
+
+    #[tokio::test]
+    async fn test_replicates_text_typed_and_deleted_in_one_edit() {
+        let client = BraidClient::new();
+        let upstream = Server::new();
+        let upstream_url = upstream.serve().await;
+        let downstream = Server::new();
+        let downstream_url = downstream.serve().await;
+        edit(&client, &upstream_url, "alice", "[0:0]", "hello").await;
+
+        let replicator = Replicator::new(upstream_url, downstream.manager.clone())
+            .with_retry_delay(Duration::from_millis(50));
+        let replication = replicator.replicate("/doc");
+        converge(&upstream, &downstream, &replication, "hello").await;
+
+        // Type " wxyz", then backspace over "xyz"
+        let request = BraidRequest::new()
+            .with_method("PUT")
+            .with_peer("bob".to_string())
+            .with_merge_type("diamond")
+            .with_parents(downstream.version())
+            .with_patches(vec![Patch::text("[5:5]", " wxyz"), Patch::text("[7:10]", "")]);
+        let response = client.fetch(&format!("{}/doc", downstream_url), request).await.unwrap();
+        assert_eq!(response.status, 200);
+        converge(&upstream, &downstream, &replication, "hello w").await;
+        assert!(replication.error().is_none());
+    }
+}
+
+#[cfg(test)]
+mod proxy_tests {
+    use super::test_server::{edit, receive, serve_router, Server};
+    use crate::client::{BraidClient, Subscription};
+    use crate::server::{BraidProxy, UpstreamConnection};
+    use crate::types::{BraidRequest, Version};
+    use std::time::Duration;
+
+    async fn serve(proxy: &BraidProxy) -> String {
+        serve_router(proxy.router()).await
+    }
+
+    async fn subscribe(client: &BraidClient, url: &str, parents: Vec<Version>) -> Subscription {
+        let request = BraidRequest::new().subscribe().with_parents(parents);
+        client.subscribe(&format!("{}/doc", url), request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_proxy_fans_out_one_upstream_subscription() {
+        let client = BraidClient::new();
+        let upstream = Server::new();
+        let upstream_url = upstream.serve().await;
+        edit(&client, &upstream_url, "alice", "[0:0]", "hello").await;
+
+        let proxy = BraidProxy::new(upstream_url.clone()).with_history_limit(2);
+        let proxy_url = serve(&proxy).await;
+
+        let (mut first_content, mut second_content) = (String::new(), String::new());
+        let mut first = subscribe(&client, &proxy_url, vec![]).await;
+        let update = receive(&mut first, &mut first_content).await;
+        assert_eq!(update.version, vec![Version::new("alice-4")]);
+        let mut second = subscribe(&client, &proxy_url, vec![]).await;
+        receive(&mut second, &mut second_content).await;
+        assert_eq!((first_content.as_str(), second_content.as_str()), ("hello", "hello"));
+        assert_eq!(proxy.subscriber_count("/doc"), 2);
+        assert!(proxy.is_connected("/doc"));
+
+        // Live updates reach every subscriber through the one upstream subscription
+        edit(&client, &upstream_url, "bob", "[5:5]", " world").await;
+        for (subscription, content) in [(&mut first, &mut first_content), (&mut second, &mut second_content)] {
+            let update = receive(subscription, content).await;
+            assert_eq!(update.version, vec![Version::new("bob-5")]);
+            assert_eq!(update.parents, vec![Version::new("alice-4")]);
+            assert_eq!(content, "hello world");
+        }
+        assert_eq!(upstream.subscriptions.lock().len(), 1);
+
+        // A subscriber with cached parents gets only what it's missing
+        let mut content = "hello".to_string();
+        let mut third = subscribe(&client, &proxy_url, vec![Version::new("alice-4")]).await;
+        let update = receive(&mut third, &mut content).await;
+        assert_eq!(update.version, vec![Version::new("bob-5")]);
+        assert_eq!(content, "hello world");
+
+        // Older parents fall back to the snapshot
+        edit(&client, &upstream_url, "carol", "[11:11]", "!").await;
+        edit(&client, &upstream_url, "dave", "[0:0]", ">> ").await;
+        for _ in 0..2 {
+            receive(&mut first, &mut first_content).await;
+        }
+        assert_eq!(first_content, ">> hello world!");
+        let mut content = "hello".to_string();
+        let mut fourth = subscribe(&client, &proxy_url, vec![Version::new("alice-4")]).await;
+        let update = receive(&mut fourth, &mut content).await;
+        assert!(update.is_snapshot());
+        assert_eq!(content, ">> hello world!");
+        assert_eq!(update.version, upstream.version());
+
+        // Plain GETs are served from the cache
+        let response = client.get(&format!("{}/doc", proxy_url)).await.unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body_str(), Some(">> hello world!"));
+        assert_eq!(upstream.subscriptions.lock().len(), 1);
+
+        // The upstream subscription ends with the last subscriber
+        drop((first, second, third, fourth));
+        let waited = tokio::time::timeout(Duration::from_secs(5), async {
+            while proxy.subscriber_count("/doc") > 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await;
+        assert!(waited.is_ok(), "subscribers never left");
+        assert!(!proxy.is_connected("/doc"));
+    }
+
+    #[tokio::test]
+    async fn test_proxy_resumes_upstream_after_disconnect() {
+        let client = BraidClient::new();
+        let upstream = Server::new();
+        let upstream_url = upstream.serve().await;
+        edit(&client, &upstream_url, "alice", "[0:0]", "hello").await;
+
+        let proxy = BraidProxy::new(upstream_url.clone()).with_retry_delay(Duration::from_millis(50));
+        let proxy_url = serve(&proxy).await;
+        let mut content = String::new();
+        let mut subscription = subscribe(&client, &proxy_url, vec![]).await;
+        receive(&mut subscription, &mut content).await;
+
+        upstream.disconnect();
+        edit(&client, &upstream_url, "bob", "[5:5]", " world").await;
+        let update = receive(&mut subscription, &mut content).await;
+        assert_eq!(update.version, vec![Version::new("bob-5")]);
+        assert_eq!(content, "hello world");
+
+        // The proxy resubscribed from the version it had, and its subscriber stayed
+        let subscriptions = upstream.subscriptions.lock().clone();
+        assert_eq!(subscriptions, vec![vec![], vec![Version::new("alice-4")]]);
+    }
+}
+
+#[cfg(test)]
+mod dir_tests {
+    use super::test_server::{receive, serve_router};
+    use crate::client::BraidClient;
+    use crate::server::BraidDir;
+    use crate::types::{BraidRequest, Patch, Version};
+    use std::time::Duration;
+
+    async fn serve(dir: &BraidDir) -> String {
+        serve_router(dir.router()).await
+    }
+
+    #[tokio::test]
+    async fn test_dir_serves_subscribes_and_merges() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::create_dir(root.path().join("docs")).unwrap();
+        let file = root.path().join("docs/intro.md");
+        std::fs::write(&file, "# Intro\n").unwrap();
+        let dir = BraidDir::new(root.path()).with_poll_interval(Duration::from_millis(20));
+        let url = format!("{}/docs/intro.md", serve(&dir).await);
+        let client = BraidClient::new();
+
+        let response = client.get(&url).await.unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body_str(), Some("# Intro\n"));
+        let original = BraidDir::content_version(b"# Intro\n");
+        assert_eq!(response.get_version(), Some(vec![original.clone()]));
+
+        let mut content = String::new();
+        let mut subscription = client.subscribe(&url, BraidRequest::new()).await.unwrap();
+        let update = receive(&mut subscription, &mut content).await;
+        assert_eq!(update.version, vec![original.clone()]);
+
+        // A change on disk is picked up by polling
+        std::fs::write(&file, "# Intro\nWelcome.\n").unwrap();
+        let update = receive(&mut subscription, &mut content).await;
+        assert_eq!(content, "# Intro\nWelcome.\n");
+        assert_eq!(update.parents, vec![original.clone()]);
+        assert_eq!(update.version, vec![BraidDir::content_version(content.as_bytes())]);
+
+        // An edit against the original version is merged with the change on disk
+        let request = BraidRequest::new()
+            .with_method("PUT")
+            .with_peer("alice".to_string())
+            .with_parents(vec![original])
+            .with_patches(vec![Patch::text("[2:7]", "Introduction")]);
+        let response = client.fetch(&url, request).await.unwrap();
+        assert_eq!(response.status, 200, "{:?}", response.body_str());
+        receive(&mut subscription, &mut content).await;
+        assert_eq!(content, "# Introduction\nWelcome.\n");
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), content);
+        assert_eq!(
+            response.get_version(),
+            Some(vec![BraidDir::content_version(content.as_bytes())])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dir_rejects_missing_and_hidden_files() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::write(root.path().join(".secret"), "hidden").unwrap();
+        let dir = BraidDir::new(root.path());
+        let url = serve(&dir).await;
+        let client = BraidClient::new();
+
+        assert_eq!(client.get(&format!("{}/missing.txt", url)).await.unwrap().status, 404);
+        assert_eq!(client.get(&format!("{}/.secret", url)).await.unwrap().status, 404);
+
+        let request = BraidRequest::new()
+            .with_method("PUT")
+            .with_parents(vec![Version::new("3-deadbeef")])
+            .with_body("x");
+        let response = client.fetch(&format!("{}/missing.txt", url), request).await.unwrap();
+        assert_eq!(response.status, 404);
+        assert!(!root.path().join("missing.txt").exists());
+    }
+}
+
+#[cfg(test)]
+mod binary_tests {
+    use super::test_server::{next_update, serve_router, Server};
+    use crate::client::BraidClient;
+    use crate::merge::apply_byte_patches;
+    use crate::server::ResourceChange;
+    use crate::types::{BraidRequest, Patch, Update, Version};
+    use axum::routing::get;
+    use axum::Router;
+    use bytes::Bytes;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_binary_resource_streams_byte_patches() {
+        let client = BraidClient::new();
+        let server = Server::new();
+        let url = format!("{}/blob", server.serve().await);
+        let original: Vec<u8> = (0..=255u8).cycle().take(1000).collect();
+
+        let request = BraidRequest::new()
+            .with_method("PUT")
+            .with_version(Version::new("alice-1"))
+            .with_merge_type("lww")
+            .with_content_type("application/octet-stream")
+            .with_body(original.clone());
+        assert_eq!(client.fetch(&url, request).await.unwrap().status, 200);
+
+        let mut subscription = client.subscribe(&url, BraidRequest::new()).await.unwrap();
+        let snapshot = next_update(&mut subscription).await;
+        assert_eq!(snapshot.body.as_deref(), Some(&original[..]));
+
+        let request = BraidRequest::new()
+            .with_method("PUT")
+            .with_version(Version::new("alice-2"))
+            .with_parents(vec![Version::new("alice-1")])
+            .with_merge_type("lww")
+            .with_patches(vec![Patch::bytes("10:20", &[0xff, 0x00, 0xfe][..])]);
+        assert_eq!(client.fetch(&url, request).await.unwrap().status, 200);
+
+        let update = next_update(&mut subscription).await;
+        assert_eq!(update.version, vec![Version::new("alice-2")]);
+        let patched = apply_byte_patches(&original, update.patches.as_deref().unwrap()).unwrap();
+        let resource = server.manager.get_resource("/blob").unwrap();
+        let expected = resource.read().document.snapshot().body.unwrap();
+        assert_eq!(patched, expected);
+        assert_eq!(patched.len(), 993);
+        assert_eq!(&patched[10..13], &[0xff, 0x00, 0xfe]);
+    }
+
+    #[tokio::test]
+    async fn test_losing_write_is_answered_to_its_writer_only() {
+        let client = BraidClient::new();
+        let server = Server::new();
+        let url = format!("{}/blob", server.serve().await);
+        let observed = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let seen = observed.clone();
+        server.manager.add_observer(Arc::new(move |change: ResourceChange| {
+            seen.lock().push(change.update.version);
+            async { Ok(()) }
+        }));
+        let put = |version: &str, parents: &[&str], body: &str| {
+            let request = BraidRequest::new()
+                .with_method("PUT")
+                .with_version(Version::new(version))
+                .with_parents(parents.iter().map(|&p| Version::new(p)).collect())
+                .with_merge_type("lww")
+                .with_body(body.to_string());
+            client.fetch(&url, request)
+        };
+
+        assert_eq!(put("v1", &[], "base").await.unwrap().status, 200);
+        let mut subscription = client.subscribe(&url, BraidRequest::new()).await.unwrap();
+        assert_eq!(next_update(&mut subscription).await.version, vec![Version::new("v1")]);
+
+        assert_eq!(put("v2-bob", &["v1"], "bob").await.unwrap().status, 200);
+        let lost = put("v2-alice", &["v1"], "alice").await.unwrap();
+        assert_eq!(lost.status, crate::protocol::STATUS_MERGE_CONFLICT);
+        assert_eq!(lost.body, "bob".as_bytes());
+        assert_eq!(put("v3", &["v2-bob"], "carol").await.unwrap().status, 200);
+
+        // Subscribers and observers go from the winner straight to the next write
+        assert_eq!(next_update(&mut subscription).await.version, vec![Version::new("v2-bob")]);
+        assert_eq!(next_update(&mut subscription).await.version, vec![Version::new("v3")]);
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        let versions: Vec<Vec<Version>> = ["v1", "v2-bob", "v3"].iter().map(|&v| vec![Version::new(v)]).collect();
+        assert_eq!(*observed.lock(), versions);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_into_streams_large_snapshot() {
+        let data = Bytes::from((0..4 * 1024 * 1024).map(|i| (i % 251) as u8).collect::<Vec<u8>>());
+        let body = data.clone();
+        let app = Router::new().route(
+            "/blob",
+            get(move || async move { Update::snapshot(Version::new("v1"), body) }),
+        );
+        let url = format!("{}/blob", serve_router(app).await);
+
+        let mut written = Vec::new();
+        let response = BraidClient::new()
+            .fetch_into(&url, BraidRequest::new(), &mut written)
+            .await
+            .unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.get_version(), Some(vec![Version::new("v1")]));
+        assert!(response.body.is_empty());
+        assert_eq!(written, data);
+    }
+}
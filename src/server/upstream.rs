@@ -0,0 +1,148 @@
+//! Connections to an upstream Braid server.
+//!
+//! [`BraidProxy`](crate::server::BraidProxy) and [`Replicator`](crate::server::Replicator)
+//! both follow resources of an upstream server through long-lived subscriptions. Each
+//! keeps an [`Upstream`]: the server's base URL, and the client, peer ID and
+//! reconnection delay its requests use. Their builder methods and accessors for it
+//! come from [`UpstreamConnection`].
+//!
+//! When a subscription ends or can't be opened, it's opened again after
+//! [`DEFAULT_RETRY_DELAY`] (see [`UpstreamConnection::with_retry_delay`]), doubled for
+//! each consecutive failure up to 32 times the first delay. The delay starts over
+//! once a subscription is open.
+//!
+//! # Examples
+//!
+//! ```
+//! use braid_axum_http::server::{BraidProxy, UpstreamConnection};
+//! use std::time::Duration;
+//!
+//! let proxy = BraidProxy::new("http://upstream.example/")
+//!     .with_peer("edge-1")
+//!     .with_retry_delay(Duration::from_millis(500));
+//! assert_eq!(proxy.upstream(), "http://upstream.example");
+//! assert_eq!(proxy.peer(), "edge-1");
+//! assert_eq!(proxy.url("/notes/today"), "http://upstream.example/notes/today");
+//! ```
+
+use crate::client::{exponential_backoff, BraidClient};
+use std::fmt;
+use std::future::Future;
+use std::ops::ControlFlow;
+use std::time::Duration;
+
+/// Default delay before the first reconnection attempt upstream.
+pub const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Reconnection delays stop doubling after this many attempts
+const MAX_BACKOFF_DOUBLINGS: u32 = 5;
+
+/// Where an upstream Braid server is, and how to connect to it.
+#[derive(Clone)]
+pub struct Upstream {
+    pub(crate) client: BraidClient,
+
+    /// Base URL, without a trailing `/`
+    base: String,
+
+    pub(crate) peer: String,
+    retry_delay: Duration,
+}
+
+impl Upstream {
+    /// Connect to the server at `base` with a default client, as a random peer ID
+    /// starting with `peer_prefix`.
+    pub(crate) fn new(base: impl Into<String>, peer_prefix: &str) -> Self {
+        Self {
+            client: BraidClient::new(),
+            base: base.into().trim_end_matches('/').to_string(),
+            peer: format!("{}-{}", peer_prefix, uuid::Uuid::new_v4()),
+            retry_delay: DEFAULT_RETRY_DELAY,
+        }
+    }
+
+    /// Run `session` again and again until it breaks, with a backoff delay between runs.
+    ///
+    /// `session` continues with whether it got connected, which starts the delays over.
+    pub(crate) async fn reconnect<F, Fut>(&self, mut session: F)
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = ControlFlow<(), bool>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match session().await {
+                ControlFlow::Break(()) => return,
+                ControlFlow::Continue(true) => attempt = 0,
+                ControlFlow::Continue(false) => {}
+            }
+            let base_ms = u64::try_from(self.retry_delay.as_millis()).unwrap_or(u64::MAX);
+            tokio::time::sleep(exponential_backoff(attempt.min(MAX_BACKOFF_DOUBLINGS), base_ms)).await;
+            attempt += 1;
+        }
+    }
+}
+
+impl fmt::Debug for Upstream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Upstream")
+            .field("base", &self.base)
+            .field("peer", &self.peer)
+            .field("retry_delay", &self.retry_delay)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Settings of a connection to an upstream server, for types that keep one.
+///
+/// The URL of a resource upstream is the upstream base URL followed by the resource
+/// ID, e.g. `http://upstream.example` and `/notes/today`.
+pub trait UpstreamConnection: Sized {
+    /// Get the connection's settings.
+    fn connection(&self) -> &Upstream;
+
+    /// Get the connection's settings to change them.
+    fn connection_mut(&mut self) -> &mut Upstream;
+
+    /// Use `client` for requests upstream (builder style).
+    #[must_use]
+    fn with_client(mut self, client: BraidClient) -> Self {
+        self.connection_mut().client = client;
+        self
+    }
+
+    /// Identify this connection to upstream with the `Peer` header (builder style).
+    #[must_use]
+    fn with_peer(mut self, peer: impl Into<String>) -> Self {
+        self.connection_mut().peer = peer.into();
+        self
+    }
+
+    /// Wait `delay` before reconnecting upstream the first time, doubling it for each
+    /// consecutive failure (builder style).
+    #[must_use]
+    fn with_retry_delay(mut self, delay: Duration) -> Self {
+        self.connection_mut().retry_delay = delay;
+        self
+    }
+
+    /// Get the base URL of the upstream server.
+    #[inline]
+    #[must_use]
+    fn upstream(&self) -> &str {
+        &self.connection().base
+    }
+
+    /// Get the peer ID sent upstream.
+    #[inline]
+    #[must_use]
+    fn peer(&self) -> &str {
+        &self.connection().peer
+    }
+
+    /// Get the upstream URL of a resource.
+    #[must_use]
+    fn url(&self, resource_id: &str) -> String {
+        format!("{}/{}", self.upstream(), resource_id.trim_start_matches('/'))
+    }
+}
@@ -0,0 +1,282 @@
+//! Validation of updates before they're merged.
+//!
+//! An [`UpdateValidator`] checks what a resource would look like with an update
+//! merged in, and can reject the update before it's committed. Validators are
+//! registered per route on a [`ConflictResolver`](crate::server::ConflictResolver),
+//! which then checks every update to a matching resource before merging it:
+//!
+//! | Validator says | Result |
+//! |----------------|--------|
+//! | `Ok(())` | The update is merged, stored and broadcast as usual |
+//! | `Err(message)` | The resource is left as it was, and the update fails with a 422 [`Rejection`] carrying the message |
+//!
+//! The merged content is [previewed](crate::merge::MergeType::preview) from the current
+//! content where the merge type can, as for `text` patches made against the current
+//! version. Otherwise the update is merged into a copy of the resource, which replaces
+//! the resource once the validator accepts it.
+//!
+//! A rejected update is never stored, passed to observers, or returned for
+//! broadcasting, so subscribers never see its version.
+//!
+//! # Routes
+//!
+//! A route is a resource ID, matched exactly, or a prefix ending in `*`, matching every
+//! resource ID starting with it. When several routes match, the longest wins.
+//!
+//! # Examples
+//!
+//! ```
+//! use braid_axum_http::server::validation::Candidate;
+//! use braid_axum_http::server::{ConflictResolver, ResourceStateManager};
+//! use braid_axum_http::{Update, Version};
+//!
+//! # tokio_test::block_on(async {
+//! let resolver = ConflictResolver::new(ResourceStateManager::new()).with_validator(
+//!     "/notes/*",
+//!     |candidate: &Candidate<'_>| match candidate.body().split(|&b| b == b'\n').count() {
+//!         lines if lines > 3 => Err(format!("Notes are limited to 3 lines, not {}", lines)),
+//!         _ => Ok(()),
+//!     },
+//! );
+//!
+//! let ok = Update::snapshot(Version::new("v1"), "a\nb").with_merge_type("diamond");
+//! assert!(resolver.resolve("/notes/today", &ok, "alice").await.is_ok());
+//!
+//! let long = Update::snapshot(Version::new("v2"), "c\nd\n").with_merge_type("diamond");
+//! let rejection = resolver.resolve("/notes/today", &long, "alice").await.unwrap_err();
+//! assert_eq!(rejection.status, 422);
+//! assert_eq!(resolver.get_resource_content("/notes/today").unwrap(), "a\nb");
+//! # });
+//! ```
+
+use crate::protocol::status;
+use crate::server::ResourceState;
+use crate::types::Update;
+use axum::response::{IntoResponse, Response};
+use bytes::Bytes;
+use parking_lot::RwLock;
+use std::fmt;
+use std::sync::Arc;
+
+/// A resource as it would be with an update merged in.
+#[derive(Debug)]
+pub struct Candidate<'a> {
+    /// Resource being updated
+    pub resource_id: &'a str,
+
+    /// The update as received
+    pub update: &'a Update,
+
+    /// The resource's state before the update
+    pub current: &'a ResourceState,
+
+    /// Content with the update merged
+    body: Bytes,
+}
+
+impl<'a> Candidate<'a> {
+    /// Describe `update` merged into `current`, leaving `body`.
+    pub(crate) fn new(resource_id: &'a str, update: &'a Update, current: &'a ResourceState, body: Bytes) -> Self {
+        Self {
+            resource_id,
+            update,
+            current,
+            body,
+        }
+    }
+
+    /// Get the merged content: the text of text resources, and the snapshot body of
+    /// other merge types.
+    #[must_use]
+    pub fn body(&self) -> Bytes {
+        self.body.clone()
+    }
+}
+
+/// Checks updates before they're merged into a resource.
+///
+/// Closures taking a [`Candidate`] and returning `Result<(), String>` implement this
+/// trait. Validators run while the resource is locked, so they should be quick and
+/// must not touch the resource themselves.
+pub trait UpdateValidator: Send + Sync {
+    /// Check the state an update would produce.
+    ///
+    /// # Errors
+    ///
+    /// Returns the message to reject the update with.
+    fn validate(&self, candidate: &Candidate<'_>) -> Result<(), String>;
+}
+
+impl<F> UpdateValidator for F
+where
+    F: Fn(&Candidate<'_>) -> Result<(), String> + Send + Sync,
+{
+    fn validate(&self, candidate: &Candidate<'_>) -> Result<(), String> {
+        self(candidate)
+    }
+}
+
+/// A route and its validator.
+type Route = (String, Arc<dyn UpdateValidator>);
+
+/// Validators by route.
+///
+/// Cloning shares the routes, so validators added to a clone apply to every copy.
+#[derive(Clone, Default)]
+pub struct ValidatorRoutes {
+    routes: Arc<RwLock<Vec<Route>>>,
+}
+
+impl ValidatorRoutes {
+    /// Create an empty set of routes.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the validator for a route, replacing any registered for it before.
+    ///
+    /// # Arguments
+    ///
+    /// * `route` - A resource ID, or a resource ID prefix ending in `*`
+    /// * `validator` - Validator for updates to matching resources
+    pub fn insert(&self, route: impl Into<String>, validator: Arc<dyn UpdateValidator>) {
+        let route = route.into();
+        let mut routes = self.routes.write();
+        routes.retain(|(existing, _)| *existing != route);
+        routes.push((route, validator));
+    }
+
+    /// Find the validator for a resource, from its longest matching route.
+    #[must_use]
+    pub fn find(&self, resource_id: &str) -> Option<Arc<dyn UpdateValidator>> {
+        self.routes
+            .read()
+            .iter()
+            .filter(|(route, _)| match route.strip_suffix('*') {
+                Some(prefix) => resource_id.starts_with(prefix),
+                None => route == resource_id,
+            })
+            .max_by_key(|(route, _)| route.len())
+            .map(|(_, validator)| Arc::clone(validator))
+    }
+
+    /// Check whether no validators are registered.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.routes.read().is_empty()
+    }
+}
+
+impl fmt::Debug for ValidatorRoutes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let routes: Vec<String> = self.routes.read().iter().map(|(route, _)| route.clone()).collect();
+        f.debug_struct("ValidatorRoutes").field("routes", &routes).finish()
+    }
+}
+
+/// An update that wasn't applied, with the status to respond with.
+///
+/// Converts into an error [`Update`], or directly into a response, with the message
+/// as its body.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rejection {
+    /// HTTP status code: 422 for failed validation, 400 for updates that can't be
+    /// merged, and 500 for storage failures
+    pub status: u16,
+
+    /// Why the update was rejected
+    pub message: String,
+}
+
+impl Rejection {
+    /// Reject an update that failed validation.
+    pub fn invalid(message: impl Into<String>) -> Self {
+        Self::new(status::UNPROCESSABLE_ENTITY, message)
+    }
+
+    /// Reject an update that can't be merged.
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(status::BAD_REQUEST, message)
+    }
+
+    /// Fail an update the server couldn't apply.
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(status::INTERNAL_SERVER_ERROR, message)
+    }
+
+    fn new(status: u16, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Rejection {}
+
+impl From<Rejection> for Update {
+    fn from(rejection: Rejection) -> Self {
+        Update {
+            status: rejection.status,
+            body: Some(rejection.message.into()),
+            ..Default::default()
+        }
+    }
+}
+
+impl IntoResponse for Rejection {
+    fn into_response(self) -> Response {
+        Update::from(self).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn named(name: &'static str) -> Arc<dyn UpdateValidator> {
+        Arc::new(move |_: &Candidate<'_>| Err(name.to_string()))
+    }
+
+    fn matched(routes: &ValidatorRoutes, resource_id: &str) -> Option<String> {
+        let state = crate::server::ResourceStateManager::new()
+            .get_or_create_resource("doc", "alice")
+            .read()
+            .clone();
+        let update = Update::default();
+        let candidate = Candidate::new(resource_id, &update, &state, Bytes::new());
+        routes.find(resource_id).map(|v| v.validate(&candidate).unwrap_err())
+    }
+
+    #[test]
+    fn test_longest_route_wins() {
+        let routes = ValidatorRoutes::new();
+        assert!(routes.is_empty());
+        routes.insert("*", named("any"));
+        routes.insert("/config/*", named("config"));
+        routes.insert("/config/app.json", named("app"));
+
+        assert_eq!(matched(&routes, "/config/app.json").as_deref(), Some("app"));
+        assert_eq!(matched(&routes, "/config/db.json").as_deref(), Some("config"));
+        assert_eq!(matched(&routes, "/notes").as_deref(), Some("any"));
+
+        routes.insert("/config/*", named("replaced"));
+        assert_eq!(matched(&routes, "/config/db.json").as_deref(), Some("replaced"));
+    }
+
+    #[test]
+    fn test_rejection_converts_to_error_update() {
+        let update = Update::from(Rejection::invalid("Config must be an object"));
+        assert_eq!(update.status, 422);
+        assert_eq!(update.body_str(), Some("Config must be an object"));
+        assert_eq!(Rejection::bad_request("x").status, 400);
+        assert_eq!(Rejection::internal("x").status, 500);
+    }
+}
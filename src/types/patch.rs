@@ -207,7 +207,8 @@ impl Patch {
 
     /// Create a byte range patch.
     ///
-    /// Byte patches address specific byte ranges within a binary resource.
+    /// Byte patches address specific byte ranges within a binary resource. The end of
+    /// the range is exclusive; see [`apply_byte_patches`](crate::merge::apply_byte_patches).
     ///
     /// # Arguments
     ///
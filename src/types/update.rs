@@ -91,6 +91,8 @@
 //! [draft-toomim-httpbis-braid-http-04]: https://datatracker.ietf.org/doc/html/draft-toomim-httpbis-braid-http
 
 use crate::types::{ContentRange, Patch, Version};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
 use bytes::Bytes;
 use std::collections::BTreeMap;
 
@@ -305,6 +307,27 @@ impl Update {
         self.patches.is_some()
     }
 
+    /// Check if this is a `293 Merge Conflict` update.
+    ///
+    /// A merge type returns one for a write that lost (see
+    /// [`LwwRegister`](crate::merge::LwwRegister)). It's meant for the writer alone, so
+    /// it shouldn't be broadcast to subscribers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use braid_axum_http::protocol::STATUS_MERGE_CONFLICT;
+    /// use braid_axum_http::{Update, Version};
+    ///
+    /// let update = Update::snapshot(Version::new("v1"), "winner").with_status(STATUS_MERGE_CONFLICT);
+    /// assert!(update.is_conflict());
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn is_conflict(&self) -> bool {
+        self.status == crate::protocol::STATUS_MERGE_CONFLICT
+    }
+
     /// Get the primary version ID.
     ///
     /// Returns the first version ID, or `None` if no versions are set.
@@ -500,7 +523,8 @@ impl Update {
 
     /// Convert to a JSON value.
     ///
-    /// Serializes the update to a JSON object with version, parents, and body.
+    /// Serializes the update to a JSON object with version, parents, and body. A
+    /// body that isn't UTF-8 is base64-encoded as `body_base64` instead.
     ///
     /// # Examples
     ///
@@ -533,12 +557,16 @@ impl Update {
         );
 
         if let Some(body) = &self.body {
-            obj.insert(
-                "body".to_string(),
-                serde_json::Value::String(
-                    String::from_utf8_lossy(body).into_owned(),
+            match std::str::from_utf8(body) {
+                Ok(text) => obj.insert(
+                    "body".to_string(),
+                    serde_json::Value::String(text.to_string()),
                 ),
-            );
+                Err(_) => obj.insert(
+                    "body_base64".to_string(),
+                    serde_json::Value::String(STANDARD.encode(body)),
+                ),
+            };
         }
 
         if let Some(merge_type) = &self.merge_type {
@@ -643,6 +671,14 @@ mod tests {
         assert!(json.get("merge_type").is_some());
     }
 
+    #[test]
+    fn test_to_json_encodes_binary_bodies() {
+        let update = Update::snapshot(Version::new("v1"), vec![0xff, 0x00, 0xfe]);
+        let json = update.to_json();
+        assert!(json.get("body").is_none());
+        assert_eq!(json["body_base64"], "/wD+");
+    }
+
     #[test]
     fn test_default() {
         let update = Update::default();
@@ -103,10 +103,23 @@ use std::hash::Hash;
 /// assert_eq!(v.to_json(), serde_json::json!(42));
 /// ```
 ///
+/// # Ordering
+///
+/// Versions have a total order so that merge algorithms can break ties between
+/// concurrent versions deterministically. String versions sort before integer
+/// versions; values of the same kind sort by their natural order.
+///
+/// ```
+/// use braid_axum_http::Version;
+///
+/// assert!(Version::new("a") < Version::new("b"));
+/// assert!(Version::new("z") < Version::Integer(0));
+/// ```
+///
 /// # Specification
 ///
 /// See Section 2 of draft-toomim-httpbis-braid-http for version semantics.
-#[derive(Clone, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize)]
 #[serde(untagged)]
 pub enum Version {
     /// String-based version ID.
@@ -477,6 +490,13 @@ mod tests {
         assert_eq!(set.len(), 3);
     }
 
+    #[test]
+    fn test_version_ordering() {
+        assert!(Version::new("v1") < Version::new("v2"));
+        assert!(Version::Integer(1) < Version::Integer(2));
+        assert!(Version::new("v9") < Version::Integer(1));
+    }
+
     #[test]
     fn test_version_default() {
         let v = Version::default();
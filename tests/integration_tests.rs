@@ -1,5 +1,5 @@
 use braid_axum_http::{
-    BraidLayer, BraidState, Update, Version, BraidRequest, BraidClient,
+    BraidLayer, BraidState, Update, Version,
 };
 use std::sync::Arc;
 
@@ -15,8 +15,9 @@ fn test_resource_state_creation() {
     let resource = layer.resource_manager.get_or_create_resource("doc1", "alice");
     
     let state = resource.read();
-    assert!(state.crdt.is_empty());
-    assert_eq!(state.crdt.agent_id(), "alice");
+    let crdt = state.crdt().unwrap();
+    assert!(crdt.is_empty());
+    assert_eq!(crdt.agent_id(), "alice");
 }
 
 #[test]
@@ -177,7 +178,7 @@ fn test_diamond_crdt_concurrent() {
     
     let mut crdt = DiamondCRDT::new("alice");
     crdt.add_insert(0, "a");
-    crdt.add_insert_remote("bob", 1, "b");
+    crdt.add_insert_remote("bob", 1, "b").unwrap();
     
     assert_eq!(crdt.content(), "ab");
 }